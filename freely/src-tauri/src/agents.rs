@@ -9,7 +9,9 @@
 //! 3. Reads stdout line-by-line, emitting `agent:stream:{session_id}` events
 //! 4. Returns a collected Vec<StreamEvent> when the process exits
 
+use crate::chat_history::ToolCall;
 use crate::claude_config;
+use crate::profiles;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::process::Stdio;
@@ -101,11 +103,15 @@ pub struct StreamEvent {
     pub agent_session_id: Option<String>,
     #[serde(rename = "tokenUsage", skip_serializing_if = "Option::is_none")]
     pub token_usage: Option<TokenUsage>,
+    /// Tool uses in this event. Their results arrive in a later event as
+    /// entries with the same `id` and an `output`.
+    #[serde(rename = "toolCalls", default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct TokenUsage {
     pub input_tokens: u64,
     pub output_tokens: u64,
@@ -134,6 +140,10 @@ pub struct AgentPayload {
     /// When set, it is prepended to the user prompt before passing to the CLI.
     #[serde(rename = "systemPrompt")]
     pub system_prompt: Option<String>,
+    /// Project profile to run under; no profile is applied when unset.
+    /// Explicit payload fields (directory, model, permissions) override the profile.
+    #[serde(rename = "profileId")]
+    pub profile_id: Option<String>,
 }
 
 // ============================================================================
@@ -416,9 +426,15 @@ pub async fn run_claude(
     cmd.env_remove("CLAUDECODE")
         .env_remove("CLAUDE_CODE_ENTRYPOINT");
 
-    // Set working directory: use the user's project directory when provided,
-    // otherwise fall back to the .claude config dir so the CLI picks up CLAUDE.md.
-    if let Some(ref working_dir) = payload.working_directory {
+    let profile = profiles::resolve_for_run(&app, payload.profile_id.as_deref())?;
+
+    // Set working directory: use the user's (or profile's) project directory when
+    // provided, otherwise fall back to the .claude config dir so the CLI picks up CLAUDE.md.
+    let working_dir = payload
+        .working_directory
+        .clone()
+        .or_else(|| profile.as_ref().map(|p| p.directory.clone()));
+    if let Some(ref working_dir) = working_dir {
         cmd.current_dir(working_dir);
     } else {
         cmd.current_dir(&claude_dir);
@@ -443,11 +459,30 @@ pub async fn run_claude(
         cmd.arg("--resume").arg(agent_sid);
     }
 
-    if let Some(ref model) = payload.model {
+    // Inside a project the CLI reads the project's CLAUDE.md instead of ours, so
+    // layer Freely's guidance and the profile's instructions on top of it.
+    if working_dir.is_some() {
+        let freely_md = std::fs::read_to_string(claude_dir.join("CLAUDE.md")).ok();
+        if let Some(layer) = profiles::instruction_layer(freely_md.as_deref(), profile.as_ref()) {
+            cmd.arg("--append-system-prompt").arg(layer);
+        }
+    }
+
+    let model = payload
+        .model
+        .clone()
+        .or_else(|| profile.as_ref().and_then(|p| p.default_model.clone()));
+    if let Some(ref model) = model {
         cmd.arg("--model").arg(model);
     }
 
-    if let Some(ref perm) = payload.permission_mode {
+    let permission_mode = payload.permission_mode.clone().or_else(|| {
+        profile
+            .as_ref()
+            .filter(|p| !p.allowed_tools.is_empty())
+            .map(|p| p.allowed_tools.join(","))
+    });
+    if let Some(ref perm) = permission_mode {
         cmd.arg("--allowedTools").arg(perm);
     }
 
@@ -617,6 +652,7 @@ async fn run_cli_process(
                 resolved_model: None,
                 agent_session_id: None,
                 token_usage: None,
+                tool_calls: Vec::new(),
                 error: None,
             };
 
@@ -660,6 +696,7 @@ async fn run_cli_process(
             resolved_model: None,
             agent_session_id: None,
             token_usage: None,
+            tool_calls: Vec::new(),
             error: Some(error_msg.clone()),
         };
         if let Err(e) = app.emit(&event_name, &error_event) {
@@ -680,6 +717,7 @@ async fn run_cli_process(
         resolved_model: None,
         agent_session_id: None,
         token_usage: None,
+        tool_calls: Vec::new(),
         error: None,
     };
     if let Err(e) = app.emit(&event_name, &complete_event) {
@@ -719,6 +757,7 @@ fn parse_json_event(json: &serde_json::Value) -> StreamEvent {
                         .and_then(|s| s.as_str())
                         .map(String::from),
                     token_usage: parse_token_usage(json),
+                    tool_calls: parse_tool_calls(json),
                     error: None,
                 }
             }
@@ -737,6 +776,7 @@ fn parse_json_event(json: &serde_json::Value) -> StreamEvent {
                         .and_then(|s| s.as_str())
                         .map(String::from),
                     token_usage: parse_token_usage(json),
+                    tool_calls: Vec::new(),
                     error: None,
                 }
             }
@@ -746,6 +786,7 @@ fn parse_json_event(json: &serde_json::Value) -> StreamEvent {
                 resolved_model: None,
                 agent_session_id: None,
                 token_usage: None,
+                tool_calls: Vec::new(),
                 error: json
                     .get("error")
                     .and_then(|e| {
@@ -757,7 +798,8 @@ fn parse_json_event(json: &serde_json::Value) -> StreamEvent {
                     .or_else(|| Some("Unknown error".to_string())),
             },
             _ => {
-                // Unknown structured type — pass through as partial if it has text
+                // Unknown structured type (e.g. "user" carrying tool results) —
+                // pass through as partial if it has text
                 let text = json.get("text").and_then(|t| t.as_str()).map(String::from);
                 StreamEvent {
                     event_type: "partial".to_string(),
@@ -765,6 +807,7 @@ fn parse_json_event(json: &serde_json::Value) -> StreamEvent {
                     resolved_model: None,
                     agent_session_id: None,
                     token_usage: None,
+                    tool_calls: parse_tool_calls(json),
                     error: None,
                 }
             }
@@ -785,11 +828,60 @@ fn parse_json_event(json: &serde_json::Value) -> StreamEvent {
             resolved_model: json.get("model").and_then(|m| m.as_str()).map(String::from),
             agent_session_id: None,
             token_usage: parse_token_usage(json),
+            tool_calls: Vec::new(),
             error: None,
         }
     }
 }
 
+/// Tool use and tool result blocks in an event's content, which the CLI
+/// nests under `message` in stream-json output.
+fn parse_tool_calls(json: &serde_json::Value) -> Vec<ToolCall> {
+    let Some(blocks) = json
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .or_else(|| json.get("content"))
+        .and_then(|c| c.as_array())
+    else {
+        return Vec::new();
+    };
+    let text = |v: &serde_json::Value| v.as_str().map(String::from);
+    blocks
+        .iter()
+        .filter_map(|block| match block.get("type")?.as_str()? {
+            "tool_use" => Some(ToolCall {
+                id: block.get("id").and_then(text),
+                name: block.get("name").and_then(text)?,
+                input: block.get("input").cloned().unwrap_or_default(),
+                output: None,
+                is_error: false,
+            }),
+            "tool_result" => {
+                let output = match block.get("content") {
+                    Some(serde_json::Value::Array(parts)) => parts
+                        .iter()
+                        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    Some(content) => content.as_str().unwrap_or_default().to_string(),
+                    None => String::new(),
+                };
+                Some(ToolCall {
+                    id: Some(block.get("tool_use_id").and_then(text)?),
+                    name: String::new(),
+                    input: serde_json::Value::Null,
+                    output: Some(output),
+                    is_error: block
+                        .get("is_error")
+                        .and_then(|e| e.as_bool())
+                        .unwrap_or(false),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
 /// Extract token usage from a JSON value if present.
 fn parse_token_usage(json: &serde_json::Value) -> Option<TokenUsage> {
     json.get("usage").and_then(|u| {
@@ -811,3 +903,39 @@ fn parse_token_usage(json: &serde_json::Value) -> Option<TokenUsage> {
         }
     })
 }
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tool_uses_and_results_from_stream_json() {
+        let call: serde_json::Value = serde_json::json!({
+            "type": "assistant",
+            "message": { "content": [
+                { "type": "text", "text": "Let me look." },
+                { "type": "tool_use", "id": "t1", "name": "Read", "input": { "path": "a.rs" } }
+            ] }
+        });
+        let event = parse_json_event(&call);
+        assert_eq!(event.tool_calls.len(), 1);
+        assert_eq!(event.tool_calls[0].name, "Read");
+        assert_eq!(event.tool_calls[0].input["path"], "a.rs");
+
+        let result: serde_json::Value = serde_json::json!({
+            "type": "user",
+            "message": { "content": [{
+                "type": "tool_result", "tool_use_id": "t1", "is_error": true,
+                "content": [{ "type": "text", "text": "not found" }]
+            }] }
+        });
+        let event = parse_json_event(&result);
+        assert_eq!(event.tool_calls[0].id.as_deref(), Some("t1"));
+        assert_eq!(event.tool_calls[0].output.as_deref(), Some("not found"));
+        assert!(event.tool_calls[0].is_error);
+    }
+}
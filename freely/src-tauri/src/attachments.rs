@@ -0,0 +1,504 @@
+//! Content-addressed storage for message attachments (migration 9).
+//!
+//! File contents live in `attachment_blobs`, keyed by the SHA-256 of the
+//! decoded bytes, so a screenshot attached to several messages is stored
+//! once. `message_attachments` links messages to blobs and keeps each
+//! file's name and declared type. Triggers on that table maintain
+//! `ref_count` and delete a blob when its last reference goes away, which
+//! covers messages removed directly, by cascade from their conversation, or
+//! by retention pruning. Keeping blobs in the database means they are
+//! encrypted and backed up together with the messages that use them.
+//!
+//! The frontend contract is unchanged: attachments are read back as base64
+//! `AttachedFile`s. Rows written before migration 9 keep their inline JSON
+//! until [`move_inline_attachments`] moves them over at startup.
+
+use crate::chat_history::{AttachedFile, ChatMessage};
+use crate::db;
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use image::codecs::png::PngEncoder;
+use image::{ColorType, ImageEncoder};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::AppHandle;
+
+/// Longest edge of generated thumbnails, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Stored attachment contents, as returned to the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentBlob {
+    pub hash: String,
+    pub mime_type: String,
+    pub byte_size: i64,
+    pub base64: String,
+}
+
+/// Result of [`collect_garbage`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GarbageReport {
+    /// Blobs whose reference count had drifted and was corrected.
+    pub recounted: usize,
+    /// Unreferenced blobs that were deleted.
+    pub removed: usize,
+    pub freed_bytes: i64,
+}
+
+/// A message's attachment as stored, before its contents are loaded.
+#[derive(Debug, Clone, PartialEq)]
+struct StoredRef {
+    attachment_id: String,
+    name: String,
+    mime_type: String,
+    size: u64,
+    hash: String,
+}
+
+fn hash_bytes(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode(file: &AttachedFile) -> Result<Vec<u8>, String> {
+    B64.decode(file.base64.trim())
+        .map_err(|e| format!("Attachment {} is not valid base64: {}", file.name, e))
+}
+
+/// PNG thumbnail for raster images, or `None` for anything that does not
+/// decode as one. SVG is skipped since it is not decoded here.
+fn make_thumbnail(mime_type: &str, data: &[u8]) -> Option<Vec<u8>> {
+    if !mime_type.starts_with("image/") || mime_type == "image/svg+xml" {
+        return None;
+    }
+    let thumbnail = image::load_from_memory(data)
+        .ok()?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgba8();
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(
+            thumbnail.as_raw(),
+            thumbnail.width(),
+            thumbnail.height(),
+            ColorType::Rgba8.into(),
+        )
+        .ok()?;
+    Some(png)
+}
+
+/// Store `data` unless a blob with the same contents exists, returning its
+/// hash. New blobs start with no references.
+pub(crate) fn store_blob(
+    conn: &Connection,
+    mime_type: &str,
+    data: &[u8],
+) -> Result<String, String> {
+    let hash = hash_bytes(data);
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM attachment_blobs WHERE hash = ?1)",
+            params![hash],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to look up attachment: {}", e))?;
+    if !exists {
+        conn.execute(
+            "INSERT INTO attachment_blobs (hash, mime_type, byte_size, data, thumbnail, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                hash,
+                mime_type,
+                data.len() as i64,
+                data,
+                make_thumbnail(mime_type, data),
+                db::now_ms()
+            ],
+        )
+        .map_err(|e| format!("Failed to store attachment: {}", e))?;
+    }
+    Ok(hash)
+}
+
+fn stored_refs(conn: &Connection, message_id: &str) -> Result<Vec<StoredRef>, String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT attachment_id, name, mime_type, size, blob_hash FROM message_attachments
+             WHERE message_id = ?1 ORDER BY position",
+        )
+        .map_err(|e| format!("Failed to query attachments: {}", e))?;
+    let refs = stmt
+        .query_map(params![message_id], |row| {
+            Ok(StoredRef {
+                attachment_id: row.get(0)?,
+                name: row.get(1)?,
+                mime_type: row.get(2)?,
+                size: row.get::<_, i64>(3)?.max(0) as u64,
+                hash: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query attachments: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attachment: {}", e))?;
+    Ok(refs)
+}
+
+/// Make `files` the attachments of `message_id`, storing new contents and
+/// releasing blobs that are no longer used. Unchanged lists are left alone.
+pub(crate) fn set_message_files(
+    conn: &Connection,
+    message_id: &str,
+    files: &[AttachedFile],
+) -> Result<(), String> {
+    let decoded = files
+        .iter()
+        .map(|file| decode(file).map(|data| (file, data)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let wanted: Vec<StoredRef> = decoded
+        .iter()
+        .map(|(file, data)| StoredRef {
+            attachment_id: file.id.clone(),
+            name: file.name.clone(),
+            mime_type: file.mime_type.clone(),
+            size: file.size,
+            hash: hash_bytes(data),
+        })
+        .collect();
+    if stored_refs(conn, message_id)? == wanted {
+        return Ok(());
+    }
+
+    conn.execute(
+        "DELETE FROM message_attachments WHERE message_id = ?1",
+        params![message_id],
+    )
+    .map_err(|e| format!("Failed to replace attachments: {}", e))?;
+    for (position, ((file, data), stored)) in decoded.iter().zip(&wanted).enumerate() {
+        store_blob(conn, &file.mime_type, data)?;
+        conn.execute(
+            "INSERT INTO message_attachments
+                 (message_id, position, attachment_id, name, mime_type, size, blob_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                message_id,
+                position as i64,
+                stored.attachment_id,
+                stored.name,
+                stored.mime_type,
+                stored.size as i64,
+                stored.hash
+            ],
+        )
+        .map_err(|e| format!("Failed to save attachment {}: {}", file.name, e))?;
+    }
+    Ok(())
+}
+
+/// Attachments of `message_id` with their contents, paired with the hash
+/// of each blob.
+pub(crate) fn load_message_files(
+    conn: &Connection,
+    message_id: &str,
+) -> Result<Vec<(String, AttachedFile)>, String> {
+    let mut files = Vec::new();
+    for stored in stored_refs(conn, message_id)? {
+        let data: Vec<u8> = conn
+            .query_row(
+                "SELECT data FROM attachment_blobs WHERE hash = ?1",
+                params![stored.hash],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to load attachment {}: {}", stored.name, e))?;
+        files.push((
+            stored.hash,
+            AttachedFile {
+                id: stored.attachment_id,
+                name: stored.name,
+                mime_type: stored.mime_type,
+                base64: B64.encode(data),
+                size: stored.size,
+            },
+        ));
+    }
+    Ok(files)
+}
+
+/// Fill in `attached_files` for messages read without inline JSON.
+pub(crate) fn hydrate(conn: &Connection, messages: &mut [ChatMessage]) -> Result<(), String> {
+    for message in messages.iter_mut().filter(|m| m.attached_files.is_none()) {
+        let files = load_message_files(conn, &message.id)?;
+        if !files.is_empty() {
+            message.attached_files = Some(files.into_iter().map(|(_, file)| file).collect());
+        }
+    }
+    Ok(())
+}
+
+/// Move attachments still stored as inline JSON into blob storage,
+/// returning how many messages were converted. Each message is converted in
+/// its own transaction; rows that do not decode are left as they are and
+/// still read correctly.
+pub(crate) fn move_inline_attachments(conn: &mut Connection) -> Result<usize, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, attached_files FROM messages WHERE attached_files IS NOT NULL")
+        .map_err(|e| format!("Failed to query attachments: {}", e))?;
+    let pending = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("Failed to query attachments: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attachment: {}", e))?;
+    drop(stmt);
+
+    let mut moved = 0;
+    for (message_id, json) in pending {
+        let Ok(files) = serde_json::from_str::<Vec<AttachedFile>>(&json) else {
+            continue;
+        };
+        if files.iter().any(|file| decode(file).is_err()) {
+            continue;
+        }
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        set_message_files(&tx, &message_id, &files)?;
+        tx.execute(
+            "UPDATE messages SET attached_files = NULL WHERE id = ?1",
+            params![message_id],
+        )
+        .map_err(|e| format!("Failed to update message {}: {}", message_id, e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit attachments: {}", e))?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+/// Recount references from `message_attachments` and delete blobs nothing
+/// refers to. The triggers normally keep counts exact; this repairs
+/// databases edited by hand or restored from a partial copy.
+pub(crate) fn collect_garbage(conn: &mut Connection) -> Result<GarbageReport, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let recounted = tx
+        .execute(
+            "UPDATE attachment_blobs
+             SET ref_count = (SELECT COUNT(*) FROM message_attachments a
+                              WHERE a.blob_hash = attachment_blobs.hash)
+             WHERE ref_count IS NOT (SELECT COUNT(*) FROM message_attachments a
+                                     WHERE a.blob_hash = attachment_blobs.hash)",
+            [],
+        )
+        .map_err(|e| format!("Failed to recount attachments: {}", e))?;
+    let freed_bytes: i64 = tx
+        .query_row(
+            "SELECT COALESCE(SUM(byte_size), 0) FROM attachment_blobs WHERE ref_count <= 0",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to measure attachments: {}", e))?;
+    let removed = tx
+        .execute("DELETE FROM attachment_blobs WHERE ref_count <= 0", [])
+        .map_err(|e| format!("Failed to delete attachments: {}", e))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to commit cleanup: {}", e))?;
+    Ok(GarbageReport {
+        recounted,
+        removed,
+        freed_bytes,
+    })
+}
+
+pub(crate) fn get_blob(conn: &Connection, hash: &str) -> Result<Option<AttachmentBlob>, String> {
+    conn.query_row(
+        "SELECT hash, mime_type, byte_size, data FROM attachment_blobs WHERE hash = ?1",
+        params![hash],
+        |row| {
+            Ok(AttachmentBlob {
+                hash: row.get(0)?,
+                mime_type: row.get(1)?,
+                byte_size: row.get(2)?,
+                base64: B64.encode(row.get::<_, Vec<u8>>(3)?),
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load attachment: {}", e))
+}
+
+pub(crate) fn get_thumbnail(conn: &Connection, hash: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT thumbnail FROM attachment_blobs WHERE hash = ?1",
+        params![hash],
+        |row| row.get::<_, Option<Vec<u8>>>(0),
+    )
+    .optional()
+    .map(|thumbnail| thumbnail.flatten().map(|png| B64.encode(png)))
+    .map_err(|e| format!("Failed to load thumbnail: {}", e))
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+/// Full contents of the attachment with the given hash.
+#[tauri::command]
+pub fn get_attachment(app: AppHandle, hash: String) -> Result<Option<AttachmentBlob>, String> {
+    let conn = db::open(&app)?;
+    get_blob(&conn, &hash)
+}
+
+/// Base64 PNG thumbnail of an image attachment, if one was generated.
+#[tauri::command]
+pub fn get_attachment_thumbnail(app: AppHandle, hash: String) -> Result<Option<String>, String> {
+    let conn = db::open(&app)?;
+    get_thumbnail(&conn, &hash)
+}
+
+#[tauri::command]
+pub fn collect_orphaned_attachments(app: AppHandle) -> Result<GarbageReport, String> {
+    let mut conn = db::open(&app)?;
+    collect_garbage(&mut conn)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_history::{get_conversation, save_conversation, ChatConversation};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbaImage::new(width, height);
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(image.as_raw(), width, height, ColorType::Rgba8.into())
+            .unwrap();
+        png
+    }
+
+    fn file(id: &str, data: &[u8]) -> AttachedFile {
+        AttachedFile {
+            id: id.to_string(),
+            name: format!("{}.png", id),
+            mime_type: "image/png".to_string(),
+            base64: B64.encode(data),
+            size: data.len() as u64,
+        }
+    }
+
+    fn conversation(id: &str, files: Vec<AttachedFile>) -> ChatConversation {
+        ChatConversation {
+            id: id.to_string(),
+            title: "Screenshots".to_string(),
+            messages: vec![ChatMessage {
+                id: format!("{}-m1", id),
+                role: "user".to_string(),
+                content: "What is on screen?".to_string(),
+                timestamp: 1,
+                attached_files: Some(files),
+                parent_id: None,
+                metadata: None,
+            }],
+            created_at: 1,
+            updated_at: 1,
+        }
+    }
+
+    fn blob_counts(conn: &Connection) -> Vec<(String, i64)> {
+        let mut stmt = conn
+            .prepare("SELECT hash, ref_count FROM attachment_blobs ORDER BY hash")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        rows.collect::<Result<Vec<_>, _>>().unwrap()
+    }
+
+    #[test]
+    fn identical_files_share_a_blob_that_is_freed_with_its_last_message() {
+        let mut conn = db::open_in_memory();
+        let shot = png(800, 400);
+        save_conversation(&mut conn, &conversation("c1", vec![file("a", &shot)])).unwrap();
+        let second = conversation("c2", vec![file("b", &shot), file("c", b"notes")]);
+        save_conversation(&mut conn, &second).unwrap();
+
+        let counts = blob_counts(&conn);
+        assert_eq!(counts.len(), 2);
+        assert!(counts.contains(&(hash_bytes(&shot), 2)));
+
+        let inline: Option<String> = conn
+            .query_row(
+                "SELECT attached_files FROM messages WHERE id = 'c2-m1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(inline.is_none(), "contents are no longer inlined");
+        let loaded = get_conversation(&conn, "c2").unwrap().unwrap();
+        assert_eq!(loaded.messages, second.messages);
+
+        // Cascading deletes go through the same triggers.
+        conn.execute("DELETE FROM conversations WHERE id = 'c2'", [])
+            .unwrap();
+        assert_eq!(blob_counts(&conn), vec![(hash_bytes(&shot), 1)]);
+        conn.execute("DELETE FROM conversations WHERE id = 'c1'", [])
+            .unwrap();
+        assert!(blob_counts(&conn).is_empty());
+    }
+
+    #[test]
+    fn images_get_bounded_thumbnails() {
+        let conn = db::open_in_memory();
+        let hash = store_blob(&conn, "image/png", &png(1024, 512)).unwrap();
+        let thumbnail = B64
+            .decode(get_thumbnail(&conn, &hash).unwrap().unwrap())
+            .unwrap();
+        let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+        let text = store_blob(&conn, "text/plain", b"hello").unwrap();
+        assert_eq!(get_thumbnail(&conn, &text).unwrap(), None);
+        assert_eq!(get_blob(&conn, &text).unwrap().unwrap().byte_size, 5);
+    }
+
+    #[test]
+    fn moves_inline_json_and_collects_orphans() {
+        let mut conn = db::open_in_memory();
+        let legacy = serde_json::to_string(&vec![file("a", b"legacy")]).unwrap();
+        conn.execute_batch(&format!(
+            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES ('c1', 't', 1, 1);
+             INSERT INTO messages (id, conversation_id, role, content, timestamp, parent_id, attached_files)
+                 VALUES ('m1', 'c1', 'user', 'hi', 1, NULL, '{}'),
+                        ('m2', 'c1', 'user', 'bad', 2, 'm1', '[{{\"id\":\"x\",\"name\":\"x\",\"type\":\"text/plain\",\"base64\":\"%%%\",\"size\":1}}]');",
+            legacy
+        ))
+        .unwrap();
+
+        assert_eq!(move_inline_attachments(&mut conn).unwrap(), 1);
+        assert_eq!(move_inline_attachments(&mut conn).unwrap(), 0);
+        let loaded = get_conversation(&conn, "c1").unwrap().unwrap();
+        assert_eq!(
+            loaded.messages[0].attached_files,
+            Some(vec![file("a", b"legacy")])
+        );
+        assert_eq!(loaded.messages[1].attached_files.as_ref().unwrap().len(), 1);
+
+        store_blob(&conn, "text/plain", b"orphan").unwrap();
+        conn.execute("UPDATE attachment_blobs SET ref_count = 7", [])
+            .unwrap();
+        let report = collect_garbage(&mut conn).unwrap();
+        assert_eq!(report.recounted, 2);
+        assert_eq!(report.removed, 1);
+        assert_eq!(report.freed_bytes, 6);
+        assert_eq!(blob_counts(&conn), vec![(hash_bytes(b"legacy"), 1)]);
+    }
+}
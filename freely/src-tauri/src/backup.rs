@@ -0,0 +1,546 @@
+//! Full backup and restore of Freely's data as a single zip archive.
+//!
+//! Archive layout (format version 1):
+//!
+//! ```text
+//! manifest.json        BackupManifest (version, app version, contents)
+//! database/freely.db   consistent snapshot of the chat database
+//! claude/...           the managed `.claude/` directory
+//! recordings/...       saved continuous-mode recordings
+//! settings.json        frontend settings, when supplied by the caller
+//! ```
+//!
+//! Whisper models are large and re-downloadable, so only their names and
+//! sizes are recorded in the manifest.
+//!
+//! Restoring copies the database into the live file with SQLite's online
+//! backup API (so connections that are already open see the new content) and
+//! swaps `.claude/` and `recordings/` atomically. Settings are returned for
+//! the frontend to apply.
+
+use crate::data_dir::{self, DataLayout};
+use crate::db;
+use crate::encryption;
+use crate::fs_utils::{archive_name, collect_files};
+use rusqlite::DatabaseName;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const MANIFEST: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "database/freely.db";
+const CLAUDE_PREFIX: &str = "claude/";
+const RECORDINGS_PREFIX: &str = "recordings/";
+const SETTINGS_ENTRY: &str = "settings.json";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhisperModelInfo {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: i64,
+    pub includes_database: bool,
+    pub includes_settings: bool,
+    pub claude_files: usize,
+    /// Absent from backups taken before recordings were included.
+    #[serde(default)]
+    pub recording_files: usize,
+    pub whisper_models: Vec<WhisperModelInfo>,
+    /// Model loaded in the local Whisper engine when the backup was taken.
+    pub loaded_whisper_model: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub manifest: BackupManifest,
+    pub database_restored: bool,
+    pub claude_files_restored: usize,
+    pub recording_files_restored: usize,
+    /// Settings captured in the backup; the frontend re-applies them.
+    pub settings: Option<serde_json::Value>,
+    /// Models listed in the backup that are not present locally.
+    pub missing_whisper_models: Vec<String>,
+    pub restart_required: bool,
+}
+
+fn zip_err(e: impl std::fmt::Display) -> String {
+    format!("Failed to write backup: {}", e)
+}
+
+/// Whisper `.bin` models present in `models_dir`.
+pub(crate) fn list_whisper_models(models_dir: &Path) -> Vec<WhisperModelInfo> {
+    let mut models: Vec<WhisperModelInfo> = std::fs::read_dir(models_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.path().extension().and_then(|ext| ext.to_str()) == Some("bin"))
+        .map(|e| WhisperModelInfo {
+            name: e.file_name().to_string_lossy().to_string(),
+            size: e.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .collect();
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    models
+}
+
+/// Write a backup of `layout` to `dest`.
+pub(crate) fn export_backup_in(
+    layout: &DataLayout,
+    dest: &Path,
+    settings: Option<&serde_json::Value>,
+    loaded_whisper_model: Option<String>,
+) -> Result<BackupManifest, String> {
+    let file = std::fs::File::create(dest)
+        .map_err(|e| format!("Failed to create backup {}: {}", dest.display(), e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+
+    let includes_database = layout.db_path.is_file();
+    if includes_database {
+        // Snapshot next to the backup so we never read a half-written WAL state.
+        let snapshot = dest.with_extension("db-snapshot");
+        let result = data_dir::snapshot_database(&layout.db_path, &snapshot)
+            .and_then(|_| data_dir::verify_database(&snapshot, None))
+            .and_then(|_| {
+                zip.start_file(DATABASE_ENTRY, options).map_err(zip_err)?;
+                copy_into(&snapshot, &mut zip)
+            });
+        let _ = std::fs::remove_file(&snapshot);
+        result?;
+    }
+
+    let claude_files = add_dir(&mut zip, options, &layout.claude_dir(), CLAUDE_PREFIX)?;
+    let recording_files = add_dir(
+        &mut zip,
+        options,
+        &layout.recordings_dir(),
+        RECORDINGS_PREFIX,
+    )?;
+
+    if let Some(settings) = settings {
+        let json = serde_json::to_vec_pretty(settings).map_err(zip_err)?;
+        zip.start_file(SETTINGS_ENTRY, options).map_err(zip_err)?;
+        zip.write_all(&json).map_err(zip_err)?;
+    }
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: db::now_ms(),
+        includes_database,
+        includes_settings: settings.is_some(),
+        claude_files,
+        recording_files,
+        whisper_models: list_whisper_models(&layout.models_dir()),
+        loaded_whisper_model,
+    };
+    zip.start_file(MANIFEST, options).map_err(zip_err)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest).map_err(zip_err)?)
+        .map_err(zip_err)?;
+    zip.finish().map_err(zip_err)?;
+
+    Ok(manifest)
+}
+
+/// Stream the file at `path` into the current archive entry.
+fn copy_into(path: &Path, zip: &mut zip::ZipWriter<std::fs::File>) -> Result<(), String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    std::io::copy(&mut file, zip).map_err(zip_err)?;
+    Ok(())
+}
+
+/// Add every file under `dir` as an entry below `prefix`, returning how many.
+fn add_dir(
+    zip: &mut zip::ZipWriter<std::fs::File>,
+    options: zip::write::SimpleFileOptions,
+    dir: &Path,
+    prefix: &str,
+) -> Result<usize, String> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        collect_files(dir, dir, &mut files)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    }
+    for rel in &files {
+        zip.start_file(format!("{}{}", prefix, archive_name(rel)), options)
+            .map_err(zip_err)?;
+        copy_into(&dir.join(rel), zip)?;
+    }
+    Ok(files.len())
+}
+
+fn read_entry(
+    archive: &mut zip::ZipArchive<std::fs::File>,
+    name: &str,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", name, e)),
+    };
+    let mut bytes = Vec::new();
+    entry
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    Ok(Some(bytes))
+}
+
+/// Stream the entry `name` to `dest`, returning whether the archive has it.
+fn extract_entry(
+    archive: &mut zip::ZipArchive<std::fs::File>,
+    name: &str,
+    dest: &Path,
+) -> Result<bool, String> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(false),
+        Err(e) => return Err(format!("Failed to read {}: {}", name, e)),
+    };
+    std::fs::File::create(dest)
+        .and_then(|mut file| std::io::copy(&mut entry, &mut file))
+        .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+    Ok(true)
+}
+
+/// Copy `snapshot` into the live database at `db_path` page by page.
+///
+/// SQLite's backup API refuses encrypted databases, so when either side is
+/// encrypted the live file is rebuilt from the snapshot instead, keeping the
+/// live database's encryption.
+fn restore_database(db_path: &Path, snapshot: &Path) -> Result<(), String> {
+    data_dir::verify_database(snapshot, None)?;
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let key = db::session_key();
+    let live_key = key
+        .as_deref()
+        .filter(|_| db::is_encrypted(db_path) || !db_path.exists());
+    let snapshot_key = key.as_deref().filter(|_| db::is_encrypted(snapshot));
+    if live_key.is_some() || snapshot_key.is_some() {
+        return encryption::replace_database(db_path, snapshot, snapshot_key, live_key);
+    }
+    let mut conn = db::open_at(db_path)?;
+    conn.restore(
+        DatabaseName::Main,
+        snapshot,
+        None::<fn(rusqlite::backup::Progress)>,
+    )
+    .map_err(|e| format!("Failed to restore database: {}", e))
+}
+
+/// Extract the entries below `prefix` into a staging directory, then swap it
+/// in for the live `dir`, putting the old one back if the swap fails.
+fn restore_dir(
+    archive: &mut zip::ZipArchive<std::fs::File>,
+    prefix: &str,
+    dir: &Path,
+) -> Result<usize, String> {
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().trim_start_matches('.').to_string())
+        .unwrap_or_default();
+    let staging = dir.with_file_name(format!(".{}.restore", name));
+    let previous = dir.with_file_name(format!(".{}.pre-restore", name));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::create_dir_all(&staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+
+    let mut restored = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read backup entry: {}", e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        // `enclosed_name` rejects absolute paths and `..` traversal.
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+        let Ok(rel) = path.strip_prefix(prefix.trim_end_matches('/')) else {
+            continue;
+        };
+        let target: PathBuf = staging.join(rel);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::File::create(&target)
+            .and_then(|mut file| std::io::copy(&mut entry, &mut file))
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+        restored += 1;
+    }
+
+    let _ = std::fs::remove_dir_all(&previous);
+    let had_previous = dir.exists();
+    if had_previous {
+        std::fs::rename(dir, &previous)
+            .map_err(|e| format!("Failed to move aside {}: {}", dir.display(), e))?;
+    }
+    if let Err(e) = std::fs::rename(&staging, dir) {
+        if had_previous {
+            let _ = std::fs::rename(&previous, dir);
+        }
+        return Err(format!("Failed to restore {}: {}", dir.display(), e));
+    }
+    let _ = std::fs::remove_dir_all(&previous);
+
+    Ok(restored)
+}
+
+/// Restore a backup produced by [`export_backup_in`] into `layout`.
+pub(crate) fn import_backup_in(layout: &DataLayout, src: &Path) -> Result<RestoreReport, String> {
+    let file = std::fs::File::open(src)
+        .map_err(|e| format!("Failed to open backup {}: {}", src.display(), e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Invalid backup: {}", e))?;
+
+    let manifest: BackupManifest = {
+        let raw = read_entry(&mut archive, MANIFEST)?
+            .ok_or_else(|| "Invalid backup: missing manifest.json".to_string())?;
+        serde_json::from_slice(&raw).map_err(|e| format!("Invalid backup manifest: {}", e))?
+    };
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "Backup format version {} is newer than this app supports ({})",
+            manifest.format_version, FORMAT_VERSION
+        ));
+    }
+
+    std::fs::create_dir_all(&layout.data_dir)
+        .map_err(|e| format!("Failed to create {}: {}", layout.data_dir.display(), e))?;
+
+    let snapshot = layout.data_dir.join(".freely-restore.db");
+    let result = extract_entry(&mut archive, DATABASE_ENTRY, &snapshot).and_then(|found| {
+        if found {
+            restore_database(&layout.db_path, &snapshot)?;
+        }
+        Ok(found)
+    });
+    let _ = std::fs::remove_file(&snapshot);
+    let database_restored = result?;
+
+    let claude_files_restored = if manifest.claude_files > 0 {
+        restore_dir(&mut archive, CLAUDE_PREFIX, &layout.claude_dir())?
+    } else {
+        0
+    };
+    let recording_files_restored = if manifest.recording_files > 0 {
+        restore_dir(&mut archive, RECORDINGS_PREFIX, &layout.recordings_dir())?
+    } else {
+        0
+    };
+
+    let settings = read_entry(&mut archive, SETTINGS_ENTRY)?
+        .map(|raw| serde_json::from_slice(&raw))
+        .transpose()
+        .map_err(|e| format!("Invalid settings in backup: {}", e))?;
+
+    let local_models: Vec<String> = list_whisper_models(&layout.models_dir())
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+    let missing_whisper_models = manifest
+        .whisper_models
+        .iter()
+        .filter(|m| !local_models.contains(&m.name))
+        .map(|m| m.name.clone())
+        .collect();
+
+    Ok(RestoreReport {
+        manifest,
+        database_restored,
+        claude_files_restored,
+        recording_files_restored,
+        settings,
+        missing_whisper_models,
+        restart_required: database_restored,
+    })
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+/// Export everything Freely owns to `dest_path`. `settings` is the frontend's
+/// settings snapshot (localStorage lives in the webview, out of Rust's reach).
+#[tauri::command]
+pub fn export_backup(
+    app: AppHandle,
+    dest_path: String,
+    settings: Option<serde_json::Value>,
+) -> Result<BackupManifest, String> {
+    let layout = data_dir::current_layout(&app)?;
+    let loaded_model = app
+        .state::<crate::WhisperState>()
+        .engine
+        .lock()
+        .as_ref()
+        .and_then(|engine| engine.status().model_path);
+    export_backup_in(
+        &layout,
+        Path::new(&dest_path),
+        settings.as_ref(),
+        loaded_model,
+    )
+}
+
+/// Restore a backup. Restart the app afterwards (see `restart_app`) so every
+/// window reloads the restored data.
+#[tauri::command]
+pub fn import_backup(app: AppHandle, src_path: String) -> Result<RestoreReport, String> {
+    let layout = data_dir::current_layout(&app)?;
+    import_backup_in(&layout, Path::new(&src_path))
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn layout_with_data(root: &Path, title: &str) -> DataLayout {
+        let layout = DataLayout::relocated(root.to_path_buf());
+        std::fs::create_dir_all(layout.claude_dir().join("skills/pdf")).unwrap();
+        std::fs::write(layout.claude_dir().join("CLAUDE.md"), title).unwrap();
+        std::fs::write(layout.claude_dir().join("skills/pdf/SKILL.md"), "pdf").unwrap();
+        std::fs::create_dir_all(layout.models_dir()).unwrap();
+        std::fs::write(layout.models_dir().join("ggml-tiny.en.bin"), [0u8; 16]).unwrap();
+        let session = layout.recordings_dir().join(format!("session-{}", title));
+        std::fs::create_dir_all(&session).unwrap();
+        std::fs::write(session.join("recording.flac"), title).unwrap();
+
+        let conn = db::open_at(&layout.db_path).unwrap();
+        for (_, _, sql) in crate::db::MIGRATIONS {
+            conn.execute_batch(sql).unwrap();
+        }
+        conn.execute(
+            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES ('c1', ?1, 1, 1)",
+            [title],
+        )
+        .unwrap();
+        layout
+    }
+
+    fn title(layout: &DataLayout) -> String {
+        db::open_at(&layout.db_path)
+            .unwrap()
+            .query_row("SELECT title FROM conversations", [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn export_then_import_restores_database_config_and_settings() {
+        let tmp = TempDir::new().unwrap();
+        let source = layout_with_data(&tmp.path().join("a"), "original");
+        let backup = tmp.path().join("freely-backup.zip");
+        let settings = serde_json::json!({ "theme": "dark" });
+
+        let manifest = export_backup_in(&source, &backup, Some(&settings), None).unwrap();
+        assert!(manifest.includes_database);
+        assert_eq!(manifest.claude_files, 2);
+        assert_eq!(manifest.recording_files, 1);
+        assert_eq!(manifest.whisper_models.len(), 1);
+
+        let target = layout_with_data(&tmp.path().join("b"), "overwritten");
+        std::fs::write(target.claude_dir().join("stale.md"), "stale").unwrap();
+        std::fs::remove_dir_all(target.models_dir()).unwrap();
+
+        let report = import_backup_in(&target, &backup).unwrap();
+        assert!(report.database_restored);
+        assert_eq!(report.claude_files_restored, 2);
+        assert_eq!(report.recording_files_restored, 1);
+        assert_eq!(report.settings, Some(settings));
+        assert_eq!(report.missing_whisper_models, vec!["ggml-tiny.en.bin"]);
+
+        assert_eq!(title(&target), "original");
+        assert_eq!(
+            std::fs::read_to_string(target.claude_dir().join("CLAUDE.md")).unwrap(),
+            "original"
+        );
+        assert!(!target.claude_dir().join("stale.md").exists());
+        assert!(!tmp.path().join("b/.claude.pre-restore").exists());
+        assert_eq!(
+            std::fs::read_to_string(
+                target
+                    .recordings_dir()
+                    .join("session-original/recording.flac")
+            )
+            .unwrap(),
+            "original"
+        );
+        assert!(!target.recordings_dir().join("session-overwritten").exists());
+        assert!(!tmp.path().join("b/.recordings.pre-restore").exists());
+    }
+
+    #[test]
+    fn restore_is_visible_to_an_already_open_connection() {
+        let tmp = TempDir::new().unwrap();
+        let source = layout_with_data(&tmp.path().join("a"), "from backup");
+        let backup = tmp.path().join("backup.zip");
+        export_backup_in(&source, &backup, None, None).unwrap();
+
+        let target = layout_with_data(&tmp.path().join("b"), "live");
+        let live = db::open_at(&target.db_path).unwrap();
+        import_backup_in(&target, &backup).unwrap();
+
+        let seen: String = live
+            .query_row("SELECT title FROM conversations", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(seen, "from backup");
+    }
+
+    #[test]
+    fn rejects_archives_without_manifest_or_from_newer_versions() {
+        let tmp = TempDir::new().unwrap();
+        let layout = DataLayout::relocated(tmp.path().join("data"));
+
+        let bogus = tmp.path().join("bogus.zip");
+        {
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&bogus).unwrap());
+            zip.start_file("hello.txt", zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(b"hi").unwrap();
+            zip.finish().unwrap();
+        }
+        assert!(import_backup_in(&layout, &bogus).is_err());
+
+        let future = tmp.path().join("future.zip");
+        {
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&future).unwrap());
+            zip.start_file(MANIFEST, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            let manifest = BackupManifest {
+                format_version: FORMAT_VERSION + 1,
+                app_version: "9.9.9".to_string(),
+                created_at: 0,
+                includes_database: false,
+                includes_settings: false,
+                claude_files: 0,
+                recording_files: 0,
+                whisper_models: vec![],
+                loaded_whisper_model: None,
+            };
+            zip.write_all(&serde_json::to_vec(&manifest).unwrap())
+                .unwrap();
+            zip.finish().unwrap();
+        }
+        let err = import_backup_in(&layout, &future).unwrap_err();
+        assert!(err.contains("newer"), "unexpected error: {}", err);
+    }
+}
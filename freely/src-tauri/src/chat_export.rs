@@ -0,0 +1,553 @@
+//! Export conversations to Markdown, JSON or standalone HTML, and import the
+//! JSON format back.
+//!
+//! # JSON format (`freely.conversation`, version 1)
+//!
+//! ```json
+//! {
+//!   "schema": "freely.conversation",
+//!   "version": 1,
+//!   "exportedAt": 1718000000000,
+//!   "appVersion": "0.1.0",
+//!   "conversation": {
+//!     "id": "…", "title": "…", "createdAt": 0, "updatedAt": 0,
+//!     "messages": [{
+//!       "id": "…",
+//!       "role": "user" | "assistant" | "system",
+//!       "content": "…",
+//!       "timestamp": 0,
+//!       "attachedFiles": [{ "id", "name", "type", "base64", "size" }],
+//!       "metadata": {
+//!         "model": "…",
+//!         "toolCalls": [{ "id", "name", "input", "output", "isError" }],
+//!         "tokenUsage": { "input_tokens": 0, "output_tokens": 0 }
+//!       }
+//!     }]
+//!   }
+//! }
+//! ```
+//!
+//! Timestamps are milliseconds since the Unix epoch. `attachedFiles` and
+//! `metadata` (and each field inside `metadata`) are optional. `messages` is
+//! a single branch in order; `parentId` is ignored on import. Readers must
+//! reject a `version` newer than they understand.
+
+use crate::chat_history::{self, AttachedFile, ChatConversation, ChatMessage};
+use crate::db;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use tauri::AppHandle;
+
+pub(crate) const EXPORT_SCHEMA: &str = "freely.conversation";
+pub(crate) const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDocument {
+    pub schema: String,
+    pub version: u32,
+    pub exported_at: i64,
+    pub app_version: String,
+    pub conversation: ChatConversation,
+}
+
+// ============================================================================
+// Rendering
+// ============================================================================
+
+fn format_timestamp(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| ms.to_string())
+}
+
+fn role_label(role: &str) -> &str {
+    match role {
+        "user" => "User",
+        "assistant" => "Assistant",
+        "system" => "System",
+        other => other,
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Backslash-escape Markdown metacharacters so `text` renders literally on a
+/// single line, as in a heading or list item.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' | '`' | '*' | '_' | '{' | '}' | '[' | ']' | '(' | ')' | '#' | '+' | '-'
+            | '.' | '!' | '|' | '<' | '>' | '~' | '&' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            '\r' | '\n' => out.push(' '),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// A fence longer than any backtick run in `text`, so content cannot close it.
+fn code_fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn usage_line(message: &ChatMessage) -> Option<String> {
+    let metadata = message.metadata.as_ref()?;
+    let mut parts = Vec::new();
+    if let Some(model) = &metadata.model {
+        parts.push(format!("model {}", escape_markdown(model)));
+    }
+    if let Some(usage) = &metadata.token_usage {
+        parts.push(format!(
+            "{} input / {} output tokens",
+            usage.input_tokens, usage.output_tokens
+        ));
+    }
+    (!parts.is_empty()).then(|| parts.join(" · "))
+}
+
+pub(crate) fn render_markdown(conversation: &ChatConversation) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", escape_markdown(&conversation.title));
+    let _ = writeln!(
+        out,
+        "_Created {} · {} messages_\n",
+        format_timestamp(conversation.created_at),
+        conversation.messages.len()
+    );
+
+    for message in &conversation.messages {
+        let _ = writeln!(
+            out,
+            "## {} — {}\n",
+            escape_markdown(role_label(&message.role)),
+            format_timestamp(message.timestamp)
+        );
+        let _ = writeln!(out, "{}\n", message.content.trim_end());
+
+        if let Some(files) = message.attached_files.as_ref().filter(|f| !f.is_empty()) {
+            let _ = writeln!(out, "**Attachments**\n");
+            for file in files {
+                let _ = writeln!(
+                    out,
+                    "- {} ({}, {})",
+                    escape_markdown(&file.name),
+                    escape_markdown(&file.mime_type),
+                    format_size(file.size)
+                );
+            }
+            out.push('\n');
+        }
+
+        if let Some(metadata) = &message.metadata {
+            for call in &metadata.tool_calls {
+                let input = serde_json::to_string_pretty(&call.input).unwrap_or_default();
+                let fence = code_fence(&input);
+                let _ = writeln!(
+                    out,
+                    "<details><summary>Tool call: {}{}</summary>\n\n{}json\n{}\n{}\n",
+                    escape_html(&call.name),
+                    if call.is_error { " (error)" } else { "" },
+                    fence,
+                    input,
+                    fence
+                );
+                if let Some(output) = &call.output {
+                    let fence = code_fence(output);
+                    let _ = writeln!(out, "{}\n{}\n{}\n", fence, output.trim_end(), fence);
+                }
+                let _ = writeln!(out, "</details>\n");
+            }
+        }
+
+        if let Some(line) = usage_line(message) {
+            let _ = writeln!(out, "_{}_\n", line);
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn render_attachment_html(file: &AttachedFile) -> String {
+    let name = escape_html(&file.name);
+    // Only inline raster images; everything else is listed by name.
+    let inline_image = file.mime_type.starts_with("image/") && file.mime_type != "image/svg+xml";
+    if inline_image {
+        format!(
+            "<figure><img src=\"data:{};base64,{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>",
+            escape_html(&file.mime_type),
+            escape_html(&file.base64),
+            name,
+            name
+        )
+    } else {
+        format!(
+            "<div class=\"file\">{} ({}, {})</div>",
+            name,
+            escape_html(&file.mime_type),
+            format_size(file.size)
+        )
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',sans-serif;max-width:820px;margin:2rem auto;padding:0 1rem;color:#1f2328;line-height:1.5}
+header{border-bottom:1px solid #d0d7de;margin-bottom:1.5rem}
+.message{border:1px solid #d0d7de;border-radius:8px;padding:.75rem 1rem;margin:1rem 0}
+.message.user{background:#f6f8fa}
+.meta{color:#656d76;font-size:.85rem}
+.content{white-space:pre-wrap;word-wrap:break-word}
+pre{background:#f6f8fa;padding:.5rem;overflow:auto}
+img{max-width:100%}
+.file{font-size:.9rem}";
+
+pub(crate) fn render_html(conversation: &ChatConversation) -> String {
+    let title = escape_html(&conversation.title);
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<header><h1>{}</h1><p class=\"meta\">Created {} · {} messages</p></header>\n",
+        title,
+        HTML_STYLE,
+        title,
+        format_timestamp(conversation.created_at),
+        conversation.messages.len()
+    );
+
+    for message in &conversation.messages {
+        let _ = write!(
+            out,
+            "<section class=\"message {}\">\n<div class=\"meta\"><strong>{}</strong> · {}</div>\n<div class=\"content\">{}</div>\n",
+            escape_html(&message.role),
+            role_label(&message.role),
+            format_timestamp(message.timestamp),
+            escape_html(message.content.trim_end())
+        );
+        for file in message.attached_files.iter().flatten() {
+            out.push_str(&render_attachment_html(file));
+            out.push('\n');
+        }
+        if let Some(metadata) = &message.metadata {
+            for call in &metadata.tool_calls {
+                let input = serde_json::to_string_pretty(&call.input).unwrap_or_default();
+                let _ = write!(
+                    out,
+                    "<details><summary>Tool call: {}{}</summary><pre>{}</pre>",
+                    escape_html(&call.name),
+                    if call.is_error { " (error)" } else { "" },
+                    escape_html(&input)
+                );
+                if let Some(output) = &call.output {
+                    let _ = write!(out, "<pre>{}</pre>", escape_html(output));
+                }
+                out.push_str("</details>\n");
+            }
+        }
+        if let Some(line) = usage_line(message) {
+            let _ = writeln!(out, "<div class=\"meta\">{}</div>", escape_html(&line));
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub(crate) fn render_json(conversation: &ChatConversation) -> Result<String, String> {
+    let mut conversation = conversation.clone();
+    for message in &mut conversation.messages {
+        message.parent_id = None;
+    }
+    let document = ExportDocument {
+        schema: EXPORT_SCHEMA.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: db::now_ms(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        conversation,
+    };
+    serde_json::to_string_pretty(&document).map_err(|e| format!("Failed to serialize: {}", e))
+}
+
+pub(crate) fn render(
+    conversation: &ChatConversation,
+    format: ExportFormat,
+) -> Result<String, String> {
+    match format {
+        ExportFormat::Markdown => Ok(render_markdown(conversation)),
+        ExportFormat::Json => render_json(conversation),
+        ExportFormat::Html => Ok(render_html(conversation)),
+    }
+}
+
+// ============================================================================
+// Import
+// ============================================================================
+
+pub(crate) fn parse_document(json: &str) -> Result<ExportDocument, String> {
+    let document: ExportDocument =
+        serde_json::from_str(json).map_err(|e| format!("Invalid conversation export: {}", e))?;
+    if document.schema != EXPORT_SCHEMA {
+        return Err(format!("Unsupported export schema: {}", document.schema));
+    }
+    if document.version > EXPORT_VERSION {
+        return Err(format!(
+            "Export version {} is newer than this app supports ({})",
+            document.version, EXPORT_VERSION
+        ));
+    }
+    Ok(document)
+}
+
+fn id_taken(conn: &Connection, sql: &str, id: &str) -> Result<bool, String> {
+    conn.query_row(sql, [id], |_| Ok(()))
+        .map(|_| true)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(false),
+            other => Err(format!("Failed to check ids: {}", other)),
+        })
+}
+
+/// Import an exported conversation. If its id (or any message id) already
+/// exists — e.g. importing the same file twice — fresh ids are assigned so
+/// nothing local is overwritten.
+pub(crate) fn import_document(
+    conn: &mut Connection,
+    document: ExportDocument,
+) -> Result<ChatConversation, String> {
+    let mut conversation = document.conversation;
+
+    let mut collides = id_taken(
+        conn,
+        "SELECT 1 FROM conversations WHERE id = ?1",
+        &conversation.id,
+    )?;
+    for message in &conversation.messages {
+        if collides {
+            break;
+        }
+        collides = id_taken(conn, "SELECT 1 FROM messages WHERE id = ?1", &message.id)?;
+    }
+    if collides {
+        conversation.id = uuid::Uuid::new_v4().to_string();
+        for message in &mut conversation.messages {
+            message.id = uuid::Uuid::new_v4().to_string();
+        }
+    }
+    for message in &mut conversation.messages {
+        message.parent_id = None;
+    }
+
+    chat_history::save_conversation(conn, &conversation)
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+/// Write a conversation (its active branch, or the branch ending at
+/// `leaf_id`) to `dest_path` in the given format.
+#[tauri::command]
+pub fn export_conversation(
+    app: AppHandle,
+    conversation_id: String,
+    format: ExportFormat,
+    dest_path: String,
+    leaf_id: Option<String>,
+) -> Result<(), String> {
+    let conn = db::open(&app)?;
+    let conversation = chat_history::export_branch(&conn, &conversation_id, leaf_id.as_deref())?;
+    let rendered = render(&conversation, format)?;
+    std::fs::write(Path::new(&dest_path), rendered)
+        .map_err(|e| format!("Failed to write {}: {}", dest_path, e))
+}
+
+/// Render a conversation without writing it, e.g. for copying to the clipboard.
+#[tauri::command]
+pub fn render_conversation(
+    app: AppHandle,
+    conversation_id: String,
+    format: ExportFormat,
+    leaf_id: Option<String>,
+) -> Result<String, String> {
+    let conn = db::open(&app)?;
+    let conversation = chat_history::export_branch(&conn, &conversation_id, leaf_id.as_deref())?;
+    render(&conversation, format)
+}
+
+#[tauri::command]
+pub fn import_conversation(app: AppHandle, src_path: String) -> Result<ChatConversation, String> {
+    let json = std::fs::read_to_string(&src_path)
+        .map_err(|e| format!("Failed to read {}: {}", src_path, e))?;
+    let document = parse_document(&json)?;
+    let mut conn = db::open(&app)?;
+    import_document(&mut conn, document)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::TokenUsage;
+    use crate::chat_history::{MessageMetadata, ToolCall};
+
+    fn sample() -> ChatConversation {
+        ChatConversation {
+            id: "c1".to_string(),
+            title: "Deploy <script>".to_string(),
+            created_at: 1_700_000_000_000,
+            updated_at: 1_700_000_100_000,
+            messages: vec![
+                ChatMessage {
+                    id: "m1".to_string(),
+                    role: "user".to_string(),
+                    content: "Why did the build fail?".to_string(),
+                    timestamp: 1_700_000_000_000,
+                    attached_files: Some(vec![AttachedFile {
+                        id: "f1".to_string(),
+                        name: "log.png".to_string(),
+                        mime_type: "image/png".to_string(),
+                        base64: "iVBORw0K".to_string(),
+                        size: 2048,
+                    }]),
+                    parent_id: None,
+                    metadata: None,
+                },
+                ChatMessage {
+                    id: "m2".to_string(),
+                    role: "assistant".to_string(),
+                    content: "A test timed out.".to_string(),
+                    timestamp: 1_700_000_060_000,
+                    attached_files: None,
+                    parent_id: Some("m1".to_string()),
+                    metadata: Some(MessageMetadata {
+                        model: Some("sonnet".to_string()),
+                        tool_calls: vec![ToolCall {
+                            id: Some("t1".to_string()),
+                            name: "Bash".to_string(),
+                            input: serde_json::json!({ "command": "cargo test" }),
+                            output: Some("```\n1 failed\n```".to_string()),
+                            is_error: false,
+                        }],
+                        token_usage: Some(TokenUsage {
+                            input_tokens: 120,
+                            output_tokens: 30,
+                        }),
+                    }),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn markdown_includes_attachments_tools_and_usage() {
+        let md = render_markdown(&sample());
+        assert!(md.starts_with("# Deploy \\<script\\>\n"));
+        assert!(md.contains("## User — 2023-11-14 22:13 UTC"));
+        assert!(md.contains("- log\\.png (image/png, 2.0 KB)"));
+        assert!(md.contains("Tool call: Bash"));
+        assert!(
+            md.contains("````\n```\n1 failed\n```\n````"),
+            "fence outgrows content: {}",
+            md
+        );
+        assert!(md.contains("_model sonnet · 120 input / 30 output tokens_"));
+    }
+
+    #[test]
+    fn markdown_escapes_titles_and_names() {
+        let mut conversation = sample();
+        conversation.title = "# [Plan](x) *v2*\nnext".to_string();
+        let metadata = conversation.messages[1].metadata.as_mut().unwrap();
+        metadata.tool_calls[0].name = "<b>Edit</b>".to_string();
+
+        let md = render_markdown(&conversation);
+        assert!(md.starts_with("# \\# \\[Plan\\]\\(x\\) \\*v2\\* next\n"));
+        assert!(md.contains("Tool call: &lt;b&gt;Edit&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn html_is_escaped_and_inlines_images() {
+        let html = render_html(&sample());
+        assert!(html.contains("<title>Deploy &lt;script&gt;</title>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("src=\"data:image/png;base64,iVBORw0K\""));
+        assert!(html.contains("cargo test"));
+    }
+
+    #[test]
+    fn json_roundtrips_and_reimport_gets_fresh_ids() {
+        let mut conn = db::open_in_memory();
+        let json = render_json(&sample()).unwrap();
+
+        let imported = import_document(&mut conn, parse_document(&json).unwrap()).unwrap();
+        assert_eq!(imported.id, "c1");
+        assert_eq!(imported.messages[1].metadata, sample().messages[1].metadata);
+        assert_eq!(
+            imported.messages[0].attached_files,
+            sample().messages[0].attached_files
+        );
+
+        let again = import_document(&mut conn, parse_document(&json).unwrap()).unwrap();
+        assert_ne!(again.id, "c1");
+        assert_eq!(again.messages.len(), 2);
+        assert_ne!(again.messages[0].id, "m1");
+        assert_eq!(
+            again.messages[1].parent_id.as_deref(),
+            Some(again.messages[0].id.as_str())
+        );
+    }
+
+    #[test]
+    fn rejects_foreign_or_newer_documents() {
+        let json = render_json(&sample()).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        value["version"] = serde_json::json!(EXPORT_VERSION + 1);
+        assert!(parse_document(&value.to_string())
+            .unwrap_err()
+            .contains("newer"));
+
+        value["version"] = serde_json::json!(EXPORT_VERSION);
+        value["schema"] = serde_json::json!("something.else");
+        assert!(parse_document(&value.to_string()).is_err());
+        assert!(parse_document("{}").is_err());
+    }
+}
@@ -0,0 +1,1375 @@
+//! Typed repository for chat history (`conversations` and `messages`).
+//!
+//! The frontend historically issued raw SQL through the SQL plugin
+//! (`chat-history.action.ts`). These functions give Rust code — agents, audio
+//! transcripts, background jobs — the same operations with validation and
+//! transactions, and the Tauri commands at the bottom expose them to the
+//! webview. Shapes match the frontend's `ChatConversation` / `ChatMessage`.
+//!
+//! Messages form a tree through `parent_id`: editing a message or
+//! regenerating a reply forks a sibling instead of overwriting it. A
+//! conversation's `messages` are always the *active branch* — the path from
+//! the root to `active_leaf_id` (or to the newest message when unset).
+
+use crate::agents::TokenUsage;
+use crate::attachments;
+use crate::db;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+const ROLES: [&str; 3] = ["user", "assistant", "system"];
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+const MAX_TITLE_CHARS: usize = 500;
+
+/// Separator for tag names aggregated with `group_concat`. Tag names cannot
+/// contain control characters (see `organization`), so it is unambiguous.
+const TAG_SEPARATOR: char = '\u{1f}';
+
+const MESSAGE_COLUMNS: &str =
+    "id, conversation_id, role, content, timestamp, attached_files, parent_id, metadata";
+
+/// File attached to a message. Contents are kept in `attachment_blobs`
+/// (see `attachments`); older rows store them inline as JSON in
+/// `attached_files`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttachedFile {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    pub base64: String,
+    pub size: u64,
+}
+
+/// Tool invocation made by an agent while producing a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub input: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+/// Agent details recorded alongside a message, stored as JSON in `metadata`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatMessage {
+    pub id: String,
+    /// "user" | "assistant" | "system"
+    pub role: String,
+    pub content: String,
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attached_files: Option<Vec<AttachedFile>>,
+    /// Previous message on this branch. Derived from position when saving a
+    /// whole conversation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MessageMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatConversation {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Conversation without its messages, for list views.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub message_count: i64,
+    pub pinned: bool,
+    pub folder_id: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Narrows [`list_conversations`]; every set field must match.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationFilter {
+    /// Tag name (case-insensitive).
+    pub tag: Option<String>,
+    pub folder_id: Option<String>,
+    /// Only conversations outside any folder. Ignored when `folder_id` is set.
+    #[serde(default)]
+    pub unfiled: bool,
+    pub pinned: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationPage {
+    pub items: Vec<ConversationSummary>,
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessagePage {
+    /// Oldest first.
+    pub items: Vec<ChatMessage>,
+    /// Whether older messages exist before the first item.
+    pub has_more: bool,
+    /// Pass as `before` to fetch the next (older) page; absent on the last.
+    pub next_cursor: Option<MessageCursor>,
+}
+
+/// Position in a conversation's messages. Pages are ordered by
+/// `(timestamp, id)`, so messages sharing a timestamp are never skipped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageCursor {
+    pub timestamp: i64,
+    pub id: String,
+}
+
+/// One alternative at a fork: a message and the branch it leads to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchInfo {
+    pub message_id: String,
+    /// Newest leaf reached by following the most recent reply at each step.
+    pub leaf_id: String,
+    pub timestamp: i64,
+    pub preview: String,
+    /// Whether this alternative is on the active branch.
+    pub is_active: bool,
+}
+
+/// Attachment together with the message it belongs to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageAttachment {
+    pub message_id: String,
+    pub timestamp: i64,
+    /// Blob hash for `get_attachment` and `get_attachment_thumbnail`; absent
+    /// for attachments still stored inline.
+    pub hash: Option<String>,
+    pub file: AttachedFile,
+}
+
+fn page_size(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+fn row_to_message(row: &Row) -> rusqlite::Result<ChatMessage> {
+    let attached: Option<String> = row.get(5)?;
+    Ok(ChatMessage {
+        id: row.get(0)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        timestamp: row.get(4)?,
+        // Malformed JSON is treated like no attachments, as the frontend does.
+        attached_files: attached.and_then(|json| serde_json::from_str(&json).ok()),
+        parent_id: row.get(6)?,
+        metadata: row
+            .get::<_, Option<String>>(7)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+    })
+}
+
+/// Tag names in the order the tag list uses (`COLLATE NOCASE`);
+/// `group_concat` itself guarantees no order.
+fn sorted_tags<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut tags: Vec<String> = names.map(str::to_string).collect();
+    tags.sort_by(|a, b| {
+        a.to_ascii_lowercase()
+            .cmp(&b.to_ascii_lowercase())
+            .then_with(|| a.cmp(b))
+    });
+    tags
+}
+
+fn row_to_summary(row: &Row) -> rusqlite::Result<ConversationSummary> {
+    Ok(ConversationSummary {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        message_count: row.get(4)?,
+        pinned: row.get(5)?,
+        folder_id: row.get(6)?,
+        tags: row
+            .get::<_, Option<String>>(7)?
+            .map(|tags| sorted_tags(tags.split(TAG_SEPARATOR)))
+            .unwrap_or_default(),
+    })
+}
+
+// ============================================================================
+// Validation
+// ============================================================================
+
+fn validate_title(title: &str) -> Result<String, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Conversation title is required".to_string());
+    }
+    Ok(title.chars().take(MAX_TITLE_CHARS).collect())
+}
+
+fn validate_message(message: &ChatMessage) -> Result<(), String> {
+    if message.id.trim().is_empty() {
+        return Err("Message id is required".to_string());
+    }
+    if !ROLES.contains(&message.role.as_str()) {
+        return Err(format!("Invalid message role: {}", message.role));
+    }
+    if message.timestamp < 0 {
+        return Err(format!("Invalid timestamp for message {}", message.id));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Queries (connection-based for testability)
+// ============================================================================
+
+/// List conversations matching `filter`, pinned first, then most recently
+/// updated.
+pub(crate) fn list_conversations(
+    conn: &Connection,
+    filter: &ConversationFilter,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<ConversationPage, String> {
+    let mut conditions = vec!["1"];
+    let mut params: Vec<Value> = Vec::new();
+    if let Some(tag) = &filter.tag {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM conversation_tags ct JOIN tags t ON t.id = ct.tag_id
+                     WHERE ct.conversation_id = c.id AND t.name = ?)",
+        );
+        params.push(tag.trim().to_string().into());
+    }
+    if let Some(folder_id) = &filter.folder_id {
+        conditions.push("c.folder_id = ?");
+        params.push(folder_id.clone().into());
+    } else if filter.unfiled {
+        conditions.push("c.folder_id IS NULL");
+    }
+    if let Some(pinned) = filter.pinned {
+        conditions.push("c.pinned = ?");
+        params.push(i64::from(pinned).into());
+    }
+    let where_clause = conditions.join(" AND ");
+
+    let total: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM conversations c WHERE {}",
+                where_clause
+            ),
+            params_from_iter(params.iter()),
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to count conversations: {}", e))?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT c.id, c.title, c.created_at, c.updated_at,
+                    (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id),
+                    c.pinned, c.folder_id,
+                    (SELECT group_concat(t.name, char(31)) FROM conversation_tags ct
+                     JOIN tags t ON t.id = ct.tag_id WHERE ct.conversation_id = c.id)
+             FROM conversations c
+             WHERE {}
+             ORDER BY c.pinned DESC, c.updated_at DESC, c.id
+             LIMIT ? OFFSET ?",
+            where_clause
+        ))
+        .map_err(|e| format!("Failed to query conversations: {}", e))?;
+    params.push(i64::from(page_size(limit)).into());
+    params.push(i64::from(offset.unwrap_or(0)).into());
+    let items = stmt
+        .query_map(params_from_iter(params), row_to_summary)
+        .map_err(|e| format!("Failed to query conversations: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read conversation: {}", e))?;
+    Ok(ConversationPage { items, total })
+}
+
+/// Leaf of the active branch: `active_leaf_id`, or the newest message.
+fn active_leaf(conn: &Connection, conversation_id: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT COALESCE(
+             (SELECT active_leaf_id FROM conversations WHERE id = ?1),
+             (SELECT id FROM messages WHERE conversation_id = ?1
+              ORDER BY timestamp DESC, rowid DESC LIMIT 1))",
+        params![conversation_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to load active branch: {}", e))
+}
+
+/// Recursive CTE `path(id, depth)` walking up from the message bound to `?1`.
+const PATH_CTE: &str = "WITH RECURSIVE path(id, depth) AS (
+         SELECT ?1, 0
+         UNION ALL
+         SELECT m.parent_id, path.depth + 1
+         FROM messages m JOIN path ON m.id = path.id
+         WHERE m.parent_id IS NOT NULL
+     )";
+
+/// Messages from the root down to `leaf_id`, oldest first.
+fn path_to(conn: &Connection, leaf_id: &str) -> Result<Vec<ChatMessage>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} SELECT {} FROM messages JOIN path USING (id) ORDER BY path.depth DESC",
+            PATH_CTE, MESSAGE_COLUMNS
+        ))
+        .map_err(|e| format!("Failed to query messages: {}", e))?;
+    let rows = stmt
+        .query_map(params![leaf_id], row_to_message)
+        .map_err(|e| format!("Failed to query messages: {}", e))?;
+    let mut messages = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read message: {}", e))?;
+    attachments::hydrate(conn, &mut messages)?;
+    Ok(messages)
+}
+
+fn active_path(conn: &Connection, conversation_id: &str) -> Result<Vec<ChatMessage>, String> {
+    match active_leaf(conn, conversation_id)? {
+        Some(leaf) => path_to(conn, &leaf),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub(crate) fn get_conversation(
+    conn: &Connection,
+    id: &str,
+) -> Result<Option<ChatConversation>, String> {
+    let conversation = conn
+        .query_row(
+            "SELECT id, title, created_at, updated_at FROM conversations WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(ChatConversation {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    messages: Vec::new(),
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Failed to load conversation: {}", e))?;
+    let Some(mut conversation) = conversation else {
+        return Ok(None);
+    };
+    conversation.messages = active_path(conn, id)?;
+    Ok(Some(conversation))
+}
+
+/// Every conversation with its active branch, most recently updated first.
+pub(crate) fn list_all_conversations(conn: &Connection) -> Result<Vec<ChatConversation>, String> {
+    let ids: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT id FROM conversations ORDER BY updated_at DESC, id")
+            .map_err(|e| format!("Failed to query conversations: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Failed to query conversations: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read conversation: {}", e))?
+    };
+    let mut conversations = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(conversation) = get_conversation(conn, &id)? {
+            conversations.push(conversation);
+        }
+    }
+    Ok(conversations)
+}
+
+/// Page backwards through the active branch: the newest `limit` messages
+/// that sort before the `before` cursor, returned oldest first.
+pub(crate) fn list_messages(
+    conn: &Connection,
+    conversation_id: &str,
+    limit: Option<u32>,
+    before: Option<&MessageCursor>,
+) -> Result<MessagePage, String> {
+    let limit = page_size(limit);
+    let Some(leaf) = active_leaf(conn, conversation_id)? else {
+        return Ok(MessagePage {
+            items: Vec::new(),
+            has_more: false,
+            next_cursor: None,
+        });
+    };
+    // One extra row tells whether an older page exists.
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} SELECT {} FROM messages JOIN path USING (id)
+             WHERE ?2 IS NULL OR (timestamp, id) < (?2, ?3)
+             ORDER BY timestamp DESC, id DESC LIMIT ?4",
+            PATH_CTE, MESSAGE_COLUMNS
+        ))
+        .map_err(|e| format!("Failed to query messages: {}", e))?;
+    let rows = stmt
+        .query_map(
+            params![
+                leaf,
+                before.map(|c| c.timestamp),
+                before.map(|c| c.id.as_str()),
+                limit + 1
+            ],
+            row_to_message,
+        )
+        .map_err(|e| format!("Failed to query messages: {}", e))?;
+    let mut items = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read message: {}", e))?;
+    let has_more = items.len() > limit as usize;
+    items.truncate(limit as usize);
+    items.reverse();
+    attachments::hydrate(conn, &mut items)?;
+    let next_cursor = items.first().filter(|_| has_more).map(|m| MessageCursor {
+        timestamp: m.timestamp,
+        id: m.id.clone(),
+    });
+    Ok(MessagePage {
+        items,
+        has_more,
+        next_cursor,
+    })
+}
+
+/// Insert `message`, or update it in place if it already exists in this
+/// conversation, linking it under `parent_id`.
+fn upsert_message(
+    conn: &Connection,
+    conversation_id: &str,
+    message: &ChatMessage,
+    parent_id: Option<&str>,
+) -> Result<(), String> {
+    validate_message(message)?;
+    let metadata = match &message.metadata {
+        Some(metadata) if *metadata != MessageMetadata::default() => {
+            Some(serde_json::to_string(metadata).map_err(|e| e.to_string())?)
+        }
+        _ => None,
+    };
+    let changed = conn
+        .execute(
+            &format!(
+                "INSERT INTO messages ({}) VALUES (?1, ?2, ?3, ?4, ?5, NULL, ?6, ?7)
+                 ON CONFLICT(id) DO UPDATE SET
+                     role = excluded.role,
+                     content = excluded.content,
+                     timestamp = excluded.timestamp,
+                     attached_files = excluded.attached_files,
+                     parent_id = excluded.parent_id,
+                     metadata = excluded.metadata
+                 WHERE messages.conversation_id = excluded.conversation_id",
+                MESSAGE_COLUMNS
+            ),
+            params![
+                message.id,
+                conversation_id,
+                message.role,
+                message.content,
+                message.timestamp,
+                parent_id,
+                metadata
+            ],
+        )
+        .map_err(|e| format!("Failed to save message {}: {}", message.id, e))?;
+    if changed == 0 {
+        return Err(format!(
+            "Message {} belongs to another conversation",
+            message.id
+        ));
+    }
+    attachments::set_message_files(
+        conn,
+        &message.id,
+        message.attached_files.as_deref().unwrap_or_default(),
+    )
+}
+
+fn set_active_leaf(
+    conn: &Connection,
+    conversation_id: &str,
+    leaf_id: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE conversations SET active_leaf_id = ?2 WHERE id = ?1",
+        params![conversation_id, leaf_id],
+    )
+    .map_err(|e| format!("Failed to update active branch: {}", e))?;
+    Ok(())
+}
+
+fn ensure_conversation(conn: &Connection, conversation_id: &str) -> Result<(), String> {
+    conn.query_row(
+        "SELECT 1 FROM conversations WHERE id = ?1",
+        params![conversation_id],
+        |_| Ok(()),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load conversation: {}", e))?
+    .ok_or_else(|| format!("Conversation not found: {}", conversation_id))
+}
+
+/// Create a conversation or replace its active branch, atomically.
+///
+/// `messages` are linked in order. Messages that were on the previous active
+/// branch but are missing now are deleted with their replies; other branches
+/// are kept.
+pub(crate) fn save_conversation(
+    conn: &mut Connection,
+    conversation: &ChatConversation,
+) -> Result<ChatConversation, String> {
+    if conversation.id.trim().is_empty() {
+        return Err("Conversation id is required".to_string());
+    }
+    let title = validate_title(&conversation.title)?;
+    let now = db::now_ms();
+    let created_at = Some(conversation.created_at)
+        .filter(|t| *t > 0)
+        .unwrap_or(now);
+    let updated_at = Some(conversation.updated_at)
+        .filter(|t| *t > 0)
+        .unwrap_or(now);
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    tx.execute(
+        "INSERT INTO conversations (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET title = excluded.title, updated_at = excluded.updated_at",
+        params![conversation.id, title, created_at, updated_at],
+    )
+    .map_err(|e| format!("Failed to save conversation: {}", e))?;
+    let previous = active_path(&tx, &conversation.id)?;
+    let mut parent: Option<&str> = None;
+    for message in &conversation.messages {
+        upsert_message(&tx, &conversation.id, message, parent)?;
+        parent = Some(&message.id);
+    }
+    // Relinking happens first, so deleting a dropped message's replies cannot
+    // reach messages that are still on the branch.
+    for dropped in previous
+        .iter()
+        .filter(|old| !conversation.messages.iter().any(|m| m.id == old.id))
+    {
+        delete_subtree(&tx, &dropped.id)
+            .map_err(|e| format!("Failed to replace messages: {}", e))?;
+    }
+    set_active_leaf(&tx, &conversation.id, parent)?;
+    // The message triggers bump updated_at; keep the caller's value.
+    tx.execute(
+        "UPDATE conversations SET updated_at = ?2 WHERE id = ?1",
+        params![conversation.id, updated_at],
+    )
+    .map_err(|e| format!("Failed to save conversation: {}", e))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to save conversation: {}", e))?;
+
+    get_conversation(conn, &conversation.id)?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation.id))
+}
+
+/// Start a new conversation, optionally with a first message.
+pub(crate) fn create_conversation(
+    conn: &mut Connection,
+    title: &str,
+    first_message: Option<ChatMessage>,
+) -> Result<ChatConversation, String> {
+    let now = db::now_ms();
+    save_conversation(
+        conn,
+        &ChatConversation {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            messages: first_message.into_iter().collect(),
+            created_at: now,
+            updated_at: now,
+        },
+    )
+}
+
+/// Append one message to the end of the active branch.
+pub(crate) fn append_message(
+    conn: &mut Connection,
+    conversation_id: &str,
+    message: &ChatMessage,
+) -> Result<ChatMessage, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    ensure_conversation(&tx, conversation_id)?;
+    let parent_id = active_leaf(&tx, conversation_id)?;
+    insert_new_message(&tx, conversation_id, message, parent_id.as_deref())?;
+    set_active_leaf(&tx, conversation_id, Some(&message.id))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to save message: {}", e))?;
+    Ok(ChatMessage {
+        parent_id,
+        ..message.clone()
+    })
+}
+
+pub(crate) fn rename_conversation(conn: &Connection, id: &str, title: &str) -> Result<(), String> {
+    let title = validate_title(title)?;
+    let updated = conn
+        .execute(
+            "UPDATE conversations SET title = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, title, db::now_ms()],
+        )
+        .map_err(|e| format!("Failed to rename conversation: {}", e))?;
+    if updated == 0 {
+        return Err(format!("Conversation not found: {}", id));
+    }
+    Ok(())
+}
+
+/// Insert a message that must not exist yet.
+fn insert_new_message(
+    conn: &Connection,
+    conversation_id: &str,
+    message: &ChatMessage,
+    parent_id: Option<&str>,
+) -> Result<(), String> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM messages WHERE id = ?1",
+            params![message.id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to save message: {}", e))?;
+    if exists.is_some() {
+        return Err(format!("Message already exists: {}", message.id));
+    }
+    upsert_message(conn, conversation_id, message, parent_id)
+}
+
+/// Delete `id` and every reply below it, returning how many messages went.
+///
+/// Replies are collected with a recursive query in a single statement; a
+/// cascade on `parent_id` would nest one trigger level per message and hit
+/// SQLite's recursion limit on long conversations.
+pub(crate) fn delete_subtree(conn: &Connection, id: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "WITH RECURSIVE sub(id) AS (
+             SELECT ?1
+             UNION
+             SELECT m.id FROM messages m JOIN sub ON m.parent_id = sub.id
+         )
+         DELETE FROM messages WHERE id IN sub",
+        params![id],
+    )
+}
+
+/// Returns whether the conversation existed. Messages cascade.
+pub(crate) fn delete_conversation(conn: &Connection, id: &str) -> Result<bool, String> {
+    conn.execute("DELETE FROM conversations WHERE id = ?1", params![id])
+        .map(|n| n > 0)
+        .map_err(|e| format!("Failed to delete conversation: {}", e))
+}
+
+pub(crate) fn delete_all_conversations(conn: &mut Connection) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    tx.execute_batch("DELETE FROM messages; DELETE FROM conversations;")
+        .map_err(|e| format!("Failed to delete conversations: {}", e))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to delete conversations: {}", e))
+}
+
+/// Delete a message together with every reply below it.
+pub(crate) fn delete_message(conn: &Connection, id: &str) -> Result<bool, String> {
+    delete_subtree(conn, id)
+        .map(|n| n > 0)
+        .map_err(|e| format!("Failed to delete message: {}", e))
+}
+
+// ============================================================================
+// Branches
+// ============================================================================
+
+fn message_conversation(
+    conn: &Connection,
+    message_id: &str,
+) -> Result<(String, Option<String>), String> {
+    conn.query_row(
+        "SELECT conversation_id, parent_id FROM messages WHERE id = ?1",
+        params![message_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load message: {}", e))?
+    .ok_or_else(|| format!("Message not found: {}", message_id))
+}
+
+/// Follow the most recent reply from `message_id` down to a leaf.
+fn newest_leaf_under(conn: &Connection, message_id: &str) -> Result<String, String> {
+    let mut current = message_id.to_string();
+    loop {
+        let child: Option<String> = conn
+            .query_row(
+                "SELECT id FROM messages WHERE parent_id = ?1 ORDER BY timestamp DESC, rowid DESC LIMIT 1",
+                params![current],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to walk branch: {}", e))?;
+        match child {
+            Some(child) => current = child,
+            None => return Ok(current),
+        }
+    }
+}
+
+/// Fork at `message_id`: store `replacement` as a sibling (same parent) and
+/// make it the active branch. Used both for editing a message and for
+/// regenerating a reply; the original branch is kept.
+pub(crate) fn fork_message(
+    conn: &mut Connection,
+    message_id: &str,
+    replacement: &ChatMessage,
+) -> Result<ChatConversation, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let (conversation_id, parent_id) = message_conversation(&tx, message_id)?;
+    insert_new_message(&tx, &conversation_id, replacement, parent_id.as_deref())?;
+    set_active_leaf(&tx, &conversation_id, Some(&replacement.id))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to fork conversation: {}", e))?;
+
+    get_conversation(conn, &conversation_id)?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))
+}
+
+/// Alternatives at the fork containing `message_id` (its siblings and
+/// itself), oldest first.
+pub(crate) fn list_branches(
+    conn: &Connection,
+    message_id: &str,
+) -> Result<Vec<BranchInfo>, String> {
+    let (conversation_id, parent_id) = message_conversation(conn, message_id)?;
+    let active: Vec<String> = active_path(conn, &conversation_id)?
+        .into_iter()
+        .map(|m| m.id)
+        .collect();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, timestamp, content FROM messages
+             WHERE conversation_id = ?1 AND parent_id IS ?2
+             ORDER BY timestamp ASC, rowid ASC",
+        )
+        .map_err(|e| format!("Failed to query branches: {}", e))?;
+    let siblings = stmt
+        .query_map(params![conversation_id, parent_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(|e| format!("Failed to query branches: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read branch: {}", e))?;
+
+    siblings
+        .into_iter()
+        .map(|(id, timestamp, content)| {
+            Ok(BranchInfo {
+                leaf_id: newest_leaf_under(conn, &id)?,
+                is_active: active.contains(&id),
+                preview: content.chars().take(120).collect(),
+                message_id: id,
+                timestamp,
+            })
+        })
+        .collect()
+}
+
+/// Make the branch through `message_id` active, continuing to its newest leaf.
+pub(crate) fn switch_branch(
+    conn: &Connection,
+    message_id: &str,
+) -> Result<ChatConversation, String> {
+    let (conversation_id, _) = message_conversation(conn, message_id)?;
+    let leaf = newest_leaf_under(conn, message_id)?;
+    set_active_leaf(conn, &conversation_id, Some(&leaf))?;
+    get_conversation(conn, &conversation_id)?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))
+}
+
+/// A single branch as a linear conversation ending at `leaf_id` (the active
+/// branch when `None`), e.g. for export.
+pub(crate) fn export_branch(
+    conn: &Connection,
+    conversation_id: &str,
+    leaf_id: Option<&str>,
+) -> Result<ChatConversation, String> {
+    let mut conversation = get_conversation(conn, conversation_id)?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+    if let Some(leaf_id) = leaf_id {
+        let (owner, _) = message_conversation(conn, leaf_id)?;
+        if owner != conversation_id {
+            return Err(format!("Message {} is not in this conversation", leaf_id));
+        }
+        conversation.messages = path_to(conn, leaf_id)?;
+    }
+    Ok(conversation)
+}
+
+/// Every attachment in a conversation, oldest first.
+pub(crate) fn list_attachments(
+    conn: &Connection,
+    conversation_id: &str,
+) -> Result<Vec<MessageAttachment>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, timestamp, attached_files FROM messages
+             WHERE conversation_id = ?1
+               AND (attached_files IS NOT NULL
+                    OR EXISTS (SELECT 1 FROM message_attachments a WHERE a.message_id = messages.id))
+             ORDER BY timestamp ASC, rowid ASC",
+        )
+        .map_err(|e| format!("Failed to query attachments: {}", e))?;
+    let rows = stmt
+        .query_map(params![conversation_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })
+        .map_err(|e| format!("Failed to query attachments: {}", e))?;
+
+    let mut attachments = Vec::new();
+    for row in rows {
+        let (message_id, timestamp, json) =
+            row.map_err(|e| format!("Failed to read attachment: {}", e))?;
+        let files: Vec<(Option<String>, AttachedFile)> = match json {
+            Some(json) => serde_json::from_str::<Vec<AttachedFile>>(&json)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|file| (None, file))
+                .collect(),
+            None => attachments::load_message_files(conn, &message_id)?
+                .into_iter()
+                .map(|(hash, file)| (Some(hash), file))
+                .collect(),
+        };
+        attachments.extend(files.into_iter().map(|(hash, file)| MessageAttachment {
+            message_id: message_id.clone(),
+            timestamp,
+            hash,
+            file,
+        }));
+    }
+    Ok(attachments)
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn list_conversations_page(
+    app: AppHandle,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    filter: Option<ConversationFilter>,
+) -> Result<ConversationPage, String> {
+    let conn = db::open(&app)?;
+    list_conversations(&conn, &filter.unwrap_or_default(), limit, offset)
+}
+
+#[tauri::command]
+pub fn get_chat_conversation(
+    app: AppHandle,
+    id: String,
+) -> Result<Option<ChatConversation>, String> {
+    let conn = db::open(&app)?;
+    get_conversation(&conn, &id)
+}
+
+#[tauri::command]
+pub fn get_all_chat_conversations(app: AppHandle) -> Result<Vec<ChatConversation>, String> {
+    let conn = db::open(&app)?;
+    list_all_conversations(&conn)
+}
+
+#[tauri::command]
+pub fn list_chat_messages(
+    app: AppHandle,
+    conversation_id: String,
+    limit: Option<u32>,
+    before: Option<MessageCursor>,
+) -> Result<MessagePage, String> {
+    let conn = db::open(&app)?;
+    list_messages(&conn, &conversation_id, limit, before.as_ref())
+}
+
+#[tauri::command]
+pub fn save_chat_conversation(
+    app: AppHandle,
+    conversation: ChatConversation,
+) -> Result<ChatConversation, String> {
+    let mut conn = db::open(&app)?;
+    save_conversation(&mut conn, &conversation)
+}
+
+#[tauri::command]
+pub fn create_chat_conversation(
+    app: AppHandle,
+    title: String,
+    first_message: Option<ChatMessage>,
+) -> Result<ChatConversation, String> {
+    let mut conn = db::open(&app)?;
+    create_conversation(&mut conn, &title, first_message)
+}
+
+#[tauri::command]
+pub fn append_chat_message(
+    app: AppHandle,
+    conversation_id: String,
+    message: ChatMessage,
+) -> Result<ChatMessage, String> {
+    let mut conn = db::open(&app)?;
+    append_message(&mut conn, &conversation_id, &message)
+}
+
+#[tauri::command]
+pub fn rename_chat_conversation(app: AppHandle, id: String, title: String) -> Result<(), String> {
+    let conn = db::open(&app)?;
+    rename_conversation(&conn, &id, &title)
+}
+
+#[tauri::command]
+pub fn delete_chat_conversation(app: AppHandle, id: String) -> Result<bool, String> {
+    let conn = db::open(&app)?;
+    delete_conversation(&conn, &id)
+}
+
+#[tauri::command]
+pub fn delete_all_chat_conversations(app: AppHandle) -> Result<(), String> {
+    let mut conn = db::open(&app)?;
+    delete_all_conversations(&mut conn)
+}
+
+#[tauri::command]
+pub fn delete_chat_message(app: AppHandle, id: String) -> Result<bool, String> {
+    let conn = db::open(&app)?;
+    delete_message(&conn, &id)
+}
+
+#[tauri::command]
+pub fn fork_chat_message(
+    app: AppHandle,
+    message_id: String,
+    replacement: ChatMessage,
+) -> Result<ChatConversation, String> {
+    let mut conn = db::open(&app)?;
+    fork_message(&mut conn, &message_id, &replacement)
+}
+
+#[tauri::command]
+pub fn list_chat_branches(app: AppHandle, message_id: String) -> Result<Vec<BranchInfo>, String> {
+    let conn = db::open(&app)?;
+    list_branches(&conn, &message_id)
+}
+
+#[tauri::command]
+pub fn switch_chat_branch(app: AppHandle, message_id: String) -> Result<ChatConversation, String> {
+    let conn = db::open(&app)?;
+    switch_branch(&conn, &message_id)
+}
+
+#[tauri::command]
+pub fn export_chat_branch(
+    app: AppHandle,
+    conversation_id: String,
+    leaf_id: Option<String>,
+) -> Result<ChatConversation, String> {
+    let conn = db::open(&app)?;
+    export_branch(&conn, &conversation_id, leaf_id.as_deref())
+}
+
+#[tauri::command]
+pub fn list_chat_attachments(
+    app: AppHandle,
+    conversation_id: String,
+) -> Result<Vec<MessageAttachment>, String> {
+    let conn = db::open(&app)?;
+    list_attachments(&conn, &conversation_id)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, role: &str, timestamp: i64) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            role: role.to_string(),
+            content: format!("content of {}", id),
+            timestamp,
+            attached_files: None,
+            parent_id: None,
+            metadata: None,
+        }
+    }
+
+    fn ids(conversation: &ChatConversation) -> Vec<&str> {
+        conversation
+            .messages
+            .iter()
+            .map(|m| m.id.as_str())
+            .collect()
+    }
+
+    fn conversation(id: &str, messages: Vec<ChatMessage>) -> ChatConversation {
+        ChatConversation {
+            id: id.to_string(),
+            title: format!("Conversation {}", id),
+            messages,
+            created_at: 10,
+            updated_at: 20,
+        }
+    }
+
+    #[test]
+    fn save_roundtrips_and_replaces_messages() {
+        let mut conn = db::open_in_memory();
+        let mut conv = conversation("c1", vec![message("m1", "user", 1)]);
+        conv.messages[0].attached_files = Some(vec![AttachedFile {
+            id: "f1".to_string(),
+            name: "shot.png".to_string(),
+            mime_type: "image/png".to_string(),
+            base64: "AAAA".to_string(),
+            size: 3,
+        }]);
+
+        let saved = save_conversation(&mut conn, &conv).unwrap();
+        assert_eq!(saved, conv);
+
+        conv.title = "Renamed".to_string();
+        conv.messages = vec![message("m2", "user", 2), message("m3", "assistant", 3)];
+        let saved = save_conversation(&mut conn, &conv).unwrap();
+        assert_eq!(saved.title, "Renamed");
+        assert_eq!(saved.updated_at, 20, "caller's updated_at is kept");
+        let ids: Vec<_> = saved.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m2", "m3"]);
+
+        let attachments = list_attachments(&conn, "c1").unwrap();
+        assert!(attachments.is_empty());
+    }
+
+    #[test]
+    fn invalid_message_rolls_back_the_whole_save() {
+        let mut conn = db::open_in_memory();
+        save_conversation(
+            &mut conn,
+            &conversation("c1", vec![message("m1", "user", 1)]),
+        )
+        .unwrap();
+
+        let bad = conversation(
+            "c1",
+            vec![message("m2", "user", 2), message("m3", "robot", 3)],
+        );
+        let err = save_conversation(&mut conn, &bad).unwrap_err();
+        assert!(err.contains("role"), "unexpected error: {}", err);
+
+        let kept = get_conversation(&conn, "c1").unwrap().unwrap();
+        assert_eq!(kept.messages.len(), 1);
+        assert_eq!(kept.messages[0].id, "m1");
+
+        assert!(save_conversation(
+            &mut conn,
+            &ChatConversation {
+                title: "  ".to_string(),
+                ..conversation("c2", vec![])
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn pages_conversations_and_messages() {
+        let mut conn = db::open_in_memory();
+        for i in 0..3 {
+            let mut conv = conversation(&format!("c{}", i), vec![]);
+            conv.updated_at = 100 + i;
+            save_conversation(&mut conn, &conv).unwrap();
+        }
+        let page =
+            list_conversations(&conn, &ConversationFilter::default(), Some(2), None).unwrap();
+        assert_eq!(page.total, 3);
+        let ids: Vec<_> = page.items.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["c2", "c1"]);
+
+        for t in 1..=5 {
+            append_message(&mut conn, "c0", &message(&format!("m{}", t), "user", t)).unwrap();
+        }
+        let latest = list_messages(&conn, "c0", Some(2), None).unwrap();
+        let ids: Vec<_> = latest.items.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m4", "m5"]);
+        assert!(latest.has_more);
+
+        let cursor = latest.next_cursor.unwrap();
+        assert_eq!(cursor.id, "m4");
+        let older = list_messages(&conn, "c0", Some(10), Some(&cursor)).unwrap();
+        assert_eq!(older.items.len(), 3);
+        assert!(!older.has_more);
+        assert!(older.next_cursor.is_none());
+    }
+
+    #[test]
+    fn message_pages_keep_messages_sharing_a_timestamp() {
+        let mut conn = db::open_in_memory();
+        save_conversation(&mut conn, &conversation("c0", vec![])).unwrap();
+        for id in ["a", "b", "c", "d", "e"] {
+            append_message(&mut conn, "c0", &message(id, "user", 7)).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = list_messages(&conn, "c0", Some(2), cursor.as_ref()).unwrap();
+            let ids: Vec<_> = page.items.iter().map(|m| m.id.clone()).collect();
+            seen.splice(0..0, ids);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn filters_conversations_by_tag_folder_and_pin() {
+        let mut conn = db::open_in_memory();
+        for i in 0..3 {
+            let mut conv = conversation(&format!("c{}", i), vec![]);
+            conv.updated_at = 100 + i;
+            save_conversation(&mut conn, &conv).unwrap();
+        }
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        crate::organization::tag_conversations(&mut conn, &ids(&["c0", "c1"]), &ids(&["b", "A"]))
+            .unwrap();
+        let folder = crate::organization::create_folder(&conn, "Interviews").unwrap();
+        crate::organization::move_conversations(&mut conn, &ids(&["c1"]), Some(&folder.id))
+            .unwrap();
+        crate::organization::set_pinned(&mut conn, &ids(&["c0"]), true).unwrap();
+
+        let list = |filter: ConversationFilter| -> Vec<String> {
+            list_conversations(&conn, &filter, None, None)
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|c| c.id)
+                .collect()
+        };
+        // Pinned first, then newest.
+        assert_eq!(list(ConversationFilter::default()), vec!["c0", "c2", "c1"]);
+        let tagged = ConversationFilter {
+            tag: Some("A".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list(tagged), vec!["c0", "c1"]);
+        let in_folder = ConversationFilter {
+            folder_id: Some(folder.id.clone()),
+            ..Default::default()
+        };
+        assert_eq!(list(in_folder), vec!["c1"]);
+        let unfiled_unpinned = ConversationFilter {
+            unfiled: true,
+            pinned: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(list(unfiled_unpinned), vec!["c2"]);
+
+        let page = list_conversations(&conn, &ConversationFilter::default(), None, None).unwrap();
+        let c1 = page.items.iter().find(|c| c.id == "c1").unwrap();
+        // Sorted by name, not in the order the tags were added.
+        assert_eq!(c1.tags, vec!["A", "b"]);
+        assert_eq!(c1.folder_id.as_deref(), Some(folder.id.as_str()));
+        assert!(page.items[0].pinned);
+    }
+
+    #[test]
+    fn append_requires_existing_conversation_and_deletes_cascade() {
+        let mut conn = db::open_in_memory();
+        assert!(append_message(&mut conn, "missing", &message("m1", "user", 1)).is_err());
+
+        let conv = create_conversation(&mut conn, "Standup notes", Some(message("m1", "user", 1)))
+            .unwrap();
+        append_message(&mut conn, &conv.id, &message("m2", "assistant", 2)).unwrap();
+        rename_conversation(&conn, &conv.id, "Retro").unwrap();
+        assert!(delete_message(&conn, "m2").unwrap());
+
+        assert!(delete_conversation(&conn, &conv.id).unwrap());
+        assert!(!delete_conversation(&conn, &conv.id).unwrap());
+        let orphans: i64 = conn
+            .query_row("SELECT COUNT(*) FROM messages", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(orphans, 0);
+    }
+
+    #[test]
+    fn deletes_conversations_with_long_reply_chains() {
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM messages", [], |r| r.get(0))
+                .unwrap()
+        };
+        let mut conn = db::open_in_memory();
+        let chain = |id: &str| {
+            conversation(
+                id,
+                (0..1500)
+                    .map(|i| message(&format!("{}-m{}", id, i), "user", i))
+                    .collect(),
+            )
+        };
+        save_conversation(&mut conn, &chain("c1")).unwrap();
+        save_conversation(&mut conn, &chain("c2")).unwrap();
+        save_conversation(&mut conn, &chain("c3")).unwrap();
+
+        // Dropping the root removes the whole branch below it.
+        save_conversation(&mut conn, &conversation("c3", vec![])).unwrap();
+        assert_eq!(count(&conn), 3000);
+        assert!(delete_message(&conn, "c2-m0").unwrap());
+        assert_eq!(count(&conn), 1500);
+        assert!(delete_conversation(&conn, "c1").unwrap());
+        assert_eq!(count(&conn), 0);
+
+        save_conversation(&mut conn, &chain("c1")).unwrap();
+        delete_all_conversations(&mut conn).unwrap();
+        assert_eq!(count(&conn), 0);
+    }
+
+    #[test]
+    fn fork_keeps_the_original_branch_and_switching_restores_it() {
+        let mut conn = db::open_in_memory();
+        let conv = conversation(
+            "c1",
+            vec![
+                message("q1", "user", 1),
+                message("a1", "assistant", 2),
+                message("q2", "user", 3),
+                message("a2", "assistant", 4),
+            ],
+        );
+        save_conversation(&mut conn, &conv).unwrap();
+
+        // Edit q2: the new question replaces it on the active branch.
+        let edited = fork_message(&mut conn, "q2", &message("q2b", "user", 5)).unwrap();
+        assert_eq!(ids(&edited), vec!["q1", "a1", "q2b"]);
+        let reply = append_message(&mut conn, "c1", &message("a2b", "assistant", 6)).unwrap();
+        assert_eq!(reply.parent_id.as_deref(), Some("q2b"));
+
+        let branches = list_branches(&conn, "q2b").unwrap();
+        let summary: Vec<_> = branches
+            .iter()
+            .map(|b| (b.message_id.as_str(), b.leaf_id.as_str(), b.is_active))
+            .collect();
+        assert_eq!(summary, vec![("q2", "a2", false), ("q2b", "a2b", true)]);
+
+        let original = switch_branch(&conn, "q2").unwrap();
+        assert_eq!(ids(&original), vec!["q1", "a1", "q2", "a2"]);
+
+        let exported = export_branch(&conn, "c1", Some("a2b")).unwrap();
+        assert_eq!(ids(&exported), vec!["q1", "a1", "q2b", "a2b"]);
+        assert!(export_branch(&conn, "c1", Some("missing")).is_err());
+    }
+
+    #[test]
+    fn saving_the_active_branch_keeps_other_branches() {
+        let mut conn = db::open_in_memory();
+        let conv = conversation(
+            "c1",
+            vec![message("q1", "user", 1), message("a1", "assistant", 2)],
+        );
+        save_conversation(&mut conn, &conv).unwrap();
+        fork_message(&mut conn, "a1", &message("a1b", "assistant", 3)).unwrap();
+
+        // The frontend saves the active branch plus a new turn.
+        let mut active = get_conversation(&conn, "c1").unwrap().unwrap();
+        active.messages.push(message("q2", "user", 4));
+        let saved = save_conversation(&mut conn, &active).unwrap();
+        assert_eq!(ids(&saved), vec!["q1", "a1b", "q2"]);
+        assert_eq!(list_branches(&conn, "a1").unwrap().len(), 2);
+
+        // Dropping a message from the branch deletes it and its replies.
+        active.messages = vec![message("q1", "user", 1)];
+        save_conversation(&mut conn, &active).unwrap();
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM messages", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 2, "q1 and the untouched a1 branch");
+    }
+
+    #[test]
+    fn branch_migration_chains_existing_messages() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        for (version, _, sql) in crate::db::MIGRATIONS.iter().filter(|m| m.0 < 5) {
+            conn.execute_batch(sql)
+                .unwrap_or_else(|e| panic!("migration {}: {}", version, e));
+        }
+        conn.execute_batch(
+            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES ('c1', 'Old', 1, 1);
+             INSERT INTO messages (id, conversation_id, role, content, timestamp) VALUES
+                ('m2', 'c1', 'assistant', 'b', 2),
+                ('m1', 'c1', 'user', 'a', 1),
+                ('m3', 'c1', 'user', 'c', 2);",
+        )
+        .unwrap();
+        for (_, _, sql) in crate::db::MIGRATIONS.iter().filter(|m| m.0 >= 5) {
+            conn.execute_batch(sql).unwrap();
+        }
+
+        let conv = get_conversation(&conn, "c1").unwrap().unwrap();
+        assert_eq!(ids(&conv), vec!["m1", "m2", "m3"]);
+        append_message(&mut conn, "c1", &message("m4", "assistant", 3)).unwrap();
+    }
+}
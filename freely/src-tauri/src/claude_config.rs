@@ -3,10 +3,16 @@
 //! Manages a `.claude/` directory in the app's local data directory.
 //! On first run, creates default CLAUDE.md and settings.json files.
 //! Subsequent runs leave existing files untouched so users can customize them.
+//!
+//! Every CLAUDE.md write made through [`update_claude_md`] is also recorded as a
+//! timestamped revision in `.claude/.history/`, which can be listed, diffed and
+//! restored. History is pruned to [`MAX_REVISIONS`] entries and
+//! [`MAX_HISTORY_BYTES`] total.
 
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
-use tauri::Manager;
+use tracing::warn;
 
 const DEFAULT_CLAUDE_MD: &str = r#"# Freely Assistant
 
@@ -32,18 +38,56 @@ const DEFAULT_SETTINGS_JSON: &str = r#"{
 }
 "#;
 
-/// Initialize the `.claude` config directory in the app's local data directory.
+/// Directory (inside `.claude/`) holding CLAUDE.md revisions.
+const HISTORY_DIR: &str = ".history";
+
+/// Largest CLAUDE.md accepted by [`update_claude_md`].
+const MAX_CLAUDE_MD_BYTES: usize = 256 * 1024;
+
+/// Number of revisions kept before the oldest are pruned.
+const MAX_REVISIONS: usize = 50;
+
+/// Total size budget for `.history/`; the newest revision is always kept.
+const MAX_HISTORY_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Above this many line comparisons a diff falls back to delete-all/insert-all
+/// instead of computing an LCS table.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Revision id accepted by the diff command to mean the live CLAUDE.md.
+const CURRENT_REVISION: &str = "current";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaudeMdRevision {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: u64,
+    pub size: u64,
+    /// Whether this revision matches the live CLAUDE.md.
+    pub is_current: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// Initialize the `.claude` config directory in Freely's data directory.
 ///
 /// Creates `CLAUDE.md` and `settings.json` only if they do not already exist,
 /// preserving any edits the user may have made. Returns the path to the `.claude/`
 /// directory so callers can set it as the working directory for the Claude CLI.
 pub fn init_claude_config(app: &AppHandle) -> Result<PathBuf, String> {
-    let data_dir = app
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("Could not resolve app_local_data_dir: {}", e))?;
-
-    init_claude_config_in(data_dir)
+    init_claude_config_in(crate::data_dir::data_dir(app)?)
 }
 
 /// Core logic for initializing the `.claude` config directory under a given
@@ -71,37 +115,284 @@ pub(crate) fn init_claude_config_in(data_dir: PathBuf) -> Result<PathBuf, String
     Ok(claude_dir)
 }
 
+fn claude_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::data_dir::data_dir(app)?.join(".claude"))
+}
+
+// ============================================================================
+// CLAUDE.md revision history
+// ============================================================================
+
+fn history_dir(claude_dir: &Path) -> PathBuf {
+    claude_dir.join(HISTORY_DIR)
+}
+
+/// Revision ids are the millisecond timestamp they were taken at.
+fn validate_revision_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid revision id: {}", id));
+    }
+    Ok(())
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// All revisions, oldest first, as (timestamp, path, size).
+fn revision_files(claude_dir: &Path) -> Vec<(u64, PathBuf, u64)> {
+    let mut revisions = Vec::new();
+    let Ok(entries) = std::fs::read_dir(history_dir(claude_dir)) else {
+        return revisions;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+            continue;
+        }
+        let Some(ts) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        revisions.push((ts, path, size));
+    }
+    revisions.sort_by_key(|(ts, _, _)| *ts);
+    revisions
+}
+
+/// Store `content` as a new revision unless it matches the latest one.
+fn record_revision(claude_dir: &Path, content: &str) -> Result<(), String> {
+    let revisions = revision_files(claude_dir);
+    if let Some((_, latest, _)) = revisions.last() {
+        if std::fs::read_to_string(latest).ok().as_deref() == Some(content) {
+            return Ok(());
+        }
+    }
+
+    let dir = history_dir(claude_dir);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create CLAUDE.md history directory: {}", e))?;
+
+    // Keep ids strictly increasing even for writes within the same millisecond.
+    let latest_ts = revisions.last().map(|(ts, _, _)| *ts).unwrap_or(0);
+    let ts = now_ms().max(latest_ts + 1);
+    std::fs::write(dir.join(format!("{}.md", ts)), content)
+        .map_err(|e| format!("Failed to write CLAUDE.md revision: {}", e))?;
+
+    prune_revisions(claude_dir);
+    Ok(())
+}
+
+/// Drop the oldest revisions beyond [`MAX_REVISIONS`] or [`MAX_HISTORY_BYTES`].
+fn prune_revisions(claude_dir: &Path) {
+    let mut revisions = revision_files(claude_dir);
+    let mut total: u64 = revisions.iter().map(|(_, _, size)| size).sum();
+
+    while revisions.len() > 1 && (revisions.len() > MAX_REVISIONS || total > MAX_HISTORY_BYTES) {
+        let (_, path, size) = revisions.remove(0);
+        if let Err(e) = std::fs::remove_file(&path) {
+            warn!("Failed to prune CLAUDE.md revision {:?}: {}", path, e);
+            break;
+        }
+        total -= size;
+    }
+}
+
+/// Write CLAUDE.md and record the new content as a revision.
+///
+/// If CLAUDE.md predates the history directory, its previous content is
+/// recorded first so the very first edit can still be rolled back.
+pub(crate) fn write_claude_md_in(claude_dir: &Path, content: &str) -> Result<(), String> {
+    if content.len() > MAX_CLAUDE_MD_BYTES {
+        return Err(format!(
+            "CLAUDE.md is too large ({} bytes, max {})",
+            content.len(),
+            MAX_CLAUDE_MD_BYTES
+        ));
+    }
+
+    std::fs::create_dir_all(claude_dir)
+        .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
+
+    let claude_md_path = claude_dir.join("CLAUDE.md");
+
+    if revision_files(claude_dir).is_empty() {
+        if let Ok(previous) = std::fs::read_to_string(&claude_md_path) {
+            record_revision(claude_dir, &previous)?;
+        }
+    }
+
+    std::fs::write(&claude_md_path, content)
+        .map_err(|e| format!("Failed to write CLAUDE.md: {}", e))?;
+
+    record_revision(claude_dir, content)
+}
+
+/// List revisions, newest first.
+pub(crate) fn list_revisions_in(claude_dir: &Path) -> Vec<ClaudeMdRevision> {
+    let current = std::fs::read_to_string(claude_dir.join("CLAUDE.md")).ok();
+    let mut revisions: Vec<ClaudeMdRevision> = revision_files(claude_dir)
+        .into_iter()
+        .map(|(ts, _, size)| ClaudeMdRevision {
+            id: ts.to_string(),
+            created_at: ts,
+            size,
+            is_current: false,
+        })
+        .collect();
+    revisions.reverse();
+
+    // Only the newest matching revision is flagged, so a restore of an old
+    // revision doesn't light up every historical copy of the same text.
+    if let Some(current) = current {
+        for revision in revisions.iter_mut() {
+            if read_revision_in(claude_dir, &revision.id).ok().as_deref() == Some(current.as_str())
+            {
+                revision.is_current = true;
+                break;
+            }
+        }
+    }
+    revisions
+}
+
+/// Read a revision's content. `"current"` reads the live CLAUDE.md.
+pub(crate) fn read_revision_in(claude_dir: &Path, id: &str) -> Result<String, String> {
+    if id == CURRENT_REVISION {
+        return std::fs::read_to_string(claude_dir.join("CLAUDE.md"))
+            .map_err(|e| format!("Failed to read CLAUDE.md: {}", e));
+    }
+    validate_revision_id(id)?;
+    std::fs::read_to_string(history_dir(claude_dir).join(format!("{}.md", id)))
+        .map_err(|e| format!("Failed to read revision {}: {}", id, e))
+}
+
+/// Restore a revision as the live CLAUDE.md. The restore is itself recorded,
+/// so it can be undone like any other edit. Returns the restored content.
+pub(crate) fn restore_revision_in(claude_dir: &Path, id: &str) -> Result<String, String> {
+    let content = read_revision_in(claude_dir, id)?;
+    write_claude_md_in(claude_dir, &content)?;
+    Ok(content)
+}
+
+/// Line-based diff of two texts using a longest-common-subsequence table.
+pub(crate) fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    let line = |op: DiffOp, text: &str| DiffLine {
+        op,
+        text: text.to_string(),
+    };
+
+    // Common prefix and suffix never need the LCS table.
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    let mut out: Vec<DiffLine> = a[..prefix].iter().map(|l| line(DiffOp::Equal, l)).collect();
+
+    if a_mid.len() * b_mid.len() > MAX_DIFF_CELLS {
+        out.extend(a_mid.iter().map(|l| line(DiffOp::Delete, l)));
+        out.extend(b_mid.iter().map(|l| line(DiffOp::Insert, l)));
+    } else {
+        let (n, m) = (a_mid.len(), b_mid.len());
+        // lcs[i][j] = LCS length of a_mid[i..] and b_mid[j..]
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if a_mid[i] == b_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if a_mid[i] == b_mid[j] {
+                out.push(line(DiffOp::Equal, a_mid[i]));
+                i += 1;
+                j += 1;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                out.push(line(DiffOp::Delete, a_mid[i]));
+                i += 1;
+            } else {
+                out.push(line(DiffOp::Insert, b_mid[j]));
+                j += 1;
+            }
+        }
+        out.extend(a_mid[i..].iter().map(|l| line(DiffOp::Delete, l)));
+        out.extend(b_mid[j..].iter().map(|l| line(DiffOp::Insert, l)));
+    }
+
+    out.extend(a[a.len() - suffix..].iter().map(|l| line(DiffOp::Equal, l)));
+    out
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
 /// Read the current CLAUDE.md content from the app's `.claude` config directory.
 #[tauri::command]
 pub fn get_claude_md(app: AppHandle) -> Result<String, String> {
-    let data_dir = app
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("Could not resolve app_local_data_dir: {}", e))?;
-
-    let claude_md_path = data_dir.join(".claude").join("CLAUDE.md");
+    let claude_md_path = claude_dir(&app)?.join("CLAUDE.md");
 
     std::fs::read_to_string(&claude_md_path)
         .map_err(|e| format!("Failed to read CLAUDE.md: {}", e))
 }
 
-/// Write new CLAUDE.md content to the app's `.claude` config directory.
+/// Write new CLAUDE.md content to the app's `.claude` config directory,
+/// recording the write as a new revision.
 #[tauri::command]
 pub fn update_claude_md(app: AppHandle, content: String) -> Result<(), String> {
-    let data_dir = app
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("Could not resolve app_local_data_dir: {}", e))?;
+    write_claude_md_in(&claude_dir(&app)?, &content)
+}
 
-    let claude_dir = data_dir.join(".claude");
+/// List CLAUDE.md revisions, newest first.
+#[tauri::command]
+pub fn list_claude_md_revisions(app: AppHandle) -> Result<Vec<ClaudeMdRevision>, String> {
+    Ok(list_revisions_in(&claude_dir(&app)?))
+}
 
-    std::fs::create_dir_all(&claude_dir)
-        .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
+/// Read the content of a single CLAUDE.md revision.
+#[tauri::command]
+pub fn get_claude_md_revision(app: AppHandle, id: String) -> Result<String, String> {
+    read_revision_in(&claude_dir(&app)?, &id)
+}
 
-    let claude_md_path = claude_dir.join("CLAUDE.md");
+/// Line diff between two revisions; either id may be `"current"`.
+#[tauri::command]
+pub fn diff_claude_md_revisions(
+    app: AppHandle,
+    from: String,
+    to: String,
+) -> Result<Vec<DiffLine>, String> {
+    let claude_dir = claude_dir(&app)?;
+    let old = read_revision_in(&claude_dir, &from)?;
+    let new = read_revision_in(&claude_dir, &to)?;
+    Ok(diff_lines(&old, &new))
+}
 
-    std::fs::write(&claude_md_path, content)
-        .map_err(|e| format!("Failed to write CLAUDE.md: {}", e))
+/// Restore a CLAUDE.md revision and return its content.
+#[tauri::command]
+pub fn restore_claude_md_revision(app: AppHandle, id: String) -> Result<String, String> {
+    restore_revision_in(&claude_dir(&app)?, &id)
 }
 
 // ============================================================================
@@ -209,4 +500,106 @@ mod tests {
         let content = std::fs::read_to_string(&canary).unwrap();
         assert!(content.contains("Canary Skill"));
     }
+
+    #[test]
+    fn first_write_records_previous_and_new_revision() {
+        let (_tmp, claude_dir) = setup();
+
+        write_claude_md_in(&claude_dir, "# Edited").unwrap();
+
+        let revisions = list_revisions_in(&claude_dir);
+        assert_eq!(revisions.len(), 2, "default + edit should both be recorded");
+        assert!(revisions[0].is_current);
+        assert!(!revisions[1].is_current);
+        assert!(read_revision_in(&claude_dir, &revisions[1].id)
+            .unwrap()
+            .contains("Freely Assistant"));
+        assert_eq!(
+            std::fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            "# Edited"
+        );
+    }
+
+    #[test]
+    fn identical_write_does_not_add_revision() {
+        let (_tmp, claude_dir) = setup();
+        write_claude_md_in(&claude_dir, "same").unwrap();
+        write_claude_md_in(&claude_dir, "same").unwrap();
+        assert_eq!(list_revisions_in(&claude_dir).len(), 2);
+    }
+
+    #[test]
+    fn restore_rolls_back_and_is_itself_a_revision() {
+        let (_tmp, claude_dir) = setup();
+        write_claude_md_in(&claude_dir, "v1").unwrap();
+        write_claude_md_in(&claude_dir, "v2").unwrap();
+
+        let v1 = list_revisions_in(&claude_dir)
+            .into_iter()
+            .find(|r| read_revision_in(&claude_dir, &r.id).unwrap() == "v1")
+            .unwrap();
+        assert_eq!(restore_revision_in(&claude_dir, &v1.id).unwrap(), "v1");
+        assert_eq!(
+            std::fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            "v1"
+        );
+
+        let revisions = list_revisions_in(&claude_dir);
+        assert_eq!(revisions.len(), 4);
+        assert!(revisions[0].is_current);
+        assert_eq!(revisions.iter().filter(|r| r.is_current).count(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_revision_ids_and_oversized_content() {
+        let (_tmp, claude_dir) = setup();
+        assert!(read_revision_in(&claude_dir, "../settings").is_err());
+        assert!(read_revision_in(&claude_dir, "").is_err());
+        assert_eq!(
+            read_revision_in(&claude_dir, CURRENT_REVISION).unwrap(),
+            DEFAULT_CLAUDE_MD
+        );
+
+        let huge = "x".repeat(MAX_CLAUDE_MD_BYTES + 1);
+        assert!(write_claude_md_in(&claude_dir, &huge).is_err());
+        assert_eq!(
+            std::fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            DEFAULT_CLAUDE_MD,
+            "rejected write must not touch CLAUDE.md"
+        );
+    }
+
+    #[test]
+    fn prunes_oldest_revisions_beyond_limit() {
+        let (_tmp, claude_dir) = setup();
+        for i in 0..(MAX_REVISIONS + 5) {
+            write_claude_md_in(&claude_dir, &format!("rev {}", i)).unwrap();
+        }
+
+        let revisions = list_revisions_in(&claude_dir);
+        assert_eq!(revisions.len(), MAX_REVISIONS);
+        let newest = read_revision_in(&claude_dir, &revisions[0].id).unwrap();
+        assert_eq!(newest, format!("rev {}", MAX_REVISIONS + 4));
+    }
+
+    #[test]
+    fn diff_reports_inserted_and_deleted_lines() {
+        let diff = diff_lines("a\nb\nc\nd", "a\nc\nx\nd");
+        let rendered: Vec<String> = diff
+            .iter()
+            .map(|l| {
+                let sign = match l.op {
+                    DiffOp::Equal => ' ',
+                    DiffOp::Insert => '+',
+                    DiffOp::Delete => '-',
+                };
+                format!("{}{}", sign, l.text)
+            })
+            .collect();
+        assert_eq!(rendered, vec![" a", "-b", " c", "+x", " d"]);
+
+        assert!(diff_lines("same\n", "same\n")
+            .iter()
+            .all(|l| l.op == DiffOp::Equal));
+    }
 }
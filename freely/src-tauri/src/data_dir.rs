@@ -0,0 +1,591 @@
+//! Location of everything Freely stores on disk.
+//!
+//! By default files live where Tauri puts them: `.claude/` and `models/` under
+//! `app_local_data_dir()`, and `freely.db` under `app_config_dir()` (where the
+//! SQL plugin used to resolve `sqlite:` URLs). The user can relocate all of it into a
+//! single directory, e.g. on a synced drive. The choice is recorded in a
+//! pointer file, `data-location.json`, which always stays in the default
+//! `app_local_data_dir()` so it can be found before anything else is opened.
+//!
+//! Relocation copies the data, verifies the copy (SQLite integrity check, row
+//! counts, byte-for-byte file comparison) and only then updates the pointer
+//! and removes the old copy. The app should restart afterwards so every
+//! window reloads its data from the new location.
+
+use crate::db::{self, DB_FILE};
+use crate::fs_utils::{copy_dir_all, files_equal, write_atomic};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+const POINTER_FILE: &str = "data-location.json";
+
+/// Directories (relative to the data directory) that Freely owns.
+pub(crate) const MANAGED_DIRS: [&str; 3] = [".claude", "models", "recordings"];
+
+/// Tables whose row counts must match after copying the database.
+pub(crate) const VERIFIED_TABLES: [&str; 3] = ["conversations", "messages", "system_prompts"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DataLocation {
+    data_dir: PathBuf,
+}
+
+/// Resolved on-disk layout: the data directory and the database file.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DataLayout {
+    pub data_dir: PathBuf,
+    pub db_path: PathBuf,
+}
+
+impl DataLayout {
+    /// Layout of a relocated data directory: everything under one root.
+    pub(crate) fn relocated(data_dir: PathBuf) -> Self {
+        let db_path = data_dir.join(DB_FILE);
+        Self { data_dir, db_path }
+    }
+
+    pub(crate) fn claude_dir(&self) -> PathBuf {
+        self.data_dir.join(".claude")
+    }
+
+    pub(crate) fn models_dir(&self) -> PathBuf {
+        self.data_dir.join("models")
+    }
+
+    /// Continuous-mode recordings, one directory per session.
+    pub(crate) fn recordings_dir(&self) -> PathBuf {
+        self.data_dir.join("recordings")
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DataDirInfo {
+    pub data_dir: String,
+    pub database_path: String,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelocationReport {
+    pub from: String,
+    pub to: String,
+    pub database_copied: bool,
+    pub files_copied: usize,
+    pub old_data_dir: String,
+    /// Whether Freely's files were removed from `old_data_dir`. When this is
+    /// false they are still there and the UI may offer to delete them.
+    pub source_removed: bool,
+    pub restart_required: bool,
+}
+
+// ============================================================================
+// Resolution
+// ============================================================================
+
+/// Read the relocation pointer stored in `default_data_dir`.
+///
+/// A pointer to a directory that no longer exists (e.g. an unmounted drive) is
+/// ignored with a warning rather than failing startup.
+pub(crate) fn read_pointer(default_data_dir: &Path) -> Option<PathBuf> {
+    let raw = std::fs::read_to_string(default_data_dir.join(POINTER_FILE)).ok()?;
+    let location: DataLocation = match serde_json::from_str(&raw) {
+        Ok(location) => location,
+        Err(e) => {
+            warn!("Ignoring malformed {}: {}", POINTER_FILE, e);
+            return None;
+        }
+    };
+    if location.data_dir.is_dir() {
+        Some(location.data_dir)
+    } else {
+        warn!(
+            "Relocated data directory {:?} is unavailable; using default",
+            location.data_dir
+        );
+        None
+    }
+}
+
+/// Point the app at `data_dir`, or back at the default location when `None`.
+pub(crate) fn write_pointer(
+    default_data_dir: &Path,
+    data_dir: Option<&Path>,
+) -> Result<(), String> {
+    let pointer = default_data_dir.join(POINTER_FILE);
+    match data_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(default_data_dir)
+                .map_err(|e| format!("Failed to create {}: {}", default_data_dir.display(), e))?;
+            let json = serde_json::to_vec_pretty(&DataLocation {
+                data_dir: dir.to_path_buf(),
+            })
+            .map_err(|e| e.to_string())?;
+            write_atomic(&pointer, &json)
+                .map_err(|e| format!("Failed to write {}: {}", POINTER_FILE, e))
+        }
+        None => match std::fs::remove_file(&pointer) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to remove {}: {}", POINTER_FILE, e)),
+        },
+    }
+}
+
+/// Layout used when the data directory has never been relocated.
+pub(crate) fn default_layout(app: &AppHandle) -> Result<DataLayout, String> {
+    let data_dir = app
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| format!("Could not resolve app_local_data_dir: {}", e))?;
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Could not resolve app_config_dir: {}", e))?;
+    Ok(DataLayout {
+        data_dir,
+        db_path: config_dir.join(DB_FILE),
+    })
+}
+
+/// Layout currently in effect, honouring the relocation pointer.
+pub(crate) fn current_layout(app: &AppHandle) -> Result<DataLayout, String> {
+    let default = default_layout(app)?;
+    Ok(match read_pointer(&default.data_dir) {
+        Some(dir) => DataLayout::relocated(dir),
+        None => default,
+    })
+}
+
+/// Directory holding `.claude/` and other files Freely owns.
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    current_layout(app).map(|layout| layout.data_dir)
+}
+
+/// Resolve the default and current layouts before the app is built, when no
+/// `AppHandle` exists yet. Tauri derives `app_local_data_dir()` and
+/// `app_config_dir()` from the `dirs` crate and the bundle identifier.
+pub(crate) fn startup_layouts(identifier: &str) -> Option<(DataLayout, DataLayout)> {
+    let default = DataLayout {
+        data_dir: dirs::data_local_dir()?.join(identifier),
+        db_path: dirs::config_dir()?.join(identifier).join(DB_FILE),
+    };
+    let current = match read_pointer(&default.data_dir) {
+        Some(dir) => DataLayout::relocated(dir),
+        None => default.clone(),
+    };
+    Some((default, current))
+}
+
+// ============================================================================
+// Database snapshot and verification
+// ============================================================================
+
+/// Write a consistent copy of the database at `src` to `dst`.
+///
+/// The WAL is checkpointed first so the main file is current, then
+/// `VACUUM INTO` produces a compact snapshot that is safe to take while other
+/// connections have the database open. An encrypted database yields a
+/// snapshot encrypted with the same key.
+pub(crate) fn snapshot_database(src: &Path, dst: &Path) -> Result<(), String> {
+    let conn = db::open_at(src)?;
+    // Best effort: a busy checkpoint is fine because VACUUM INTO reads through the WAL.
+    let _ = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()));
+
+    if dst.exists() {
+        std::fs::remove_file(dst)
+            .map_err(|e| format!("Failed to replace {}: {}", dst.display(), e))?;
+    }
+    conn.execute("VACUUM INTO ?1", [dst.to_string_lossy()])
+        .map_err(|e| format!("Failed to snapshot database: {}", e))?;
+    Ok(())
+}
+
+fn table_count(conn: &Connection, table: &str) -> Option<i64> {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+        row.get(0)
+    })
+    .ok()
+}
+
+/// Check `copy` passes SQLite's integrity check and, when `original` is
+/// given, holds the same number of rows in every core table.
+pub(crate) fn verify_database(copy: &Path, original: Option<&Path>) -> Result<(), String> {
+    let conn = db::open_at(copy)?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Integrity check failed: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("Database copy is corrupt: {}", integrity));
+    }
+
+    if let Some(original) = original {
+        let source = db::open_at(original)?;
+        for table in VERIFIED_TABLES {
+            let (expected, actual) = (table_count(&source, table), table_count(&conn, table));
+            if expected != actual {
+                return Err(format!(
+                    "Row count mismatch in {}: expected {:?}, found {:?}",
+                    table, expected, actual
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Relocation
+// ============================================================================
+
+fn validate_target(
+    from: &DataLayout,
+    to: &DataLayout,
+    allow_overwrite: bool,
+) -> Result<(), String> {
+    if !to.data_dir.is_absolute() {
+        return Err("Data directory must be an absolute path".to_string());
+    }
+    if to.data_dir == from.data_dir {
+        return Err("Data directory is already at that location".to_string());
+    }
+    if to.data_dir.starts_with(&from.data_dir) {
+        return Err("New data directory cannot be inside the current one".to_string());
+    }
+    if to.data_dir.exists() && !to.data_dir.is_dir() {
+        return Err(format!("{} is not a directory", to.data_dir.display()));
+    }
+    if !allow_overwrite {
+        let existing = std::iter::once(to.db_path.clone())
+            .chain(MANAGED_DIRS.iter().map(|d| to.data_dir.join(d)))
+            .find(|p| p.exists());
+        if let Some(existing) = existing {
+            return Err(format!(
+                "{} already contains Freely data ({})",
+                to.data_dir.display(),
+                existing.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Remove whatever a failed relocation managed to write.
+fn cleanup_target(to: &DataLayout) {
+    let _ = std::fs::remove_file(&to.db_path);
+    for dir in MANAGED_DIRS {
+        let _ = std::fs::remove_dir_all(to.data_dir.join(dir));
+    }
+}
+
+fn copy_and_verify(from: &DataLayout, to: &DataLayout) -> Result<(bool, usize), String> {
+    let database_copied = from.db_path.is_file();
+    if database_copied {
+        if let Some(parent) = to.db_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        snapshot_database(&from.db_path, &to.db_path)?;
+        verify_database(&to.db_path, Some(&from.db_path))?;
+    }
+
+    let mut files_copied = 0;
+    for dir in MANAGED_DIRS {
+        let (src, dst) = (from.data_dir.join(dir), to.data_dir.join(dir));
+        if !src.is_dir() {
+            continue;
+        }
+        if dst.exists() {
+            std::fs::remove_dir_all(&dst)
+                .map_err(|e| format!("Failed to replace {}: {}", dst.display(), e))?;
+        }
+        let copied = copy_dir_all(&src, &dst)
+            .map_err(|e| format!("Failed to copy {}: {}", src.display(), e))?;
+        for rel in &copied {
+            let same = files_equal(&src.join(rel), &dst.join(rel))
+                .map_err(|e| format!("Failed to verify {}: {}", rel.display(), e))?;
+            if !same {
+                return Err(format!("Verification failed for {}", rel.display()));
+            }
+        }
+        files_copied += copied.len();
+    }
+
+    Ok((database_copied, files_copied))
+}
+
+/// Copy and verify all data from `from` into `to`.
+///
+/// `allow_overwrite` is used when moving back to the default location, where
+/// stale pre-relocation files are expected and should be replaced. On failure
+/// anything written to `to` is removed and `from` is untouched.
+pub(crate) fn relocate(
+    from: &DataLayout,
+    to: &DataLayout,
+    allow_overwrite: bool,
+) -> Result<RelocationReport, String> {
+    validate_target(from, to, allow_overwrite)?;
+
+    std::fs::create_dir_all(&to.data_dir)
+        .map_err(|e| format!("Failed to create {}: {}", to.data_dir.display(), e))?;
+
+    // Fail fast on read-only or permission-restricted targets.
+    let probe = to.data_dir.join(".freely-write-test");
+    std::fs::write(&probe, b"ok")
+        .map_err(|e| format!("{} is not writable: {}", to.data_dir.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
+
+    match copy_and_verify(from, to) {
+        Ok((database_copied, files_copied)) => Ok(RelocationReport {
+            from: from.data_dir.to_string_lossy().to_string(),
+            to: to.data_dir.to_string_lossy().to_string(),
+            database_copied,
+            files_copied,
+            old_data_dir: from.data_dir.to_string_lossy().to_string(),
+            source_removed: false,
+            restart_required: true,
+        }),
+        Err(e) => {
+            if !allow_overwrite {
+                cleanup_target(to);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Delete Freely's files from `from` once a relocation away from it is
+/// verified and the pointer switched. The directory itself is removed too
+/// when nothing else is left in it.
+pub(crate) fn remove_source(from: &DataLayout) -> Result<(), String> {
+    let not_found = |e: &std::io::Error| e.kind() == std::io::ErrorKind::NotFound;
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = from.db_path.clone().into_os_string();
+        path.push(suffix);
+        let path = PathBuf::from(path);
+        match std::fs::remove_file(&path) {
+            Err(e) if !not_found(&e) => {
+                return Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => {}
+        }
+    }
+    for dir in MANAGED_DIRS {
+        let path = from.data_dir.join(dir);
+        match std::fs::remove_dir_all(&path) {
+            Err(e) if !not_found(&e) => {
+                return Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => {}
+        }
+    }
+    // Fails harmlessly when the directory holds anything else, such as the
+    // pointer file in the default location.
+    let _ = std::fs::remove_dir(&from.data_dir);
+    Ok(())
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn get_data_dir(app: AppHandle) -> Result<DataDirInfo, String> {
+    let default = default_layout(&app)?;
+    let current = current_layout(&app)?;
+    Ok(DataDirInfo {
+        data_dir: current.data_dir.to_string_lossy().to_string(),
+        database_path: current.db_path.to_string_lossy().to_string(),
+        is_default: current == default,
+    })
+}
+
+/// Move Freely's data to `target` (or back to the default location when
+/// `null`). Call [`restart_app`] afterwards to switch over.
+///
+/// The old copy is removed only after the new one is verified and the pointer
+/// names it; failing to remove it does not fail the move.
+#[tauri::command]
+pub fn relocate_data_dir(
+    app: AppHandle,
+    target: Option<String>,
+) -> Result<RelocationReport, String> {
+    let default = default_layout(&app)?;
+    let current = current_layout(&app)?;
+
+    let (to, moving_home) = match target {
+        Some(dir) => (DataLayout::relocated(PathBuf::from(dir)), false),
+        None => (default.clone(), true),
+    };
+    if to == default && !moving_home {
+        return Err("That is the default data directory; pass null to move back".to_string());
+    }
+
+    let mut report = relocate(&current, &to, moving_home)?;
+    write_pointer(
+        &default.data_dir,
+        (!moving_home).then_some(to.data_dir.as_path()),
+    )?;
+    match remove_source(&current) {
+        Ok(()) => report.source_removed = true,
+        Err(e) => warn!("Relocated data, but the old copy remains: {}", e),
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn restart_app(app: AppHandle) {
+    app.restart();
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A default-style layout with a migrated database and some config files.
+    fn populated_layout(root: &Path) -> DataLayout {
+        let layout = DataLayout {
+            data_dir: root.join("local"),
+            db_path: root.join("config").join(DB_FILE),
+        };
+        std::fs::create_dir_all(layout.db_path.parent().unwrap()).unwrap();
+        let conn = db::open_at(&layout.db_path).unwrap();
+        for (_, _, sql) in crate::db::MIGRATIONS {
+            conn.execute_batch(sql).unwrap();
+        }
+        conn.execute(
+            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES ('c1', 'Standup', 1, 1)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO messages (id, conversation_id, role, content, timestamp) VALUES ('m1', 'c1', 'user', 'hello', 1)",
+            [],
+        )
+        .unwrap();
+
+        std::fs::create_dir_all(layout.claude_dir().join("commands")).unwrap();
+        std::fs::write(layout.claude_dir().join("CLAUDE.md"), "# Mine").unwrap();
+        std::fs::write(layout.claude_dir().join("commands/x.md"), "X").unwrap();
+        layout
+    }
+
+    #[test]
+    fn pointer_roundtrip_and_missing_target_falls_back() {
+        let tmp = TempDir::new().unwrap();
+        let default_dir = tmp.path().join("default");
+        let target = tmp.path().join("synced");
+        std::fs::create_dir_all(&target).unwrap();
+
+        assert!(read_pointer(&default_dir).is_none());
+        write_pointer(&default_dir, Some(&target)).unwrap();
+        assert_eq!(read_pointer(&default_dir), Some(target.clone()));
+
+        std::fs::remove_dir_all(&target).unwrap();
+        assert!(
+            read_pointer(&default_dir).is_none(),
+            "unavailable dir is ignored"
+        );
+
+        write_pointer(&default_dir, None).unwrap();
+        assert!(!default_dir.join(POINTER_FILE).exists());
+        write_pointer(&default_dir, None).unwrap();
+    }
+
+    #[test]
+    fn relocate_copies_and_verifies_everything() {
+        let tmp = TempDir::new().unwrap();
+        let from = populated_layout(tmp.path());
+        let to = DataLayout::relocated(tmp.path().join("synced/Freely"));
+
+        let report = relocate(&from, &to, false).unwrap();
+        assert!(report.database_copied);
+        assert_eq!(report.files_copied, 2);
+        assert!(report.restart_required);
+
+        let conn = db::open_at(&to.db_path).unwrap();
+        let title: String = conn
+            .query_row("SELECT title FROM conversations WHERE id = 'c1'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(title, "Standup");
+        assert_eq!(
+            std::fs::read_to_string(to.claude_dir().join("CLAUDE.md")).unwrap(),
+            "# Mine"
+        );
+        assert!(from.db_path.is_file(), "source is left until the pointer moves");
+    }
+
+    #[test]
+    fn remove_source_deletes_only_freely_files() {
+        let tmp = TempDir::new().unwrap();
+        let from = populated_layout(tmp.path());
+        std::fs::create_dir_all(from.models_dir()).unwrap();
+        std::fs::write(from.models_dir().join("ggml-tiny.en.bin"), [0u8; 4]).unwrap();
+        write_pointer(&from.data_dir, Some(tmp.path())).unwrap();
+
+        remove_source(&from).unwrap();
+        assert!(!from.db_path.exists());
+        assert!(!from.claude_dir().exists());
+        assert!(!from.models_dir().exists());
+        assert!(
+            from.data_dir.join(POINTER_FILE).is_file(),
+            "unrelated files are kept"
+        );
+
+        // A relocated directory left empty is removed entirely.
+        let relocated = DataLayout::relocated(tmp.path().join("synced"));
+        std::fs::create_dir_all(relocated.claude_dir()).unwrap();
+        std::fs::write(&relocated.db_path, b"").unwrap();
+        remove_source(&relocated).unwrap();
+        assert!(!relocated.data_dir.exists());
+        remove_source(&relocated).unwrap();
+    }
+
+    #[test]
+    fn relocate_refuses_to_clobber_existing_data() {
+        let tmp = TempDir::new().unwrap();
+        let from = populated_layout(tmp.path());
+        let to = DataLayout::relocated(tmp.path().join("other"));
+        std::fs::create_dir_all(to.claude_dir()).unwrap();
+        std::fs::write(to.claude_dir().join("CLAUDE.md"), "theirs").unwrap();
+
+        let err = relocate(&from, &to, false).unwrap_err();
+        assert!(
+            err.contains("already contains"),
+            "unexpected error: {}",
+            err
+        );
+        assert_eq!(
+            std::fs::read_to_string(to.claude_dir().join("CLAUDE.md")).unwrap(),
+            "theirs"
+        );
+
+        let nested = DataLayout::relocated(from.data_dir.join("inner"));
+        assert!(relocate(&from, &nested, false).is_err());
+        assert!(relocate(&from, &DataLayout::relocated("relative".into()), false).is_err());
+    }
+
+    #[test]
+    fn verify_database_detects_row_count_mismatch() {
+        let tmp = TempDir::new().unwrap();
+        let from = populated_layout(tmp.path());
+        let copy = tmp.path().join("copy.db");
+        snapshot_database(&from.db_path, &copy).unwrap();
+        verify_database(&copy, Some(&from.db_path)).unwrap();
+
+        db::open_at(&copy)
+            .unwrap()
+            .execute("DELETE FROM messages", [])
+            .unwrap();
+        assert!(verify_database(&copy, Some(&from.db_path)).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Migrations for on-disk artifacts (renamed files, moved directories) that
+//! the backend's schema migrations (`db::MIGRATIONS`) cannot express.
+//!
+//! Each [`DataMigration`] inspects the filesystem and returns a plan of
+//! reversible [`FileOp`]s. The runner executes pending plans in order at
+//! startup, before the database or any plugin opens a file, and records each applied id in
+//! `data-migrations.json` in the default `app_local_data_dir()`. If an
+//! operation fails, the operations already performed for that migration are
+//! undone in reverse order and the migration stays pending for the next
+//! launch. A dry run returns the plans without touching anything.
+
+use crate::data_dir::DataLayout;
+use crate::db;
+use crate::fs_utils::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tracing::{error, info, warn};
+
+const MANIFEST_FILE: &str = "data-migrations.json";
+
+/// Directories a migration may read from or write to.
+#[derive(Debug, Clone)]
+pub(crate) struct MigrationContext {
+    /// Default `app_local_data_dir()`; holds the manifest.
+    pub local_data_dir: PathBuf,
+    /// `app_config_dir()`, where the legacy SQL plugin kept the database.
+    pub config_dir: PathBuf,
+    /// Layout currently in effect (may be relocated).
+    pub layout: DataLayout,
+}
+
+/// A single reversible filesystem operation. Operations never overwrite an
+/// existing path, so undoing one cannot destroy data.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum FileOp {
+    CreateDir { path: PathBuf },
+    Rename { from: PathBuf, to: PathBuf },
+    Copy { from: PathBuf, to: PathBuf },
+}
+
+impl FileOp {
+    fn apply(&self) -> Result<(), String> {
+        match self {
+            FileOp::CreateDir { path } => std::fs::create_dir(path)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e)),
+            FileOp::Rename { from, to } => {
+                ensure_absent(to)?;
+                std::fs::rename(from, to).map_err(|e| {
+                    format!(
+                        "Failed to rename {} to {}: {}",
+                        from.display(),
+                        to.display(),
+                        e
+                    )
+                })
+            }
+            FileOp::Copy { from, to } => {
+                ensure_absent(to)?;
+                std::fs::copy(from, to).map(|_| ()).map_err(|e| {
+                    format!(
+                        "Failed to copy {} to {}: {}",
+                        from.display(),
+                        to.display(),
+                        e
+                    )
+                })
+            }
+        }
+    }
+
+    fn undo(&self) -> Result<(), String> {
+        let result = match self {
+            FileOp::CreateDir { path } => std::fs::remove_dir(path),
+            FileOp::Rename { from, to } => std::fs::rename(to, from),
+            FileOp::Copy { to, .. } => std::fs::remove_file(to),
+        };
+        result.map_err(|e| format!("Failed to undo {:?}: {}", self, e))
+    }
+}
+
+fn ensure_absent(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        Err(format!("Refusing to overwrite {}", path.display()))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) trait DataMigration {
+    /// Stable identifier recorded in the manifest. Never reuse or rename one.
+    fn id(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    /// Inspect the filesystem and return the operations to perform. An empty
+    /// plan means there is nothing to do; the migration is still recorded.
+    fn plan(&self, ctx: &MigrationContext) -> Result<Vec<FileOp>, String>;
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Manifest {
+    applied: Vec<AppliedMigration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub applied_at: i64,
+    pub operations: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationOutcome {
+    pub id: String,
+    pub description: String,
+    pub operations: Vec<FileOp>,
+    /// False for a dry run.
+    pub applied: bool,
+}
+
+fn read_manifest(dir: &Path) -> Result<Manifest, String> {
+    match std::fs::read_to_string(dir.join(MANIFEST_FILE)) {
+        Ok(raw) => {
+            serde_json::from_str(&raw).map_err(|e| format!("Malformed {}: {}", MANIFEST_FILE, e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", MANIFEST_FILE, e)),
+    }
+}
+
+fn write_manifest(dir: &Path, manifest: &Manifest) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let json = serde_json::to_vec_pretty(manifest).map_err(|e| e.to_string())?;
+    write_atomic(&dir.join(MANIFEST_FILE), &json)
+        .map_err(|e| format!("Failed to write {}: {}", MANIFEST_FILE, e))
+}
+
+/// Apply `ops` in order, undoing the completed ones if any fails.
+fn apply_all(ops: &[FileOp]) -> Result<(), String> {
+    for (i, op) in ops.iter().enumerate() {
+        if let Err(e) = op.apply() {
+            for done in ops[..i].iter().rev() {
+                if let Err(undo_err) = done.undo() {
+                    error!("Data migration rollback incomplete: {}", undo_err);
+                }
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Run every migration not yet recorded in the manifest, in order.
+///
+/// Stops at the first failure (after rolling it back); migrations applied
+/// before it stay recorded.
+pub(crate) fn run_migrations(
+    ctx: &MigrationContext,
+    migrations: &[&dyn DataMigration],
+    dry_run: bool,
+) -> Result<Vec<MigrationOutcome>, String> {
+    let mut manifest = read_manifest(&ctx.local_data_dir)?;
+    let mut outcomes = Vec::new();
+
+    for migration in migrations {
+        if manifest.applied.iter().any(|m| m.id == migration.id()) {
+            continue;
+        }
+        let ops = migration
+            .plan(ctx)
+            .map_err(|e| format!("Migration {} failed to plan: {}", migration.id(), e))?;
+
+        if !dry_run {
+            apply_all(&ops)
+                .map_err(|e| format!("Migration {} rolled back: {}", migration.id(), e))?;
+            manifest.applied.push(AppliedMigration {
+                id: migration.id().to_string(),
+                applied_at: db::now_ms(),
+                operations: ops.len(),
+            });
+            write_manifest(&ctx.local_data_dir, &manifest)?;
+        }
+
+        outcomes.push(MigrationOutcome {
+            id: migration.id().to_string(),
+            description: migration.description().to_string(),
+            operations: ops,
+            applied: !dry_run,
+        });
+    }
+
+    Ok(outcomes)
+}
+
+// ============================================================================
+// Migrations
+// ============================================================================
+
+/// Rename `pluely.db` (and its WAL sidecars) to `freely.db` so users keep
+/// their history across the rename. Builds that used the SQL plugin stored it
+/// in `app_config_dir()`; earlier builds looked in `app_local_data_dir()`, so
+/// both are checked. Nothing happens if `freely.db` already exists.
+struct RenamePluelyDb;
+
+impl DataMigration for RenamePluelyDb {
+    fn id(&self) -> &'static str {
+        "0001_rename_pluely_db"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rename pluely.db to freely.db"
+    }
+
+    fn plan(&self, ctx: &MigrationContext) -> Result<Vec<FileOp>, String> {
+        let target = &ctx.layout.db_path;
+        if target.exists() {
+            return Ok(Vec::new());
+        }
+        let Some(source_dir) = [&ctx.config_dir, &ctx.local_data_dir]
+            .into_iter()
+            .find(|dir| dir.join("pluely.db").is_file())
+        else {
+            return Ok(Vec::new());
+        };
+
+        let mut ops = Vec::new();
+        if let Some(parent) = target.parent() {
+            if !parent.exists() {
+                ops.push(FileOp::CreateDir {
+                    path: parent.to_path_buf(),
+                });
+            }
+        }
+        for suffix in ["", "-wal", "-shm"] {
+            let from = source_dir.join(format!("pluely.db{}", suffix));
+            if from.exists() {
+                ops.push(FileOp::Rename {
+                    from,
+                    to: PathBuf::from(format!("{}{}", target.display(), suffix)),
+                });
+            }
+        }
+        Ok(ops)
+    }
+}
+
+/// All data migrations, oldest first. Append new ones at the end.
+fn registry() -> Vec<&'static dyn DataMigration> {
+    vec![&RenamePluelyDb]
+}
+
+// ============================================================================
+// Entry points
+// ============================================================================
+
+/// Apply pending migrations before the Tauri builder starts, so neither the
+/// database nor a plugin has opened the files being moved. Failures are logged, not fatal: the
+/// migration is retried on the next launch.
+pub fn run_at_startup(identifier: &str) {
+    let (Some((default, layout)), Some(config_dir)) = (
+        crate::data_dir::startup_layouts(identifier),
+        dirs::config_dir().map(|dir| dir.join(identifier)),
+    ) else {
+        warn!("Could not resolve app directories; skipping data migrations");
+        return;
+    };
+    let ctx = MigrationContext {
+        local_data_dir: default.data_dir,
+        config_dir,
+        layout,
+    };
+
+    match run_migrations(&ctx, &registry(), false) {
+        Ok(outcomes) => {
+            for outcome in outcomes {
+                info!(
+                    "Applied data migration {} ({} operation(s))",
+                    outcome.id,
+                    outcome.operations.len()
+                );
+            }
+        }
+        Err(e) => error!("Data migrations failed: {}", e),
+    }
+}
+
+/// Dry run: list pending data migrations and the operations they would perform.
+#[tauri::command]
+pub fn plan_data_migrations(app: AppHandle) -> Result<Vec<MigrationOutcome>, String> {
+    let default = crate::data_dir::default_layout(&app)?;
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Could not resolve app_config_dir: {}", e))?;
+    let ctx = MigrationContext {
+        local_data_dir: default.data_dir,
+        config_dir,
+        layout: crate::data_dir::current_layout(&app)?,
+    };
+    run_migrations(&ctx, &registry(), true)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DB_FILE;
+    use tempfile::TempDir;
+
+    fn context(root: &Path) -> MigrationContext {
+        let (local, config) = (root.join("local"), root.join("config"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&config).unwrap();
+        MigrationContext {
+            local_data_dir: local.clone(),
+            config_dir: config.clone(),
+            layout: DataLayout {
+                data_dir: local,
+                db_path: config.join(DB_FILE),
+            },
+        }
+    }
+
+    /// Plans a fixed list of operations.
+    struct Fixed(&'static str, Vec<FileOp>);
+
+    impl DataMigration for Fixed {
+        fn id(&self) -> &'static str {
+            self.0
+        }
+        fn description(&self) -> &'static str {
+            "test"
+        }
+        fn plan(&self, _ctx: &MigrationContext) -> Result<Vec<FileOp>, String> {
+            Ok(self.1.clone())
+        }
+    }
+
+    #[test]
+    fn renames_pluely_db_and_sidecars_once() {
+        let tmp = TempDir::new().unwrap();
+        let ctx = context(tmp.path());
+        std::fs::write(ctx.config_dir.join("pluely.db"), "db").unwrap();
+        std::fs::write(ctx.config_dir.join("pluely.db-wal"), "wal").unwrap();
+
+        let outcomes = run_migrations(&ctx, &registry(), false).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].operations.len(), 2);
+        assert_eq!(std::fs::read_to_string(&ctx.layout.db_path).unwrap(), "db");
+        assert!(ctx.config_dir.join("freely.db-wal").exists());
+        assert!(!ctx.config_dir.join("pluely.db").exists());
+
+        // Recorded in the manifest, so a later pluely.db is left alone.
+        std::fs::write(ctx.config_dir.join("pluely.db"), "again").unwrap();
+        assert!(run_migrations(&ctx, &registry(), false).unwrap().is_empty());
+        assert!(ctx.config_dir.join("pluely.db").exists());
+    }
+
+    #[test]
+    fn keeps_existing_freely_db_and_finds_legacy_location() {
+        let tmp = TempDir::new().unwrap();
+        let ctx = context(tmp.path());
+        std::fs::write(ctx.local_data_dir.join("pluely.db"), "old").unwrap();
+        std::fs::write(&ctx.layout.db_path, "new").unwrap();
+        assert!(RenamePluelyDb.plan(&ctx).unwrap().is_empty());
+
+        std::fs::remove_file(&ctx.layout.db_path).unwrap();
+        let ops = RenamePluelyDb.plan(&ctx).unwrap();
+        assert_eq!(
+            ops,
+            vec![FileOp::Rename {
+                from: ctx.local_data_dir.join("pluely.db"),
+                to: ctx.layout.db_path.clone(),
+            }]
+        );
+    }
+
+    #[test]
+    fn dry_run_changes_nothing() {
+        let tmp = TempDir::new().unwrap();
+        let ctx = context(tmp.path());
+        std::fs::write(ctx.config_dir.join("pluely.db"), "db").unwrap();
+
+        let outcomes = run_migrations(&ctx, &registry(), true).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(!outcomes[0].applied);
+        assert!(ctx.config_dir.join("pluely.db").exists());
+        assert!(!ctx.local_data_dir.join(MANIFEST_FILE).exists());
+    }
+
+    #[test]
+    fn failed_migration_is_rolled_back_and_not_recorded() {
+        let tmp = TempDir::new().unwrap();
+        let ctx = context(tmp.path());
+        let (a, b, clash) = (
+            tmp.path().join("a"),
+            tmp.path().join("b"),
+            tmp.path().join("clash"),
+        );
+        std::fs::write(&a, "a").unwrap();
+        std::fs::write(&clash, "keep").unwrap();
+
+        let ok = Fixed("0001_ok", vec![]);
+        let bad = Fixed(
+            "0002_bad",
+            vec![
+                FileOp::CreateDir {
+                    path: tmp.path().join("new-dir"),
+                },
+                FileOp::Rename {
+                    from: a.clone(),
+                    to: b.clone(),
+                },
+                FileOp::Copy {
+                    from: b.clone(),
+                    to: clash.clone(),
+                },
+            ],
+        );
+
+        let err = run_migrations(&ctx, &[&ok, &bad], false).unwrap_err();
+        assert!(err.contains("0002_bad"), "unexpected error: {}", err);
+        assert!(a.exists() && !b.exists(), "rename was undone");
+        assert!(
+            !tmp.path().join("new-dir").exists(),
+            "directory was removed"
+        );
+        assert_eq!(std::fs::read_to_string(&clash).unwrap(), "keep");
+
+        let manifest = read_manifest(&ctx.local_data_dir).unwrap();
+        let ids: Vec<_> = manifest.applied.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["0001_ok"]);
+    }
+}
@@ -0,0 +1,203 @@
+//! Rust-side access to `freely.db`.
+//!
+//! The backend owns the schema ([`super::migrate`] runs at startup, or on
+//! unlock for an encrypted database) and every command opens its own
+//! short-lived connection.
+//!
+//! The database may be encrypted with SQLCipher (see `encryption`). Its key
+//! is held for the session in memory and applied to every connection opened
+//! here, so callers never deal with keys themselves.
+
+use rusqlite::Connection;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+/// File name of the database inside its directory.
+pub const DB_FILE: &str = "freely.db";
+
+/// How long a connection waits on a lock held by another connection.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// First 16 bytes of every plaintext SQLite file. SQLCipher encrypts the
+/// whole file, header included, so its absence marks an encrypted database.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Returned when the database is encrypted and no key has been loaded.
+pub const LOCKED_ERROR: &str = "Database is encrypted and locked; unlock it to continue";
+
+/// Raw SQLCipher key (64 hex digits) for this session. Process-wide so that
+/// connections opened by path-only helpers, such as snapshots and integrity
+/// checks, are keyed too.
+static SESSION_KEY: RwLock<Option<String>> = RwLock::new(None);
+
+/// Load (or, with `None`, forget) the key applied to encrypted databases.
+pub(crate) fn set_session_key(key: Option<String>) {
+    *SESSION_KEY.write().unwrap_or_else(|e| e.into_inner()) = key;
+}
+
+pub(crate) fn session_key() -> Option<String> {
+    SESSION_KEY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Whether the file at `path` exists and is not a plaintext SQLite database.
+pub(crate) fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        // Missing or empty files are not encrypted (yet).
+        Err(_) => false,
+    }
+}
+
+/// Resolve the on-disk path of `freely.db`, honouring a relocated data directory.
+pub fn db_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::data_dir::current_layout(app)?.db_path)
+}
+
+/// Open a connection to `freely.db`.
+pub fn open(app: &AppHandle) -> Result<Connection, String> {
+    open_at(&db_path(app)?)
+}
+
+/// Open a connection to the database at `path` with foreign keys on, so
+/// `ON DELETE CASCADE` works.
+///
+/// Encrypted files are opened with the session key. While a key is loaded,
+/// new databases are created encrypted as well.
+pub(crate) fn open_at(path: &Path) -> Result<Connection, String> {
+    let key = session_key();
+    let encrypted = is_encrypted(path);
+    if encrypted && key.is_none() {
+        return Err(LOCKED_ERROR.to_string());
+    }
+    let key = key.filter(|_| encrypted || !path.exists());
+    open_with_key(path, key.as_deref())
+}
+
+/// Open the database at `path`, keying it with `key` (64 hex digits) first.
+pub(crate) fn open_with_key(path: &Path, key: Option<&str>) -> Result<Connection, String> {
+    let conn = Connection::open(path)
+        .map_err(|e| format!("Failed to open database {}: {}", path.display(), e))?;
+    if let Some(key) = key {
+        apply_key(&conn, key)?;
+    }
+    configure(&conn)?;
+    // Opening is lazy; reading the schema is the first access that decrypts
+    // a page, so a wrong or missing key surfaces here.
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|e| match key {
+            Some(_) => "Encryption key does not match the database".to_string(),
+            None => format!("Failed to read database {}: {}", path.display(), e),
+        })?;
+    Ok(conn)
+}
+
+fn apply_key(conn: &Connection, key: &str) -> Result<(), String> {
+    if key.len() != 64 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("Encryption key must be 64 hexadecimal digits".to_string());
+    }
+    conn.execute_batch(&format!("PRAGMA key = \"x'{}'\";", key))
+        .map_err(|e| format!("Failed to apply encryption key: {}", e))
+}
+
+fn configure(conn: &Connection) -> Result<(), String> {
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("Failed to set busy timeout: {}", e))?;
+    conn.pragma_update(None, "foreign_keys", "ON")
+        .map_err(|e| format!("Failed to enable foreign keys: {}", e))
+}
+
+/// Current time in milliseconds since the Unix epoch, matching the
+/// `Date.now()` timestamps the frontend stores.
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Open an in-memory database with every migration applied.
+#[cfg(test)]
+pub(crate) fn open_in_memory() -> Connection {
+    let mut conn = Connection::open_in_memory().expect("failed to open in-memory db");
+    configure(&conn).expect("failed to configure in-memory db");
+    super::migrate(&mut conn).expect("migrations failed");
+    conn
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migration_versions_are_strictly_increasing() {
+        let versions: Vec<i64> = crate::db::MIGRATIONS.iter().map(|m| m.0).collect();
+        assert!(
+            versions.windows(2).all(|w| w[0] < w[1]),
+            "migration versions must be unique and ascending: {:?}",
+            versions
+        );
+    }
+
+    #[test]
+    fn all_migrations_apply_with_foreign_keys_enabled() {
+        let conn = open_in_memory();
+        let fk: i64 = conn
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(fk, 1, "foreign keys should be enabled");
+
+        let tables: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('conversations', 'messages', 'system_prompts')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tables, 3);
+    }
+
+    #[test]
+    fn migrate_continues_from_sql_plugin_history() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE _sqlx_migrations (
+                version BIGINT PRIMARY KEY, description TEXT NOT NULL,
+                installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                success BOOLEAN NOT NULL, checksum BLOB NOT NULL,
+                execution_time BIGINT NOT NULL);",
+        )
+        .unwrap();
+        for (version, description, sql) in &crate::db::MIGRATIONS[..3] {
+            conn.execute_batch(sql).unwrap();
+            conn.execute(
+                "INSERT INTO _sqlx_migrations VALUES (?1, ?2, 0, 1, x'', 0)",
+                rusqlite::params![version, description],
+            )
+            .unwrap();
+        }
+
+        let latest = crate::db::MIGRATIONS.last().unwrap().0;
+        assert_eq!(crate::db::migrate(&mut conn).unwrap(), latest);
+        let fts: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'messages_fts'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(fts, 1);
+        // Already current: nothing re-runs.
+        assert_eq!(crate::db::migrate(&mut conn).unwrap(), latest);
+    }
+}
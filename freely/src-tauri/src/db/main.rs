@@ -1,21 +1,134 @@
-use tauri_plugin_sql::{Migration, MigrationKind};
+use rusqlite::{Connection, OptionalExtension};
 
-/// Returns all database migrations
-pub fn migrations() -> Vec<Migration> {
-    vec![
-        // Migration 1: Create system_prompts table with indexes and triggers
-        Migration {
-            version: 1,
-            description: "create_system_prompts_table",
-            sql: include_str!("migrations/system-prompts.sql"),
-            kind: MigrationKind::Up,
-        },
-        // Migration 2: Create chat history tables (conversations and messages)
-        Migration {
-            version: 2,
-            description: "create_chat_history_tables",
-            sql: include_str!("migrations/chat-history.sql"),
-            kind: MigrationKind::Up,
-        },
-    ]
+/// Every migration as `(version, description, sql)`, applied in order by
+/// [`migrate`].
+pub(crate) const MIGRATIONS: &[(i64, &str, &str)] = &[
+    // Migration 1: Create system_prompts table with indexes and triggers
+    (
+        1,
+        "create_system_prompts_table",
+        include_str!("migrations/system-prompts.sql"),
+    ),
+    // Migration 2: Create chat history tables (conversations and messages)
+    (
+        2,
+        "create_chat_history_tables",
+        include_str!("migrations/chat-history.sql"),
+    ),
+    // Migration 3: Create project profiles table
+    (
+        3,
+        "create_project_profiles_table",
+        include_str!("migrations/project-profiles.sql"),
+    ),
+    // Migration 4: Full-text search index over message content
+    (
+        4,
+        "create_messages_fts",
+        include_str!("migrations/messages-fts.sql"),
+    ),
+    // Migration 5: Parent links and active branch for conversation branching
+    (
+        5,
+        "add_message_branches",
+        include_str!("migrations/message-branches.sql"),
+    ),
+    // Migration 6: Agent metadata (model, tool calls, token usage) on messages
+    (
+        6,
+        "add_message_metadata",
+        include_str!("migrations/message-metadata.sql"),
+    ),
+    // Migration 7: Pinned flag, retention policy and prune log
+    (
+        7,
+        "add_retention",
+        include_str!("migrations/retention.sql"),
+    ),
+    // Migration 8: Folders and tags for conversations
+    (
+        8,
+        "add_conversation_organization",
+        include_str!("migrations/conversation-organization.sql"),
+    ),
+    // Migration 9: Content-addressed attachment storage
+    (
+        9,
+        "add_attachment_blobs",
+        include_str!("migrations/attachments.sql"),
+    ),
+    // Migration 10: Version history for system prompts
+    (
+        10,
+        "add_system_prompt_versions",
+        include_str!("migrations/system-prompt-versions.sql"),
+    ),
+    // Migration 11: Embedding settings and message chunk vectors
+    (
+        11,
+        "add_semantic_search",
+        include_str!("migrations/semantic-search.sql"),
+    ),
+];
+
+/// Table in which the SQL plugin recorded applied migrations before the
+/// backend took over the schema.
+const LEGACY_MIGRATIONS_TABLE: &str = "_sqlx_migrations";
+
+/// Highest migration recorded by the SQL plugin, if it ever ran on `conn`.
+fn legacy_version(conn: &Connection) -> Result<Option<i64>, String> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [LEGACY_MIGRATIONS_TABLE],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to inspect schema: {}", e))?;
+    if !exists {
+        return Ok(None);
+    }
+    conn.query_row(
+        &format!(
+            "SELECT MAX(version) FROM {} WHERE success = 1",
+            LEGACY_MIGRATIONS_TABLE
+        ),
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+    .map_err(|e| format!("Failed to read migration history: {}", e))
+}
+
+/// Bring the schema up to date, returning the resulting version.
+///
+/// The applied version is tracked in `PRAGMA user_version`. Databases created
+/// while the SQL plugin owned the schema start from the version it recorded.
+/// Each migration runs in its own transaction together with the version bump.
+pub(crate) fn migrate(conn: &mut Connection) -> Result<i64, String> {
+    let mut version: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+    if version == 0 {
+        if let Some(legacy) = legacy_version(conn)? {
+            conn.pragma_update(None, "user_version", legacy)
+                .map_err(|e| format!("Failed to record schema version: {}", e))?;
+            version = legacy;
+        }
+    }
+
+    for &(next, description, sql) in MIGRATIONS {
+        if next <= version {
+            continue;
+        }
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start migration {}: {}", next, e))?;
+        tx.execute_batch(sql)
+            .and_then(|_| tx.pragma_update(None, "user_version", next))
+            .and_then(|_| tx.commit())
+            .map_err(|e| format!("Migration {} ({}) failed: {}", next, description, e))?;
+        version = next;
+    }
+    Ok(version)
 }
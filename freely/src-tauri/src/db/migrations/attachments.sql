@@ -0,0 +1,42 @@
+-- Attachment contents, stored once per distinct SHA-256 hash. Thumbnails
+-- are PNGs generated for raster images. ref_count is maintained by the
+-- triggers below; a blob is removed as soon as nothing references it.
+CREATE TABLE IF NOT EXISTS attachment_blobs (
+    hash TEXT PRIMARY KEY,
+    mime_type TEXT NOT NULL,
+    byte_size INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    thumbnail BLOB,
+    ref_count INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+-- Files attached to a message, in display order. Replaces the inline JSON
+-- in messages.attached_files, which is kept for rows not yet moved over.
+CREATE TABLE IF NOT EXISTS message_attachments (
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    attachment_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    blob_hash TEXT NOT NULL REFERENCES attachment_blobs(hash),
+    PRIMARY KEY (message_id, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_attachments_blob_hash ON message_attachments(blob_hash);
+
+CREATE TRIGGER IF NOT EXISTS attachment_blobs_ref_on_insert
+AFTER INSERT ON message_attachments
+FOR EACH ROW
+BEGIN
+    UPDATE attachment_blobs SET ref_count = ref_count + 1 WHERE hash = NEW.blob_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachment_blobs_unref_on_delete
+AFTER DELETE ON message_attachments
+FOR EACH ROW
+BEGIN
+    UPDATE attachment_blobs SET ref_count = ref_count - 1 WHERE hash = OLD.blob_hash;
+    DELETE FROM attachment_blobs WHERE hash = OLD.blob_hash AND ref_count <= 0;
+END;
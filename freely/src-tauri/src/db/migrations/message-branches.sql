@@ -0,0 +1,25 @@
+-- Turn each conversation's message list into a tree so edits and
+-- regenerations fork a new branch instead of overwriting history.
+--
+-- parent_id: previous message on the same branch (NULL for a root). It has
+-- no delete action: replies are removed with a recursive query, since a
+-- cascade nests one trigger level per message.
+-- active_leaf_id: last message of the branch currently shown; NULL means
+-- "the most recent message".
+ALTER TABLE messages ADD COLUMN parent_id TEXT REFERENCES messages(id);
+ALTER TABLE conversations ADD COLUMN active_leaf_id TEXT REFERENCES messages(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_messages_parent_id ON messages(parent_id);
+
+-- Chain existing messages in timestamp order, so every conversation starts
+-- as a single branch
+UPDATE messages
+SET parent_id = (
+    SELECT p.id
+    FROM messages p
+    WHERE p.conversation_id = messages.conversation_id
+      AND (p.timestamp < messages.timestamp
+           OR (p.timestamp = messages.timestamp AND p.rowid < messages.rowid))
+    ORDER BY p.timestamp DESC, p.rowid DESC
+    LIMIT 1
+);
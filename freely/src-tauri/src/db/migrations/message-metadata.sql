@@ -0,0 +1,3 @@
+-- JSON object with the agent details behind a message: resolved model,
+-- tool calls and token usage (see chat_history::MessageMetadata)
+ALTER TABLE messages ADD COLUMN metadata TEXT;
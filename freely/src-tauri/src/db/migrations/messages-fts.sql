@@ -0,0 +1,54 @@
+-- Full-text index over message content.
+--
+-- FTS5 rows are keyed by a stable integer docid from message_search_ids rather
+-- than messages.rowid, which VACUUM may renumber (messages has a TEXT key).
+CREATE TABLE IF NOT EXISTS message_search_ids (
+    docid INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id TEXT NOT NULL UNIQUE
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+    content,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- Index messages that existed before this migration
+INSERT INTO message_search_ids (message_id)
+SELECT id FROM messages WHERE id NOT IN (SELECT message_id FROM message_search_ids);
+
+INSERT INTO messages_fts (rowid, content)
+SELECT s.docid, m.content
+FROM message_search_ids s
+JOIN messages m ON m.id = s.message_id
+WHERE s.docid NOT IN (SELECT rowid FROM messages_fts);
+
+-- Keep the index in sync with messages
+CREATE TRIGGER IF NOT EXISTS messages_fts_insert
+AFTER INSERT ON messages
+FOR EACH ROW
+BEGIN
+    -- Drop a stale entry left by INSERT OR REPLACE, which skips delete triggers
+    DELETE FROM messages_fts
+    WHERE rowid = (SELECT docid FROM message_search_ids WHERE message_id = NEW.id);
+    DELETE FROM message_search_ids WHERE message_id = NEW.id;
+    INSERT INTO message_search_ids (message_id) VALUES (NEW.id);
+    INSERT INTO messages_fts (rowid, content) VALUES (last_insert_rowid(), NEW.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS messages_fts_update
+AFTER UPDATE OF content ON messages
+FOR EACH ROW
+BEGIN
+    UPDATE messages_fts
+    SET content = NEW.content
+    WHERE rowid = (SELECT docid FROM message_search_ids WHERE message_id = NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS messages_fts_delete
+AFTER DELETE ON messages
+FOR EACH ROW
+BEGIN
+    DELETE FROM messages_fts
+    WHERE rowid = (SELECT docid FROM message_search_ids WHERE message_id = OLD.id);
+    DELETE FROM message_search_ids WHERE message_id = OLD.id;
+END;
@@ -0,0 +1,16 @@
+-- Create project_profiles table
+CREATE TABLE IF NOT EXISTS project_profiles (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    directory TEXT NOT NULL,
+    default_backend TEXT NOT NULL DEFAULT 'claude' CHECK(default_backend IN ('claude', 'codex', 'gemini')),
+    default_model TEXT,
+    allowed_tools TEXT, -- JSON array of tool patterns passed to --allowedTools
+    extra_instructions TEXT,
+    is_active INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- At most one profile can be active at a time
+CREATE UNIQUE INDEX IF NOT EXISTS idx_project_profiles_active ON project_profiles(is_active) WHERE is_active = 1;
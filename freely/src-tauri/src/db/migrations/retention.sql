@@ -0,0 +1,29 @@
+-- Pinned conversations are exempt from retention rules (when keep_pinned
+-- is set)
+ALTER TABLE conversations ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+
+-- Retention rules enforced by the background pruning task. A single row;
+-- NULL limits are not enforced.
+CREATE TABLE IF NOT EXISTS retention_policy (
+    id INTEGER PRIMARY KEY CHECK(id = 1),
+    enabled INTEGER NOT NULL DEFAULT 0,
+    max_age_days INTEGER CHECK(max_age_days IS NULL OR max_age_days > 0),
+    max_conversations INTEGER CHECK(max_conversations IS NULL OR max_conversations > 0),
+    keep_pinned INTEGER NOT NULL DEFAULT 1,
+    updated_at INTEGER NOT NULL
+);
+
+-- One row per pruned conversation
+CREATE TABLE IF NOT EXISTS prune_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pruned_at INTEGER NOT NULL,
+    conversation_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    message_count INTEGER NOT NULL,
+    last_updated_at INTEGER NOT NULL,
+    reason TEXT NOT NULL CHECK(reason IN ('maxAge', 'maxConversations')),
+    source TEXT NOT NULL CHECK(source IN ('manual', 'automatic'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_prune_log_pruned_at ON prune_log(pruned_at DESC);
+CREATE INDEX IF NOT EXISTS idx_conversations_pinned ON conversations(pinned);
@@ -0,0 +1,36 @@
+-- Embedding backend used for semantic search. A single row.
+CREATE TABLE IF NOT EXISTS semantic_search_settings (
+    id INTEGER PRIMARY KEY CHECK(id = 1),
+    enabled INTEGER NOT NULL DEFAULT 0,
+    provider TEXT NOT NULL DEFAULT 'local' CHECK(provider IN ('local', 'openai')),
+    model_dir TEXT,
+    endpoint TEXT,
+    model TEXT,
+    updated_at INTEGER NOT NULL
+);
+
+-- Embedded chunks of message content. Vectors are little-endian f32
+-- arrays, normalized to unit length. Chunks from a model other than the
+-- configured one are ignored and removed on the next indexing pass.
+CREATE TABLE IF NOT EXISTS message_chunks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    model TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    dims INTEGER NOT NULL,
+    vector BLOB NOT NULL,
+    created_at INTEGER NOT NULL,
+    UNIQUE (message_id, model, chunk_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_chunks_model ON message_chunks(model);
+
+-- Edited messages are re-embedded
+CREATE TRIGGER IF NOT EXISTS message_chunks_reset_on_edit
+AFTER UPDATE OF content ON messages
+FOR EACH ROW
+WHEN OLD.content IS NOT NEW.content
+BEGIN
+    DELETE FROM message_chunks WHERE message_id = NEW.id;
+END;
@@ -0,0 +1,33 @@
+-- Every saved revision of a system prompt, numbered from 1 per prompt.
+-- Rows are written by the triggers below, so any change to a prompt's name
+-- or text is recorded no matter which code path made it.
+CREATE TABLE IF NOT EXISTS system_prompt_versions (
+    prompt_id INTEGER NOT NULL REFERENCES system_prompts(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')) NOT NULL,
+    PRIMARY KEY (prompt_id, version)
+);
+
+-- Existing prompts start at version 1
+INSERT INTO system_prompt_versions (prompt_id, version, name, prompt, created_at)
+SELECT id, 1, name, prompt, updated_at FROM system_prompts;
+
+CREATE TRIGGER IF NOT EXISTS record_system_prompt_version_on_insert
+AFTER INSERT ON system_prompts
+FOR EACH ROW
+BEGIN
+    INSERT INTO system_prompt_versions (prompt_id, version, name, prompt)
+    VALUES (NEW.id, 1, NEW.name, NEW.prompt);
+END;
+
+CREATE TRIGGER IF NOT EXISTS record_system_prompt_version_on_update
+AFTER UPDATE OF name, prompt ON system_prompts
+FOR EACH ROW
+WHEN OLD.name IS NOT NEW.name OR OLD.prompt IS NOT NEW.prompt
+BEGIN
+    INSERT INTO system_prompt_versions (prompt_id, version, name, prompt)
+    SELECT NEW.id, COALESCE(MAX(version), 0) + 1, NEW.name, NEW.prompt
+    FROM system_prompt_versions WHERE prompt_id = NEW.id;
+END;
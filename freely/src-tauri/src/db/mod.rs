@@ -1,3 +1,5 @@
+mod connection;
 mod main;
 
-pub use main::*;
+pub use connection::*;
+pub(crate) use main::*;
@@ -0,0 +1,287 @@
+//! Text embedding backends for semantic search.
+//!
+//! Two backends implement [`Embedder`]:
+//! - [`LocalEmbedder`] runs a small sentence-transformer (BERT family, e.g.
+//!   all-MiniLM-L6-v2) on the CPU. It loads `config.json`, `tokenizer.json`
+//!   and `model.safetensors` from a directory, usually under `models/`.
+//! - [`HttpEmbedder`] calls an OpenAI-compatible `/embeddings` endpoint.
+//!
+//! Vectors are L2-normalized, so cosine similarity is a dot product.
+
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use tokenizers::{PaddingParams, PaddingStrategy, Tokenizer, TruncationParams};
+
+/// Files a local model directory must contain.
+pub(crate) const LOCAL_MODEL_FILES: [&str; 3] =
+    ["config.json", "tokenizer.json", "model.safetensors"];
+
+/// Longest input, in tokens, passed to a local model.
+const MAX_TOKENS: usize = 256;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub(crate) trait Embedder: Send + Sync {
+    /// Identifies the model; vectors from different models are not compared.
+    fn model_id(&self) -> &str;
+
+    /// One normalized vector per input, in order.
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String>;
+}
+
+/// Scale `vector` to unit length. Zero vectors are left unchanged.
+pub(crate) fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+// ============================================================================
+// Local model
+// ============================================================================
+
+pub(crate) struct LocalEmbedder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+    model_id: String,
+}
+
+impl LocalEmbedder {
+    pub(crate) fn load(dir: &Path, model_id: String) -> Result<Self, String> {
+        for file in LOCAL_MODEL_FILES {
+            if !dir.join(file).is_file() {
+                return Err(format!("Embedding model is missing {}", file));
+            }
+        }
+        let device = Device::Cpu;
+        let config: Config = std::fs::read_to_string(dir.join("config.json"))
+            .map_err(|e| format!("Failed to read model config: {}", e))
+            .and_then(|json| {
+                serde_json::from_str(&json).map_err(|e| format!("Invalid model config: {}", e))
+            })?;
+
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| format!("Failed to load tokenizer: {}", e))?;
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            ..Default::default()
+        }));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..Default::default()
+            }))
+            .map_err(|e| format!("Failed to configure tokenizer: {}", e))?;
+
+        // SAFETY: the weights file is only read, and is not modified while
+        // the model is loaded.
+        let weights = unsafe {
+            VarBuilder::from_mmaped_safetensors(&[dir.join("model.safetensors")], DTYPE, &device)
+        }
+        .map_err(|e| format!("Failed to load model weights: {}", e))?;
+        let model = BertModel::load(weights, &config)
+            .map_err(|e| format!("Failed to load model: {}", e))?;
+
+        Ok(Self {
+            model,
+            tokenizer,
+            device,
+            model_id,
+        })
+    }
+
+    /// Mean of the token embeddings, ignoring padding.
+    fn pooled(&self, texts: &[String]) -> candle_core::Result<Vec<Vec<f32>>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|e| candle_core::Error::Msg(e.to_string()))?;
+        let ids = encodings
+            .iter()
+            .map(|e| Tensor::new(e.get_ids(), &self.device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let masks = encodings
+            .iter()
+            .map(|e| Tensor::new(e.get_attention_mask(), &self.device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let input_ids = Tensor::stack(&ids, 0)?;
+        let attention_mask = Tensor::stack(&masks, 0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+
+        let hidden = self
+            .model
+            .forward(&input_ids, &token_type_ids, Some(&attention_mask))?;
+        let mask = attention_mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+        let counts = mask.sum(1)?;
+        summed.broadcast_div(&counts)?.to_vec2::<f32>()
+    }
+}
+
+impl Embedder for LocalEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let vectors = self
+            .pooled(texts)
+            .map_err(|e| format!("Embedding failed: {}", e))?;
+        Ok(vectors.into_iter().map(normalize).collect())
+    }
+}
+
+// ============================================================================
+// OpenAI-compatible endpoint
+// ============================================================================
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// `/embeddings` URL for a base URL such as `https://api.openai.com/v1`.
+/// A URL that already ends in `/embeddings` is used as is.
+pub(crate) fn embeddings_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim().trim_end_matches('/');
+    if endpoint.ends_with("/embeddings") {
+        endpoint.to_string()
+    } else {
+        format!("{}/embeddings", endpoint)
+    }
+}
+
+/// Vectors from an embeddings response body, ordered by input index.
+pub(crate) fn parse_response(body: &str, expected: usize) -> Result<Vec<Vec<f32>>, String> {
+    let mut response: EmbeddingResponse =
+        serde_json::from_str(body).map_err(|e| format!("Invalid embeddings response: {}", e))?;
+    if response.data.len() != expected {
+        return Err(format!(
+            "Embeddings endpoint returned {} vectors for {} inputs",
+            response.data.len(),
+            expected
+        ));
+    }
+    response.data.sort_by_key(|d| d.index);
+    Ok(response
+        .data
+        .into_iter()
+        .map(|d| normalize(d.embedding))
+        .collect())
+}
+
+pub(crate) struct HttpEmbedder {
+    client: reqwest::Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    model_id: String,
+}
+
+impl HttpEmbedder {
+    pub(crate) fn new(
+        endpoint: &str,
+        model: &str,
+        api_key: Option<String>,
+        model_id: String,
+    ) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        Ok(Self {
+            client,
+            url: embeddings_url(endpoint),
+            model: model.to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+            model_id,
+        })
+    }
+}
+
+impl Embedder for HttpEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut request = self.client.post(&self.url).json(&serde_json::json!({
+            "model": self.model,
+            "input": texts,
+        }));
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        // Embedding runs on blocking worker threads, where waiting on the
+        // request is fine.
+        let (status, body) = tauri::async_runtime::block_on(async move {
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Embeddings request failed: {}", e))?;
+            let status = response.status();
+            let body = response
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read embeddings response: {}", e))?;
+            Ok::<_, String>((status, body))
+        })?;
+        if !status.is_success() {
+            return Err(format!("Embeddings endpoint returned {}: {}", status, body));
+        }
+        parse_response(&body, texts.len())
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_endpoint_urls() {
+        assert_eq!(
+            embeddings_url("https://api.openai.com/v1/"),
+            "https://api.openai.com/v1/embeddings"
+        );
+        assert_eq!(
+            embeddings_url("http://localhost:11434/v1/embeddings"),
+            "http://localhost:11434/v1/embeddings"
+        );
+    }
+
+    #[test]
+    fn parses_and_normalizes_responses_in_input_order() {
+        let body = r#"{"data":[
+            {"index":1,"embedding":[0.0,2.0]},
+            {"index":0,"embedding":[3.0,4.0]}
+        ]}"#;
+        let vectors = parse_response(body, 2).unwrap();
+        assert_eq!(vectors, vec![vec![0.6, 0.8], vec![0.0, 1.0]]);
+        assert!(parse_response(body, 3).is_err());
+        assert!(parse_response("{}", 1).is_err());
+    }
+}
@@ -0,0 +1,459 @@
+//! Optional encryption at rest for `freely.db`.
+//!
+//! The database is encrypted with SQLCipher using a random 256-bit raw key.
+//! The key lives in the OS keychain (see [`crate::keychain`]) and, once
+//! loaded, in memory for the session (see `db::set_session_key`).
+//!
+//! Turning encryption on or off rewrites the database in place: the contents
+//! are exported with `sqlcipher_export` into a sibling file, the copy is
+//! verified, and only then swapped in for the original. The plaintext copy
+//! is overwritten before it is removed.
+//!
+//! When the keychain has no key (a new machine, a restored backup, or the
+//! entry was removed) the database stays locked until the user unlocks it
+//! with the recovery key shown when encryption was enabled.
+
+use crate::attachments;
+use crate::data_dir;
+use crate::db;
+use crate::keychain;
+use parking_lot::Mutex;
+use rusqlite::{Connection, DatabaseName};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Keychain account holding the key; the service is the app identifier.
+const KEYCHAIN_ACCOUNT: &str = "database-key";
+
+const KEY_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionStatus {
+    pub encrypted: bool,
+    /// False while the database is encrypted and no key is loaded.
+    pub unlocked: bool,
+    pub key_in_keychain: bool,
+    /// Why the database could not be opened at startup, until it is unlocked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup_error: Option<String>,
+}
+
+/// Error from [`init_database`] at launch, kept so the UI can show it.
+#[derive(Default)]
+pub struct DatabaseStartup {
+    error: Mutex<Option<String>>,
+}
+
+// ============================================================================
+// Keys and keychain
+// ============================================================================
+
+fn generate_key() -> Result<String, String> {
+    let mut bytes = [0u8; KEY_BYTES];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate key: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Accept a recovery key as typed by the user: surrounding whitespace,
+/// spaces and dashes are ignored and case does not matter.
+fn normalize_key(input: &str) -> Result<String, String> {
+    let key: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if key.len() != KEY_BYTES * 2 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("Recovery key must be 64 hexadecimal digits".to_string());
+    }
+    Ok(key)
+}
+
+fn keychain_key(app: &AppHandle) -> Result<Option<String>, String> {
+    keychain::get(app, KEYCHAIN_ACCOUNT)
+}
+
+fn store_keychain_key(app: &AppHandle, key: &str) -> Result<(), String> {
+    keychain::set(app, KEYCHAIN_ACCOUNT, key)
+}
+
+fn delete_keychain_key(app: &AppHandle) -> Result<(), String> {
+    keychain::delete(app, KEYCHAIN_ACCOUNT)
+}
+
+// ============================================================================
+// In-place rewrite
+// ============================================================================
+
+/// SQL form of a raw key for `ATTACH ... KEY`; an empty key means plaintext.
+fn key_literal(key: Option<&str>) -> String {
+    match key {
+        Some(key) => format!("\"x'{}'\"", key),
+        None => "''".to_string(),
+    }
+}
+
+fn row_counts(conn: &Connection) -> Vec<Option<i64>> {
+    data_dir::VERIFIED_TABLES
+        .iter()
+        .map(|table| {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .ok()
+        })
+        .collect()
+}
+
+/// Overwrite `path` with zeros before removing it, so the plaintext does not
+/// linger in free blocks (best effort: SSDs and copy-on-write filesystems
+/// may keep old blocks regardless).
+fn scrub_file(path: &Path) {
+    if let Ok(meta) = std::fs::metadata(path) {
+        let _ = std::fs::write(path, vec![0u8; meta.len() as usize]);
+    }
+    let _ = std::fs::remove_file(path);
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Copy the database at `src` (readable with `src_key`) into a new file `dst`
+/// encrypted with `dst_key`, `None` meaning plaintext on either side, and
+/// verify the copy. `dst` is removed if anything fails.
+fn export_verified(
+    src: &Path,
+    src_key: Option<&str>,
+    dst: &Path,
+    dst_key: Option<&str>,
+) -> Result<(), String> {
+    let _ = std::fs::remove_file(dst);
+
+    let (version, counts) = {
+        let conn = db::open_with_key(src, src_key)?;
+        // Fold the WAL into the main file so the export sees every commit.
+        let _ = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()));
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+        conn.execute(
+            &format!(
+                "ATTACH DATABASE ?1 AS exported KEY {}",
+                key_literal(dst_key)
+            ),
+            [dst.to_string_lossy()],
+        )
+        .map_err(|e| format!("Failed to create {}: {}", dst.display(), e))?;
+        // `sqlcipher_export` copies schema and data but not `user_version`.
+        let exported = conn
+            .query_row("SELECT sqlcipher_export('exported')", [], |_| Ok(()))
+            .and_then(|_| {
+                conn.pragma_update(
+                    Some(DatabaseName::Attached("exported")),
+                    "user_version",
+                    version,
+                )
+            });
+        let _ = conn.execute_batch("DETACH DATABASE exported");
+        if let Err(e) = exported {
+            scrub_file(dst);
+            return Err(format!("Failed to export database: {}", e));
+        }
+        (version, row_counts(&conn))
+    };
+
+    let verified = db::open_with_key(dst, dst_key).and_then(|conn| {
+        let integrity: String = conn
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .map_err(|e| format!("Integrity check failed: {}", e))?;
+        let copied_version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+        if integrity != "ok" || copied_version != version || row_counts(&conn) != counts {
+            return Err("Exported database does not match the original".to_string());
+        }
+        Ok(())
+    });
+    if verified.is_err() {
+        scrub_file(dst);
+    }
+    verified
+}
+
+/// Move `replacement` into place at `path`, then scrub the file it replaced.
+/// The original is put back if the final rename fails.
+fn swap_in(path: &Path, replacement: &Path) -> Result<(), String> {
+    let previous = sidecar(path, ".previous");
+    let had_previous = path.exists();
+    if had_previous {
+        std::fs::rename(path, &previous)
+            .map_err(|e| format!("Failed to move aside {}: {}", path.display(), e))?;
+    }
+    if let Err(e) = std::fs::rename(replacement, path) {
+        if had_previous {
+            let _ = std::fs::rename(&previous, path);
+        }
+        return Err(format!("Failed to replace {}: {}", path.display(), e));
+    }
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(sidecar(path, suffix));
+    }
+    scrub_file(&previous);
+    Ok(())
+}
+
+/// Re-encrypt the database at `path` from `from_key` to `to_key` (`None` is
+/// plaintext). This is the migration that encrypts an existing database in
+/// place, and its inverse.
+///
+/// The original is untouched unless the export verifies. Connections opened
+/// before the swap keep reading the old file, so call this while idle.
+pub(crate) fn rewrite_in_place(
+    path: &Path,
+    from_key: Option<&str>,
+    to_key: Option<&str>,
+) -> Result<(), String> {
+    replace_database(path, path, from_key, to_key)
+}
+
+/// Replace the database at `path` with the contents of `src`, encrypting
+/// the result with `key`. Used to restore backups, since SQLite's online
+/// backup API refuses encrypted databases.
+pub(crate) fn replace_database(
+    path: &Path,
+    src: &Path,
+    src_key: Option<&str>,
+    key: Option<&str>,
+) -> Result<(), String> {
+    let staging = sidecar(path, ".rewrite");
+    export_verified(src, src_key, &staging, key)?;
+    swap_in(path, &staging)
+}
+
+// ============================================================================
+// Startup
+// ============================================================================
+
+/// Load the key for an encrypted database from the keychain and bring the
+/// schema up to date, moving inline attachments into blob storage. If the
+/// key is unavailable the database stays locked and migrations run on
+/// [`unlock_database`] instead.
+pub fn init_database(app: &AppHandle) -> Result<(), String> {
+    let result = open_and_migrate(app);
+    *app.state::<DatabaseStartup>().error.lock() = result.as_ref().err().cloned();
+    result
+}
+
+fn open_and_migrate(app: &AppHandle) -> Result<(), String> {
+    let path = db::db_path(app)?;
+    if db::is_encrypted(&path) && db::session_key().is_none() {
+        match keychain_key(app)? {
+            Some(key) => db::set_session_key(Some(key)),
+            None => return Err(db::LOCKED_ERROR.to_string()),
+        }
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let mut conn = db::open_at(&path)?;
+    db::migrate(&mut conn)?;
+    attachments::move_inline_attachments(&mut conn)?;
+    Ok(())
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn get_database_encryption_status(app: AppHandle) -> Result<EncryptionStatus, String> {
+    let encrypted = db::is_encrypted(&db::db_path(&app)?);
+    Ok(EncryptionStatus {
+        encrypted,
+        unlocked: !encrypted || db::session_key().is_some(),
+        key_in_keychain: keychain_key(&app)?.is_some(),
+        startup_error: app.state::<DatabaseStartup>().error.lock().clone(),
+    })
+}
+
+/// Encrypt the existing database in place. Returns the recovery key, which
+/// the user should store somewhere safe: it is the only way to unlock the
+/// database if the keychain entry is lost.
+#[tauri::command]
+pub fn enable_database_encryption(app: AppHandle) -> Result<String, String> {
+    let path = db::db_path(&app)?;
+    if db::is_encrypted(&path) {
+        return Err("Database is already encrypted".to_string());
+    }
+    let key = generate_key()?;
+    // Save the key first: a database encrypted with a lost key is unrecoverable.
+    store_keychain_key(&app, &key)?;
+    if path.exists() {
+        if let Err(e) = rewrite_in_place(&path, None, Some(&key)) {
+            let _ = delete_keychain_key(&app);
+            return Err(e);
+        }
+    }
+    db::set_session_key(Some(key.clone()));
+    Ok(key)
+}
+
+/// Decrypt the database in place and remove its key from the keychain.
+#[tauri::command]
+pub fn disable_database_encryption(app: AppHandle) -> Result<(), String> {
+    let path = db::db_path(&app)?;
+    if !db::is_encrypted(&path) {
+        return Err("Database is not encrypted".to_string());
+    }
+    let key = db::session_key().ok_or_else(|| db::LOCKED_ERROR.to_string())?;
+    rewrite_in_place(&path, Some(&key), None)?;
+    db::set_session_key(None);
+    delete_keychain_key(&app)
+}
+
+/// Forget the in-memory key. Commands that touch the database fail until
+/// [`unlock_database`] is called; the keychain entry is kept.
+#[tauri::command]
+pub fn lock_database(app: AppHandle) -> Result<EncryptionStatus, String> {
+    if !db::is_encrypted(&db::db_path(&app)?) {
+        return Err("Database is not encrypted".to_string());
+    }
+    db::set_session_key(None);
+    get_database_encryption_status(app)
+}
+
+/// Unlock the database with `recovery_key`, or with the keychain entry when
+/// no key is given. With `remember`, a working recovery key is saved to the
+/// keychain so later launches unlock automatically.
+#[tauri::command]
+pub fn unlock_database(
+    app: AppHandle,
+    recovery_key: Option<String>,
+    remember: Option<bool>,
+) -> Result<EncryptionStatus, String> {
+    let path = db::db_path(&app)?;
+    if !db::is_encrypted(&path) {
+        return get_database_encryption_status(app);
+    }
+    let key = match recovery_key.as_deref() {
+        Some(input) => normalize_key(input)?,
+        None => keychain_key(&app)?
+            .ok_or_else(|| "No key in the keychain; enter the recovery key".to_string())?,
+    };
+
+    let mut conn = db::open_with_key(&path, Some(&key))?;
+    db::set_session_key(Some(key.clone()));
+    db::migrate(&mut conn)?;
+    attachments::move_inline_attachments(&mut conn)?;
+    app.state::<DatabaseStartup>().error.lock().take();
+    if recovery_key.is_some() && remember.unwrap_or(false) {
+        store_keychain_key(&app, &key)?;
+    }
+    get_database_encryption_status(app)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn populated_db(path: &Path) {
+        let mut conn = db::open_with_key(path, None).unwrap();
+        db::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO conversations (id, title, created_at, updated_at)
+                VALUES ('c1', 'Interview notes', 1, 1);
+             INSERT INTO messages (id, conversation_id, role, content, timestamp)
+                VALUES ('m1', 'c1', 'user', 'secret answer', 1);",
+        )
+        .unwrap();
+    }
+
+    fn title(conn: &Connection) -> String {
+        conn.query_row("SELECT title FROM conversations WHERE id = 'c1'", [], |r| {
+            r.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn encrypts_in_place_and_decrypts_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(db::DB_FILE);
+        populated_db(&path);
+        let key = generate_key().unwrap();
+
+        rewrite_in_place(&path, None, Some(&key)).unwrap();
+        assert!(db::is_encrypted(&path));
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(13).any(|w| w == b"secret answer"));
+        assert!(db::open_with_key(&path, None).is_err());
+        let wrong = generate_key().unwrap();
+        assert!(db::open_with_key(&path, Some(&wrong)).is_err());
+
+        let conn = db::open_with_key(&path, Some(&key)).unwrap();
+        assert_eq!(title(&conn), "Interview notes");
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(version, db::MIGRATIONS.last().unwrap().0);
+        // The FTS index survives the export.
+        let hits: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'secret'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 1);
+        drop(conn);
+
+        rewrite_in_place(&path, Some(&key), None).unwrap();
+        assert!(!db::is_encrypted(&path));
+        assert_eq!(
+            title(&db::open_with_key(&path, None).unwrap()),
+            "Interview notes"
+        );
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers, vec![std::ffi::OsString::from(db::DB_FILE)]);
+    }
+
+    #[test]
+    fn failed_rewrite_leaves_original_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(db::DB_FILE);
+        populated_db(&path);
+        let key = generate_key().unwrap();
+
+        // Wrong source key: nothing is written.
+        assert!(rewrite_in_place(&path, Some(&key), None).is_err());
+        assert!(!db::is_encrypted(&path));
+        assert_eq!(
+            title(&db::open_with_key(&path, None).unwrap()),
+            "Interview notes"
+        );
+        assert!(!sidecar(&path, ".rewrite").exists());
+        assert!(!sidecar(&path, ".previous").exists());
+    }
+
+    #[test]
+    fn recovery_keys_are_normalized() {
+        let key = generate_key().unwrap();
+        assert_eq!(key.len(), 64);
+        let typed = format!(" {}-{} ", &key[..32].to_uppercase(), &key[32..]);
+        assert_eq!(normalize_key(&typed).unwrap(), key);
+        assert!(normalize_key("abc").is_err());
+        assert!(normalize_key(&"g".repeat(64)).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Small filesystem helpers shared by the config, library and backup modules.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Recursively collect files under `dir`, returning paths relative to `base`.
+pub(crate) fn collect_files(
+    base: &Path,
+    dir: &Path,
+    out: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(base, &path, out)?;
+        } else if let Ok(rel) = path.strip_prefix(base) {
+            out.push(rel.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Zip archive names always use `/` regardless of platform.
+pub(crate) fn archive_name(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Copy `src` into `dst` recursively, creating directories as needed.
+/// Returns the relative paths of every file copied.
+pub(crate) fn copy_dir_all(src: &Path, dst: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_files(src, src, &mut files)?;
+    for rel in &files {
+        let target = dst.join(rel);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(src.join(rel), &target)?;
+    }
+    Ok(files)
+}
+
+/// Byte-for-byte comparison that streams both files instead of loading them,
+/// so multi-hundred-megabyte model files can be verified cheaply.
+pub(crate) fn files_equal(a: &Path, b: &Path) -> std::io::Result<bool> {
+    let (meta_a, meta_b) = (std::fs::metadata(a)?, std::fs::metadata(b)?);
+    if meta_a.len() != meta_b.len() {
+        return Ok(false);
+    }
+
+    let mut ra = BufReader::new(File::open(a)?);
+    let mut rb = BufReader::new(File::open(b)?);
+    let mut buf_a = [0u8; 64 * 1024];
+    let mut buf_b = [0u8; 64 * 1024];
+    loop {
+        let n = ra.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        rb.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Write `contents` to a sibling temp file and rename it into place, so
+/// readers never observe a half-written file.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn copy_dir_all_copies_nested_files() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(src.join("a/b")).unwrap();
+        std::fs::write(src.join("top.txt"), "top").unwrap();
+        std::fs::write(src.join("a/b/deep.txt"), "deep").unwrap();
+
+        let dst = tmp.path().join("dst");
+        let mut copied = copy_dir_all(&src, &dst).unwrap();
+        copied.sort();
+        assert_eq!(
+            copied,
+            vec![PathBuf::from("a/b/deep.txt"), PathBuf::from("top.txt")]
+        );
+        assert!(files_equal(&src.join("a/b/deep.txt"), &dst.join("a/b/deep.txt")).unwrap());
+        assert_eq!(archive_name(Path::new("a").join("b").as_path()), "a/b");
+    }
+
+    #[test]
+    fn files_equal_detects_differences() {
+        let tmp = TempDir::new().unwrap();
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+        std::fs::write(&a, "same length").unwrap();
+        std::fs::write(&b, "same lengtH").unwrap();
+        assert!(!files_equal(&a, &b).unwrap());
+
+        write_atomic(&b, b"same length").unwrap();
+        assert!(files_equal(&a, &b).unwrap());
+    }
+}
@@ -0,0 +1,83 @@
+//! Secrets kept in the OS credential store, one entry per account under the
+//! app identifier.
+//!
+//! Storage goes through `tauri-plugin-keychain`. The plugin only implements
+//! its API on Android and iOS, so desktop builds reach the same stores
+//! (Keychain on macOS, Credential Manager on Windows, Secret Service on Linux)
+//! through `keyring` instead.
+
+use tauri::AppHandle;
+
+/// Secret stored for `account`, or `None` when there is none.
+pub fn get(app: &AppHandle, account: &str) -> Result<Option<String>, String> {
+    backend::get(app, account)
+        .map_err(|e| format!("Failed to read {} from keychain: {}", account, e))
+}
+
+/// Store `secret` for `account`, replacing any previous value.
+pub fn set(app: &AppHandle, account: &str, secret: &str) -> Result<(), String> {
+    backend::set(app, account, secret)
+        .map_err(|e| format!("Failed to save {} to keychain: {}", account, e))
+}
+
+/// Remove the secret for `account`; a missing entry is not an error.
+pub fn delete(app: &AppHandle, account: &str) -> Result<(), String> {
+    backend::delete(app, account)
+        .map_err(|e| format!("Failed to remove {} from keychain: {}", account, e))
+}
+
+#[cfg(mobile)]
+mod backend {
+    use tauri::AppHandle;
+    use tauri_plugin_keychain::{KeychainExt, KeychainRequest};
+
+    fn request(account: &str, password: Option<&str>) -> KeychainRequest {
+        KeychainRequest {
+            key: Some(account.to_string()),
+            password: password.map(str::to_string),
+        }
+    }
+
+    pub fn get(app: &AppHandle, account: &str) -> tauri_plugin_keychain::Result<Option<String>> {
+        let response = app.get_item().get_item(request(account, None))?;
+        Ok(response.password.filter(|p| !p.is_empty()))
+    }
+
+    pub fn set(app: &AppHandle, account: &str, secret: &str) -> tauri_plugin_keychain::Result<()> {
+        app.save_item().save_item(request(account, Some(secret)))?;
+        Ok(())
+    }
+
+    pub fn delete(app: &AppHandle, account: &str) -> tauri_plugin_keychain::Result<()> {
+        app.remove_item().remove_item(request(account, None))?;
+        Ok(())
+    }
+}
+
+#[cfg(desktop)]
+mod backend {
+    use tauri::{AppHandle, Manager};
+
+    fn entry(app: &AppHandle, account: &str) -> keyring::Result<keyring::Entry> {
+        keyring::Entry::new(&app.config().identifier, account)
+    }
+
+    pub fn get(app: &AppHandle, account: &str) -> keyring::Result<Option<String>> {
+        match entry(app, account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set(app: &AppHandle, account: &str, secret: &str) -> keyring::Result<()> {
+        entry(app, account)?.set_password(secret)
+    }
+
+    pub fn delete(app: &AppHandle, account: &str) -> keyring::Result<()> {
+        match entry(app, account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
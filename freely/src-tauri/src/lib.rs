@@ -1,16 +1,34 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 mod agents;
 mod api;
+mod attachments;
+mod backup;
 mod claude_config;
 mod capture;
+mod chat_export;
+mod chat_history;
+mod data_dir;
+mod data_migrations;
 mod db;
+mod embeddings;
+mod encryption;
+mod fs_utils;
+mod keychain;
+mod organization;
+mod profiles;
+mod prompt_variables;
+mod retention;
+mod search;
+mod semantic_search;
 mod shortcuts;
+mod skills;
+mod system_prompts;
 mod window;
 use std::sync::{Arc, Mutex};
 use parking_lot::Mutex as PLMutex;
-use tauri::{AppHandle, Manager};
+use tauri::Manager;
 #[cfg(target_os = "macos")]
-use tauri::WebviewWindow;
+use tauri::{AppHandle, WebviewWindow};
 use tauri_plugin_posthog::{init as posthog_init, PostHogConfig, PostHogOptions};
 use tokio::task::JoinHandle;
 mod speaker;
@@ -41,19 +59,19 @@ fn get_app_version() -> String {
 pub fn run() {
     // Get PostHog API key
     let posthog_api_key = option_env!("POSTHOG_API_KEY").unwrap_or("").to_string();
+    let context = tauri::generate_context!();
+    // Move legacy files into place before anything opens them.
+    data_migrations::run_at_startup(&context.config().identifier);
     #[allow(unused_mut)]
     let mut builder = tauri::Builder::default()
-        .plugin(
-            tauri_plugin_sql::Builder::default()
-                .add_migrations("sqlite:freely.db", db::migrations())
-                .build(),
-        )
         .manage(AudioState::default())
         .manage(CaptureState::default())
         .manage(WhisperState {
             engine: PLMutex::new(None),
         })
         .manage(agents::AgentProcessRegistry::default())
+        .manage(semantic_search::SemanticSearchState::default())
+        .manage(encryption::DatabaseStartup::default())
         .manage(shortcuts::WindowVisibility {
             is_hidden: Mutex::new(false),
         })
@@ -63,6 +81,7 @@ pub fn run() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_keychain::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_shell::init()) // Add shell plugin
         .plugin(posthog_init(PostHogConfig {
             api_key: posthog_api_key,
@@ -106,12 +125,15 @@ pub fn run() {
             api::fetch_models,
             api::check_license_status,
             speaker::start_system_audio_capture,
+            speaker::start_meeting_capture,
             speaker::stop_system_audio_capture,
             speaker::manual_stop_continuous,
             speaker::check_system_audio_access,
             speaker::request_system_audio_access,
             speaker::get_vad_config,
             speaker::update_vad_config,
+            speaker::calibrate_vad,
+            speaker::benchmark_vad,
             speaker::get_capture_status,
             speaker::get_audio_sample_rate,
             speaker::get_input_devices,
@@ -126,15 +148,105 @@ pub fn run() {
             agents::kill_agent_process,
             claude_config::get_claude_md,
             claude_config::update_claude_md,
+            claude_config::list_claude_md_revisions,
+            claude_config::get_claude_md_revision,
+            claude_config::diff_claude_md_revisions,
+            claude_config::restore_claude_md_revision,
+            skills::list_skills,
+            skills::get_skill,
+            skills::create_skill,
+            skills::update_skill,
+            skills::delete_skill,
+            skills::export_skills,
+            skills::import_skills,
+            skills::run_skill,
+            profiles::list_project_profiles,
+            profiles::get_active_project_profile,
+            profiles::save_project_profile,
+            profiles::delete_project_profile,
+            profiles::switch_project_profile,
+            data_dir::get_data_dir,
+            data_dir::relocate_data_dir,
+            data_dir::restart_app,
+            data_migrations::plan_data_migrations,
+            encryption::get_database_encryption_status,
+            encryption::enable_database_encryption,
+            encryption::disable_database_encryption,
+            encryption::lock_database,
+            encryption::unlock_database,
+            system_prompts::list_system_prompts,
+            system_prompts::get_system_prompt,
+            system_prompts::create_system_prompt,
+            system_prompts::update_system_prompt,
+            system_prompts::delete_system_prompt,
+            system_prompts::list_system_prompt_versions,
+            system_prompts::restore_system_prompt_version,
+            system_prompts::export_system_prompts,
+            system_prompts::import_system_prompts,
+            prompt_variables::list_prompt_variables,
+            prompt_variables::resolve_prompt_variables,
+            organization::list_conversation_folders,
+            organization::create_conversation_folder,
+            organization::rename_conversation_folder,
+            organization::delete_conversation_folder,
+            organization::move_conversations_to_folder,
+            organization::list_conversation_tags,
+            organization::tag_conversations_bulk,
+            organization::untag_conversations_bulk,
+            organization::update_conversation_tag,
+            organization::delete_conversation_tag,
+            organization::set_conversations_pinned,
+            attachments::get_attachment,
+            attachments::get_attachment_thumbnail,
+            attachments::collect_orphaned_attachments,
+            retention::get_retention_policy,
+            retention::set_retention_policy,
+            retention::preview_retention,
+            retention::prune_conversations_now,
+            retention::list_prune_log,
+            retention::clear_prune_log,
+            search::search_messages,
+            semantic_search::get_semantic_search_settings,
+            semantic_search::set_semantic_search_settings,
+            semantic_search::get_semantic_index_status,
+            semantic_search::reindex_semantic_search,
+            semantic_search::semantic_search,
+            chat_history::list_conversations_page,
+            chat_history::get_chat_conversation,
+            chat_history::get_all_chat_conversations,
+            chat_history::list_chat_messages,
+            chat_history::save_chat_conversation,
+            chat_history::create_chat_conversation,
+            chat_history::append_chat_message,
+            chat_history::rename_chat_conversation,
+            chat_history::delete_chat_conversation,
+            chat_history::delete_all_chat_conversations,
+            chat_history::delete_chat_message,
+            chat_history::list_chat_attachments,
+            chat_history::fork_chat_message,
+            chat_history::list_chat_branches,
+            chat_history::switch_chat_branch,
+            chat_history::export_chat_branch,
+            chat_export::export_conversation,
+            chat_export::render_conversation,
+            chat_export::import_conversation,
+            backup::export_backup,
+            backup::import_backup,
             speaker::init_local_whisper,
+            speaker::download_local_whisper_model,
             speaker::transcribe_local,
             speaker::get_local_whisper_status,
+            speaker::diarize::diarize_recording,
         ])
         .setup(|app| {
-            // Migrate pluely.db → freely.db for existing users before the SQL plugin
-            // opens the database for the first time.
-            migrate_pluely_db(app.handle());
-
+            // Load the encryption key and migrate the schema. A locked
+            // database is migrated later, by `unlock_database`; the error is
+            // reported to the UI through `get_database_encryption_status`.
+            if let Err(e) = encryption::init_database(app.handle()) {
+                tracing::error!("Database not ready: {}", e);
+            }
+            retention::spawn_scheduler(app.handle().clone());
+            semantic_search::spawn_indexer(app.handle().clone());
             // Setup main window positioning
             window::setup_main_window(app).expect("Failed to setup main window");
             #[cfg(target_os = "macos")]
@@ -224,60 +336,10 @@ pub fn run() {
     }
 
     builder
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 }
 
-/// One-time migration: rename `pluely.db` to `freely.db` so existing users
-/// retain their conversation history after the rename.
-///
-/// The tauri-plugin-sql stores SQLite files in `app_local_data_dir()`.
-/// We rename before the plugin opens the file (which happens lazily on first JS access).
-/// If `freely.db` already exists we leave both files untouched to avoid overwriting data.
-fn migrate_pluely_db(app: &AppHandle) {
-    let data_dir = match app.path().app_local_data_dir() {
-        Ok(dir) => dir,
-        Err(e) => {
-            eprintln!("[migrate_pluely_db] Could not resolve app_local_data_dir: {}", e);
-            return;
-        }
-    };
-
-    let old_path = data_dir.join("pluely.db");
-    let new_path = data_dir.join("freely.db");
-
-    if old_path.exists() && !new_path.exists() {
-        match std::fs::rename(&old_path, &new_path) {
-            Ok(()) => println!(
-                "[migrate_pluely_db] Renamed {:?} → {:?}",
-                old_path, new_path
-            ),
-            Err(e) => eprintln!(
-                "[migrate_pluely_db] Failed to rename {:?} → {:?}: {}",
-                old_path, new_path, e
-            ),
-        }
-
-        // Also rename SQLite WAL sidecar files if they exist.
-        for suffix in &["-wal", "-shm"] {
-            let old_sidecar = data_dir.join(format!("pluely.db{}", suffix));
-            let new_sidecar = data_dir.join(format!("freely.db{}", suffix));
-            if old_sidecar.exists() {
-                match std::fs::rename(&old_sidecar, &new_sidecar) {
-                    Ok(()) => println!(
-                        "[migrate_pluely_db] Renamed {:?} → {:?}",
-                        old_sidecar, new_sidecar
-                    ),
-                    Err(e) => eprintln!(
-                        "[migrate_pluely_db] Failed to rename {:?} → {:?}: {}",
-                        old_sidecar, new_sidecar, e
-                    ),
-                }
-            }
-        }
-    }
-}
-
 #[cfg(target_os = "macos")]
 #[allow(deprecated, unexpected_cfgs)]
 fn init(app_handle: &AppHandle) {
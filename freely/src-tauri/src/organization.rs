@@ -0,0 +1,521 @@
+//! Folders, tags and pinning for conversations (migration 8).
+//!
+//! A conversation lives in at most one folder and can carry any number of
+//! tags. Tags are addressed by name (case-insensitive) and created on first
+//! use, so the frontend can tag with free text. Bulk operations take a list
+//! of conversation ids and report how many conversations changed; unknown
+//! ids are skipped. Filtering conversation lists by folder, tag or pinned
+//! state lives in `chat_history::list_conversations`.
+
+use crate::db;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::Serialize;
+use tauri::AppHandle;
+
+const MAX_NAME_CHARS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Folder {
+    pub id: String,
+    pub name: String,
+    pub conversation_count: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub color: Option<String>,
+    pub conversation_count: i64,
+}
+
+fn validate_name(name: &str, kind: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("{} name is required", kind));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(format!("{} name cannot contain control characters", kind));
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!(
+            "{} name must be at most {} characters",
+            kind, MAX_NAME_CHARS
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn map_unique(e: rusqlite::Error, kind: &str, name: &str) -> String {
+    match e {
+        rusqlite::Error::SqliteFailure(err, _)
+            if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            format!("A {} named \"{}\" already exists", kind, name)
+        }
+        e => format!("Failed to save {}: {}", kind, e),
+    }
+}
+
+// ============================================================================
+// Folders
+// ============================================================================
+
+pub(crate) fn list_folders(conn: &Connection) -> Result<Vec<Folder>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.id, f.name, f.created_at, f.updated_at,
+                    (SELECT COUNT(*) FROM conversations c WHERE c.folder_id = f.id)
+             FROM folders f
+             ORDER BY f.name COLLATE NOCASE",
+        )
+        .map_err(|e| format!("Failed to list folders: {}", e))?;
+    let folders = stmt
+        .query_map([], |row| {
+            Ok(Folder {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+                conversation_count: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to list folders: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to list folders: {}", e))?;
+    Ok(folders)
+}
+
+fn get_folder(conn: &Connection, id: &str) -> Result<Option<Folder>, String> {
+    Ok(list_folders(conn)?.into_iter().find(|f| f.id == id))
+}
+
+pub(crate) fn create_folder(conn: &Connection, name: &str) -> Result<Folder, String> {
+    let name = validate_name(name, "Folder")?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = db::now_ms();
+    conn.execute(
+        "INSERT INTO folders (id, name, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+        params![id, name, now],
+    )
+    .map_err(|e| map_unique(e, "folder", &name))?;
+    get_folder(conn, &id)?.ok_or_else(|| "Failed to retrieve created folder".to_string())
+}
+
+pub(crate) fn rename_folder(conn: &Connection, id: &str, name: &str) -> Result<Folder, String> {
+    let name = validate_name(name, "Folder")?;
+    let changed = conn
+        .execute(
+            "UPDATE folders SET name = ?1, updated_at = ?2 WHERE id = ?3",
+            params![name, db::now_ms(), id],
+        )
+        .map_err(|e| map_unique(e, "folder", &name))?;
+    if changed == 0 {
+        return Err(format!("Folder {} not found", id));
+    }
+    get_folder(conn, id)?.ok_or_else(|| format!("Folder {} not found", id))
+}
+
+/// Delete a folder; its conversations move back to the top level.
+pub(crate) fn delete_folder(conn: &Connection, id: &str) -> Result<bool, String> {
+    let deleted = conn
+        .execute("DELETE FROM folders WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to delete folder {}: {}", id, e))?;
+    Ok(deleted > 0)
+}
+
+/// Move conversations into `folder_id`, or to the top level with `None`.
+pub(crate) fn move_conversations(
+    conn: &mut Connection,
+    conversation_ids: &[String],
+    folder_id: Option<&str>,
+) -> Result<usize, String> {
+    if let Some(folder_id) = folder_id {
+        if get_folder(conn, folder_id)?.is_none() {
+            return Err(format!("Folder {} not found", folder_id));
+        }
+    }
+    bulk(conn, conversation_ids, |tx, id| {
+        tx.execute(
+            "UPDATE conversations SET folder_id = ?1
+             WHERE id = ?2 AND folder_id IS NOT ?1",
+            params![folder_id, id],
+        )
+    })
+}
+
+// ============================================================================
+// Tags
+// ============================================================================
+
+pub(crate) fn list_tags(conn: &Connection) -> Result<Vec<Tag>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, t.color,
+                    (SELECT COUNT(*) FROM conversation_tags ct WHERE ct.tag_id = t.id)
+             FROM tags t
+             ORDER BY t.name COLLATE NOCASE",
+        )
+        .map_err(|e| format!("Failed to list tags: {}", e))?;
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                conversation_count: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Failed to list tags: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to list tags: {}", e))?;
+    Ok(tags)
+}
+
+/// Id of the tag called `name`, creating it if needed.
+fn ensure_tag(tx: &Transaction, name: &str) -> Result<i64, String> {
+    let name = validate_name(name, "Tag")?;
+    tx.execute(
+        "INSERT INTO tags (name, created_at) VALUES (?1, ?2) ON CONFLICT(name) DO NOTHING",
+        params![name, db::now_ms()],
+    )
+    .map_err(|e| format!("Failed to create tag {}: {}", name, e))?;
+    tx.query_row("SELECT id FROM tags WHERE name = ?1", [&name], |row| {
+        row.get(0)
+    })
+    .map_err(|e| format!("Failed to load tag {}: {}", name, e))
+}
+
+fn tag_id(conn: &Connection, name: &str) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT id FROM tags WHERE name = ?1",
+        [name.trim()],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load tag {}: {}", name, e))
+}
+
+/// Run `op` for every id in one transaction and count the ids it changed.
+fn bulk(
+    conn: &mut Connection,
+    conversation_ids: &[String],
+    mut op: impl FnMut(&Transaction, &str) -> rusqlite::Result<usize>,
+) -> Result<usize, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut changed = 0;
+    for id in conversation_ids {
+        if op(&tx, id).map_err(|e| format!("Failed to update conversation {}: {}", id, e))? > 0 {
+            changed += 1;
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit changes: {}", e))?;
+    Ok(changed)
+}
+
+/// Add `tags` (created as needed) to every conversation in `conversation_ids`.
+pub(crate) fn tag_conversations(
+    conn: &mut Connection,
+    conversation_ids: &[String],
+    tags: &[String],
+) -> Result<usize, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let tag_ids = tags
+        .iter()
+        .map(|name| ensure_tag(&tx, name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut changed = 0;
+    for id in conversation_ids {
+        let mut added = 0;
+        for tag_id in &tag_ids {
+            added += tx
+                .execute(
+                    "INSERT OR IGNORE INTO conversation_tags (conversation_id, tag_id)
+                     SELECT id, ?2 FROM conversations WHERE id = ?1",
+                    params![id, tag_id],
+                )
+                .map_err(|e| format!("Failed to tag conversation {}: {}", id, e))?;
+        }
+        if added > 0 {
+            changed += 1;
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit changes: {}", e))?;
+    Ok(changed)
+}
+
+/// Remove `tags` from every conversation in `conversation_ids`. The tags
+/// themselves are kept.
+pub(crate) fn untag_conversations(
+    conn: &mut Connection,
+    conversation_ids: &[String],
+    tags: &[String],
+) -> Result<usize, String> {
+    let tag_ids: Vec<i64> = tags
+        .iter()
+        .map(|name| tag_id(conn, name))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    bulk(conn, conversation_ids, |tx, id| {
+        let mut removed = 0;
+        for tag_id in &tag_ids {
+            removed += tx.execute(
+                "DELETE FROM conversation_tags WHERE conversation_id = ?1 AND tag_id = ?2",
+                params![id, tag_id],
+            )?;
+        }
+        Ok(removed)
+    })
+}
+
+pub(crate) fn update_tag(
+    conn: &Connection,
+    id: i64,
+    name: Option<&str>,
+    color: Option<&str>,
+) -> Result<Tag, String> {
+    let name = name.map(|n| validate_name(n, "Tag")).transpose()?;
+    let changed = conn
+        .execute(
+            "UPDATE tags SET name = COALESCE(?1, name), color = COALESCE(?2, color) WHERE id = ?3",
+            params![name, color, id],
+        )
+        .map_err(|e| map_unique(e, "tag", name.as_deref().unwrap_or_default()))?;
+    if changed == 0 {
+        return Err(format!("Tag {} not found", id));
+    }
+    list_tags(conn)?
+        .into_iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| format!("Tag {} not found", id))
+}
+
+/// Delete a tag and remove it from every conversation.
+pub(crate) fn delete_tag(conn: &Connection, id: i64) -> Result<bool, String> {
+    let deleted = conn
+        .execute("DELETE FROM tags WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to delete tag {}: {}", id, e))?;
+    Ok(deleted > 0)
+}
+
+// ============================================================================
+// Pinning
+// ============================================================================
+
+pub(crate) fn set_pinned(
+    conn: &mut Connection,
+    conversation_ids: &[String],
+    pinned: bool,
+) -> Result<usize, String> {
+    bulk(conn, conversation_ids, |tx, id| {
+        tx.execute(
+            "UPDATE conversations SET pinned = ?1 WHERE id = ?2 AND pinned != ?1",
+            params![pinned, id],
+        )
+    })
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn list_conversation_folders(app: AppHandle) -> Result<Vec<Folder>, String> {
+    let conn = db::open(&app)?;
+    list_folders(&conn)
+}
+
+#[tauri::command]
+pub fn create_conversation_folder(app: AppHandle, name: String) -> Result<Folder, String> {
+    let conn = db::open(&app)?;
+    create_folder(&conn, &name)
+}
+
+#[tauri::command]
+pub fn rename_conversation_folder(
+    app: AppHandle,
+    id: String,
+    name: String,
+) -> Result<Folder, String> {
+    let conn = db::open(&app)?;
+    rename_folder(&conn, &id, &name)
+}
+
+#[tauri::command]
+pub fn delete_conversation_folder(app: AppHandle, id: String) -> Result<bool, String> {
+    let conn = db::open(&app)?;
+    delete_folder(&conn, &id)
+}
+
+/// Move conversations into a folder, or to the top level when `folderId` is
+/// null. Returns how many conversations moved.
+#[tauri::command]
+pub fn move_conversations_to_folder(
+    app: AppHandle,
+    conversation_ids: Vec<String>,
+    folder_id: Option<String>,
+) -> Result<usize, String> {
+    let mut conn = db::open(&app)?;
+    move_conversations(&mut conn, &conversation_ids, folder_id.as_deref())
+}
+
+#[tauri::command]
+pub fn list_conversation_tags(app: AppHandle) -> Result<Vec<Tag>, String> {
+    let conn = db::open(&app)?;
+    list_tags(&conn)
+}
+
+#[tauri::command]
+pub fn tag_conversations_bulk(
+    app: AppHandle,
+    conversation_ids: Vec<String>,
+    tags: Vec<String>,
+) -> Result<usize, String> {
+    let mut conn = db::open(&app)?;
+    tag_conversations(&mut conn, &conversation_ids, &tags)
+}
+
+#[tauri::command]
+pub fn untag_conversations_bulk(
+    app: AppHandle,
+    conversation_ids: Vec<String>,
+    tags: Vec<String>,
+) -> Result<usize, String> {
+    let mut conn = db::open(&app)?;
+    untag_conversations(&mut conn, &conversation_ids, &tags)
+}
+
+#[tauri::command]
+pub fn update_conversation_tag(
+    app: AppHandle,
+    id: i64,
+    name: Option<String>,
+    color: Option<String>,
+) -> Result<Tag, String> {
+    let conn = db::open(&app)?;
+    update_tag(&conn, id, name.as_deref(), color.as_deref())
+}
+
+#[tauri::command]
+pub fn delete_conversation_tag(app: AppHandle, id: i64) -> Result<bool, String> {
+    let conn = db::open(&app)?;
+    delete_tag(&conn, id)
+}
+
+#[tauri::command]
+pub fn set_conversations_pinned(
+    app: AppHandle,
+    conversation_ids: Vec<String>,
+    pinned: bool,
+) -> Result<usize, String> {
+    let mut conn = db::open(&app)?;
+    set_pinned(&mut conn, &conversation_ids, pinned)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed() -> Connection {
+        let conn = db::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES
+                ('c1', 'Interview: Ada', 1, 1),
+                ('c2', 'Interview: Bob', 2, 2),
+                ('c3', 'Standup', 3, 3);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn ids(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn bulk_tagging_creates_tags_and_counts() {
+        let mut conn = seed();
+        let changed = tag_conversations(
+            &mut conn,
+            &ids(&["c1", "c2", "missing"]),
+            &ids(&["hiring", "Backend"]),
+        )
+        .unwrap();
+        assert_eq!(changed, 2);
+        // Names are case-insensitive, so this reuses "hiring".
+        assert_eq!(
+            tag_conversations(&mut conn, &ids(&["c1", "c3"]), &ids(&["HIRING"])).unwrap(),
+            1
+        );
+
+        let tags = list_tags(&conn).unwrap();
+        let counts: Vec<(&str, i64)> = tags
+            .iter()
+            .map(|t| (t.name.as_str(), t.conversation_count))
+            .collect();
+        assert_eq!(counts, vec![("Backend", 2), ("hiring", 3)]);
+
+        assert_eq!(
+            untag_conversations(&mut conn, &ids(&["c1", "c2"]), &ids(&["backend"])).unwrap(),
+            2
+        );
+        let hiring = tags.iter().find(|t| t.name == "hiring").unwrap();
+        assert!(delete_tag(&conn, hiring.id).unwrap());
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM conversation_tags", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn folders_move_conversations_and_release_them_on_delete() {
+        let mut conn = seed();
+        let folder = create_folder(&conn, " Interviews ").unwrap();
+        assert_eq!(folder.name, "Interviews");
+        assert!(create_folder(&conn, "interviews").is_err());
+
+        let moved = move_conversations(&mut conn, &ids(&["c1", "c2"]), Some(&folder.id)).unwrap();
+        assert_eq!(moved, 2);
+        // Already there: nothing changes.
+        assert_eq!(
+            move_conversations(&mut conn, &ids(&["c1"]), Some(&folder.id)).unwrap(),
+            0
+        );
+        assert!(move_conversations(&mut conn, &ids(&["c3"]), Some("nope")).is_err());
+        assert_eq!(list_folders(&conn).unwrap()[0].conversation_count, 2);
+
+        assert!(delete_folder(&conn, &folder.id).unwrap());
+        let filed: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM conversations WHERE folder_id IS NOT NULL",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(filed, 0);
+    }
+
+    #[test]
+    fn pinning_reports_only_changed_conversations() {
+        let mut conn = seed();
+        assert_eq!(set_pinned(&mut conn, &ids(&["c1", "c2"]), true).unwrap(), 2);
+        assert_eq!(set_pinned(&mut conn, &ids(&["c2", "c3"]), true).unwrap(), 1);
+        assert_eq!(set_pinned(&mut conn, &ids(&["c1"]), false).unwrap(), 1);
+    }
+}
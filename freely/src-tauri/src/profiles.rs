@@ -0,0 +1,442 @@
+//! Named project profiles for agent runs.
+//!
+//! A profile bundles a project directory with defaults for the agent backend,
+//! model, allowed tools and extra instructions. Profiles live in the
+//! `project_profiles` table of `freely.db`; at most one is active at a time.
+//!
+//! When `run_claude` runs inside a project directory the CLI picks up the
+//! project's own `CLAUDE.md`, so Freely's `.claude/CLAUDE.md` plus the
+//! profile's extra instructions are layered on top via `--append-system-prompt`
+//! (see [`instruction_layer`]).
+
+use crate::db;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+const BACKENDS: [&str; 3] = ["claude", "codex", "gemini"];
+
+const PROFILE_COLUMNS: &str = "id, name, directory, default_backend, default_model, allowed_tools, extra_instructions, is_active, created_at, updated_at";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectProfile {
+    pub id: String,
+    pub name: String,
+    pub directory: String,
+    /// Agent backend runs under this profile use: "claude" | "codex" | "gemini".
+    pub default_backend: String,
+    pub default_model: Option<String>,
+    /// Tool patterns passed to `--allowedTools` when the run doesn't specify any.
+    pub allowed_tools: Vec<String>,
+    pub extra_instructions: Option<String>,
+    pub is_active: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Create/update payload. A missing `id` creates a new profile.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectProfileInput {
+    pub id: Option<String>,
+    pub name: String,
+    pub directory: String,
+    pub default_backend: Option<String>,
+    pub default_model: Option<String>,
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    pub extra_instructions: Option<String>,
+}
+
+fn row_to_profile(row: &Row) -> rusqlite::Result<ProjectProfile> {
+    let allowed_tools: Option<String> = row.get(5)?;
+    Ok(ProjectProfile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        directory: row.get(2)?,
+        default_backend: row.get(3)?,
+        default_model: row.get(4)?,
+        allowed_tools: allowed_tools
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        extra_instructions: row.get(6)?,
+        is_active: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+/// Treat empty strings from form fields as "not set".
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+// ============================================================================
+// Queries (connection-based for testability)
+// ============================================================================
+
+pub(crate) fn list_profiles(conn: &Connection) -> Result<Vec<ProjectProfile>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM project_profiles ORDER BY name COLLATE NOCASE",
+            PROFILE_COLUMNS
+        ))
+        .map_err(|e| format!("Failed to query profiles: {}", e))?;
+    let rows = stmt
+        .query_map([], row_to_profile)
+        .map_err(|e| format!("Failed to query profiles: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read profile: {}", e))
+}
+
+pub(crate) fn get_profile(conn: &Connection, id: &str) -> Result<Option<ProjectProfile>, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM project_profiles WHERE id = ?1",
+            PROFILE_COLUMNS
+        ),
+        params![id],
+        row_to_profile,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load profile: {}", e))
+}
+
+pub(crate) fn get_active_profile(conn: &Connection) -> Result<Option<ProjectProfile>, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM project_profiles WHERE is_active = 1",
+            PROFILE_COLUMNS
+        ),
+        [],
+        row_to_profile,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load active profile: {}", e))
+}
+
+pub(crate) fn save_profile(
+    conn: &Connection,
+    input: ProjectProfileInput,
+) -> Result<ProjectProfile, String> {
+    let name = input.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name is required".to_string());
+    }
+    if !std::path::Path::new(&input.directory).is_dir() {
+        return Err(format!(
+            "Project directory does not exist: {}",
+            input.directory
+        ));
+    }
+    let backend = non_empty(input.default_backend).unwrap_or_else(|| "claude".to_string());
+    if !BACKENDS.contains(&backend.as_str()) {
+        return Err(format!("Unknown agent backend: {}", backend));
+    }
+    let allowed_tools: Vec<String> = input
+        .allowed_tools
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let allowed_tools_json = if allowed_tools.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&allowed_tools).map_err(|e| e.to_string())?)
+    };
+
+    let now = db::now_ms();
+    let id = match input.id {
+        Some(id) => {
+            let updated = conn
+                .execute(
+                    "UPDATE project_profiles SET name = ?2, directory = ?3, default_backend = ?4, default_model = ?5, allowed_tools = ?6, extra_instructions = ?7, updated_at = ?8 WHERE id = ?1",
+                    params![
+                        id,
+                        name,
+                        input.directory,
+                        backend,
+                        non_empty(input.default_model),
+                        allowed_tools_json,
+                        non_empty(input.extra_instructions),
+                        now
+                    ],
+                )
+                .map_err(|e| map_unique_error(e, &name))?;
+            if updated == 0 {
+                return Err(format!("Profile not found: {}", id));
+            }
+            id
+        }
+        None => {
+            let id = uuid::Uuid::new_v4().to_string();
+            conn.execute(
+                "INSERT INTO project_profiles (id, name, directory, default_backend, default_model, allowed_tools, extra_instructions, is_active, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 0, ?8, ?8)",
+                params![
+                    id,
+                    name,
+                    input.directory,
+                    backend,
+                    non_empty(input.default_model),
+                    allowed_tools_json,
+                    non_empty(input.extra_instructions),
+                    now
+                ],
+            )
+            .map_err(|e| map_unique_error(e, &name))?;
+            id
+        }
+    };
+
+    get_profile(conn, &id)?.ok_or_else(|| format!("Profile not found: {}", id))
+}
+
+fn map_unique_error(e: rusqlite::Error, name: &str) -> String {
+    match e {
+        rusqlite::Error::SqliteFailure(err, _)
+            if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            format!("A profile named '{}' already exists", name)
+        }
+        other => format!("Failed to save profile: {}", other),
+    }
+}
+
+pub(crate) fn delete_profile(conn: &Connection, id: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM project_profiles WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to delete profile: {}", e))?;
+    Ok(())
+}
+
+/// Make `id` the active profile, or deactivate all profiles when `None`.
+pub(crate) fn set_active_profile(
+    conn: &mut Connection,
+    id: Option<&str>,
+) -> Result<Option<ProjectProfile>, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    tx.execute(
+        "UPDATE project_profiles SET is_active = 0 WHERE is_active = 1",
+        [],
+    )
+    .map_err(|e| format!("Failed to switch profile: {}", e))?;
+    if let Some(id) = id {
+        let updated = tx
+            .execute(
+                "UPDATE project_profiles SET is_active = 1 WHERE id = ?1",
+                params![id],
+            )
+            .map_err(|e| format!("Failed to switch profile: {}", e))?;
+        if updated == 0 {
+            return Err(format!("Profile not found: {}", id));
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to switch profile: {}", e))?;
+
+    get_active_profile(conn)
+}
+
+// ============================================================================
+// Agent integration
+// ============================================================================
+
+/// Resolve the profile a `run_claude` call asked for. Runs without a
+/// `profile_id` use no profile; the frontend sends the active one's id.
+///
+/// An unknown id is an error.
+pub(crate) fn resolve_for_run(
+    app: &AppHandle,
+    profile_id: Option<&str>,
+) -> Result<Option<ProjectProfile>, String> {
+    let Some(id) = profile_id else {
+        return Ok(None);
+    };
+    let conn = db::open(app)?;
+    get_profile(&conn, id)?
+        .map(Some)
+        .ok_or_else(|| format!("Profile not found: {}", id))
+}
+
+/// Build the extra instruction layer appended to the system prompt when the
+/// CLI runs inside a project directory: Freely's own `CLAUDE.md` followed by
+/// the profile's extra instructions. Returns `None` when both are empty.
+pub(crate) fn instruction_layer(
+    freely_claude_md: Option<&str>,
+    profile: Option<&ProjectProfile>,
+) -> Option<String> {
+    let mut sections = Vec::new();
+
+    if let Some(md) = freely_claude_md.map(str::trim).filter(|s| !s.is_empty()) {
+        sections.push(md.to_string());
+    }
+    if let Some(profile) = profile {
+        if let Some(extra) = profile
+            .extra_instructions
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            sections.push(format!("## Project profile: {}\n\n{}", profile.name, extra));
+        }
+    }
+
+    if sections.is_empty() {
+        None
+    } else {
+        Some(sections.join("\n\n"))
+    }
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn list_project_profiles(app: AppHandle) -> Result<Vec<ProjectProfile>, String> {
+    let conn = db::open(&app)?;
+    list_profiles(&conn)
+}
+
+#[tauri::command]
+pub fn get_active_project_profile(app: AppHandle) -> Result<Option<ProjectProfile>, String> {
+    let conn = db::open(&app)?;
+    get_active_profile(&conn)
+}
+
+#[tauri::command]
+pub fn save_project_profile(
+    app: AppHandle,
+    profile: ProjectProfileInput,
+) -> Result<ProjectProfile, String> {
+    let conn = db::open(&app)?;
+    save_profile(&conn, profile)
+}
+
+#[tauri::command]
+pub fn delete_project_profile(app: AppHandle, id: String) -> Result<(), String> {
+    let conn = db::open(&app)?;
+    delete_profile(&conn, &id)
+}
+
+/// Switch the active profile. Pass `null` to run without a profile.
+#[tauri::command]
+pub fn switch_project_profile(
+    app: AppHandle,
+    id: Option<String>,
+) -> Result<Option<ProjectProfile>, String> {
+    let mut conn = db::open(&app)?;
+    set_active_profile(&mut conn, id.as_deref())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn input(name: &str, dir: &TempDir) -> ProjectProfileInput {
+        ProjectProfileInput {
+            id: None,
+            name: name.to_string(),
+            directory: dir.path().to_string_lossy().to_string(),
+            default_backend: None,
+            default_model: Some("sonnet".to_string()),
+            allowed_tools: vec![
+                "Read".to_string(),
+                " ".to_string(),
+                "Bash(git diff)".to_string(),
+            ],
+            extra_instructions: Some("Use pnpm, not npm.".to_string()),
+        }
+    }
+
+    #[test]
+    fn save_creates_and_updates_profile() {
+        let conn = db::open_in_memory();
+        let dir = TempDir::new().unwrap();
+
+        let created = save_profile(&conn, input("api", &dir)).unwrap();
+        assert_eq!(created.default_backend, "claude");
+        assert_eq!(created.allowed_tools, vec!["Read", "Bash(git diff)"]);
+        assert!(!created.is_active);
+
+        let mut update = input("api-server", &dir);
+        update.id = Some(created.id.clone());
+        update.default_model = Some("  ".to_string());
+        let updated = save_profile(&conn, update).unwrap();
+        assert_eq!(updated.id, created.id);
+        assert_eq!(updated.name, "api-server");
+        assert!(
+            updated.default_model.is_none(),
+            "blank model should be cleared"
+        );
+
+        assert_eq!(list_profiles(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn save_validates_input() {
+        let conn = db::open_in_memory();
+        let dir = TempDir::new().unwrap();
+
+        let mut missing_dir = input("x", &dir);
+        missing_dir.directory = dir.path().join("nope").to_string_lossy().to_string();
+        assert!(save_profile(&conn, missing_dir).is_err());
+
+        let mut bad_backend = input("x", &dir);
+        bad_backend.default_backend = Some("cursor".to_string());
+        assert!(save_profile(&conn, bad_backend).is_err());
+
+        save_profile(&conn, input("dup", &dir)).unwrap();
+        let err = save_profile(&conn, input("dup", &dir)).unwrap_err();
+        assert!(err.contains("already exists"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn only_one_profile_is_active() {
+        let mut conn = db::open_in_memory();
+        let dir = TempDir::new().unwrap();
+        let a = save_profile(&conn, input("a", &dir)).unwrap();
+        let b = save_profile(&conn, input("b", &dir)).unwrap();
+
+        let active = set_active_profile(&mut conn, Some(&a.id)).unwrap().unwrap();
+        assert_eq!(active.id, a.id);
+
+        let active = set_active_profile(&mut conn, Some(&b.id)).unwrap().unwrap();
+        assert_eq!(active.id, b.id);
+        assert!(!get_profile(&conn, &a.id).unwrap().unwrap().is_active);
+
+        assert!(set_active_profile(&mut conn, None).unwrap().is_none());
+        assert!(get_active_profile(&conn).unwrap().is_none());
+
+        // Unknown id rolls back and leaves the previous state intact.
+        set_active_profile(&mut conn, Some(&a.id)).unwrap();
+        assert!(set_active_profile(&mut conn, Some("missing")).is_err());
+        assert_eq!(get_active_profile(&conn).unwrap().unwrap().id, a.id);
+    }
+
+    #[test]
+    fn instruction_layer_merges_freely_md_and_profile() {
+        let conn = db::open_in_memory();
+        let dir = TempDir::new().unwrap();
+        let profile = save_profile(&conn, input("web", &dir)).unwrap();
+
+        let layer = instruction_layer(Some("# Freely Assistant\n"), Some(&profile)).unwrap();
+        assert!(layer.starts_with("# Freely Assistant"));
+        assert!(layer.contains("## Project profile: web\n\nUse pnpm, not npm."));
+
+        assert_eq!(
+            instruction_layer(Some("# Freely"), None).as_deref(),
+            Some("# Freely")
+        );
+        assert!(instruction_layer(Some("  "), None).is_none());
+    }
+}
@@ -0,0 +1,262 @@
+//! `{{variable}}` placeholders in system prompts, resolved when a message is
+//! sent rather than when the prompt is saved.
+//!
+//! Each variable is looked up at most once per prompt, and only if the
+//! prompt uses it, so the clipboard is not read unless asked for.
+//! Placeholders that are unknown or have no value right now (an empty
+//! clipboard, no selected conversation) are left in the text as written and
+//! reported back, so the frontend can point them out.
+
+use crate::chat_history;
+use crate::db;
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// Messages quoted in `{{conversation_summary}}`.
+const SUMMARY_MESSAGES: usize = 6;
+/// Characters kept from each quoted message.
+const SUMMARY_MESSAGE_CHARS: usize = 200;
+
+/// Supported variables as `(name, description)`.
+pub(crate) const VARIABLES: &[(&str, &str)] = &[
+    ("date", "Today's date, e.g. 2024-06-10"),
+    ("time", "Current local time, e.g. 14:05"),
+    ("clipboard", "Text currently on the clipboard"),
+    (
+        "conversation_summary",
+        "Title and latest messages of the selected conversation",
+    ),
+    (
+        "active_window",
+        "Title of the frontmost window of another app",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PromptVariable {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedPrompt {
+    pub prompt: String,
+    /// Placeholders left unexpanded, in order of first use.
+    pub unresolved: Vec<String>,
+}
+
+/// Expand `{{name}}` placeholders in `template` using `lookup`. Whitespace
+/// inside the braces is ignored. Values are inserted verbatim and never
+/// expanded again.
+pub(crate) fn render(
+    template: &str,
+    mut lookup: impl FnMut(&str) -> Option<String>,
+) -> ResolvedPrompt {
+    let mut values: HashMap<String, Option<String>> = HashMap::new();
+    let mut unresolved: Vec<String> = Vec::new();
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + 2 + len + 2];
+        let name = rest[start + 2..start + 2 + len].trim();
+        out.push_str(&rest[..start]);
+        rest = &rest[start + placeholder.len()..];
+
+        let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            out.push_str(placeholder);
+            continue;
+        }
+        let value = values
+            .entry(name.to_string())
+            .or_insert_with(|| lookup(name));
+        match value {
+            Some(value) => out.push_str(value),
+            None => {
+                out.push_str(placeholder);
+                if !unresolved.iter().any(|n| n == name) {
+                    unresolved.push(name.to_string());
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+    ResolvedPrompt {
+        prompt: out,
+        unresolved,
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// Title and the last few user and assistant messages of a conversation's
+/// active branch, each shortened to a single line.
+pub(crate) fn conversation_summary(
+    conn: &rusqlite::Connection,
+    conversation_id: &str,
+) -> Result<Option<String>, String> {
+    let Some(conversation) = chat_history::get_conversation(conn, conversation_id)? else {
+        return Ok(None);
+    };
+    let messages: Vec<_> = conversation
+        .messages
+        .iter()
+        .filter(|m| m.role != "system" && !m.content.trim().is_empty())
+        .collect();
+    let mut lines = vec![format!("Conversation: {}", conversation.title)];
+    for message in &messages[messages.len().saturating_sub(SUMMARY_MESSAGES)..] {
+        let speaker = if message.role == "user" {
+            "User"
+        } else {
+            "Assistant"
+        };
+        lines.push(format!(
+            "{}: {}",
+            speaker,
+            truncate(&message.content, SUMMARY_MESSAGE_CHARS)
+        ));
+    }
+    Ok(Some(lines.join("\n")))
+}
+
+/// Title of the frontmost visible window that does not belong to this app.
+fn active_window_title(app: &AppHandle) -> Option<String> {
+    let own_name = app.package_info().name.to_lowercase();
+    xcap::Window::all()
+        .ok()?
+        .into_iter()
+        .filter(|w| !w.is_minimized() && w.app_name().to_lowercase() != own_name)
+        .map(|w| w.title().trim().to_string())
+        .find(|title| !title.is_empty())
+}
+
+fn clipboard_text(app: &AppHandle) -> Option<String> {
+    app.clipboard()
+        .read_text()
+        .ok()
+        .filter(|text| !text.trim().is_empty())
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn list_prompt_variables() -> Vec<PromptVariable> {
+    VARIABLES
+        .iter()
+        .map(|(name, description)| PromptVariable {
+            name: name.to_string(),
+            description: description.to_string(),
+        })
+        .collect()
+}
+
+/// Expand the variables in `prompt` for a message about to be sent in
+/// `conversation_id`.
+#[tauri::command]
+pub fn resolve_prompt_variables(
+    app: AppHandle,
+    prompt: String,
+    conversation_id: Option<String>,
+) -> Result<ResolvedPrompt, String> {
+    let now = chrono::Local::now();
+    let mut error = None;
+    let resolved = render(&prompt, |name| match name {
+        "date" => Some(now.format("%Y-%m-%d").to_string()),
+        "time" => Some(now.format("%H:%M").to_string()),
+        "clipboard" => clipboard_text(&app),
+        "active_window" => active_window_title(&app),
+        "conversation_summary" => {
+            let id = conversation_id.as_deref()?;
+            db::open(&app)
+                .and_then(|conn| conversation_summary(&conn, id))
+                .unwrap_or_else(|e| {
+                    error = Some(e);
+                    None
+                })
+        }
+        _ => None,
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(resolved),
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_history::{save_conversation, ChatConversation, ChatMessage};
+
+    #[test]
+    fn expands_known_values_once_and_keeps_the_rest() {
+        let mut calls = Vec::new();
+        let resolved = render(
+            "Today is {{ date }}. {{date}} again; {{clipboard}}, {{nope}}, {{not a var}}, {{open",
+            |name| {
+                calls.push(name.to_string());
+                (name == "date").then(|| "2024-06-10 {{clipboard}}".to_string())
+            },
+        );
+        assert_eq!(
+            resolved.prompt,
+            "Today is 2024-06-10 {{clipboard}}. 2024-06-10 {{clipboard}} again; \
+             {{clipboard}}, {{nope}}, {{not a var}}, {{open"
+        );
+        assert_eq!(resolved.unresolved, vec!["clipboard", "nope"]);
+        assert_eq!(calls, vec!["date", "clipboard", "nope"]);
+    }
+
+    #[test]
+    fn summarizes_the_latest_messages() {
+        let mut conn = db::open_in_memory();
+        let messages = (0..8)
+            .map(|i| ChatMessage {
+                id: format!("m{}", i),
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: format!("message {}\n{}", i, "x".repeat(300)),
+                timestamp: i + 1,
+                attached_files: None,
+                parent_id: None,
+                metadata: None,
+            })
+            .collect();
+        save_conversation(
+            &mut conn,
+            &ChatConversation {
+                id: "c1".to_string(),
+                title: "Standup".to_string(),
+                messages,
+                created_at: 1,
+                updated_at: 1,
+            },
+        )
+        .unwrap();
+
+        let summary = conversation_summary(&conn, "c1").unwrap().unwrap();
+        let lines: Vec<_> = summary.lines().collect();
+        assert_eq!(lines.len(), 1 + SUMMARY_MESSAGES);
+        assert_eq!(lines[0], "Conversation: Standup");
+        assert!(lines[1].starts_with("User: message 2 xxx"));
+        assert!(lines[6].starts_with("Assistant: message 7"));
+        assert!(lines[6].ends_with('…'));
+        assert_eq!(conversation_summary(&conn, "missing").unwrap(), None);
+    }
+}
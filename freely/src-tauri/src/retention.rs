@@ -0,0 +1,697 @@
+//! Retention rules and automatic pruning of old conversations.
+//!
+//! The policy (migration 7) can cap conversation age and count, optionally
+//! sparing pinned conversations. A background task applies it every
+//! [`CHECK_INTERVAL`]; [`preview_retention`] shows what a policy would remove
+//! without touching anything.
+//!
+//! Pruning runs with `secure_delete` on, optimizes the full-text index so
+//! deleted terms are dropped from its segments, then `VACUUM`s and truncates
+//! the WAL, so removed content does not linger in free pages. Each pruned
+//! conversation is recorded in `prune_log`.
+//!
+//! The age rule also applies to continuous-mode recordings: the background
+//! task removes recording sessions last written longer ago than that.
+
+use crate::db;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter};
+use tracing::{error, warn};
+
+/// Delay before the first automatic run, so startup stays fast.
+const STARTUP_DELAY: Duration = Duration::from_secs(60);
+
+/// How often the background task re-applies the policy.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+const DEFAULT_LOG_LIMIT: u32 = 100;
+
+/// Event emitted with a [`PruneReport`] when the background task removes
+/// conversations.
+pub const PRUNED_EVENT: &str = "conversations-pruned";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Whether the background task applies the policy.
+    pub enabled: bool,
+    pub max_age_days: Option<u32>,
+    pub max_conversations: Option<u32>,
+    #[serde(default = "default_keep_pinned")]
+    pub keep_pinned: bool,
+}
+
+fn default_keep_pinned() -> bool {
+    true
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_age_days: None,
+            max_conversations: None,
+            keep_pinned: true,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    fn has_rules(&self) -> bool {
+        self.max_age_days.is_some() || self.max_conversations.is_some()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.max_age_days == Some(0) {
+            return Err("Maximum age must be at least one day".to_string());
+        }
+        if self.max_conversations == Some(0) {
+            return Err("Maximum conversations must be at least one".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PruneReason {
+    MaxAge,
+    MaxConversations,
+}
+
+impl PruneReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            PruneReason::MaxAge => "maxAge",
+            PruneReason::MaxConversations => "maxConversations",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PruneSource {
+    Manual,
+    Automatic,
+}
+
+impl PruneSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            PruneSource::Manual => "manual",
+            PruneSource::Automatic => "automatic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneCandidate {
+    pub conversation_id: String,
+    pub title: String,
+    pub message_count: i64,
+    pub updated_at: i64,
+    pub reason: PruneReason,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub conversations: Vec<PruneCandidate>,
+    /// Messages removed, or that would be removed on a dry run.
+    pub messages_deleted: i64,
+    /// False when the database could not be compacted afterwards (for
+    /// example because another connection was busy); the rows are still
+    /// deleted and their pages zeroed.
+    pub vacuumed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneLogEntry {
+    pub id: i64,
+    pub pruned_at: i64,
+    pub conversation_id: String,
+    pub title: String,
+    pub message_count: i64,
+    pub last_updated_at: i64,
+    pub reason: String,
+    pub source: String,
+}
+
+// ============================================================================
+// Policy
+// ============================================================================
+
+pub(crate) fn load_policy(conn: &Connection) -> Result<RetentionPolicy, String> {
+    let policy = conn
+        .query_row(
+            "SELECT enabled, max_age_days, max_conversations, keep_pinned
+             FROM retention_policy WHERE id = 1",
+            [],
+            |row| {
+                Ok(RetentionPolicy {
+                    enabled: row.get(0)?,
+                    max_age_days: row.get(1)?,
+                    max_conversations: row.get(2)?,
+                    keep_pinned: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Failed to load retention policy: {}", e))?;
+    Ok(policy.unwrap_or_default())
+}
+
+pub(crate) fn save_policy(conn: &Connection, policy: &RetentionPolicy) -> Result<(), String> {
+    policy.validate()?;
+    conn.execute(
+        "INSERT INTO retention_policy (id, enabled, max_age_days, max_conversations, keep_pinned, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(id) DO UPDATE SET
+            enabled = excluded.enabled,
+            max_age_days = excluded.max_age_days,
+            max_conversations = excluded.max_conversations,
+            keep_pinned = excluded.keep_pinned,
+            updated_at = excluded.updated_at",
+        params![
+            policy.enabled,
+            policy.max_age_days,
+            policy.max_conversations,
+            policy.keep_pinned,
+            db::now_ms()
+        ],
+    )
+    .map_err(|e| format!("Failed to save retention policy: {}", e))?;
+    Ok(())
+}
+
+// ============================================================================
+// Pruning
+// ============================================================================
+
+const CANDIDATE_COLUMNS: &str = "c.id, c.title, c.updated_at,
+    (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id)";
+
+fn query_candidates(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+    reason: PruneReason,
+) -> Result<Vec<PruneCandidate>, String> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| format!("Failed to find conversations to prune: {}", e))?;
+    let candidates = stmt
+        .query_map(params, |row| {
+            Ok(PruneCandidate {
+                conversation_id: row.get(0)?,
+                title: row.get(1)?,
+                updated_at: row.get(2)?,
+                message_count: row.get(3)?,
+                reason,
+            })
+        })
+        .map_err(|e| format!("Failed to find conversations to prune: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to find conversations to prune: {}", e))?;
+    Ok(candidates)
+}
+
+/// Conversations `policy` would remove at time `now` (ms), oldest first.
+/// With `keep_pinned`, pinned conversations are neither removed nor counted
+/// towards `max_conversations`.
+pub(crate) fn find_candidates(
+    conn: &Connection,
+    policy: &RetentionPolicy,
+    now: i64,
+) -> Result<Vec<PruneCandidate>, String> {
+    policy.validate()?;
+    let eligible = if policy.keep_pinned {
+        "c.pinned = 0"
+    } else {
+        "1"
+    };
+    let mut candidates = Vec::new();
+
+    if let Some(days) = policy.max_age_days {
+        let cutoff = now - i64::from(days) * DAY_MS;
+        candidates.extend(query_candidates(
+            conn,
+            &format!(
+                "SELECT {} FROM conversations c WHERE {} AND c.updated_at < ?1",
+                CANDIDATE_COLUMNS, eligible
+            ),
+            [cutoff],
+            PruneReason::MaxAge,
+        )?);
+    }
+
+    if let Some(max) = policy.max_conversations {
+        let seen: HashSet<String> = candidates
+            .iter()
+            .map(|c| c.conversation_id.clone())
+            .collect();
+        let over_limit = query_candidates(
+            conn,
+            &format!(
+                "SELECT {} FROM conversations c WHERE {}
+                 ORDER BY c.updated_at DESC, c.id LIMIT -1 OFFSET ?1",
+                CANDIDATE_COLUMNS, eligible
+            ),
+            [i64::from(max)],
+            PruneReason::MaxConversations,
+        )?;
+        candidates.extend(
+            over_limit
+                .into_iter()
+                .filter(|c| !seen.contains(&c.conversation_id)),
+        );
+    }
+
+    candidates.sort_by(|a, b| {
+        (a.updated_at, &a.conversation_id).cmp(&(b.updated_at, &b.conversation_id))
+    });
+    Ok(candidates)
+}
+
+/// Reclaim the space of deleted rows so their content does not survive in
+/// free pages, the FTS index or the WAL.
+fn compact(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "INSERT INTO messages_fts(messages_fts) VALUES ('optimize');
+         VACUUM;",
+    )
+    .map_err(|e| format!("Failed to compact database: {}", e))?;
+    let _ = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()));
+    Ok(())
+}
+
+fn prune(
+    conn: &mut Connection,
+    policy: &RetentionPolicy,
+    now: i64,
+    dry_run: bool,
+    source: PruneSource,
+) -> Result<PruneReport, String> {
+    let conversations = find_candidates(conn, policy, now)?;
+    let messages_deleted = conversations.iter().map(|c| c.message_count).sum();
+    if dry_run || conversations.is_empty() {
+        return Ok(PruneReport {
+            dry_run,
+            conversations,
+            messages_deleted,
+            vacuumed: false,
+        });
+    }
+
+    // Overwrite deleted content with zeros instead of just unlinking pages.
+    conn.pragma_update(None, "secure_delete", "ON")
+        .map_err(|e| format!("Failed to enable secure delete: {}", e))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start pruning: {}", e))?;
+    for c in &conversations {
+        tx.execute(
+            "INSERT INTO prune_log
+                (pruned_at, conversation_id, title, message_count, last_updated_at, reason, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                now,
+                c.conversation_id,
+                c.title,
+                c.message_count,
+                c.updated_at,
+                c.reason.as_str(),
+                source.as_str()
+            ],
+        )
+        .map_err(|e| format!("Failed to log pruned conversation: {}", e))?;
+        // Messages first, by conversation id, so the delete does not depend
+        // on how the schema cascades.
+        tx.execute(
+            "DELETE FROM messages WHERE conversation_id = ?1",
+            [&c.conversation_id],
+        )
+        .map_err(|e| format!("Failed to delete messages of {}: {}", c.conversation_id, e))?;
+        tx.execute(
+            "DELETE FROM conversations WHERE id = ?1",
+            [&c.conversation_id],
+        )
+        .map_err(|e| format!("Failed to delete conversation {}: {}", c.conversation_id, e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit pruning: {}", e))?;
+
+    let vacuumed = match compact(conn) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("{}", e);
+            false
+        }
+    };
+    Ok(PruneReport {
+        dry_run,
+        conversations,
+        messages_deleted,
+        vacuumed,
+    })
+}
+
+pub(crate) fn list_log(
+    conn: &Connection,
+    limit: Option<u32>,
+) -> Result<Vec<PruneLogEntry>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, pruned_at, conversation_id, title, message_count, last_updated_at, reason, source
+             FROM prune_log ORDER BY pruned_at DESC, id DESC LIMIT ?1",
+        )
+        .map_err(|e| format!("Failed to read prune log: {}", e))?;
+    let entries = stmt
+        .query_map([i64::from(limit.unwrap_or(DEFAULT_LOG_LIMIT))], |row| {
+            Ok(PruneLogEntry {
+                id: row.get(0)?,
+                pruned_at: row.get(1)?,
+                conversation_id: row.get(2)?,
+                title: row.get(3)?,
+                message_count: row.get(4)?,
+                last_updated_at: row.get(5)?,
+                reason: row.get(6)?,
+                source: row.get(7)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read prune log: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read prune log: {}", e))?;
+    Ok(entries)
+}
+
+/// Remove recording sessions in `dir` last modified more than
+/// `max_age_days` before `now`. Returns how many were removed.
+pub(crate) fn prune_recordings(dir: &Path, max_age_days: u32, now: SystemTime) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let max_age = Duration::from_secs(u64::from(max_age_days) * 24 * 60 * 60);
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > max_age));
+        if !path.is_dir() || !expired {
+            continue;
+        }
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Failed to remove recording {}: {}", path.display(), e),
+        }
+    }
+    removed
+}
+
+// ============================================================================
+// Background task
+// ============================================================================
+
+fn run_scheduled(app: &AppHandle) -> Result<Option<PruneReport>, String> {
+    let mut conn = db::open(app)?;
+    let policy = load_policy(&conn)?;
+    if !policy.enabled || !policy.has_rules() {
+        return Ok(None);
+    }
+    if let Some(days) = policy.max_age_days {
+        let dir = crate::data_dir::current_layout(app)?.recordings_dir();
+        prune_recordings(&dir, days, SystemTime::now());
+    }
+    let report = prune(
+        &mut conn,
+        &policy,
+        db::now_ms(),
+        false,
+        PruneSource::Automatic,
+    )?;
+    Ok((!report.conversations.is_empty()).then_some(report))
+}
+
+/// Apply the saved policy shortly after startup and then every
+/// [`CHECK_INTERVAL`]. Runs are skipped while the database is locked.
+pub fn spawn_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        loop {
+            let handle = app.clone();
+            match tauri::async_runtime::spawn_blocking(move || run_scheduled(&handle)).await {
+                Ok(Ok(Some(report))) => {
+                    let _ = app.emit(PRUNED_EVENT, &report);
+                }
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) if e == db::LOCKED_ERROR => {}
+                Ok(Err(e)) => error!("Automatic pruning failed: {}", e),
+                Err(e) => error!("Pruning task panicked: {}", e),
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn get_retention_policy(app: AppHandle) -> Result<RetentionPolicy, String> {
+    let conn = db::open(&app)?;
+    load_policy(&conn)
+}
+
+#[tauri::command]
+pub fn set_retention_policy(app: AppHandle, policy: RetentionPolicy) -> Result<(), String> {
+    let conn = db::open(&app)?;
+    save_policy(&conn, &policy)
+}
+
+/// Dry run: what `policy` (or the saved policy) would prune right now.
+/// `enabled` is ignored so rules can be previewed before turning them on.
+#[tauri::command]
+pub fn preview_retention(
+    app: AppHandle,
+    policy: Option<RetentionPolicy>,
+) -> Result<PruneReport, String> {
+    let mut conn = db::open(&app)?;
+    let policy = match policy {
+        Some(policy) => policy,
+        None => load_policy(&conn)?,
+    };
+    prune(&mut conn, &policy, db::now_ms(), true, PruneSource::Manual)
+}
+
+/// Apply the saved policy now, whether or not automatic pruning is enabled.
+#[tauri::command]
+pub fn prune_conversations_now(app: AppHandle) -> Result<PruneReport, String> {
+    let mut conn = db::open(&app)?;
+    let policy = load_policy(&conn)?;
+    prune(&mut conn, &policy, db::now_ms(), false, PruneSource::Manual)
+}
+
+#[tauri::command]
+pub fn list_prune_log(app: AppHandle, limit: Option<u32>) -> Result<Vec<PruneLogEntry>, String> {
+    let conn = db::open(&app)?;
+    list_log(&conn, limit)
+}
+
+#[tauri::command]
+pub fn clear_prune_log(app: AppHandle) -> Result<(), String> {
+    let conn = db::open(&app)?;
+    conn.execute("DELETE FROM prune_log", [])
+        .map_err(|e| format!("Failed to clear prune log: {}", e))?;
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 100 * DAY_MS;
+
+    /// Conversations c1..c4 updated 40, 20, 10 and 1 days ago; c1 is pinned.
+    fn seed(conn: &Connection) {
+        for (id, days_ago, pinned) in [("c1", 40, 1), ("c2", 20, 0), ("c3", 10, 0), ("c4", 1, 0)] {
+            let ts = NOW - days_ago * DAY_MS;
+            conn.execute(
+                "INSERT INTO conversations (id, title, created_at, updated_at, pinned)
+                 VALUES (?1, ?1, ?2, ?2, ?3)",
+                params![id, ts, pinned],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO messages (id, conversation_id, role, content, timestamp)
+                 VALUES (?1 || '-m', ?1, 'user', 'budget discussion', ?2)",
+                params![id, ts],
+            )
+            .unwrap();
+        }
+    }
+
+    fn ids(candidates: &[PruneCandidate]) -> Vec<&str> {
+        candidates
+            .iter()
+            .map(|c| c.conversation_id.as_str())
+            .collect()
+    }
+
+    fn policy(max_age_days: Option<u32>, max_conversations: Option<u32>) -> RetentionPolicy {
+        RetentionPolicy {
+            enabled: true,
+            max_age_days,
+            max_conversations,
+            keep_pinned: true,
+        }
+    }
+
+    #[test]
+    fn selects_by_age_and_count_sparing_pinned() {
+        let conn = db::open_in_memory();
+        seed(&conn);
+
+        let by_age = find_candidates(&conn, &policy(Some(15), None), NOW).unwrap();
+        assert_eq!(ids(&by_age), vec!["c2"]);
+
+        // Pinned c1 does not count towards the limit of two.
+        let by_count = find_candidates(&conn, &policy(None, Some(2)), NOW).unwrap();
+        assert_eq!(ids(&by_count), vec!["c2"]);
+
+        let both = find_candidates(
+            &conn,
+            &RetentionPolicy {
+                keep_pinned: false,
+                ..policy(Some(30), Some(2))
+            },
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(ids(&both), vec!["c1", "c2"]);
+        assert_eq!(both[0].reason, PruneReason::MaxAge);
+        assert_eq!(both[1].reason, PruneReason::MaxConversations);
+    }
+
+    #[test]
+    fn dry_run_changes_nothing_and_prune_logs_and_deletes() {
+        let mut conn = db::open_in_memory();
+        seed(&conn);
+        let rules = policy(Some(5), None);
+
+        let preview = prune(&mut conn, &rules, NOW, true, PruneSource::Manual).unwrap();
+        assert_eq!(ids(&preview.conversations), vec!["c2", "c3"]);
+        assert_eq!(preview.messages_deleted, 2);
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM conversations", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 4);
+
+        let report = prune(&mut conn, &rules, NOW, false, PruneSource::Automatic).unwrap();
+        assert_eq!(report.messages_deleted, 2);
+        assert!(report.vacuumed);
+        let remaining: Vec<String> = conn
+            .prepare("SELECT id FROM conversations ORDER BY id")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(remaining, vec!["c1", "c4"]);
+        let hits: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'budget'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 2);
+
+        let log = list_log(&conn, None).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log
+            .iter()
+            .all(|e| e.source == "automatic" && e.reason == "maxAge"));
+    }
+
+    #[test]
+    fn prunes_conversations_with_long_reply_chains() {
+        let mut conn = db::open_in_memory();
+        seed(&conn);
+        let ts = NOW - 30 * DAY_MS;
+        let tx = conn.transaction().unwrap();
+        for i in 0..1500 {
+            tx.execute(
+                "INSERT INTO messages (id, conversation_id, parent_id, role, content, timestamp)
+                 VALUES ('long-' || ?1, 'c2', CASE WHEN ?1 = 0 THEN 'c2-m' ELSE 'long-' || (?1 - 1) END,
+                         'user', 'more', ?2)",
+                params![i, ts + i],
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+
+        let report = prune(
+            &mut conn,
+            &policy(Some(15), None),
+            NOW,
+            false,
+            PruneSource::Manual,
+        )
+        .unwrap();
+        assert_eq!(ids(&report.conversations), vec!["c2"]);
+        assert_eq!(report.messages_deleted, 1501);
+        let left: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM messages WHERE conversation_id = 'c2'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    #[test]
+    fn policy_roundtrips_and_rejects_zero_limits() {
+        let conn = db::open_in_memory();
+        assert_eq!(load_policy(&conn).unwrap(), RetentionPolicy::default());
+
+        let saved = policy(Some(30), Some(200));
+        save_policy(&conn, &saved).unwrap();
+        assert_eq!(load_policy(&conn).unwrap(), saved);
+
+        assert!(save_policy(&conn, &policy(Some(0), None)).is_err());
+        assert!(save_policy(&conn, &policy(None, Some(0))).is_err());
+        assert_eq!(load_policy(&conn).unwrap(), saved);
+    }
+
+    #[test]
+    fn prunes_recordings_past_the_age_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let session = tmp.path().join("20260101-120000");
+        std::fs::create_dir(&session).unwrap();
+        std::fs::write(session.join("recording.wav"), [0u8; 4]).unwrap();
+
+        let now = SystemTime::now();
+        assert_eq!(prune_recordings(tmp.path(), 1, now), 0);
+        assert!(session.exists());
+        let later = now + Duration::from_secs(2 * 24 * 60 * 60);
+        assert_eq!(prune_recordings(tmp.path(), 1, later), 1);
+        assert!(!session.exists());
+        assert_eq!(prune_recordings(&tmp.path().join("missing"), 1, later), 0);
+    }
+}
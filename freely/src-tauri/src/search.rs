@@ -0,0 +1,301 @@
+//! Full-text search over chat history.
+//!
+//! Backed by the `messages_fts` FTS5 table (migration 4), which triggers keep
+//! in sync with `messages`. Results are ranked by BM25 and carry a snippet of
+//! the matching text plus the offsets of the highlighted terms, so the
+//! frontend can render highlights without re-tokenizing.
+
+use crate::db;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, Row};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 200;
+
+/// Tokens of context on each side of a match in the snippet.
+const SNIPPET_TOKENS: i64 = 16;
+
+// Control characters cannot appear in the tokenized text, so they are safe
+// markers for locating highlights in the snippet.
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    pub query: String,
+    pub conversation_id: Option<String>,
+    /// "user" | "assistant" | "system"
+    pub role: Option<String>,
+    /// Inclusive lower bound on the message timestamp (ms since the Unix epoch).
+    pub from: Option<i64>,
+    /// Inclusive upper bound on the message timestamp (ms since the Unix epoch).
+    pub to: Option<i64>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Highlighted range within [`SearchHit::snippet`], in UTF-16 code units so
+/// it can be passed straight to `String.prototype.slice`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub message_id: String,
+    pub conversation_id: String,
+    pub conversation_title: String,
+    pub role: String,
+    pub timestamp: i64,
+    pub snippet: String,
+    pub highlights: Vec<HighlightRange>,
+    /// BM25 score; lower is more relevant.
+    pub rank: f64,
+}
+
+/// Turn free-form user input into an FTS5 query: every word is quoted so
+/// punctuation and operators are matched literally, and the last word is a
+/// prefix match so results update while typing.
+pub(crate) fn to_fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", terms.join(" ")))
+}
+
+/// Strip highlight markers from `marked`, returning the plain snippet and
+/// the highlighted ranges.
+fn extract_highlights(marked: &str) -> (String, Vec<HighlightRange>) {
+    let mut snippet = String::with_capacity(marked.len());
+    let mut highlights = Vec::new();
+    let mut pos = 0;
+    let mut start = None;
+    for ch in marked.chars() {
+        match ch {
+            HIGHLIGHT_START => start = Some(pos),
+            HIGHLIGHT_END => {
+                if let Some(start) = start.take() {
+                    highlights.push(HighlightRange { start, end: pos });
+                }
+            }
+            _ => {
+                snippet.push(ch);
+                pos += ch.len_utf16();
+            }
+        }
+    }
+    (snippet, highlights)
+}
+
+fn row_to_hit(row: &Row) -> rusqlite::Result<SearchHit> {
+    let marked: String = row.get(5)?;
+    let (snippet, highlights) = extract_highlights(&marked);
+    Ok(SearchHit {
+        message_id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        conversation_title: row.get(2)?,
+        role: row.get(3)?,
+        timestamp: row.get(4)?,
+        snippet,
+        highlights,
+        rank: row.get(6)?,
+    })
+}
+
+/// Search message content. An empty query returns no results.
+pub(crate) fn search_messages_in(
+    conn: &Connection,
+    query: &SearchQuery,
+) -> Result<Vec<SearchHit>, String> {
+    let Some(fts_query) = to_fts_query(&query.query) else {
+        return Ok(Vec::new());
+    };
+
+    let mut sql = format!(
+        "SELECT m.id, m.conversation_id, c.title, m.role, m.timestamp,
+                snippet(messages_fts, 0, '{}', '{}', '…', {}), messages_fts.rank
+         FROM messages_fts
+         JOIN message_search_ids s ON s.docid = messages_fts.rowid
+         JOIN messages m ON m.id = s.message_id
+         JOIN conversations c ON c.id = m.conversation_id
+         WHERE messages_fts MATCH ?",
+        HIGHLIGHT_START, HIGHLIGHT_END, SNIPPET_TOKENS
+    );
+    let mut params: Vec<Value> = vec![fts_query.into()];
+
+    if let Some(conversation_id) = &query.conversation_id {
+        sql.push_str(" AND m.conversation_id = ?");
+        params.push(conversation_id.clone().into());
+    }
+    if let Some(role) = &query.role {
+        sql.push_str(" AND m.role = ?");
+        params.push(role.clone().into());
+    }
+    if let Some(from) = query.from {
+        sql.push_str(" AND m.timestamp >= ?");
+        params.push(from.into());
+    }
+    if let Some(to) = query.to {
+        sql.push_str(" AND m.timestamp <= ?");
+        params.push(to.into());
+    }
+
+    sql.push_str(" ORDER BY messages_fts.rank, m.timestamp DESC LIMIT ? OFFSET ?");
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    params.push(i64::from(limit).into());
+    params.push(i64::from(query.offset.unwrap_or(0)).into());
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("Failed to prepare search: {}", e))?;
+    let hits = stmt
+        .query_map(params_from_iter(params), row_to_hit)
+        .map_err(|e| format!("Search failed: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Search failed: {}", e))?;
+    Ok(hits)
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn search_messages(app: AppHandle, query: SearchQuery) -> Result<Vec<SearchHit>, String> {
+    let conn = db::open(&app)?;
+    search_messages_in(&conn, &query)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(conn: &Connection) {
+        conn.execute_batch(
+            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES
+                ('c1', 'Rust questions', 1, 1),
+                ('c2', 'Trip planning', 1, 1);
+             INSERT INTO messages (id, conversation_id, role, content, timestamp) VALUES
+                ('m1', 'c1', 'user', 'How do lifetimes work in Rust?', 100),
+                ('m2', 'c1', 'assistant', 'Lifetimes describe how long references are valid.', 200),
+                ('m3', 'c2', 'user', 'Plan a café crawl in Lisbon', 300);",
+        )
+        .unwrap();
+    }
+
+    fn search(conn: &Connection, query: SearchQuery) -> Vec<SearchHit> {
+        search_messages_in(conn, &query).unwrap()
+    }
+
+    fn text(q: &str) -> SearchQuery {
+        SearchQuery {
+            query: q.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_prefix_matches_with_highlight_offsets() {
+        let conn = db::open_in_memory();
+        seed(&conn);
+
+        let hits = search(&conn, text("lifetim"));
+        assert_eq!(hits.len(), 2);
+        let hit = hits.iter().find(|h| h.message_id == "m2").unwrap();
+        assert_eq!(hit.conversation_title, "Rust questions");
+        let first = &hit.highlights[0];
+        assert_eq!(&hit.snippet[first.start..first.end], "Lifetimes");
+
+        // Diacritics are folded and offsets count UTF-16 units.
+        let hits = search(&conn, text("cafe"));
+        assert_eq!(hits.len(), 1);
+        let h = &hits[0].highlights[0];
+        let units: Vec<u16> = hits[0].snippet.encode_utf16().collect();
+        assert_eq!(String::from_utf16(&units[h.start..h.end]).unwrap(), "café");
+    }
+
+    #[test]
+    fn filters_by_role_conversation_and_date() {
+        let conn = db::open_in_memory();
+        seed(&conn);
+
+        let by_role = search(
+            &conn,
+            SearchQuery {
+                role: Some("user".to_string()),
+                ..text("lifetimes")
+            },
+        );
+        assert_eq!(by_role.len(), 1);
+        assert_eq!(by_role[0].message_id, "m1");
+
+        let by_date = search(
+            &conn,
+            SearchQuery {
+                from: Some(150),
+                to: Some(250),
+                ..text("lifetimes")
+            },
+        );
+        assert_eq!(by_date.len(), 1);
+        assert_eq!(by_date[0].message_id, "m2");
+
+        let other_conversation = search(
+            &conn,
+            SearchQuery {
+                conversation_id: Some("c2".to_string()),
+                ..text("lifetimes")
+            },
+        );
+        assert!(other_conversation.is_empty());
+    }
+
+    #[test]
+    fn index_tracks_updates_and_deletes() {
+        let conn = db::open_in_memory();
+        seed(&conn);
+
+        conn.execute(
+            "UPDATE messages SET content = 'Borrowing rules' WHERE id = 'm1'",
+            [],
+        )
+        .unwrap();
+        assert_eq!(search(&conn, text("lifetimes")).len(), 1);
+        assert_eq!(search(&conn, text("borrowing")).len(), 1);
+
+        conn.execute("DELETE FROM conversations WHERE id = 'c1'", [])
+            .unwrap();
+        assert!(search(&conn, text("borrowing")).is_empty());
+        let ids: i64 = conn
+            .query_row("SELECT COUNT(*) FROM message_search_ids", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(ids, 1);
+    }
+
+    #[test]
+    fn user_input_is_not_parsed_as_fts_syntax() {
+        let conn = db::open_in_memory();
+        seed(&conn);
+
+        assert!(search(&conn, text("   ")).is_empty());
+        assert!(search(&conn, text("\"unbalanced AND (")).is_empty());
+        assert_eq!(
+            to_fts_query("say \"hi\"").unwrap(),
+            "\"say\" \"\"\"hi\"\"\"*"
+        );
+    }
+}
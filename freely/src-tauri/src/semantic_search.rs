@@ -0,0 +1,918 @@
+//! Semantic search over chat history.
+//!
+//! Message content is split into overlapping chunks of words, embedded with
+//! the configured backend (see `embeddings`) and stored in `message_chunks`
+//! (migration 11). A background task embeds new and edited messages every
+//! [`INDEX_INTERVAL`]; editing a message clears its chunks through a
+//! trigger, and deleting it cascades. Chunks are tagged with the model that
+//! produced them, so switching models re-indexes everything.
+//!
+//! Search embeds the query and scans the stored vectors for the configured
+//! model. With a few hundred thousand chunks a linear scan stays well under
+//! a second, so there is no separate vector index. Each message is scored by
+//! its best chunk, and hits carry the neighbouring messages on their branch
+//! as context.
+
+use crate::db;
+use crate::embeddings::{self, Embedder, HttpEmbedder, LocalEmbedder};
+use crate::keychain;
+use parking_lot::Mutex;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{error, warn};
+
+/// Delay before the first background pass, so startup stays fast.
+const STARTUP_DELAY: Duration = Duration::from_secs(30);
+
+/// How often the background task looks for messages to embed.
+const INDEX_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Words per chunk, and words shared by consecutive chunks.
+const CHUNK_WORDS: usize = 120;
+const CHUNK_OVERLAP: usize = 20;
+
+/// Messages embedded per request to the backend.
+const BATCH_MESSAGES: usize = 16;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 50;
+
+/// Characters kept from each context message.
+const CONTEXT_CHARS: usize = 300;
+
+const KEYCHAIN_ACCOUNT: &str = "embeddings-api-key";
+
+/// Event emitted with an [`IndexReport`] when the background task embeds
+/// messages.
+pub const INDEXED_EVENT: &str = "semantic-index-updated";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingProvider {
+    /// Sentence-transformer run on the CPU from `model_dir`.
+    #[default]
+    Local,
+    /// OpenAI-compatible `/embeddings` endpoint.
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+impl EmbeddingProvider {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::OpenAi => "openai",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticSearchSettings {
+    /// Whether new messages are embedded in the background.
+    pub enabled: bool,
+    #[serde(default)]
+    pub provider: EmbeddingProvider,
+    /// Directory holding the local model's files.
+    pub model_dir: Option<String>,
+    /// Base URL of the embeddings API, e.g. `https://api.openai.com/v1`.
+    pub endpoint: Option<String>,
+    /// Model name sent to the embeddings API.
+    pub model: Option<String>,
+}
+
+impl SemanticSearchSettings {
+    fn validate(&self) -> Result<(), String> {
+        match self.provider {
+            EmbeddingProvider::Local => {
+                let dir = non_blank(&self.model_dir)
+                    .ok_or("A model directory is required for local embeddings")?;
+                for file in embeddings::LOCAL_MODEL_FILES {
+                    if !Path::new(dir).join(file).is_file() {
+                        return Err(format!("The model directory has no {}", file));
+                    }
+                }
+            }
+            EmbeddingProvider::OpenAi => {
+                let endpoint =
+                    non_blank(&self.endpoint).ok_or("An embeddings endpoint is required")?;
+                if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                    return Err("The embeddings endpoint must be an http(s) URL".to_string());
+                }
+                non_blank(&self.model).ok_or("An embeddings model name is required")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tag stored with every chunk this configuration produces.
+    pub(crate) fn model_id(&self) -> String {
+        match self.provider {
+            EmbeddingProvider::Local => {
+                format!("local:{}", non_blank(&self.model_dir).unwrap_or_default())
+            }
+            EmbeddingProvider::OpenAi => format!(
+                "openai:{}:{}",
+                embeddings::embeddings_url(non_blank(&self.endpoint).unwrap_or_default()),
+                non_blank(&self.model).unwrap_or_default()
+            ),
+        }
+    }
+}
+
+fn non_blank(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticQuery {
+    pub query: String,
+    pub conversation_id: Option<String>,
+    /// Inclusive lower bound on the message timestamp (ms since the Unix epoch).
+    pub from: Option<i64>,
+    /// Inclusive upper bound on the message timestamp (ms since the Unix epoch).
+    pub to: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContextMessage {
+    pub id: String,
+    pub role: String,
+    pub content: String,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticHit {
+    pub message_id: String,
+    pub conversation_id: String,
+    pub conversation_title: String,
+    pub role: String,
+    pub timestamp: i64,
+    /// Best-matching chunk of the message.
+    pub chunk: String,
+    /// Cosine similarity; higher is more relevant.
+    pub score: f32,
+    /// The message this one replies to.
+    pub previous: Option<ContextMessage>,
+    /// The latest reply to this message.
+    pub next: Option<ContextMessage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexReport {
+    pub indexed_messages: usize,
+    pub chunks: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStatus {
+    pub model: String,
+    pub indexed_messages: i64,
+    pub pending_messages: i64,
+    pub chunks: i64,
+    pub has_api_key: bool,
+    /// Most recent background indexing error, cleared on success.
+    pub last_error: Option<String>,
+}
+
+/// Loaded embedding backend and indexing bookkeeping, managed by Tauri.
+#[derive(Default)]
+pub struct SemanticSearchState {
+    embedder: Mutex<Option<Arc<dyn Embedder>>>,
+    /// Held for the duration of an indexing pass so passes never overlap.
+    indexing: Mutex<()>,
+    last_error: Mutex<Option<String>>,
+}
+
+// ============================================================================
+// Settings
+// ============================================================================
+
+pub(crate) fn load_settings(conn: &Connection) -> Result<SemanticSearchSettings, String> {
+    let settings = conn
+        .query_row(
+            "SELECT enabled, provider, model_dir, endpoint, model
+             FROM semantic_search_settings WHERE id = 1",
+            [],
+            |row| {
+                let provider: String = row.get(1)?;
+                Ok(SemanticSearchSettings {
+                    enabled: row.get(0)?,
+                    provider: if provider == "openai" {
+                        EmbeddingProvider::OpenAi
+                    } else {
+                        EmbeddingProvider::Local
+                    },
+                    model_dir: row.get(2)?,
+                    endpoint: row.get(3)?,
+                    model: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Failed to load semantic search settings: {}", e))?;
+    Ok(settings.unwrap_or_default())
+}
+
+pub(crate) fn save_settings(
+    conn: &Connection,
+    settings: &SemanticSearchSettings,
+) -> Result<(), String> {
+    if settings.enabled {
+        settings.validate()?;
+    }
+    conn.execute(
+        "INSERT INTO semantic_search_settings (id, enabled, provider, model_dir, endpoint, model, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(id) DO UPDATE SET
+            enabled = excluded.enabled,
+            provider = excluded.provider,
+            model_dir = excluded.model_dir,
+            endpoint = excluded.endpoint,
+            model = excluded.model,
+            updated_at = excluded.updated_at",
+        params![
+            settings.enabled,
+            settings.provider.as_str(),
+            non_blank(&settings.model_dir),
+            non_blank(&settings.endpoint),
+            non_blank(&settings.model),
+            db::now_ms()
+        ],
+    )
+    .map_err(|e| format!("Failed to save semantic search settings: {}", e))?;
+    Ok(())
+}
+
+fn api_key(app: &AppHandle) -> Result<Option<String>, String> {
+    keychain::get(app, KEYCHAIN_ACCOUNT)
+}
+
+fn set_api_key(app: &AppHandle, key: &str) -> Result<(), String> {
+    if key.trim().is_empty() {
+        keychain::delete(app, KEYCHAIN_ACCOUNT)
+    } else {
+        keychain::set(app, KEYCHAIN_ACCOUNT, key.trim())
+    }
+}
+
+/// Backend for `settings`, reusing the loaded one if it matches.
+fn embedder_for(
+    app: &AppHandle,
+    settings: &SemanticSearchSettings,
+) -> Result<Arc<dyn Embedder>, String> {
+    let state = app.state::<SemanticSearchState>();
+    let model_id = settings.model_id();
+    let mut slot = state.embedder.lock();
+    if let Some(embedder) = slot.as_ref().filter(|e| e.model_id() == model_id) {
+        return Ok(embedder.clone());
+    }
+    settings.validate()?;
+    let embedder: Arc<dyn Embedder> = match settings.provider {
+        EmbeddingProvider::Local => Arc::new(LocalEmbedder::load(
+            Path::new(non_blank(&settings.model_dir).unwrap_or_default()),
+            model_id,
+        )?),
+        EmbeddingProvider::OpenAi => Arc::new(HttpEmbedder::new(
+            non_blank(&settings.endpoint).unwrap_or_default(),
+            non_blank(&settings.model).unwrap_or_default(),
+            api_key(app)?,
+            model_id,
+        )?),
+    };
+    *slot = Some(embedder.clone());
+    Ok(embedder)
+}
+
+// ============================================================================
+// Indexing
+// ============================================================================
+
+/// Split `text` into chunks of up to [`CHUNK_WORDS`] words, each sharing
+/// [`CHUNK_OVERLAP`] words with the one before.
+pub(crate) fn chunk_text(text: &str) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let end = (start + CHUNK_WORDS).min(words.len());
+        chunks.push(words[start..end].join(" "));
+        if end == words.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+// Whitespace-only messages have no chunks and would stay pending forever.
+const PENDING_CONDITION: &str = "trim(m.content, ' ' || char(9, 10, 11, 12, 13)) <> ''
+    AND NOT EXISTS (SELECT 1 FROM message_chunks c WHERE c.message_id = m.id AND c.model = ?1)";
+
+fn pending_ids(conn: &Connection, model: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT m.id FROM messages m WHERE {} ORDER BY m.timestamp DESC",
+            PENDING_CONDITION
+        ))
+        .map_err(|e| format!("Failed to find messages to index: {}", e))?;
+    let ids = stmt
+        .query_map([model], |row| row.get(0))
+        .map_err(|e| format!("Failed to find messages to index: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to find messages to index: {}", e))?;
+    Ok(ids)
+}
+
+fn message_content(conn: &Connection, id: &str) -> Result<Option<String>, String> {
+    conn.query_row("SELECT content FROM messages WHERE id = ?1", [id], |row| {
+        row.get(0)
+    })
+    .optional()
+    .map_err(|e| format!("Failed to load message {}: {}", id, e))
+}
+
+/// Embed every message without chunks for the embedder's model, newest
+/// first, and drop chunks left over from other models. Messages edited
+/// while their batch was being embedded are picked up on the next pass.
+pub(crate) fn index_pending(
+    conn: &mut Connection,
+    embedder: &dyn Embedder,
+) -> Result<IndexReport, String> {
+    let model = embedder.model_id().to_string();
+    conn.execute("DELETE FROM message_chunks WHERE model <> ?1", [&model])
+        .map_err(|e| format!("Failed to clear old embeddings: {}", e))?;
+
+    let mut report = IndexReport::default();
+    for batch in pending_ids(conn, &model)?.chunks(BATCH_MESSAGES) {
+        let mut messages = Vec::new();
+        for id in batch {
+            if let Some(content) = message_content(conn, id)? {
+                let chunks = chunk_text(&content);
+                if !chunks.is_empty() {
+                    messages.push((id, chunks, content));
+                }
+            }
+        }
+        if messages.is_empty() {
+            continue;
+        }
+        let texts: Vec<String> = messages
+            .iter()
+            .flat_map(|(_, chunks, _)| chunks.iter().cloned())
+            .collect();
+        let mut vectors = embedder.embed(&texts)?.into_iter();
+
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let now = db::now_ms();
+        for (id, chunks, content) in &messages {
+            let message_vectors: Vec<Vec<f32>> = vectors.by_ref().take(chunks.len()).collect();
+            if message_content(&tx, id)?.as_ref() != Some(content) {
+                continue;
+            }
+            tx.execute(
+                "DELETE FROM message_chunks WHERE message_id = ?1 AND model = ?2",
+                params![id, model],
+            )
+            .map_err(|e| format!("Failed to replace embeddings: {}", e))?;
+            for (index, (text, vector)) in chunks.iter().zip(&message_vectors).enumerate() {
+                tx.execute(
+                    "INSERT INTO message_chunks
+                         (message_id, model, chunk_index, text, dims, vector, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        id,
+                        model,
+                        index as i64,
+                        text,
+                        vector.len() as i64,
+                        to_blob(vector),
+                        now
+                    ],
+                )
+                .map_err(|e| format!("Failed to store embedding: {}", e))?;
+            }
+            report.indexed_messages += 1;
+            report.chunks += chunks.len();
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to commit embeddings: {}", e))?;
+    }
+    Ok(report)
+}
+
+pub(crate) fn index_status(conn: &Connection, model: &str) -> Result<IndexStatus, String> {
+    let count = |sql: &str| -> Result<i64, String> {
+        conn.query_row(sql, [model], |row| row.get(0))
+            .map_err(|e| format!("Failed to read index status: {}", e))
+    };
+    Ok(IndexStatus {
+        model: model.to_string(),
+        indexed_messages: count(
+            "SELECT COUNT(DISTINCT message_id) FROM message_chunks WHERE model = ?1",
+        )?,
+        pending_messages: count(&format!(
+            "SELECT COUNT(*) FROM messages m WHERE {}",
+            PENDING_CONDITION
+        ))?,
+        chunks: count("SELECT COUNT(*) FROM message_chunks WHERE model = ?1")?,
+        ..Default::default()
+    })
+}
+
+// ============================================================================
+// Search
+// ============================================================================
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn context_message(
+    conn: &Connection,
+    sql: &str,
+    id: &str,
+) -> Result<Option<ContextMessage>, String> {
+    conn.query_row(sql, [id], |row| {
+        Ok(ContextMessage {
+            id: row.get(0)?,
+            role: row.get(1)?,
+            content: truncate(&row.get::<_, String>(2)?, CONTEXT_CHARS),
+            timestamp: row.get(3)?,
+        })
+    })
+    .optional()
+    .map_err(|e| format!("Failed to load context: {}", e))
+}
+
+/// Messages most similar to `query.query`, best first.
+pub(crate) fn search(
+    conn: &Connection,
+    embedder: &dyn Embedder,
+    query: &SemanticQuery,
+) -> Result<Vec<SemanticHit>, String> {
+    let text = query.query.trim();
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(target) = embedder.embed(&[text.to_string()])?.pop() else {
+        return Ok(Vec::new());
+    };
+
+    let mut sql = "SELECT c.message_id, c.text, c.vector FROM message_chunks c
+                   JOIN messages m ON m.id = c.message_id
+                   WHERE c.model = ? AND c.dims = ?"
+        .to_string();
+    let mut params: Vec<Value> = vec![
+        embedder.model_id().to_string().into(),
+        (target.len() as i64).into(),
+    ];
+    if let Some(conversation_id) = &query.conversation_id {
+        sql.push_str(" AND m.conversation_id = ?");
+        params.push(conversation_id.clone().into());
+    }
+    if let Some(from) = query.from {
+        sql.push_str(" AND m.timestamp >= ?");
+        params.push(from.into());
+    }
+    if let Some(to) = query.to {
+        sql.push_str(" AND m.timestamp <= ?");
+        params.push(to.into());
+    }
+
+    let mut best: HashMap<String, (f32, String)> = HashMap::new();
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("Failed to prepare search: {}", e))?;
+    let mut rows = stmt
+        .query(params_from_iter(params))
+        .map_err(|e| format!("Search failed: {}", e))?;
+    while let Some(row) = rows.next().map_err(|e| format!("Search failed: {}", e))? {
+        let blob: Vec<u8> = row.get(2).map_err(|e| format!("Search failed: {}", e))?;
+        let score: f32 = from_blob(&blob)
+            .iter()
+            .zip(&target)
+            .map(|(a, b)| a * b)
+            .sum();
+        let message_id: String = row.get(0).map_err(|e| format!("Search failed: {}", e))?;
+        if best.get(&message_id).is_some_and(|(s, _)| *s >= score) {
+            continue;
+        }
+        let chunk: String = row.get(1).map_err(|e| format!("Search failed: {}", e))?;
+        best.insert(message_id, (score, chunk));
+    }
+
+    let mut ranked: Vec<(String, (f32, String))> = best.into_iter().collect();
+    ranked.sort_by(|a, b| b.1 .0.total_cmp(&a.1 .0));
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize;
+    ranked.truncate(limit);
+
+    let mut hits = Vec::with_capacity(ranked.len());
+    for (message_id, (score, chunk)) in ranked {
+        let (conversation_id, conversation_title, role, timestamp) = conn
+            .query_row(
+                "SELECT m.conversation_id, c.title, m.role, m.timestamp
+                 FROM messages m JOIN conversations c ON c.id = m.conversation_id
+                 WHERE m.id = ?1",
+                [&message_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .map_err(|e| format!("Failed to load message {}: {}", message_id, e))?;
+        hits.push(SemanticHit {
+            previous: context_message(
+                conn,
+                "SELECT p.id, p.role, p.content, p.timestamp FROM messages m
+                 JOIN messages p ON p.id = m.parent_id WHERE m.id = ?1",
+                &message_id,
+            )?,
+            next: context_message(
+                conn,
+                "SELECT id, role, content, timestamp FROM messages WHERE parent_id = ?1
+                 ORDER BY timestamp DESC, rowid DESC LIMIT 1",
+                &message_id,
+            )?,
+            message_id,
+            conversation_id,
+            conversation_title,
+            role,
+            timestamp,
+            chunk,
+            score,
+        });
+    }
+    Ok(hits)
+}
+
+// ============================================================================
+// Background indexing
+// ============================================================================
+
+/// One indexing pass with the saved settings, if enabled. Errors are
+/// recorded for [`get_semantic_index_status`].
+fn run_pass(app: &AppHandle, force: bool) -> Result<IndexReport, String> {
+    let mut conn = db::open(app)?;
+    let settings = load_settings(&conn)?;
+    if !settings.enabled && !force {
+        return Ok(IndexReport::default());
+    }
+    let state = app.state::<SemanticSearchState>();
+    let result = embedder_for(app, &settings).and_then(|embedder| {
+        let _guard = state.indexing.lock();
+        index_pending(&mut conn, embedder.as_ref())
+    });
+    let mut last_error = state.last_error.lock();
+    match &result {
+        Ok(_) => *last_error = None,
+        Err(e) => {
+            if last_error.as_ref() != Some(e) {
+                warn!("Semantic search indexing failed: {}", e);
+            }
+            *last_error = Some(e.clone());
+        }
+    }
+    result
+}
+
+/// Embed new messages shortly after startup and then every
+/// [`INDEX_INTERVAL`]. Passes are skipped while the database is locked.
+pub fn spawn_indexer(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        loop {
+            let handle = app.clone();
+            match tauri::async_runtime::spawn_blocking(move || run_pass(&handle, false)).await {
+                Ok(Ok(report)) if report.indexed_messages > 0 => {
+                    let _ = app.emit(INDEXED_EVENT, &report);
+                }
+                Ok(_) => {}
+                Err(e) => error!("Semantic search indexing task panicked: {}", e),
+            }
+            tokio::time::sleep(INDEX_INTERVAL).await;
+        }
+    });
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn get_semantic_search_settings(app: AppHandle) -> Result<SemanticSearchSettings, String> {
+    let conn = db::open(&app)?;
+    load_settings(&conn)
+}
+
+/// Save settings and, if given, the API key for the endpoint (an empty key
+/// removes it). The next pass indexes with the new backend.
+#[tauri::command]
+pub fn set_semantic_search_settings(
+    app: AppHandle,
+    settings: SemanticSearchSettings,
+    api_key: Option<String>,
+) -> Result<(), String> {
+    let conn = db::open(&app)?;
+    save_settings(&conn, &settings)?;
+    if let Some(key) = api_key {
+        set_api_key(&app, &key)?;
+    }
+    let state = app.state::<SemanticSearchState>();
+    *state.embedder.lock() = None;
+    *state.last_error.lock() = None;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_semantic_index_status(app: AppHandle) -> Result<IndexStatus, String> {
+    let conn = db::open(&app)?;
+    let settings = load_settings(&conn)?;
+    let mut status = index_status(&conn, &settings.model_id())?;
+    status.has_api_key = api_key(&app)?.is_some();
+    status.last_error = app.state::<SemanticSearchState>().last_error.lock().clone();
+    Ok(status)
+}
+
+/// Index pending messages now, even if background indexing is off. With
+/// `rebuild`, existing embeddings are discarded first.
+#[tauri::command]
+pub async fn reindex_semantic_search(
+    app: AppHandle,
+    rebuild: Option<bool>,
+) -> Result<IndexReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if rebuild.unwrap_or(false) {
+            let conn = db::open(&app)?;
+            let state = app.state::<SemanticSearchState>();
+            let _guard = state.indexing.lock();
+            conn.execute("DELETE FROM message_chunks", [])
+                .map_err(|e| format!("Failed to clear embeddings: {}", e))?;
+        }
+        run_pass(&app, true)
+    })
+    .await
+    .map_err(|e| format!("Indexing task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn semantic_search(
+    app: AppHandle,
+    query: SemanticQuery,
+) -> Result<Vec<SemanticHit>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = db::open(&app)?;
+        let settings = load_settings(&conn)?;
+        let embedder = embedder_for(&app, &settings)?;
+        search(&conn, embedder.as_ref(), &query)
+    })
+    .await
+    .map_err(|e| format!("Search task failed: {}", e))?
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_history::{save_conversation, ChatConversation, ChatMessage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Bag-of-words vectors over a tiny vocabulary, so related texts share
+    /// dimensions.
+    struct FakeEmbedder {
+        model: String,
+        calls: AtomicUsize,
+    }
+
+    const VOCAB: [&str; 6] = ["rate", "limit", "interview", "salary", "deploy", "cache"];
+
+    impl FakeEmbedder {
+        fn new(model: &str) -> Self {
+            Self {
+                model: model.to_string(),
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl Embedder for FakeEmbedder {
+        fn model_id(&self) -> &str {
+            &self.model
+        }
+
+        fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    let mut vector: Vec<f32> = VOCAB
+                        .iter()
+                        .map(|word| text.matches(word).count() as f32)
+                        .collect();
+                    vector.push(0.1);
+                    embeddings::normalize(vector)
+                })
+                .collect())
+        }
+    }
+
+    fn message(id: &str, content: &str, timestamp: i64) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            role: if timestamp % 2 == 1 {
+                "user"
+            } else {
+                "assistant"
+            }
+            .to_string(),
+            content: content.to_string(),
+            timestamp,
+            attached_files: None,
+            parent_id: None,
+            metadata: None,
+        }
+    }
+
+    fn seed(conn: &mut Connection) {
+        for (id, title, messages) in [
+            (
+                "c1",
+                "Backend interview",
+                vec![
+                    message("m1", "Tell me about your last project", 1),
+                    message(
+                        "m2",
+                        "We hit the rate limit so I added a token bucket rate limiter",
+                        2,
+                    ),
+                    message("m3", "How would you cache it?", 3),
+                ],
+            ),
+            (
+                "c2",
+                "Offer call",
+                vec![message("m4", "The salary discussion went well", 5)],
+            ),
+        ] {
+            save_conversation(
+                conn,
+                &ChatConversation {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                    messages,
+                    created_at: 1,
+                    updated_at: 1,
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn chunks_overlap_and_cover_every_word() {
+        let text: Vec<String> = (0..250).map(|i| format!("w{}", i)).collect();
+        let chunks = chunk_text(&text.join(" "));
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with("w0 ") && chunks[0].ends_with(" w119"));
+        assert!(chunks[1].starts_with("w100 ") && chunks[1].ends_with(" w219"));
+        assert!(chunks[2].starts_with("w200 ") && chunks[2].ends_with(" w249"));
+        assert!(chunk_text("  \n ").is_empty());
+        assert_eq!(from_blob(&to_blob(&[1.5, -2.0])), vec![1.5, -2.0]);
+    }
+
+    #[test]
+    fn indexes_incrementally_and_tracks_edits_and_model_changes() {
+        let mut conn = db::open_in_memory();
+        seed(&mut conn);
+        let embedder = FakeEmbedder::new("fake:1");
+
+        let report = index_pending(&mut conn, &embedder).unwrap();
+        assert_eq!(report.indexed_messages, 4);
+        assert_eq!(
+            index_pending(&mut conn, &embedder)
+                .unwrap()
+                .indexed_messages,
+            0
+        );
+        assert_eq!(embedder.calls.load(Ordering::SeqCst), 1, "nothing to embed");
+
+        conn.execute(
+            "UPDATE messages SET content = 'Deploy notes' WHERE id = 'm4'",
+            [],
+        )
+        .unwrap();
+        let status = index_status(&conn, "fake:1").unwrap();
+        assert_eq!((status.indexed_messages, status.pending_messages), (3, 1));
+        assert_eq!(
+            index_pending(&mut conn, &embedder)
+                .unwrap()
+                .indexed_messages,
+            1
+        );
+
+        let other = FakeEmbedder::new("fake:2");
+        assert_eq!(
+            index_pending(&mut conn, &other).unwrap().indexed_messages,
+            4
+        );
+        assert_eq!(index_status(&conn, "fake:1").unwrap().chunks, 0);
+
+        conn.execute("DELETE FROM conversations WHERE id = 'c2'", [])
+            .unwrap();
+        assert_eq!(index_status(&conn, "fake:2").unwrap().indexed_messages, 3);
+    }
+
+    #[test]
+    fn whitespace_only_messages_are_never_embedded() {
+        let mut conn = db::open_in_memory();
+        save_conversation(
+            &mut conn,
+            &ChatConversation {
+                id: "c1".to_string(),
+                title: "Blank".to_string(),
+                // The non-breaking space passes SQLite's trim, but has no words
+                messages: vec![message("m1", "\n\t\r\n", 1), message("m2", "\u{a0}", 2)],
+                created_at: 1,
+                updated_at: 1,
+            },
+        )
+        .unwrap();
+        let embedder = FakeEmbedder::new("fake:1");
+
+        assert_eq!(index_status(&conn, "fake:1").unwrap().pending_messages, 1);
+        for _ in 0..2 {
+            let report = index_pending(&mut conn, &embedder).unwrap();
+            assert_eq!((report.indexed_messages, report.chunks), (0, 0));
+        }
+        assert_eq!(embedder.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn search_ranks_by_similarity_with_branch_context() {
+        let mut conn = db::open_in_memory();
+        seed(&mut conn);
+        let embedder = FakeEmbedder::new("fake:1");
+        index_pending(&mut conn, &embedder).unwrap();
+
+        let hits = search(
+            &conn,
+            &embedder,
+            &SemanticQuery {
+                query: "that interview where we discussed rate limiting".to_string(),
+                limit: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].message_id, "m2");
+        assert_eq!(hits[0].conversation_title, "Backend interview");
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(hits[0].previous.as_ref().unwrap().id, "m1");
+        assert_eq!(hits[0].next.as_ref().unwrap().id, "m3");
+
+        let scoped = search(
+            &conn,
+            &embedder,
+            &SemanticQuery {
+                query: "rate limit".to_string(),
+                conversation_id: Some("c2".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].message_id, "m4");
+        assert!(search(&conn, &embedder, &SemanticQuery::default())
+            .unwrap()
+            .is_empty());
+    }
+}
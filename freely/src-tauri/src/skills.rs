@@ -0,0 +1,815 @@
+//! Slash-command and skill library management.
+//!
+//! Claude Code discovers custom slash commands under `.claude/commands/<name>.md`
+//! and skills under `.claude/skills/<name>/SKILL.md`. This module manages both
+//! inside Freely's own `.claude/` directory (see [`crate::claude_config`]):
+//!
+//! - CRUD for commands and skills, with YAML-style frontmatter parsing
+//! - Name validation and collision detection across both kinds
+//! - Import/export of the whole library as a zip bundle
+//! - Invoking a command or skill through [`crate::agents::run_claude`]
+//!
+//! Commands and skills are expanded into the prompt before the CLI is spawned,
+//! so they also work when `run_claude` runs inside a user's project directory
+//! where the CLI would not otherwise see Freely's `.claude/` folder.
+
+use crate::agents::{self, AgentPayload, AgentProcessRegistry, StreamEvent};
+use crate::claude_config;
+use crate::fs_utils::{archive_name, collect_files};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tracing::warn;
+
+const COMMANDS_DIR: &str = "commands";
+const SKILLS_DIR: &str = "skills";
+const SKILL_FILE: &str = "SKILL.md";
+const BUNDLE_MANIFEST: &str = "manifest.json";
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+const MAX_NAME_LEN: usize = 64;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkillKind {
+    /// A custom slash command: `.claude/commands/<name>.md`
+    Command,
+    /// A skill: `.claude/skills/<name>/SKILL.md` (plus optional supporting files)
+    Skill,
+}
+
+impl SkillKind {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Command => "command",
+            Self::Skill => "skill",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillEntry {
+    pub kind: SkillKind,
+    pub name: String,
+    pub description: Option<String>,
+    /// Parsed frontmatter key/value pairs (e.g. `allowed-tools`, `model`).
+    pub frontmatter: BTreeMap<String, String>,
+    /// Markdown body with the frontmatter block removed.
+    pub body: String,
+    /// Full file content as stored on disk.
+    pub content: String,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleManifest {
+    format_version: u32,
+    entries: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleEntry {
+    kind: SkillKind,
+    name: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SkillImportReport {
+    pub imported: Vec<String>,
+    /// Entries skipped because a command or skill with that name already exists.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SkillInvocation {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub kind: SkillKind,
+    pub name: String,
+    /// Free-form arguments, substituted for `$ARGUMENTS` / `$1..$9` in commands.
+    #[serde(default)]
+    pub arguments: String,
+    #[serde(rename = "workingDirectory")]
+    pub working_directory: Option<String>,
+    pub model: Option<String>,
+    #[serde(rename = "agentSessionId")]
+    pub agent_session_id: Option<String>,
+}
+
+// ============================================================================
+// Frontmatter
+// ============================================================================
+
+/// Split a markdown document into its `---` delimited frontmatter and body.
+///
+/// Only flat `key: value` pairs are supported, which covers every field Claude
+/// Code reads from commands and skills. Surrounding quotes are stripped.
+/// Documents without frontmatter return an empty map and the full content.
+pub(crate) fn parse_frontmatter(content: &str) -> (BTreeMap<String, String>, String) {
+    let mut map = BTreeMap::new();
+
+    let rest = match content
+        .strip_prefix("---\r\n")
+        .or_else(|| content.strip_prefix("---\n"))
+    {
+        Some(rest) => rest,
+        None => return (map, content.to_string()),
+    };
+
+    let mut offset = 0;
+    let mut closed = false;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        if trimmed == "---" {
+            closed = true;
+            break;
+        }
+        if let Some((key, value)) = trimmed.split_once(':') {
+            let key = key.trim();
+            if key.is_empty() || key.starts_with('#') {
+                continue;
+            }
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            map.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    if !closed {
+        // Unterminated block — treat the whole document as body.
+        return (BTreeMap::new(), content.to_string());
+    }
+
+    let body = rest[offset..].trim_start_matches(['\r', '\n']).to_string();
+    (map, body)
+}
+
+/// Validate a command/skill name. Names become `/name` in the CLI, so only
+/// lowercase ASCII letters, digits, `-` and `_` are allowed.
+pub(crate) fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "Invalid name '{}': must be 1-{} characters",
+            name, MAX_NAME_LEN
+        ));
+    }
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid || name.starts_with('-') {
+        return Err(format!(
+            "Invalid name '{}': use lowercase letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Library operations (path-based for testability)
+// ============================================================================
+
+fn entry_path(claude_dir: &Path, kind: SkillKind, name: &str) -> PathBuf {
+    match kind {
+        SkillKind::Command => claude_dir.join(COMMANDS_DIR).join(format!("{}.md", name)),
+        SkillKind::Skill => claude_dir.join(SKILLS_DIR).join(name).join(SKILL_FILE),
+    }
+}
+
+fn read_entry(claude_dir: &Path, kind: SkillKind, name: &str) -> Result<SkillEntry, String> {
+    let path = entry_path(claude_dir, kind, name);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {} '{}': {}", kind.label(), name, e))?;
+    let (frontmatter, body) = parse_frontmatter(&content);
+
+    Ok(SkillEntry {
+        kind,
+        name: name.to_string(),
+        description: frontmatter.get("description").cloned(),
+        frontmatter,
+        body,
+        content,
+        path: path.to_string_lossy().to_string(),
+    })
+}
+
+fn list_names(claude_dir: &Path, kind: SkillKind) -> Vec<String> {
+    let mut names = Vec::new();
+    let dir = match kind {
+        SkillKind::Command => claude_dir.join(COMMANDS_DIR),
+        SkillKind::Skill => claude_dir.join(SKILLS_DIR),
+    };
+    let Ok(read_dir) = std::fs::read_dir(&dir) else {
+        return names;
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        let name = match kind {
+            SkillKind::Command if path.extension().is_some_and(|ext| ext == "md") => {
+                path.file_stem().and_then(|s| s.to_str()).map(String::from)
+            }
+            SkillKind::Skill if path.join(SKILL_FILE).is_file() => {
+                path.file_name().and_then(|s| s.to_str()).map(String::from)
+            }
+            _ => None,
+        };
+        if let Some(name) = name {
+            names.push(name);
+        }
+    }
+    names.sort();
+    names
+}
+
+/// Return the existing entry (of either kind) whose name collides with `name`.
+/// Comparison is case-insensitive because macOS and Windows filesystems are.
+fn find_collision(claude_dir: &Path, name: &str) -> Option<(SkillKind, String)> {
+    [SkillKind::Command, SkillKind::Skill]
+        .into_iter()
+        .flat_map(|kind| {
+            list_names(claude_dir, kind)
+                .into_iter()
+                .map(move |n| (kind, n))
+        })
+        .find(|(_, existing)| existing.eq_ignore_ascii_case(name))
+}
+
+pub(crate) fn list_skills_in(claude_dir: &Path) -> Vec<SkillEntry> {
+    let mut entries = Vec::new();
+    for kind in [SkillKind::Command, SkillKind::Skill] {
+        for name in list_names(claude_dir, kind) {
+            match read_entry(claude_dir, kind, &name) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Skipping unreadable skill entry: {}", e),
+            }
+        }
+    }
+    entries
+}
+
+pub(crate) fn create_skill_in(
+    claude_dir: &Path,
+    kind: SkillKind,
+    name: &str,
+    content: &str,
+) -> Result<SkillEntry, String> {
+    validate_name(name)?;
+
+    if let Some((existing_kind, existing)) = find_collision(claude_dir, name) {
+        return Err(format!(
+            "Name '{}' collides with existing {} '{}'",
+            name,
+            existing_kind.label(),
+            existing
+        ));
+    }
+
+    let path = entry_path(claude_dir, kind, name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {} directory: {}", kind.label(), e))?;
+    }
+    std::fs::write(&path, content)
+        .map_err(|e| format!("Failed to write {} '{}': {}", kind.label(), name, e))?;
+
+    read_entry(claude_dir, kind, name)
+}
+
+pub(crate) fn update_skill_in(
+    claude_dir: &Path,
+    kind: SkillKind,
+    name: &str,
+    content: &str,
+) -> Result<SkillEntry, String> {
+    validate_name(name)?;
+
+    let path = entry_path(claude_dir, kind, name);
+    if !path.is_file() {
+        return Err(format!("No {} named '{}'", kind.label(), name));
+    }
+    std::fs::write(&path, content)
+        .map_err(|e| format!("Failed to write {} '{}': {}", kind.label(), name, e))?;
+
+    read_entry(claude_dir, kind, name)
+}
+
+pub(crate) fn delete_skill_in(
+    claude_dir: &Path,
+    kind: SkillKind,
+    name: &str,
+) -> Result<(), String> {
+    validate_name(name)?;
+
+    let path = entry_path(claude_dir, kind, name);
+    if !path.is_file() {
+        return Err(format!("No {} named '{}'", kind.label(), name));
+    }
+    let result = match kind {
+        SkillKind::Command => std::fs::remove_file(&path),
+        // Skills are directories that may carry supporting files.
+        SkillKind::Skill => std::fs::remove_dir_all(claude_dir.join(SKILLS_DIR).join(name)),
+    };
+    result.map_err(|e| format!("Failed to delete {} '{}': {}", kind.label(), name, e))
+}
+
+// ============================================================================
+// Zip bundle import/export
+// ============================================================================
+
+/// Write every command and skill to a zip bundle at `dest`.
+/// Returns the number of library entries exported.
+pub(crate) fn export_skills_in(claude_dir: &Path, dest: &Path) -> Result<usize, String> {
+    let file = std::fs::File::create(dest)
+        .map_err(|e| format!("Failed to create bundle {}: {}", dest.display(), e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    let mut manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        entries: Vec::new(),
+    };
+
+    for kind in [SkillKind::Command, SkillKind::Skill] {
+        for name in list_names(claude_dir, kind) {
+            let files = match kind {
+                SkillKind::Command => {
+                    vec![PathBuf::from(COMMANDS_DIR).join(format!("{}.md", name))]
+                }
+                SkillKind::Skill => {
+                    let mut files = Vec::new();
+                    collect_files(
+                        claude_dir,
+                        &claude_dir.join(SKILLS_DIR).join(&name),
+                        &mut files,
+                    )
+                    .map_err(|e| format!("Failed to read skill '{}': {}", name, e))?;
+                    files
+                }
+            };
+
+            for rel in files {
+                let bytes = std::fs::read(claude_dir.join(&rel))
+                    .map_err(|e| format!("Failed to read {}: {}", rel.display(), e))?;
+                zip.start_file(archive_name(&rel), options)
+                    .map_err(|e| format!("Failed to write bundle: {}", e))?;
+                zip.write_all(&bytes)
+                    .map_err(|e| format!("Failed to write bundle: {}", e))?;
+            }
+
+            manifest.entries.push(BundleEntry { kind, name });
+        }
+    }
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    zip.start_file(BUNDLE_MANIFEST, options)
+        .map_err(|e| format!("Failed to write bundle: {}", e))?;
+    zip.write_all(&manifest_json)
+        .map_err(|e| format!("Failed to write bundle: {}", e))?;
+    zip.finish()
+        .map_err(|e| format!("Failed to finalize bundle: {}", e))?;
+
+    Ok(manifest.entries.len())
+}
+
+/// Import a bundle produced by [`export_skills_in`].
+///
+/// Entries whose name collides with an existing command or skill are skipped
+/// unless `overwrite` is set, in which case the existing entry of the same kind
+/// is replaced. Archive paths are confined to `commands/` and `skills/`.
+pub(crate) fn import_skills_in(
+    claude_dir: &Path,
+    src: &Path,
+    overwrite: bool,
+) -> Result<SkillImportReport, String> {
+    let file = std::fs::File::open(src)
+        .map_err(|e| format!("Failed to open bundle {}: {}", src.display(), e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Invalid skill bundle: {}", e))?;
+
+    let manifest: BundleManifest = {
+        let mut entry = archive
+            .by_name(BUNDLE_MANIFEST)
+            .map_err(|_| "Invalid skill bundle: missing manifest.json".to_string())?;
+        let mut raw = String::new();
+        entry
+            .read_to_string(&mut raw)
+            .map_err(|e| format!("Failed to read manifest: {}", e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("Invalid manifest: {}", e))?
+    };
+
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(format!(
+            "Unsupported bundle format version {} (expected <= {})",
+            manifest.format_version, BUNDLE_FORMAT_VERSION
+        ));
+    }
+
+    let mut report = SkillImportReport::default();
+
+    for item in &manifest.entries {
+        validate_name(&item.name)?;
+
+        if let Some((existing_kind, _)) = find_collision(claude_dir, &item.name) {
+            if !overwrite || existing_kind != item.kind {
+                report.skipped.push(item.name.clone());
+                continue;
+            }
+            delete_skill_in(claude_dir, item.kind, &item.name)?;
+        }
+
+        let prefix = match item.kind {
+            SkillKind::Command => format!("{}/{}.md", COMMANDS_DIR, item.name),
+            SkillKind::Skill => format!("{}/{}/", SKILLS_DIR, item.name),
+        };
+
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .map_err(|e| format!("Failed to read bundle entry: {}", e))?;
+            if file.is_dir() {
+                continue;
+            }
+            // `enclosed_name` rejects absolute paths and `..` traversal.
+            let Some(rel) = file.enclosed_name() else {
+                continue;
+            };
+            if !archive_name(&rel).starts_with(&prefix) {
+                continue;
+            }
+
+            let dest = claude_dir.join(&rel);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)
+                .map_err(|e| format!("Failed to read {}: {}", rel.display(), e))?;
+            std::fs::write(&dest, bytes)
+                .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        }
+
+        if entry_path(claude_dir, item.kind, &item.name).is_file() {
+            report.imported.push(item.name.clone());
+        } else {
+            warn!(
+                "Bundle lists {} '{}' but contains no file for it",
+                item.kind.label(),
+                item.name
+            );
+        }
+    }
+
+    Ok(report)
+}
+
+// ============================================================================
+// Invocation
+// ============================================================================
+
+/// Expand `$ARGUMENTS` and positional `$1`..`$9` placeholders in a command body.
+/// When the body has no placeholder at all, non-empty arguments are appended,
+/// matching Claude Code's own behaviour.
+pub(crate) fn expand_arguments(body: &str, arguments: &str) -> String {
+    let positional: Vec<&str> = arguments.split_whitespace().collect();
+    let mut has_placeholder = body.contains("$ARGUMENTS");
+
+    let mut expanded = body.replace("$ARGUMENTS", arguments);
+    // Replace from $9 down so `$1` never clobbers the prefix of `$10`-style text.
+    for i in (1..=9).rev() {
+        let placeholder = format!("${}", i);
+        if expanded.contains(&placeholder) {
+            has_placeholder = true;
+            expanded = expanded.replace(&placeholder, positional.get(i - 1).copied().unwrap_or(""));
+        }
+    }
+
+    if !has_placeholder && !arguments.trim().is_empty() {
+        expanded = format!("{}\n\nARGUMENTS: {}", expanded.trim_end(), arguments.trim());
+    }
+    expanded
+}
+
+/// Build the `run_claude` payload for a command or skill invocation.
+///
+/// - Commands expand into the prompt itself.
+/// - Skills are injected as the system prompt with the arguments as the prompt.
+///
+/// `allowed-tools` and `model` frontmatter are honoured unless the caller
+/// overrides the model explicitly.
+pub(crate) fn build_invocation_payload(
+    entry: &SkillEntry,
+    invocation: SkillInvocation,
+) -> AgentPayload {
+    let (prompt, system_prompt) = match entry.kind {
+        SkillKind::Command => (expand_arguments(&entry.body, &invocation.arguments), None),
+        SkillKind::Skill => (invocation.arguments.clone(), Some(entry.body.clone())),
+    };
+
+    AgentPayload {
+        session_id: invocation.session_id,
+        prompt,
+        task_id: None,
+        permission_mode: entry.frontmatter.get("allowed-tools").cloned(),
+        working_directory: invocation.working_directory,
+        api_key: None,
+        model: invocation
+            .model
+            .or_else(|| entry.frontmatter.get("model").cloned()),
+        agent_session_id: invocation.agent_session_id,
+        system_prompt,
+        profile_id: None,
+    }
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn list_skills(app: AppHandle) -> Result<Vec<SkillEntry>, String> {
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    Ok(list_skills_in(&claude_dir))
+}
+
+#[tauri::command]
+pub fn get_skill(app: AppHandle, kind: SkillKind, name: String) -> Result<SkillEntry, String> {
+    validate_name(&name)?;
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    read_entry(&claude_dir, kind, &name)
+}
+
+#[tauri::command]
+pub fn create_skill(
+    app: AppHandle,
+    kind: SkillKind,
+    name: String,
+    content: String,
+) -> Result<SkillEntry, String> {
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    create_skill_in(&claude_dir, kind, &name, &content)
+}
+
+#[tauri::command]
+pub fn update_skill(
+    app: AppHandle,
+    kind: SkillKind,
+    name: String,
+    content: String,
+) -> Result<SkillEntry, String> {
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    update_skill_in(&claude_dir, kind, &name, &content)
+}
+
+#[tauri::command]
+pub fn delete_skill(app: AppHandle, kind: SkillKind, name: String) -> Result<(), String> {
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    delete_skill_in(&claude_dir, kind, &name)
+}
+
+#[tauri::command]
+pub fn export_skills(app: AppHandle, dest_path: String) -> Result<usize, String> {
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    export_skills_in(&claude_dir, Path::new(&dest_path))
+}
+
+#[tauri::command]
+pub fn import_skills(
+    app: AppHandle,
+    src_path: String,
+    overwrite: Option<bool>,
+) -> Result<SkillImportReport, String> {
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    import_skills_in(
+        &claude_dir,
+        Path::new(&src_path),
+        overwrite.unwrap_or(false),
+    )
+}
+
+/// Run a command or skill through the Claude CLI, streaming events on
+/// `agent:stream:{sessionId}` exactly like [`agents::run_claude`].
+#[tauri::command]
+pub async fn run_skill(
+    app: AppHandle,
+    invocation: SkillInvocation,
+    registry: tauri::State<'_, AgentProcessRegistry>,
+) -> Result<Vec<StreamEvent>, String> {
+    validate_name(&invocation.name)?;
+    let claude_dir = claude_config::init_claude_config(&app)?;
+    let entry = read_entry(&claude_dir, invocation.kind, &invocation.name)?;
+    let payload = build_invocation_payload(&entry, invocation);
+    agents::run_claude(app, payload, registry).await
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const REVIEW_CMD: &str = "---\ndescription: \"Review the diff\"\nallowed-tools: Bash(git diff:*), Read\nmodel: sonnet\n---\n\nReview $ARGUMENTS carefully.\n";
+
+    fn setup() -> (TempDir, PathBuf) {
+        let tmp = TempDir::new().expect("failed to create temp dir");
+        let claude_dir = claude_config::init_claude_config_in(tmp.path().to_path_buf())
+            .expect("init_claude_config_in failed");
+        (tmp, claude_dir)
+    }
+
+    fn invocation(kind: SkillKind, name: &str, arguments: &str) -> SkillInvocation {
+        SkillInvocation {
+            session_id: "s1".to_string(),
+            kind,
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+            working_directory: None,
+            model: None,
+            agent_session_id: None,
+        }
+    }
+
+    #[test]
+    fn parses_frontmatter_and_body() {
+        let (fm, body) = parse_frontmatter(REVIEW_CMD);
+        assert_eq!(fm.get("description").unwrap(), "Review the diff");
+        assert_eq!(fm.get("allowed-tools").unwrap(), "Bash(git diff:*), Read");
+        assert_eq!(body, "Review $ARGUMENTS carefully.\n");
+    }
+
+    #[test]
+    fn content_without_frontmatter_is_all_body() {
+        let (fm, body) = parse_frontmatter("# Just markdown\n---\nnot: frontmatter\n");
+        assert!(fm.is_empty());
+        assert!(body.starts_with("# Just markdown"));
+
+        let (fm, body) = parse_frontmatter("---\nunterminated: true\n");
+        assert!(fm.is_empty(), "unterminated block should not be parsed");
+        assert!(body.contains("unterminated"));
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        for bad in ["", "Upper", "../escape", "with space", "-leading", "a/b"] {
+            assert!(validate_name(bad).is_err(), "{:?} should be rejected", bad);
+        }
+        assert!(validate_name("review-pr_2").is_ok());
+    }
+
+    #[test]
+    fn create_list_update_delete_roundtrip() {
+        let (_tmp, claude_dir) = setup();
+
+        let created =
+            create_skill_in(&claude_dir, SkillKind::Command, "review", REVIEW_CMD).unwrap();
+        assert_eq!(created.description.as_deref(), Some("Review the diff"));
+        assert!(claude_dir.join("commands/review.md").is_file());
+
+        create_skill_in(
+            &claude_dir,
+            SkillKind::Skill,
+            "pdf",
+            "---\nname: pdf\n---\nUse pdftotext.",
+        )
+        .unwrap();
+        assert!(claude_dir.join("skills/pdf/SKILL.md").is_file());
+
+        let names: Vec<_> = list_skills_in(&claude_dir)
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["review", "pdf"]);
+
+        let updated =
+            update_skill_in(&claude_dir, SkillKind::Command, "review", "Updated").unwrap();
+        assert_eq!(updated.body, "Updated");
+        assert!(updated.description.is_none());
+
+        delete_skill_in(&claude_dir, SkillKind::Skill, "pdf").unwrap();
+        assert!(!claude_dir.join("skills/pdf").exists());
+        assert!(update_skill_in(&claude_dir, SkillKind::Skill, "pdf", "x").is_err());
+    }
+
+    #[test]
+    fn detects_name_collisions_across_kinds() {
+        let (_tmp, claude_dir) = setup();
+        create_skill_in(&claude_dir, SkillKind::Command, "deploy", "Deploy it").unwrap();
+
+        let same_kind = create_skill_in(&claude_dir, SkillKind::Command, "deploy", "again");
+        assert!(same_kind.is_err());
+
+        let other_kind = create_skill_in(&claude_dir, SkillKind::Skill, "deploy", "skill");
+        let err = other_kind.unwrap_err();
+        assert!(err.contains("collides"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn export_import_roundtrip_preserves_supporting_files() {
+        let (_src_tmp, src_dir) = setup();
+        create_skill_in(&src_dir, SkillKind::Command, "review", REVIEW_CMD).unwrap();
+        create_skill_in(&src_dir, SkillKind::Skill, "pdf", "Use the helper script.").unwrap();
+        std::fs::write(src_dir.join("skills/pdf/extract.py"), "print('hi')").unwrap();
+
+        let bundle_tmp = TempDir::new().unwrap();
+        let bundle = bundle_tmp.path().join("library.zip");
+        assert_eq!(export_skills_in(&src_dir, &bundle).unwrap(), 2);
+
+        let (_dst_tmp, dst_dir) = setup();
+        let report = import_skills_in(&dst_dir, &bundle, false).unwrap();
+        assert_eq!(report.imported, vec!["review", "pdf"]);
+        assert!(report.skipped.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(dst_dir.join("commands/review.md")).unwrap(),
+            REVIEW_CMD
+        );
+        assert!(dst_dir.join("skills/pdf/extract.py").is_file());
+    }
+
+    #[test]
+    fn import_skips_collisions_unless_overwrite() {
+        let (_src_tmp, src_dir) = setup();
+        create_skill_in(&src_dir, SkillKind::Command, "review", "From bundle").unwrap();
+        let bundle_tmp = TempDir::new().unwrap();
+        let bundle = bundle_tmp.path().join("library.zip");
+        export_skills_in(&src_dir, &bundle).unwrap();
+
+        let (_dst_tmp, dst_dir) = setup();
+        create_skill_in(&dst_dir, SkillKind::Command, "review", "Local").unwrap();
+
+        let report = import_skills_in(&dst_dir, &bundle, false).unwrap();
+        assert_eq!(report.skipped, vec!["review"]);
+        assert_eq!(
+            std::fs::read_to_string(dst_dir.join("commands/review.md")).unwrap(),
+            "Local"
+        );
+
+        let report = import_skills_in(&dst_dir, &bundle, true).unwrap();
+        assert_eq!(report.imported, vec!["review"]);
+        assert_eq!(
+            std::fs::read_to_string(dst_dir.join("commands/review.md")).unwrap(),
+            "From bundle"
+        );
+    }
+
+    #[test]
+    fn expands_arguments_and_positionals() {
+        assert_eq!(
+            expand_arguments("Fix $ARGUMENTS", "issue 42"),
+            "Fix issue 42"
+        );
+        assert_eq!(expand_arguments("From $1 to $2", "a b"), "From a to b");
+        assert_eq!(
+            expand_arguments("Summarize the meeting", "briefly"),
+            "Summarize the meeting\n\nARGUMENTS: briefly"
+        );
+        assert_eq!(expand_arguments("No args", ""), "No args");
+    }
+
+    #[test]
+    fn invocation_payload_uses_frontmatter_defaults() {
+        let (_tmp, claude_dir) = setup();
+        let command =
+            create_skill_in(&claude_dir, SkillKind::Command, "review", REVIEW_CMD).unwrap();
+
+        let payload = build_invocation_payload(
+            &command,
+            invocation(SkillKind::Command, "review", "main.rs"),
+        );
+        assert_eq!(payload.prompt, "Review main.rs carefully.\n");
+        assert_eq!(payload.model.as_deref(), Some("sonnet"));
+        assert_eq!(
+            payload.permission_mode.as_deref(),
+            Some("Bash(git diff:*), Read")
+        );
+        assert!(payload.system_prompt.is_none());
+
+        let skill =
+            create_skill_in(&claude_dir, SkillKind::Skill, "pdf", "Use pdftotext.").unwrap();
+        let mut inv = invocation(SkillKind::Skill, "pdf", "extract page 2");
+        inv.model = Some("opus".to_string());
+        let payload = build_invocation_payload(&skill, inv);
+        assert_eq!(payload.prompt, "extract page 2");
+        assert_eq!(payload.system_prompt.as_deref(), Some("Use pdftotext."));
+        assert_eq!(
+            payload.model.as_deref(),
+            Some("opus"),
+            "explicit model wins"
+        );
+    }
+}
@@ -0,0 +1,606 @@
+//! Acoustic echo cancellation for meeting capture.
+//!
+//! Without headphones the microphone also hears the meeting playing through
+//! the speakers, which system-audio capture already records. System audio is
+//! used as the far-end reference: [`EchoCanceller`] learns the echo path
+//! (speaker, room, microphone) with an NLMS adaptive filter and subtracts the
+//! predicted echo from the microphone signal. Adaptation pauses while the
+//! user talks over the far end (Geigel double-talk detection), so their voice
+//! is not learned as echo.
+//!
+//! Playback and capture buffering put the echo hundreds of milliseconds
+//! behind the reference, far more than the filter covers. [`DelayEstimator`]
+//! finds that bulk delay with GCC-PHAT and the canceller delays the reference
+//! to match, so the filter only has to model the room.
+//!
+//! Both signals are processed at [`AEC_SAMPLE_RATE`]. [`ReferenceTap`] copies
+//! system audio into a shared [`EchoReference`] as it is captured, and
+//! [`EchoCancelledStream`] pairs each microphone sample with the reference
+//! sample captured at the same point.
+
+use crate::speaker::denoise::Fft;
+use crate::speaker::resample::Resampler;
+use futures_util::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Rate both signals are converted to before cancellation.
+pub const AEC_SAMPLE_RATE: u32 = 16_000;
+/// Echo tail covered by the filter: 128 ms at [`AEC_SAMPLE_RATE`].
+pub const FILTER_LEN: usize = 2048;
+/// NLMS step size.
+const STEP_SIZE: f32 = 0.5;
+/// Regularization for the step normalization, so silence does not blow up
+/// the update.
+const REGULARIZATION: f32 = 1e-6;
+/// Near-end talk is assumed when the microphone is louder than this fraction
+/// of the recent far-end peak; real echo paths lose at least 6 dB.
+const DOUBLE_TALK_RATIO: f32 = 0.5;
+/// Samples adaptation stays frozen after double talk was last detected, so
+/// the quieter parts of near-end speech are not learned either (30 ms).
+const DOUBLE_TALK_HOLD: usize = 480;
+/// Longest bulk delay of the echo behind the reference that is compensated.
+pub const MAX_DELAY: usize = AEC_SAMPLE_RATE as usize / 2;
+/// Filter taps kept ahead of the estimated delay, for reflections that
+/// arrive before the strongest one.
+const DELAY_MARGIN: usize = 160;
+/// Microphone audio correlated per delay estimate (about 0.5 s).
+const ESTIMATE_WINDOW: usize = 8192;
+/// Samples between delay estimates.
+const ESTIMATE_INTERVAL: usize = AEC_SAMPLE_RATE as usize / 2;
+/// An estimate is only trusted when its correlation peak stands this far
+/// above the mean, which rules out silence and near-end-only talk.
+const MIN_PEAK_RATIO: f32 = 10.0;
+/// Samples converted at a time by the stream adapters.
+const BLOCK: usize = 256;
+/// Reference samples kept waiting for the microphone, at most one second.
+const MAX_REFERENCE_BACKLOG: usize = AEC_SAMPLE_RATE as usize;
+
+/// NLMS echo canceller over a window of far-end samples, delayed by the
+/// estimated bulk delay of the echo.
+pub struct EchoCanceller {
+    weights: Vec<f32>,
+    /// Far-end history of the window plus [`MAX_DELAY`], stored twice so any
+    /// `len` consecutive samples are a contiguous slice.
+    history: Vec<f32>,
+    pos: usize,
+    /// Far-end samples pushed so far.
+    count: u64,
+    /// Bulk delay applied to the reference, in samples.
+    delay: usize,
+    /// Sum of squares of the samples in the window.
+    energy: f32,
+    /// Sample numbers and magnitudes of the window's decreasing run of
+    /// peaks, so the far-end peak is the front.
+    peaks: VecDeque<(u64, f32)>,
+    /// Samples left before adaptation resumes after double talk.
+    hold: usize,
+    estimator: DelayEstimator,
+}
+
+impl EchoCanceller {
+    pub fn new(len: usize) -> Self {
+        let len = len.max(1);
+        Self {
+            weights: vec![0.0; len],
+            history: vec![0.0; 2 * (len + MAX_DELAY)],
+            pos: 0,
+            count: 0,
+            delay: 0,
+            energy: 0.0,
+            peaks: VecDeque::new(),
+            hold: 0,
+            estimator: DelayEstimator::new(MAX_DELAY, ESTIMATE_WINDOW),
+        }
+    }
+
+    /// Bulk delay currently applied to the reference, in samples.
+    pub fn delay(&self) -> usize {
+        self.delay
+    }
+
+    fn capacity(&self) -> usize {
+        self.history.len() / 2
+    }
+
+    /// Start of the window, oldest sample first.
+    fn window_start(&self) -> usize {
+        let (cap, len) = (self.capacity(), self.weights.len());
+        (self.pos + cap - self.delay - len) % cap
+    }
+
+    fn push_peak(&mut self, number: u64, sample: f32) {
+        let magnitude = sample.abs();
+        while self.peaks.back().is_some_and(|&(_, m)| m <= magnitude) {
+            self.peaks.pop_back();
+        }
+        self.peaks.push_back((number, magnitude));
+    }
+
+    fn push_reference(&mut self, sample: f32) {
+        let (cap, len) = (self.capacity(), self.weights.len());
+        self.history[self.pos] = sample;
+        self.history[self.pos + cap] = sample;
+        self.pos = (self.pos + 1) % cap;
+        self.count += 1;
+
+        // The sample entering the window is `delay` behind the one just
+        // written, and the one before the window start just left it.
+        let start = self.window_start();
+        let entering = self.history[start + len - 1];
+        let leaving = self.history[(start + cap - 1) % cap];
+        self.energy = (self.energy + entering * entering - leaving * leaving).max(0.0);
+
+        // Early on the window reaches back before the first sample; those
+        // are zeros and never the peak.
+        let Some(number) = self.count.checked_sub(1 + self.delay as u64) else {
+            return;
+        };
+        self.push_peak(number, entering);
+        while self
+            .peaks
+            .front()
+            .is_some_and(|&(n, _)| n + len as u64 <= number)
+        {
+            self.peaks.pop_front();
+        }
+    }
+
+    /// Delay the reference by `delay` samples, moving the learned echo path
+    /// along with it.
+    fn set_delay(&mut self, delay: usize) {
+        let delay = delay.min(MAX_DELAY);
+        if delay == self.delay {
+            return;
+        }
+        let len = self.weights.len();
+        if delay > self.delay {
+            let shift = (delay - self.delay).min(len);
+            self.weights.rotate_left(shift);
+            self.weights[len - shift..].fill(0.0);
+        } else {
+            let shift = (self.delay - delay).min(len);
+            self.weights.rotate_right(shift);
+            self.weights[..shift].fill(0.0);
+        }
+        self.delay = delay;
+
+        let start = self.window_start();
+        let window = &self.history[start..start + len];
+        self.energy = window.iter().map(|x| x * x).sum();
+        self.peaks.clear();
+        let newest = self.count as i64 - 1 - delay as i64;
+        for i in 0..len {
+            let number = newest - (len - 1 - i) as i64;
+            if number >= 0 {
+                self.push_peak(number as u64, self.history[start + i]);
+            }
+        }
+    }
+
+    /// Cancel the echo of `reference` from `mic`. Both must hold the same
+    /// number of samples, captured over the same span of time.
+    pub fn process(&mut self, mic: &[f32], reference: &[f32]) -> Vec<f32> {
+        let len = self.weights.len();
+        let mut output = Vec::with_capacity(mic.len());
+        for (&near, &far) in mic.iter().zip(reference) {
+            if let Some(delay) = self.estimator.push(near, far) {
+                self.set_delay(delay.saturating_sub(DELAY_MARGIN));
+            }
+            self.push_reference(far);
+            // Oldest first, so it lines up with the reversed weights (tap 0
+            // is the newest sample).
+            let start = self.window_start();
+            let window = &self.history[start..start + len];
+            let estimate: f32 = self
+                .weights
+                .iter()
+                .rev()
+                .zip(window)
+                .map(|(w, x)| w * x)
+                .sum();
+            let error = near - estimate;
+
+            let far_peak = self.peaks.front().map_or(0.0, |&(_, m)| m);
+            if near.abs() > DOUBLE_TALK_RATIO * far_peak {
+                self.hold = DOUBLE_TALK_HOLD;
+            } else if self.hold > 0 {
+                self.hold -= 1;
+            } else {
+                let step = STEP_SIZE * error / (self.energy + REGULARIZATION);
+                for (w, x) in self.weights.iter_mut().rev().zip(window) {
+                    *w += step * x;
+                }
+            }
+            output.push(error);
+        }
+        output
+    }
+}
+
+/// How far the microphone lags the reference, found with GCC-PHAT: the peak
+/// of the cross-correlation of the two signals with every frequency weighted
+/// equally, which keeps the peak sharp for speech and music as well as noise.
+pub struct DelayEstimator {
+    max_delay: usize,
+    window: usize,
+    far: VecDeque<f32>,
+    mic: VecDeque<f32>,
+    until_estimate: usize,
+    fft: Fft,
+}
+
+impl DelayEstimator {
+    /// Estimates delays up to `max_delay` from the last `window` microphone
+    /// samples.
+    pub fn new(max_delay: usize, window: usize) -> Self {
+        Self {
+            max_delay,
+            window,
+            far: VecDeque::with_capacity(window + max_delay),
+            mic: VecDeque::with_capacity(window),
+            until_estimate: ESTIMATE_INTERVAL,
+            fft: Fft::new((window + max_delay).next_power_of_two()),
+        }
+    }
+
+    /// Add one pair of samples captured at the same time. Every
+    /// [`ESTIMATE_INTERVAL`] samples, returns the delay of the microphone
+    /// behind the reference if the correlation has a clear peak.
+    pub fn push(&mut self, mic: f32, far: f32) -> Option<usize> {
+        if self.far.len() == self.window + self.max_delay {
+            self.far.pop_front();
+        }
+        self.far.push_back(far);
+        if self.mic.len() == self.window {
+            self.mic.pop_front();
+        }
+        self.mic.push_back(mic);
+
+        self.until_estimate = self.until_estimate.saturating_sub(1);
+        if self.until_estimate > 0 || self.far.len() < self.window + self.max_delay {
+            return None;
+        }
+        self.until_estimate = ESTIMATE_INTERVAL;
+        self.estimate()
+    }
+
+    fn estimate(&self) -> Option<usize> {
+        let n = (self.window + self.max_delay).next_power_of_two();
+        let (mut far_re, mut far_im) = (vec![0.0; n], vec![0.0; n]);
+        let (mut mic_re, mut mic_im) = (vec![0.0; n], vec![0.0; n]);
+        for (slot, &x) in far_re.iter_mut().zip(&self.far) {
+            *slot = x;
+        }
+        for (slot, &x) in mic_re.iter_mut().zip(&self.mic) {
+            *slot = x;
+        }
+        self.fft.transform(&mut far_re, &mut far_im, false);
+        self.fft.transform(&mut mic_re, &mut mic_im, false);
+
+        // Far times the conjugate of mic, keeping only the phase. Its inverse
+        // peaks at the lag `k` where far[i + k] lines up with mic[i], and
+        // the far segment starts `max_delay` earlier, so delay = max - k.
+        for i in 0..n {
+            let re = far_re[i] * mic_re[i] + far_im[i] * mic_im[i];
+            let im = far_im[i] * mic_re[i] - far_re[i] * mic_im[i];
+            let magnitude = (re * re + im * im).sqrt() + 1e-12;
+            far_re[i] = re / magnitude;
+            far_im[i] = im / magnitude;
+        }
+        self.fft.transform(&mut far_re, &mut far_im, true);
+
+        let lags = &far_re[..=self.max_delay];
+        let (lag, peak) =
+            lags.iter().enumerate().fold(
+                (0, f32::MIN),
+                |best, (k, &r)| if r > best.1 { (k, r) } else { best },
+            );
+        let mean = lags.iter().map(|r| r.abs()).sum::<f32>() / lags.len() as f32;
+        (peak > MIN_PEAK_RATIO * mean).then(|| self.max_delay - lag)
+    }
+}
+
+/// Far-end samples shared between the system-audio and microphone streams.
+#[derive(Clone, Default)]
+pub struct EchoReference {
+    queue: Arc<Mutex<VecDeque<f32>>>,
+}
+
+impl EchoReference {
+    fn push(&self, samples: &[f32]) {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.extend(samples);
+        // The microphone stalled or stopped; don't let the lag grow.
+        let excess = queue.len().saturating_sub(MAX_REFERENCE_BACKLOG);
+        queue.drain(..excess);
+    }
+
+    /// The next `n` reference samples, padded with silence if system audio
+    /// has fallen behind.
+    fn take(&self, n: usize) -> Vec<f32> {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let available = n.min(queue.len());
+        let mut samples: Vec<f32> = queue.drain(..available).collect();
+        samples.resize(n, 0.0);
+        samples
+    }
+}
+
+/// Passes system audio through unchanged while copying it, at
+/// [`AEC_SAMPLE_RATE`], into an [`EchoReference`].
+pub struct ReferenceTap<S> {
+    inner: S,
+    reference: EchoReference,
+    resampler: Resampler,
+    block: Vec<f32>,
+}
+
+impl<S> ReferenceTap<S> {
+    pub fn new(inner: S, sample_rate: u32, reference: EchoReference) -> Result<Self, String> {
+        Ok(Self {
+            inner,
+            reference,
+            resampler: Resampler::new(sample_rate, AEC_SAMPLE_RATE)?,
+            block: Vec::with_capacity(BLOCK),
+        })
+    }
+}
+
+impl<S: Stream<Item = f32> + Unpin> Stream for ReferenceTap<S> {
+    type Item = f32;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<f32>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_next(cx);
+        if let Poll::Ready(Some(sample)) = poll {
+            this.block.push(sample);
+            if this.block.len() >= BLOCK {
+                let converted = this.resampler.process(&this.block);
+                this.reference.push(&converted);
+                this.block.clear();
+            }
+        }
+        poll
+    }
+}
+
+/// Microphone samples at [`AEC_SAMPLE_RATE`] with the far-end echo removed.
+pub struct EchoCancelledStream<S> {
+    inner: S,
+    reference: EchoReference,
+    resampler: Resampler,
+    canceller: EchoCanceller,
+    block: Vec<f32>,
+    output: VecDeque<f32>,
+}
+
+impl<S> EchoCancelledStream<S> {
+    pub fn new(inner: S, sample_rate: u32, reference: EchoReference) -> Result<Self, String> {
+        Ok(Self {
+            inner,
+            reference,
+            resampler: Resampler::new(sample_rate, AEC_SAMPLE_RATE)?,
+            canceller: EchoCanceller::new(FILTER_LEN),
+            block: Vec::with_capacity(BLOCK),
+            output: VecDeque::new(),
+        })
+    }
+}
+
+impl<S: Stream<Item = f32> + Unpin> Stream for EchoCancelledStream<S> {
+    type Item = f32;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<f32>> {
+        let this = &mut *self;
+        loop {
+            if let Some(sample) = this.output.pop_front() {
+                return Poll::Ready(Some(sample));
+            }
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(sample)) => {
+                    this.block.push(sample);
+                    if this.block.len() >= BLOCK {
+                        let mic = this.resampler.process(&this.block);
+                        this.block.clear();
+                        let far = this.reference.take(mic.len());
+                        this.output.extend(this.canceller.process(&mic, &far));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic white noise in -0.5..0.5.
+    fn noise(len: usize, mut seed: u32) -> Vec<f32> {
+        (0..len)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    /// Far-end signal through a room: 5 ms of delay, then decaying
+    /// reflections, about 14 dB quieter overall.
+    fn echo_of(far: &[f32]) -> Vec<f32> {
+        let path = [
+            (80, 0.15),
+            (95, -0.1),
+            (130, 0.06),
+            (210, 0.03),
+            (300, -0.015),
+        ];
+        (0..far.len())
+            .map(|i| {
+                path.iter()
+                    .filter(|(delay, _)| i >= *delay)
+                    .map(|(delay, gain)| gain * far[i - delay])
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn power(samples: &[f32]) -> f32 {
+        samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32
+    }
+
+    #[test]
+    fn converges_to_high_echo_return_loss_enhancement() {
+        let far = noise(3 * AEC_SAMPLE_RATE as usize, 1);
+        let mic = echo_of(&far);
+        let mut canceller = EchoCanceller::new(512);
+        let out = canceller.process(&mic, &far);
+
+        let tail = mic.len() - AEC_SAMPLE_RATE as usize / 2;
+        let erle_db = 10.0 * (power(&mic[tail..]) / power(&out[tail..])).log10();
+        assert!(erle_db > 30.0, "ERLE {:.1} dB", erle_db);
+    }
+
+    #[test]
+    fn keeps_near_end_speech_during_double_talk() {
+        let len = 3 * AEC_SAMPLE_RATE as usize;
+        let far = noise(len, 7);
+        let echo = echo_of(&far);
+        // The user starts talking after two seconds.
+        let near: Vec<f32> = (0..len)
+            .map(|i| {
+                if i < 2 * AEC_SAMPLE_RATE as usize {
+                    0.0
+                } else {
+                    0.4 * (i as f32 * 2.0 * std::f32::consts::PI * 300.0 / AEC_SAMPLE_RATE as f32)
+                        .sin()
+                }
+            })
+            .collect();
+        let mic: Vec<f32> = echo.iter().zip(&near).map(|(e, n)| e + n).collect();
+        let mut canceller = EchoCanceller::new(512);
+        let out = canceller.process(&mic, &far);
+
+        // Whatever differs from the near-end speech is residual echo. The
+        // first few samples of speech are quieter than the echo and still
+        // adapt the filter, so this is lower than without double talk.
+        let tail = len - AEC_SAMPLE_RATE as usize / 2;
+        let residual: Vec<f32> = out[tail..]
+            .iter()
+            .zip(&near[tail..])
+            .map(|(o, n)| o - n)
+            .collect();
+        let erle_db = 10.0 * (power(&echo[tail..]) / power(&residual)).log10();
+        assert!(erle_db > 12.0, "ERLE {:.1} dB", erle_db);
+    }
+
+    fn delayed(samples: &[f32], delay: usize) -> Vec<f32> {
+        let mut out = vec![0.0; delay.min(samples.len())];
+        out.extend_from_slice(&samples[..samples.len() - out.len()]);
+        out
+    }
+
+    #[test]
+    fn estimates_playback_delay() {
+        let far = noise(2 * AEC_SAMPLE_RATE as usize, 5);
+        for delay_ms in [200, 300, 400] {
+            let delay = delay_ms * AEC_SAMPLE_RATE as usize / 1000;
+            let mic = delayed(&echo_of(&far), delay);
+            let mut estimator = DelayEstimator::new(MAX_DELAY, ESTIMATE_WINDOW);
+            let estimates: Vec<usize> = mic
+                .iter()
+                .zip(&far)
+                .filter_map(|(&m, &f)| estimator.push(m, f))
+                .collect();
+            // The strongest reflection is 80 samples after the bulk delay.
+            assert!(!estimates.is_empty(), "no estimate at {} ms", delay_ms);
+            for estimate in estimates {
+                assert!(
+                    estimate.abs_diff(delay + 80) <= 2,
+                    "{} ms: estimated {}",
+                    delay_ms,
+                    estimate
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_silent_reference() {
+        let len = 2 * AEC_SAMPLE_RATE as usize;
+        let mic = noise(len, 9);
+        let mut estimator = DelayEstimator::new(MAX_DELAY, ESTIMATE_WINDOW);
+        assert!(mic.iter().all(|&m| estimator.push(m, 0.0).is_none()));
+    }
+
+    #[test]
+    fn cancels_echo_behind_a_300_ms_playback_delay() {
+        let len = 4 * AEC_SAMPLE_RATE as usize;
+        let delay = 3 * AEC_SAMPLE_RATE as usize / 10;
+        let far = noise(len, 11);
+        let mic = delayed(&echo_of(&far), delay);
+        let mut canceller = EchoCanceller::new(512);
+        let out = canceller.process(&mic, &far);
+
+        assert_eq!(canceller.delay(), delay + 80 - DELAY_MARGIN);
+        let tail = len - AEC_SAMPLE_RATE as usize / 2;
+        let erle_db = 10.0 * (power(&mic[tail..]) / power(&out[tail..])).log10();
+        assert!(erle_db > 30.0, "ERLE {:.1} dB", erle_db);
+    }
+
+    #[test]
+    fn tracks_far_end_peak_over_the_window() {
+        let mut canceller = EchoCanceller::new(4);
+        let peaks: Vec<f32> = [0.1, -0.5, 0.2, 0.3, 0.1, 0.05, -0.4]
+            .iter()
+            .map(|&x| {
+                canceller.push_reference(x);
+                canceller.peaks.front().map_or(0.0, |&(_, m)| m)
+            })
+            .collect();
+        assert_eq!(peaks, vec![0.1, 0.5, 0.5, 0.5, 0.5, 0.3, 0.4]);
+
+        // Moving the window back brings the older peak into view again.
+        canceller.set_delay(2);
+        assert_eq!(canceller.peaks.front().map(|&(_, m)| m), Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn streams_pair_microphone_with_reference() {
+        use futures_util::{stream, StreamExt};
+
+        let rate = AEC_SAMPLE_RATE as usize;
+        let far = noise(2 * rate, 3);
+        let mic = echo_of(&far);
+        let reference = EchoReference::default();
+        let mut tap = ReferenceTap::new(
+            stream::iter(far.clone()),
+            AEC_SAMPLE_RATE,
+            reference.clone(),
+        )
+        .unwrap();
+        let mut cancelled =
+            EchoCancelledStream::new(stream::iter(mic.clone()), AEC_SAMPLE_RATE, reference)
+                .unwrap();
+
+        // Capture delivers both in blocks, system audio slightly ahead.
+        let (mut passed, mut out) = (Vec::new(), Vec::new());
+        for _ in 0..mic.len() / BLOCK {
+            passed.extend((&mut tap).take(BLOCK).collect::<Vec<_>>().await);
+            out.extend((&mut cancelled).take(BLOCK).collect::<Vec<_>>().await);
+        }
+        assert_eq!(passed, far);
+        assert_eq!(out.len(), mic.len());
+        let tail = out.len() - rate / 2;
+        assert!(power(&out[tail..]) < power(&mic[tail..]) / 100.0);
+    }
+}
@@ -1,14 +1,24 @@
 // Pluely AI Speech Detection, and capture system audio (speaker output) as a stream of f32 samples.
-use crate::speaker::{AudioDevice, SpeakerInput};
+use crate::speaker::aec::{EchoCancelledStream, EchoReference, ReferenceTap, AEC_SAMPLE_RATE};
+use crate::speaker::denoise::{NoiseSuppressor, DEFAULT_SUPPRESSION_DB};
+use crate::speaker::encode::{self, AudioEncoding};
+use crate::speaker::microphone::MicrophoneStream;
+use crate::speaker::recording::{
+    CompletedSegment, RecordingSegment, SegmentedRecording, SEGMENT_SECS,
+};
+use crate::speaker::streaming::StreamingTranscriber;
+use crate::speaker::vad::{
+    self, calculate_audio_metrics, CalibratedThresholds, EnergyDetector, NoiseFloorTracker,
+    VadBenchmarkReport, VadDetector, VoiceDetector, WebRtcDetector, CALIBRATION_SECS,
+};
+use crate::speaker::{AudioDevice, Speaker, SpeakerInput};
 use anyhow::Result;
 use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 use futures_util::StreamExt;
-use hound::{WavSpec, WavWriter};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::io::Cursor;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter, Listener, Manager};
 use tauri_plugin_shell::ShellExt;
@@ -26,6 +36,41 @@ pub struct VadConfig {
     pub pre_speech_chunks: usize,
     pub noise_gate_threshold: f32,
     pub max_recording_duration_secs: u64,
+    #[serde(default)]
+    pub detector: VadDetector,
+    // WebRTC detector only: 0 (most permissive) to 3 (strictest)
+    #[serde(default = "default_vad_aggressiveness")]
+    pub vad_aggressiveness: u8,
+    // Derive thresholds from the measured noise floor instead of the values above
+    #[serde(default)]
+    pub auto_calibrate: bool,
+    // Emit transcript-partial/-final events from local Whisper while speaking
+    #[serde(default)]
+    pub streaming_transcription: bool,
+    // Meeting capture only: cancel system audio picked up by the microphone
+    #[serde(default = "default_echo_cancellation")]
+    pub echo_cancellation: bool,
+    // Spectral noise suppression before VAD and encoding
+    #[serde(default)]
+    pub noise_suppression: bool,
+    // Most a frequency band is attenuated by noise suppression, in dB
+    #[serde(default = "default_noise_suppression_db")]
+    pub noise_suppression_db: f32,
+    // Encoding of emitted speech, chosen by the frontend from what the STT provider accepts
+    #[serde(default)]
+    pub audio_encoding: AudioEncoding,
+}
+
+fn default_vad_aggressiveness() -> u8 {
+    2
+}
+
+fn default_echo_cancellation() -> bool {
+    true
+}
+
+fn default_noise_suppression_db() -> f32 {
+    DEFAULT_SUPPRESSION_DB
 }
 
 impl Default for VadConfig {
@@ -40,8 +85,41 @@ impl Default for VadConfig {
             pre_speech_chunks: 12,  // ~0.27s - enough to catch word start
             noise_gate_threshold: 0.003, // Stronger noise filtering
             max_recording_duration_secs: 180, // 3 minutes default
+            detector: VadDetector::Energy,
+            vad_aggressiveness: default_vad_aggressiveness(),
+            auto_calibrate: false,
+            streaming_transcription: false,
+            echo_cancellation: default_echo_cancellation(),
+            noise_suppression: false,
+            noise_suppression_db: default_noise_suppression_db(),
+            audio_encoding: AudioEncoding::Wav,
+        }
+    }
+}
+
+impl VadConfig {
+    fn voice_detector(&self, sample_rate: u32) -> Box<dyn VoiceDetector> {
+        match self.detector {
+            VadDetector::Energy => Box::new(EnergyDetector {
+                sensitivity_rms: self.sensitivity_rms,
+                peak_threshold: self.peak_threshold,
+            }),
+            VadDetector::Webrtc => {
+                Box::new(WebRtcDetector::new(sample_rate, self.vad_aggressiveness))
+            }
         }
     }
+
+    fn noise_suppressor(&self, sample_rate: u32) -> Option<NoiseSuppressor> {
+        self.noise_suppression
+            .then(|| NoiseSuppressor::new(sample_rate, self.noise_suppression_db))
+    }
+
+    fn apply_thresholds(&mut self, thresholds: &CalibratedThresholds) {
+        self.sensitivity_rms = thresholds.sensitivity_rms;
+        self.peak_threshold = thresholds.peak_threshold;
+        self.noise_gate_threshold = thresholds.noise_gate_threshold;
+    }
 }
 
 #[tauri::command]
@@ -112,7 +190,7 @@ pub async fn start_system_audio_capture(
     let state_clone = app.state::<crate::AudioState>();
     let task = tokio::spawn(async move {
         if vad_config.enabled {
-            run_vad_capture(app_clone.clone(), stream, sr, vad_config).await;
+            run_vad_capture(app_clone.clone(), stream, sr, vad_config, None).await;
         } else {
             run_continuous_capture(app_clone.clone(), stream, sr, vad_config).await;
         }
@@ -133,12 +211,162 @@ pub async fn start_system_audio_capture(
     Ok(())
 }
 
+/// Capture the microphone and system audio together, each with its own VAD.
+/// Utterances are emitted as `meeting-speech-detected` with a
+/// `{ speaker, audio }` payload, where speaker is "me" for the microphone and
+/// "them" for system audio. Stopped with `stop_system_audio_capture`.
+#[tauri::command]
+pub async fn start_meeting_capture(
+    app: AppHandle,
+    vad_config: Option<VadConfig>,
+    microphone_id: Option<String>,
+    device_id: Option<String>,
+) -> Result<(), String> {
+    let state = app.state::<crate::AudioState>();
+
+    {
+        let guard = state
+            .stream_task
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+
+        if guard.is_some() {
+            warn!("Capture already running");
+            return Err("Capture already running".to_string());
+        }
+    }
+
+    if let Some(config) = vad_config {
+        *state
+            .vad_config
+            .lock()
+            .map_err(|e| format!("Failed to acquire VAD config lock: {}", e))? = config;
+    }
+    let vad_config = state
+        .vad_config
+        .lock()
+        .map_err(|e| format!("Failed to read VAD config: {}", e))?
+        .clone();
+    if !vad_config.enabled {
+        return Err("Meeting capture needs VAD enabled".to_string());
+    }
+
+    let system = SpeakerInput::new_with_device(device_id)
+        .map_err(|e| {
+            error!("Failed to create speaker input: {}", e);
+            format!("Failed to access system audio: {}", e)
+        })?
+        .stream();
+    let microphone = MicrophoneStream::open(microphone_id).map_err(|e| {
+        error!("Failed to open microphone: {}", e);
+        format!("Failed to access microphone: {}", e)
+    })?;
+    let (system_sr, microphone_sr) = (system.sample_rate(), microphone.sample_rate());
+    for sr in [system_sr, microphone_sr] {
+        if !(8000..=96000).contains(&sr) {
+            error!("Invalid sample rate: {}", sr);
+            return Err(format!(
+                "Invalid sample rate: {}. Expected 8000-96000 Hz",
+                sr
+            ));
+        }
+    }
+
+    *state
+        .is_capturing
+        .lock()
+        .map_err(|e| format!("Failed to set capturing state: {}", e))? = true;
+
+    if let Err(e) = app.emit("capture-started", system_sr) {
+        warn!("Failed to emit capture-started: {}", e);
+    }
+
+    // Without headphones the microphone hears the far end too; cancel it so
+    // the other side isn't also transcribed as "me".
+    type Samples = std::pin::Pin<Box<dyn futures_util::Stream<Item = f32> + Send>>;
+    let (system, microphone, microphone_sr): (Samples, Samples, u32) =
+        if vad_config.echo_cancellation {
+            let reference = EchoReference::default();
+            (
+                Box::pin(ReferenceTap::new(system, system_sr, reference.clone())?),
+                Box::pin(EchoCancelledStream::new(
+                    microphone,
+                    microphone_sr,
+                    reference,
+                )?),
+                AEC_SAMPLE_RATE,
+            )
+        } else {
+            (Box::pin(system), Box::pin(microphone), microphone_sr)
+        };
+
+    let app_clone = app.clone();
+    let task = tokio::spawn(async move {
+        tokio::join!(
+            run_vad_capture(
+                app_clone.clone(),
+                microphone,
+                microphone_sr,
+                vad_config.clone(),
+                Some(Speaker::Me),
+            ),
+            run_vad_capture(
+                app_clone.clone(),
+                system,
+                system_sr,
+                vad_config,
+                Some(Speaker::Them),
+            ),
+        );
+
+        let state = app_clone.state::<crate::AudioState>();
+        if let Ok(mut guard) = state.stream_task.lock() {
+            *guard = None;
+        };
+    });
+
+    *state
+        .stream_task
+        .lock()
+        .map_err(|e| format!("Failed to store task: {}", e))? = Some(task);
+
+    Ok(())
+}
+
+#[derive(Clone, Serialize)]
+struct LabeledSpeech {
+    speaker: Speaker,
+    audio: String,
+}
+
+// Meeting capture labels utterances under its own event, so `speech-detected`
+// listeners keep receiving the bare base64 payload
+fn emit_speech_detected(app: &AppHandle, speaker: Option<Speaker>, b64: String) {
+    let (event, result) = match speaker {
+        Some(speaker) => (
+            "meeting-speech-detected",
+            app.emit(
+                "meeting-speech-detected",
+                LabeledSpeech {
+                    speaker,
+                    audio: b64,
+                },
+            ),
+        ),
+        None => ("speech-detected", app.emit("speech-detected", b64)),
+    };
+    if let Err(e) = result {
+        warn!("Failed to emit {}: {}", event, e);
+    }
+}
+
 // VAD-enabled capture - OPTIMIZED for real-time speech detection
 async fn run_vad_capture(
     app: AppHandle,
     stream: impl StreamExt<Item = f32> + Unpin,
     sr: u32,
-    config: VadConfig,
+    mut config: VadConfig,
+    speaker: Option<Speaker>,
 ) {
     let mut stream = stream;
     let mut buffer: VecDeque<f32> = VecDeque::new();
@@ -149,6 +377,18 @@ async fn run_vad_capture(
     let mut silence_chunks = 0;
     let mut speech_chunks = 0;
     let max_samples = sr as usize * 30; // 30s safety cap per utterance
+    let mut detector = config.voice_detector(sr);
+    let mut suppressor = config.noise_suppressor(sr);
+    let mut calibration = config
+        .auto_calibrate
+        .then(|| NoiseFloorTracker::new(sr, config.hop_size, CALIBRATION_SECS));
+    let mut transcriber = if config.streaming_transcription {
+        StreamingTranscriber::start(app.clone(), sr, speaker)
+            .map_err(|e| warn!("Streaming transcription unavailable: {}", e))
+            .ok()
+    } else {
+        None
+    };
 
     while let Some(sample) = stream.next().await {
         buffer.push_back(sample);
@@ -162,11 +402,31 @@ async fn run_vad_capture(
                 }
             }
 
+            // Suppress noise first: VAD, calibration and the emitted audio
+            // all see the cleaned signal
+            let mono = match suppressor.as_mut() {
+                Some(suppressor) => suppressor.process(&mono),
+                None => mono,
+            };
+
+            // Measure the floor before the gate shapes the hop
+            let (raw_rms, _) = calculate_audio_metrics(&mono);
+
             // Apply noise gate BEFORE VAD (critical for accuracy)
             let mono = apply_noise_gate(&mono, config.noise_gate_threshold);
 
-            let (rms, peak) = calculate_audio_metrics(&mono);
-            let is_speech = rms > config.sensitivity_rms || peak > config.peak_threshold;
+            let is_speech = detector.is_speech(&mono);
+
+            // Adopted from the next hop on
+            if let Some(tracker) = calibration.as_mut() {
+                if let Some(thresholds) = tracker.observe(raw_rms, in_speech || is_speech) {
+                    config.apply_thresholds(&thresholds);
+                    detector.set_thresholds(&thresholds);
+                    if let Err(e) = app.emit("vad-calibrated", thresholds) {
+                        warn!("Failed to emit vad-calibrated: {}", e);
+                    }
+                }
+            }
 
             if is_speech {
                 if !in_speech {
@@ -177,7 +437,7 @@ async fn run_vad_capture(
                     // Include pre-speech buffer for natural sound
                     speech_buffer.extend(pre_speech.drain(..));
 
-                    if let Err(e) = app.emit("speech-start", ()) {
+                    if let Err(e) = app.emit("speech-start", speaker) {
                         warn!("Failed to emit speech-start: {}", e);
                     }
                 }
@@ -185,15 +445,19 @@ async fn run_vad_capture(
                 speech_chunks += 1;
                 speech_buffer.extend_from_slice(&mono);
                 silence_chunks = 0; // Reset silence counter on any speech
+                if let Some(transcriber) = transcriber.as_mut() {
+                    transcriber.update(&speech_buffer);
+                }
 
                 // Safety cap: force emit if exceeds 30s
                 if speech_buffer.len() > max_samples {
                     let normalized_buffer = normalize_audio_level(&speech_buffer, 0.1);
-                    if let Ok(b64) = samples_to_wav_b64(sr, &normalized_buffer) {
+                    if let Ok(b64) = samples_to_b64(sr, &normalized_buffer, config.audio_encoding) {
                         // let duration = speech_buffer.len() as f32 / sr as f32;
-                        if let Err(e) = app.emit("speech-detected", b64) {
-                            warn!("Failed to emit speech-detected: {}", e);
-                        }
+                        emit_speech_detected(&app, speaker, b64);
+                    }
+                    if let Some(transcriber) = transcriber.as_mut() {
+                        transcriber.finish(&speech_buffer, false);
                     }
                     speech_buffer.clear();
                     in_speech = false;
@@ -206,6 +470,9 @@ async fn run_vad_capture(
 
                     // Continue collecting during silence (important for natural speech)
                     speech_buffer.extend_from_slice(&mono);
+                    if let Some(transcriber) = transcriber.as_mut() {
+                        transcriber.update(&speech_buffer);
+                    }
 
                     // Check if silence duration exceeds threshold
                     if silence_chunks >= config.silence_chunks {
@@ -223,22 +490,30 @@ async fn run_vad_capture(
 
                             // Emit complete speech segment
                             let normalized_buffer = normalize_audio_level(&speech_buffer, 0.1);
-                            if let Ok(b64) = samples_to_wav_b64(sr, &normalized_buffer) {
+                            if let Ok(b64) =
+                                samples_to_b64(sr, &normalized_buffer, config.audio_encoding)
+                            {
                                 // let duration = speech_buffer.len() as f32 / sr as f32;
-                                if let Err(e) = app.emit("speech-detected", b64) {
-                                    warn!("Failed to emit speech-detected: {}", e);
-                                }
+                                emit_speech_detected(&app, speaker, b64);
                             } else {
-                                error!("Failed to encode speech to WAV");
+                                error!("Failed to encode speech");
                                 if let Err(e) = app.emit("audio-encoding-error", "Failed to encode speech") {
                                     warn!("Failed to emit audio-encoding-error: {}", e);
                                 }
                             }
-                        } else if let Err(e) = app.emit(
-                            "speech-discarded",
-                            "Audio too short (likely background noise)",
-                        ) {
-                            warn!("Failed to emit speech-discarded: {}", e);
+                            if let Some(transcriber) = transcriber.as_mut() {
+                                transcriber.finish(&speech_buffer, false);
+                            }
+                        } else {
+                            if let Some(transcriber) = transcriber.as_mut() {
+                                transcriber.finish(&speech_buffer, true);
+                            }
+                            if let Err(e) = app.emit(
+                                "speech-discarded",
+                                "Audio too short (likely background noise)",
+                            ) {
+                                warn!("Failed to emit speech-discarded: {}", e);
+                            }
                         }
 
                         // Reset for next speech detection
@@ -266,7 +541,13 @@ async fn run_vad_capture(
     }
 }
 
-// Continuous capture (VAD disabled)
+// Samples cleaned and written at a time in continuous capture
+const SPILL_BLOCK: usize = 4096;
+
+// Continuous capture (VAD disabled). Audio is spilled to disk in segments,
+// each emitted as it completes, and joined into one file on stop. Segment
+// writes and encoding run on the blocking pool; if the task is aborted the
+// recording is dropped with it and removes its segments.
 async fn run_continuous_capture(
     app: AppHandle,
     stream: impl StreamExt<Item = f32> + Unpin,
@@ -275,12 +556,31 @@ async fn run_continuous_capture(
 ) {
     let mut stream = stream;
     let max_samples = (sr as u64 * config.max_recording_duration_secs) as usize;
-
-    // Pre-allocate buffer to prevent reallocations
-    let mut audio_buffer = Vec::with_capacity(max_samples);
     let start_time = Instant::now();
     let max_duration = Duration::from_secs(config.max_recording_duration_secs);
 
+    let session = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let recording = crate::data_dir::current_layout(&app).and_then(|layout| {
+        SegmentedRecording::create(layout.recordings_dir().join(session), sr, SEGMENT_SECS)
+    });
+    let recording = match recording {
+        Ok(recording) => Arc::new(Mutex::new(recording)),
+        Err(e) => {
+            error!("Failed to start recording: {}", e);
+            if let Err(e2) = app.emit("audio-encoding-error", e) {
+                warn!("Failed to emit audio-encoding-error: {}", e2);
+            }
+            if let Err(e) = app.emit("continuous-recording-stopped", ()) {
+                warn!("Failed to emit continuous-recording-stopped: {}", e);
+            }
+            return;
+        }
+    };
+    let encoding = config.audio_encoding;
+    let mut cleaner = ContinuousCleaner::new(&config, sr);
+    let mut pending = Vec::with_capacity(SPILL_BLOCK);
+    let mut captured = 0usize;
+
     // Atomic flag for manual stop
     let stop_flag = Arc::new(AtomicBool::new(false));
     let stop_flag_for_listener = stop_flag.clone();
@@ -310,19 +610,35 @@ async fn run_continuous_capture(
                             break;
                         }
 
-                        audio_buffer.push(sample);
+                        pending.push(sample);
+                        captured += 1;
+                        if pending.len() >= SPILL_BLOCK {
+                            let block = cleaner.clean(&pending);
+                            pending.clear();
+                            let (handle, session) = (app.clone(), recording.clone());
+                            let spilled = tokio::task::spawn_blocking(move || {
+                                let mut recording =
+                                    session.lock().unwrap_or_else(|e| e.into_inner());
+                                spill(&handle, sr, encoding, &mut recording, &block)
+                            })
+                            .await
+                            .unwrap_or(false);
+                            if !spilled {
+                                break;
+                            }
+                        }
 
                         let elapsed = start_time.elapsed();
 
                         // Emit progress every second
-                        if audio_buffer.len() % (sr as usize) == 0 {
+                        if captured % (sr as usize) == 0 {
                             if let Err(e) = app.emit("recording-progress", elapsed.as_secs()) {
                                 warn!("Failed to emit recording-progress: {}", e);
                             }
                         }
 
                         // Check size limit (safety)
-                        if audio_buffer.len() >= max_samples {
+                        if captured >= max_samples {
                             break;
                         }
 
@@ -345,31 +661,46 @@ async fn run_continuous_capture(
     // Clean up event listener (CRITICAL)
     app.unlisten(stop_listener);
 
-    // Process and emit audio
-    if !audio_buffer.is_empty() {
-        // let duration = start_time.elapsed().as_secs_f32();
-
-        // Apply noise gate
-        let cleaned_audio = apply_noise_gate(&audio_buffer, config.noise_gate_threshold);
-        let cleaned_audio = normalize_audio_level(&cleaned_audio, 0.1);
-
-        match samples_to_wav_b64(sr, &cleaned_audio) {
-            Ok(b64) => {
-                if let Err(e) = app.emit("speech-detected", b64) {
-                    warn!("Failed to emit speech-detected: {}", e);
-                }
+    // Write out what is left, including the noise suppressor's tail, and
+    // join the segments
+    let block = cleaner.finish(&pending);
+    let handle = app.clone();
+    let saved = tokio::task::spawn_blocking(move || {
+        let mut recording = recording.lock().unwrap_or_else(|e| e.into_inner());
+        if spill(&handle, sr, encoding, &mut recording, &block) {
+            match recording.close() {
+                Ok(last) => emit_segments(&handle, sr, encoding, last),
+                Err(e) => error!("Failed to write recording segment: {}", e),
             }
-            Err(e) => {
-                error!("Failed to encode continuous audio: {}", e);
-                if let Err(e2) = app.emit("audio-encoding-error", e) {
-                    warn!("Failed to emit audio-encoding-error: {}", e2);
-                }
+        }
+        if recording.is_empty() {
+            return Ok(None);
+        }
+        let gain = normalization_gain(recording.rms(), 0.1);
+        recording
+            .assemble(|sample| soft_clip(sample * gain))
+            .map(Some)
+    })
+    .await
+    .map_err(|e| format!("Recording task failed: {}", e))
+    .and_then(|result| result);
+    match saved {
+        Ok(Some(saved)) => {
+            if let Err(e) = app.emit("continuous-recording-saved", saved) {
+                warn!("Failed to emit continuous-recording-saved: {}", e);
             }
         }
-    } else {
-        warn!("No audio captured in continuous mode");
-        if let Err(e) = app.emit("audio-encoding-error", "No audio recorded") {
-            warn!("Failed to emit audio-encoding-error: {}", e);
+        Ok(None) => {
+            warn!("No audio captured in continuous mode");
+            if let Err(e) = app.emit("audio-encoding-error", "No audio recorded") {
+                warn!("Failed to emit audio-encoding-error: {}", e);
+            }
+        }
+        Err(e) => {
+            error!("Failed to assemble continuous recording: {}", e);
+            if let Err(e2) = app.emit("audio-encoding-error", e) {
+                warn!("Failed to emit audio-encoding-error: {}", e2);
+            }
         }
     }
 
@@ -378,6 +709,97 @@ async fn run_continuous_capture(
     }
 }
 
+// Noise suppression and gate for continuous capture, applied block by block
+struct ContinuousCleaner {
+    suppressor: Option<NoiseSuppressor>,
+    // Leading output samples that are only the suppressor's delay
+    delay_left: usize,
+    gate: f32,
+}
+
+impl ContinuousCleaner {
+    fn new(config: &VadConfig, sr: u32) -> Self {
+        let suppressor = config.noise_suppressor(sr);
+        Self {
+            delay_left: suppressor.as_ref().map_or(0, |s| s.latency()),
+            suppressor,
+            gate: config.noise_gate_threshold,
+        }
+    }
+
+    fn clean(&mut self, block: &[f32]) -> Vec<f32> {
+        let block = match self.suppressor.as_mut() {
+            Some(suppressor) => {
+                let mut out = suppressor.process(block);
+                let skip = self.delay_left.min(out.len());
+                self.delay_left -= skip;
+                out.drain(..skip);
+                out
+            }
+            None => block.to_vec(),
+        };
+        apply_noise_gate(&block, self.gate)
+    }
+
+    // Clean the last block and push the suppressor's delayed tail out
+    fn finish(&mut self, block: &[f32]) -> Vec<f32> {
+        let latency = self.suppressor.as_ref().map_or(0, |s| s.latency());
+        let mut block = block.to_vec();
+        block.resize(block.len() + latency, 0.0);
+        self.clean(&block)
+    }
+}
+
+// Append to the recording and emit any finished segments. False if the
+// recording can't continue.
+fn spill(
+    app: &AppHandle,
+    sr: u32,
+    encoding: AudioEncoding,
+    recording: &mut SegmentedRecording,
+    block: &[f32],
+) -> bool {
+    match recording.push(block) {
+        Ok(completed) => {
+            emit_segments(app, sr, encoding, completed);
+            true
+        }
+        Err(e) => {
+            error!("Failed to write recording segment: {}", e);
+            if let Err(e2) = app.emit("audio-encoding-error", e) {
+                warn!("Failed to emit audio-encoding-error: {}", e2);
+            }
+            false
+        }
+    }
+}
+
+fn emit_segments(
+    app: &AppHandle,
+    sr: u32,
+    encoding: AudioEncoding,
+    segments: impl IntoIterator<Item = CompletedSegment>,
+) {
+    for segment in segments {
+        let duration_secs = segment.samples.len() as f32 / sr as f32;
+        let normalized = normalize_audio_level(&segment.samples, 0.1);
+        match samples_to_b64(sr, &normalized, encoding) {
+            Ok(audio) => {
+                let payload = RecordingSegment {
+                    index: segment.index,
+                    start_secs: segment.start_secs,
+                    duration_secs,
+                    audio,
+                };
+                if let Err(e) = app.emit("recording-segment", payload) {
+                    warn!("Failed to emit recording-segment: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to encode recording segment: {}", e),
+        }
+    }
+}
+
 // Apply noise gate
 fn apply_noise_gate(samples: &[f32], threshold: f32) -> Vec<f32> {
     const KNEE_RATIO: f32 = 3.0; // Compression ratio for soft knee
@@ -395,21 +817,6 @@ fn apply_noise_gate(samples: &[f32], threshold: f32) -> Vec<f32> {
         .collect()
 }
 
-// Calculate RMS and peak (optimized)
-fn calculate_audio_metrics(chunk: &[f32]) -> (f32, f32) {
-    let mut sumsq = 0.0f32;
-    let mut peak = 0.0f32;
-
-    for &v in chunk {
-        let a = v.abs();
-        peak = peak.max(a);
-        sumsq += v * v;
-    }
-
-    let rms = (sumsq / chunk.len() as f32).sqrt();
-    (rms, peak)
-}
-
 fn normalize_audio_level(samples: &[f32], target_rms: f32) -> Vec<f32> {
     if samples.is_empty() {
         return Vec::new();
@@ -418,27 +825,33 @@ fn normalize_audio_level(samples: &[f32], target_rms: f32) -> Vec<f32> {
     let sum_squares: f32 = samples.iter().map(|&s| s * s).sum();
     let current_rms = (sum_squares / samples.len() as f32).sqrt();
 
+    let gain = normalization_gain(current_rms, target_rms);
+    samples.iter().map(|&s| soft_clip(s * gain)).collect()
+}
+
+// Gain that brings a recording at `current_rms` to `target_rms`
+fn normalization_gain(current_rms: f32, target_rms: f32) -> f32 {
     if current_rms < 0.001 {
-        return samples.to_vec();
+        return 1.0;
     }
+    (target_rms / current_rms).min(10.0)
+}
 
-    let gain = (target_rms / current_rms).min(10.0);
-
-    samples
-        .iter()
-        .map(|&s| {
-            let amplified = s * gain;
-            if amplified.abs() > 1.0 {
-                amplified.signum() * (1.0 - (-amplified.abs()).exp())
-            } else {
-                amplified
-            }
-        })
-        .collect()
+// Soft-clip anything the gain pushed past full scale
+fn soft_clip(amplified: f32) -> f32 {
+    if amplified.abs() > 1.0 {
+        amplified.signum() * (1.0 - (-amplified.abs()).exp())
+    } else {
+        amplified
+    }
 }
 
-// Convert samples to WAV base64 (with proper error handling)
-fn samples_to_wav_b64(sample_rate: u32, mono_f32: &[f32]) -> Result<String, String> {
+// Encode samples as base64 WAV, FLAC or Ogg Opus (with proper error handling)
+fn samples_to_b64(
+    sample_rate: u32,
+    mono_f32: &[f32],
+    encoding: AudioEncoding,
+) -> Result<String, String> {
     // Validate sample rate
     if !(8000..=96000).contains(&sample_rate) {
         error!("Invalid sample rate: {}", sample_rate);
@@ -453,28 +866,11 @@ fn samples_to_wav_b64(sample_rate: u32, mono_f32: &[f32]) -> Result<String, Stri
         return Err("Empty audio buffer".to_string());
     }
 
-    let mut cursor = Cursor::new(Vec::new());
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-
-    let mut writer = WavWriter::new(&mut cursor, spec).map_err(|e| {
-        error!("Failed to create WAV writer: {}", e);
-        e.to_string()
+    let bytes = encode::encode(encoding, sample_rate, mono_f32).map_err(|e| {
+        error!("Failed to encode audio: {}", e);
+        e
     })?;
-
-    for &s in mono_f32 {
-        let clamped = s.clamp(-1.0, 1.0);
-        let sample_i16 = (clamped * i16::MAX as f32) as i16;
-        writer.write_sample(sample_i16).map_err(|e| e.to_string())?;
-    }
-
-    writer.finalize().map_err(|e| e.to_string())?;
-
-    Ok(B64.encode(cursor.into_inner()))
+    Ok(B64.encode(bytes))
 }
 
 #[tauri::command]
@@ -596,6 +992,12 @@ pub async fn update_vad_config(app: AppHandle, config: VadConfig) -> Result<(),
     if config.sensitivity_rms < 0.0 || config.sensitivity_rms > 1.0 {
         return Err("Invalid sensitivity_rms: must be 0.0-1.0".to_string());
     }
+    if config.vad_aggressiveness > 3 {
+        return Err("Invalid vad_aggressiveness: must be 0-3".to_string());
+    }
+    if !(0.0..=40.0).contains(&config.noise_suppression_db) {
+        return Err("Invalid noise_suppression_db: must be 0-40".to_string());
+    }
     if config.max_recording_duration_secs > 3600 {
         return Err("Invalid max_recording_duration_secs: must be <= 3600 (1 hour)".to_string());
     }
@@ -609,6 +1011,114 @@ pub async fn update_vad_config(app: AppHandle, config: VadConfig) -> Result<(),
     Ok(())
 }
 
+/// Measure the ambient level of the capture device for `duration_secs` and
+/// return the current VAD config with thresholds derived from it. Keep the
+/// room quiet while this runs.
+#[tauri::command]
+pub async fn calibrate_vad(
+    app: AppHandle,
+    device_id: Option<String>,
+    duration_secs: Option<f32>,
+) -> Result<VadConfig, String> {
+    let duration_secs = duration_secs.unwrap_or(CALIBRATION_SECS);
+    if !(0.5..=30.0).contains(&duration_secs) {
+        return Err("Invalid duration_secs: must be 0.5-30".to_string());
+    }
+    let state = app.state::<crate::AudioState>();
+    let mut config = state
+        .vad_config
+        .lock()
+        .map_err(|e| format!("Failed to read VAD config: {}", e))?
+        .clone();
+    if state
+        .stream_task
+        .lock()
+        .map_err(|e| format!("Failed to acquire lock: {}", e))?
+        .is_some()
+    {
+        return Err("Stop capture before calibrating".to_string());
+    }
+
+    let input = SpeakerInput::new_with_device(device_id).map_err(|e| {
+        error!("Failed to create speaker input: {}", e);
+        format!("Failed to access system audio: {}", e)
+    })?;
+    let mut stream = input.stream();
+    let sr = stream.sample_rate();
+    if !(8000..=96000).contains(&sr) {
+        return Err(format!(
+            "Invalid sample rate: {}. Expected 8000-96000 Hz",
+            sr
+        ));
+    }
+
+    let hop_size = config.hop_size.max(1);
+    let mut tracker = NoiseFloorTracker::new(sr, hop_size, duration_secs);
+    let total = (sr as f32 * duration_secs) as usize;
+    let mut hop = Vec::with_capacity(hop_size);
+    // Capture measures the floor after noise suppression, so do the same
+    let mut suppressor = config.noise_suppressor(sr);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs_f32(duration_secs + 2.0);
+
+    for _ in 0..total {
+        // A silent device may deliver nothing; stop at the deadline
+        let sample = match tokio::time::timeout_at(deadline, stream.next()).await {
+            Ok(Some(sample)) => sample,
+            Ok(None) | Err(_) => break,
+        };
+        hop.push(sample);
+        if hop.len() == hop_size {
+            if let Some(suppressor) = suppressor.as_mut() {
+                hop = suppressor.process(&hop);
+            }
+            tracker.observe(calculate_audio_metrics(&hop).0, false);
+            hop.clear();
+        }
+    }
+
+    let thresholds = tracker
+        .thresholds()
+        .ok_or("No audio received during calibration")?;
+    config.apply_thresholds(&thresholds);
+    Ok(config)
+}
+
+/// Score the detector selected in `config` (or the current one) against the
+/// labeled WAV fixtures in `fixtures_dir`.
+#[tauri::command]
+pub async fn benchmark_vad(
+    app: AppHandle,
+    fixtures_dir: String,
+    config: Option<VadConfig>,
+) -> Result<VadBenchmarkReport, String> {
+    let config = match config {
+        Some(config) => config,
+        None => app
+            .state::<crate::AudioState>()
+            .vad_config
+            .lock()
+            .map_err(|e| format!("Failed to read VAD config: {}", e))?
+            .clone(),
+    };
+    tokio::task::spawn_blocking(move || {
+        vad::benchmark(std::path::Path::new(&fixtures_dir), config.hop_size, |sr| {
+            // Same noise suppression and gate as live capture
+            let mut detector = config.voice_detector(sr);
+            let mut suppressor = config.noise_suppressor(sr);
+            let gate = config.noise_gate_threshold;
+            Box::new(move |frame: &[f32]| {
+                let frame = match suppressor.as_mut() {
+                    Some(suppressor) => suppressor.process(frame),
+                    None => frame.to_vec(),
+                };
+                detector.is_speech(&apply_noise_gate(&frame, gate))
+            })
+        })
+    })
+    .await
+    .map_err(|e| format!("Benchmark task failed: {}", e))?
+}
+
 #[tauri::command]
 pub async fn get_capture_status(app: AppHandle) -> Result<bool, String> {
     let state = app.state::<crate::AudioState>();
@@ -864,13 +1374,13 @@ mod tests {
         }
     }
 
-    // --- samples_to_wav_b64 tests ---
+    // --- samples_to_b64 tests ---
 
     #[test]
     fn wav_b64_valid_input_produces_valid_riff_header() {
         let sr = 44100u32;
         let samples = vec![0.0f32; 1024];
-        let result = samples_to_wav_b64(sr, &samples);
+        let result = samples_to_b64(sr, &samples, AudioEncoding::Wav);
         assert!(result.is_ok(), "valid input should succeed");
         let b64 = result.unwrap();
         let bytes = B64.decode(&b64).expect("should be valid base64");
@@ -883,21 +1393,21 @@ mod tests {
 
     #[test]
     fn wav_b64_empty_buffer_returns_err() {
-        let result = samples_to_wav_b64(44100, &[]);
+        let result = samples_to_b64(44100, &[], AudioEncoding::Wav);
         assert!(result.is_err(), "empty buffer should return Err");
     }
 
     #[test]
     fn wav_b64_zero_sample_rate_returns_err() {
         let samples = vec![0.1f32; 64];
-        let result = samples_to_wav_b64(0, &samples);
+        let result = samples_to_b64(0, &samples, AudioEncoding::Wav);
         assert!(result.is_err(), "sample rate 0 should return Err");
     }
 
     #[test]
     fn wav_b64_too_high_sample_rate_returns_err() {
         let samples = vec![0.1f32; 64];
-        let result = samples_to_wav_b64(100_000, &samples);
+        let result = samples_to_b64(100_000, &samples, AudioEncoding::Wav);
         assert!(result.is_err(), "sample rate 100000 should return Err");
     }
 
@@ -906,7 +1416,7 @@ mod tests {
         let sr = 16000u32;
         let n_samples = 1600usize; // 0.1 seconds
         let samples = vec![0.5f32; n_samples];
-        let result = samples_to_wav_b64(sr, &samples);
+        let result = samples_to_b64(sr, &samples, AudioEncoding::Wav);
         assert!(result.is_ok());
         let bytes = B64.decode(result.unwrap()).expect("valid base64");
         // WAV header is 44 bytes; each 16-bit sample is 2 bytes
@@ -920,4 +1430,17 @@ mod tests {
             n_samples, samples_in_wav
         );
     }
+
+    #[test]
+    fn b64_uses_requested_encoding() {
+        let samples = vec![0.25f32; 1600];
+        let flac = B64
+            .decode(samples_to_b64(16000, &samples, AudioEncoding::Flac).unwrap())
+            .unwrap();
+        assert_eq!(&flac[0..4], b"fLaC", "FLAC should start with fLaC");
+        let wav = B64
+            .decode(samples_to_b64(16000, &samples, AudioEncoding::Wav).unwrap())
+            .unwrap();
+        assert!(flac.len() < wav.len(), "FLAC should be smaller than WAV");
+    }
 }
@@ -0,0 +1,721 @@
+//! Offline speaker diarization for recorded meetings.
+//!
+//! A single system-audio stream still carries every remote participant, so
+//! the "them" channel alone cannot tell who said what. [`diarize`] splits a
+//! 16 kHz recording into overlapping windows, summarizes each window's voice
+//! as the mean and spread of its MFCCs (a cheap speaker embedding that runs
+//! anywhere), and groups the windows by agglomerative clustering on the
+//! distance between them. [`label_segments`] then gives every Whisper
+//! segment the speaker it overlaps most and renders a "Speaker 1: ..."
+//! transcript.
+
+use crate::speaker::denoise::Fft;
+use crate::speaker::local_whisper::{loaded_engine, WhisperSegment};
+use crate::speaker::resample::{wav_to_whisper_input, WHISPER_SAMPLE_RATE};
+use serde::Serialize;
+use std::f32::consts::PI;
+use tauri::AppHandle;
+
+/// Analysis frame for MFCCs: 25 ms every 10 ms.
+const FRAME_SECS: f32 = 0.025;
+const FRAME_STEP_SECS: f32 = 0.01;
+const MEL_FILTERS: usize = 26;
+/// Cepstral coefficients kept, after dropping c0 (overall loudness).
+const CEPSTRA: usize = 12;
+/// Audio summarized by one embedding, and how far apart embeddings are.
+const WINDOW_SECS: f32 = 1.5;
+const WINDOW_STEP_SECS: f32 = 0.75;
+/// Silence that separates two runs of speech.
+const MIN_PAUSE_SECS: f32 = 0.2;
+/// Clusters further apart than this are different speakers, unless a
+/// speaker count was given.
+const MERGE_DISTANCE: f32 = 2.0;
+/// Fewest windows (about this many times [`WINDOW_STEP_SECS`] of speech) a
+/// speaker must have when the count is estimated.
+const MIN_SPEAKER_WINDOWS: usize = 3;
+
+/// One speaker talking without interruption.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeakerTurn {
+    /// Numbered from 1 in order of first appearance.
+    pub speaker: usize,
+    pub start_secs: f32,
+    pub end_secs: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiarizedSegment {
+    pub speaker: usize,
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiarizedTranscript {
+    pub speakers: usize,
+    pub segments: Vec<DiarizedSegment>,
+    /// One "Speaker N: text" line per segment.
+    pub transcript: String,
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// MFCCs and RMS level of every analysis frame.
+fn mfcc_frames(samples: &[f32], sample_rate: u32) -> Vec<(Vec<f32>, f32)> {
+    let frame = (FRAME_SECS * sample_rate as f32) as usize;
+    let step = (FRAME_STEP_SECS * sample_rate as f32) as usize;
+    let size = frame.next_power_of_two();
+    let bins = size / 2 + 1;
+    let fft = Fft::new(size);
+    let window: Vec<f32> = (0..frame)
+        .map(|i| 0.54 - 0.46 * (2.0 * PI * i as f32 / (frame - 1) as f32).cos())
+        .collect();
+
+    // Triangular filters evenly spaced on the mel scale up to Nyquist.
+    let top = hz_to_mel(sample_rate as f32 / 2.0);
+    let edges: Vec<f32> = (0..MEL_FILTERS + 2)
+        .map(|i| {
+            mel_to_hz(top * i as f32 / (MEL_FILTERS + 1) as f32) * size as f32 / sample_rate as f32
+        })
+        .collect();
+    let filters: Vec<Vec<f32>> = edges
+        .windows(3)
+        .map(|e| {
+            (0..bins)
+                .map(|b| {
+                    let b = b as f32;
+                    if b <= e[0] || b >= e[2] {
+                        0.0
+                    } else if b <= e[1] {
+                        (b - e[0]) / (e[1] - e[0])
+                    } else {
+                        (e[2] - b) / (e[2] - e[1])
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    if samples.len() < frame {
+        return Vec::new();
+    }
+    (0..=(samples.len() - frame) / step)
+        .map(|f| {
+            let chunk = &samples[f * step..f * step + frame];
+            let rms = (chunk.iter().map(|s| s * s).sum::<f32>() / frame as f32).sqrt();
+
+            let mut re = vec![0.0; size];
+            let mut im = vec![0.0; size];
+            for ((r, s), w) in re.iter_mut().zip(chunk).zip(&window) {
+                *r = s * w;
+            }
+            fft.transform(&mut re, &mut im, false);
+            let power: Vec<f32> = (0..bins).map(|b| re[b] * re[b] + im[b] * im[b]).collect();
+
+            let log_mel: Vec<f32> = filters
+                .iter()
+                .map(|filter| {
+                    let energy: f32 = filter.iter().zip(&power).map(|(f, p)| f * p).sum();
+                    (energy + 1e-10).ln()
+                })
+                .collect();
+            // DCT-II, skipping c0.
+            let cepstra = (1..=CEPSTRA)
+                .map(|c| {
+                    log_mel
+                        .iter()
+                        .enumerate()
+                        .map(|(m, v)| {
+                            v * (PI * c as f32 * (m as f32 + 0.5) / MEL_FILTERS as f32).cos()
+                        })
+                        .sum()
+                })
+                .collect();
+            (cepstra, rms)
+        })
+        .collect()
+}
+
+/// Frames within 20 dB of the recording's loud parts. Meetings are mostly
+/// speech, so the quiet end of the distribution can't be trusted as the
+/// background level.
+fn voiced_frames(levels: &[f32]) -> Vec<bool> {
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let loud = sorted[((sorted.len() - 1) as f32 * 0.95) as usize];
+    let threshold = (loud * 0.1).max(1e-4);
+    levels.iter().map(|&l| l > threshold).collect()
+}
+
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt()
+}
+
+struct Window {
+    start_secs: f32,
+    end_secs: f32,
+    embedding: Vec<f32>,
+}
+
+/// Embeddings of windows of speech and the time each one accounts for.
+/// An embedding is the mean and spread of each coefficient over the voiced
+/// frames, in units of that coefficient's spread over the whole recording,
+/// so distances compare voices rather than whichever coefficient happens to
+/// be largest.
+fn embed_windows(samples: &[f32], sample_rate: u32) -> Vec<Window> {
+    let frames = mfcc_frames(samples, sample_rate);
+    if frames.is_empty() {
+        return Vec::new();
+    }
+    let levels: Vec<f32> = frames.iter().map(|(_, rms)| *rms).collect();
+    let voiced = voiced_frames(&levels);
+
+    let speech: Vec<&Vec<f32>> = frames
+        .iter()
+        .zip(&voiced)
+        .filter(|(_, v)| **v)
+        .map(|((c, _), _)| c)
+        .collect();
+    if speech.is_empty() {
+        return Vec::new();
+    }
+    let stats = |frames: &[&Vec<f32>], c: usize| {
+        let n = frames.len() as f32;
+        let mean = frames.iter().map(|f| f[c]).sum::<f32>() / n;
+        let var = frames.iter().map(|f| (f[c] - mean).powi(2)).sum::<f32>() / n;
+        (mean, var.sqrt())
+    };
+    let scale: Vec<f32> = (0..CEPSTRA)
+        .map(|c| stats(&speech, c).1.max(1e-6))
+        .collect();
+
+    // Runs of speech, split at pauses. Windows stay inside one run, so a
+    // change of speaker at a pause never mixes two voices in one window.
+    let pause = (MIN_PAUSE_SECS / FRAME_STEP_SECS) as usize;
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (f, _) in voiced.iter().enumerate().filter(|(_, v)| **v) {
+        match runs.last_mut() {
+            Some(run) if f - run.1 < pause => run.1 = f + 1,
+            _ => runs.push((f, f + 1)),
+        }
+    }
+
+    let per_window = (WINDOW_SECS / FRAME_STEP_SECS) as usize;
+    let per_step = (WINDOW_STEP_SECS / FRAME_STEP_SECS) as usize;
+    let secs = |frame: usize| frame as f32 * FRAME_STEP_SECS;
+    let mut windows = Vec::new();
+    for (run_start, run_end) in runs {
+        if run_end - run_start < per_window / 3 {
+            continue;
+        }
+        let mut start = run_start;
+        let mut claimed = run_start;
+        loop {
+            let end = (start + per_window).min(run_end);
+            let voiced_in: Vec<&Vec<f32>> = (start..end)
+                .filter(|&f| voiced[f])
+                .map(|f| &frames[f].0)
+                .collect();
+            let (means, spreads): (Vec<f32>, Vec<f32>) = (0..CEPSTRA)
+                .map(|c| {
+                    let (mean, spread) = stats(&voiced_in, c);
+                    (mean / scale[c], spread / scale[c])
+                })
+                .unzip();
+            // Each window stands for the step around its center, and the
+            // first and last for the ends of the run.
+            let until = if end == run_end {
+                run_end
+            } else {
+                ((start + end) / 2 + per_step / 2).max(claimed)
+            };
+            windows.push(Window {
+                start_secs: secs(claimed),
+                end_secs: secs(until),
+                embedding: [means, spreads].concat(),
+            });
+            if end == run_end {
+                break;
+            }
+            claimed = until;
+            // The last window is moved back to end with the run rather than
+            // cut short.
+            start = (start + per_step).min(run_end.saturating_sub(per_window));
+        }
+    }
+    windows
+}
+
+/// Position of the pair `i`, `j` in a condensed (upper triangle) distance
+/// matrix of `n` points.
+fn pair_index(n: usize, i: usize, j: usize) -> usize {
+    let (i, j) = if i < j { (i, j) } else { (j, i) };
+    i * (2 * n - i - 1) / 2 + (j - i - 1)
+}
+
+/// Average-linkage merges of the embeddings, as the two merged clusters
+/// (named by a member) and their distance, from closest to furthest.
+///
+/// Uses the nearest-neighbour chain algorithm: follow nearest neighbours
+/// until two clusters are each other's nearest and merge them. That is
+/// O(n²) time instead of O(n³) for searching the closest pair before every
+/// merge, and average linkage is reducible, so sorting the merges gives the
+/// same dendrogram.
+fn average_linkage(embeddings: &[Vec<f32>]) -> Vec<(usize, usize, f32)> {
+    let n = embeddings.len();
+    let mut distances = Vec::with_capacity(n * n.saturating_sub(1) / 2);
+    for i in 0..n {
+        for j in i + 1..n {
+            distances.push(distance(&embeddings[i], &embeddings[j]));
+        }
+    }
+    let mut sizes = vec![1usize; n];
+    let mut alive = vec![true; n];
+    let mut chain: Vec<usize> = Vec::new();
+    let mut merges = Vec::with_capacity(n.saturating_sub(1));
+
+    while merges.len() + 1 < n {
+        if chain.is_empty() {
+            chain.extend(alive.iter().position(|&a| a));
+        }
+        let a = chain[chain.len() - 1];
+        // Prefer the previous link on ties, or the chain could cycle.
+        let previous = chain.len().checked_sub(2).map(|i| chain[i]);
+        let mut nearest = previous.map(|p| (p, distances[pair_index(n, a, p)]));
+        for k in (0..n).filter(|&k| alive[k] && k != a) {
+            let d = distances[pair_index(n, a, k)];
+            if nearest.is_none_or(|(_, best)| d < best) {
+                nearest = Some((k, d));
+            }
+        }
+        let Some((b, d)) = nearest else {
+            break;
+        };
+        if Some(b) != previous {
+            chain.push(b);
+            continue;
+        }
+
+        chain.truncate(chain.len() - 2);
+        // Lance-Williams update for average linkage; `a` lives on as the
+        // merged cluster.
+        let (size_a, size_b) = (sizes[a] as f32, sizes[b] as f32);
+        for k in (0..n).filter(|&k| alive[k] && k != a && k != b) {
+            let (ak, bk) = (pair_index(n, a, k), pair_index(n, b, k));
+            distances[ak] = (size_a * distances[ak] + size_b * distances[bk]) / (size_a + size_b);
+        }
+        alive[b] = false;
+        sizes[a] += sizes[b];
+        merges.push((a, b, d));
+    }
+
+    merges.sort_by(|x, y| x.2.total_cmp(&y.2));
+    merges
+}
+
+fn average_distance(embeddings: &[Vec<f32>], a: &[usize], b: &[usize]) -> f32 {
+    let total: f32 = a
+        .iter()
+        .flat_map(|&i| b.iter().map(move |&j| (i, j)))
+        .map(|(i, j)| distance(&embeddings[i], &embeddings[j]))
+        .sum();
+    total / (a.len() * b.len()) as f32
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Average-linkage agglomerative clustering. Returns a cluster index per
+/// embedding.
+fn cluster(embeddings: &[Vec<f32>], num_speakers: Option<usize>) -> Vec<usize> {
+    let n = embeddings.len();
+    let merges = average_linkage(embeddings);
+    let applied = match num_speakers {
+        Some(count) => n.saturating_sub(count),
+        None => merges
+            .iter()
+            .take_while(|&&(_, _, d)| d <= MERGE_DISTANCE)
+            .count(),
+    };
+    let mut parents: Vec<usize> = (0..n).collect();
+    for &(a, b, _) in &merges[..applied.min(merges.len())] {
+        let (a, b) = (find(&mut parents, a), find(&mut parents, b));
+        parents[b] = a;
+    }
+
+    // Clusters in order of their first window.
+    let mut members: Vec<Vec<usize>> = Vec::new();
+    let mut roots: Vec<usize> = Vec::new();
+    for i in 0..n {
+        let root = find(&mut parents, i);
+        match roots.iter().position(|&r| r == root) {
+            Some(c) => members[c].push(i),
+            None => {
+                roots.push(root);
+                members.push(vec![i]);
+            }
+        }
+    }
+
+    // Windows straddling a change of speaker sit between two voices and can
+    // end up on their own; fold clusters too short to be a real speaker into
+    // their nearest neighbour.
+    if num_speakers.is_none() {
+        while let Some(small) = (0..members.len())
+            .filter(|&c| members[c].len() < MIN_SPEAKER_WINDOWS)
+            .min_by_key(|&c| members[c].len())
+        {
+            let linkage = |c: usize| average_distance(embeddings, &members[small], &members[c]);
+            let Some(nearest) = (0..members.len())
+                .filter(|&c| c != small)
+                .min_by(|&a, &b| linkage(a).total_cmp(&linkage(b)))
+            else {
+                break;
+            };
+            let moved = members.remove(small);
+            members[nearest - usize::from(nearest > small)].extend(moved);
+        }
+    }
+
+    let mut labels = vec![0; n];
+    for (label, cluster) in members.iter().enumerate() {
+        for &member in cluster {
+            labels[member] = label;
+        }
+    }
+    labels
+}
+
+/// Who spoke when in 16 kHz mono `samples`. `num_speakers` fixes the number
+/// of speakers when it is known; otherwise it is estimated.
+pub fn diarize(samples: &[f32], sample_rate: u32, num_speakers: Option<usize>) -> Vec<SpeakerTurn> {
+    let windows = embed_windows(samples, sample_rate);
+    if windows.is_empty() {
+        return Vec::new();
+    }
+    let embeddings: Vec<Vec<f32>> = windows.iter().map(|w| w.embedding.clone()).collect();
+    let labels = cluster(&embeddings, num_speakers);
+
+    // Number speakers by first appearance.
+    let mut order: Vec<usize> = Vec::new();
+    for &label in &labels {
+        if !order.contains(&label) {
+            order.push(label);
+        }
+    }
+
+    // Consecutive windows of the same speaker form one turn.
+    let mut turns: Vec<SpeakerTurn> = Vec::new();
+    for (window, label) in windows.iter().zip(&labels) {
+        let speaker = order.iter().position(|l| l == label).unwrap_or(0) + 1;
+        match turns.last_mut() {
+            Some(last) if last.speaker == speaker => last.end_secs = window.end_secs,
+            _ => turns.push(SpeakerTurn {
+                speaker,
+                start_secs: window.start_secs,
+                end_secs: window.end_secs,
+            }),
+        }
+    }
+    turns
+}
+
+/// Give every transcript segment the speaker it overlaps most (or the
+/// nearest one, if it falls in a gap) and join consecutive segments of the
+/// same speaker.
+pub fn label_segments(segments: &[WhisperSegment], turns: &[SpeakerTurn]) -> DiarizedTranscript {
+    let mut labeled: Vec<DiarizedSegment> = Vec::new();
+    for segment in segments {
+        let speaker = turns
+            .iter()
+            .max_by(|a, b| {
+                // Negative overlap is the distance to the turn.
+                let overlap = |t: &SpeakerTurn| {
+                    t.end_secs.min(segment.end_secs) - t.start_secs.max(segment.start_secs)
+                };
+                overlap(a).total_cmp(&overlap(b))
+            })
+            .map(|t| t.speaker)
+            .unwrap_or(1);
+        match labeled.last_mut() {
+            Some(last) if last.speaker == speaker => {
+                last.end_secs = segment.end_secs;
+                last.text = format!("{} {}", last.text, segment.text);
+            }
+            _ => labeled.push(DiarizedSegment {
+                speaker,
+                start_secs: segment.start_secs,
+                end_secs: segment.end_secs,
+                text: segment.text.clone(),
+            }),
+        }
+    }
+
+    let transcript = labeled
+        .iter()
+        .map(|s| format!("Speaker {}: {}", s.speaker, s.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+    DiarizedTranscript {
+        speakers: turns.iter().map(|t| t.speaker).max().unwrap_or(0),
+        segments: labeled,
+        transcript,
+    }
+}
+
+/// Transcribe a recorded WAV file with local Whisper and attribute each part
+/// to a speaker.
+#[tauri::command]
+pub async fn diarize_recording(
+    app: AppHandle,
+    audio_path: String,
+    num_speakers: Option<usize>,
+) -> Result<DiarizedTranscript, String> {
+    if num_speakers == Some(0) {
+        return Err("Invalid num_speakers: must be at least 1".to_string());
+    }
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&audio_path)
+            .map_err(|e| format!("Failed to open {}: {}", audio_path, e))?;
+        let samples = wav_to_whisper_input(std::io::BufReader::new(file))?;
+        // Clustering needs no model; the engine is cloned out of its lock so
+        // a long recording doesn't block other transcriptions.
+        let turns = diarize(&samples, WHISPER_SAMPLE_RATE, num_speakers);
+
+        let engine = loaded_engine(&app)?;
+        let segments = engine.transcribe_segments(&samples, WHISPER_SAMPLE_RATE)?;
+        Ok(label_segments(&segments, &turns))
+    })
+    .await
+    .map_err(|e| format!("Diarization task failed: {}", e))?
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = WHISPER_SAMPLE_RATE;
+
+    /// A vowel-like voice: harmonics of `pitch` shaped by two formants, with
+    /// a little vibrato and breath noise.
+    fn voice(secs: f32, pitch: f32, formants: [f32; 2], seed: u32) -> Vec<f32> {
+        let mut seed = seed;
+        let len = (secs * RATE as f32) as usize;
+        let harmonics: Vec<(f32, f32)> = (1..)
+            .map(|h| h as f32 * pitch)
+            .take_while(|f| *f < 4000.0)
+            .map(|f| {
+                let gain: f32 = formants
+                    .iter()
+                    .map(|formant| (-((f - formant) / 150.0).powi(2)).exp())
+                    .sum();
+                (f, 0.02 + gain)
+            })
+            .collect();
+        let mut phase = vec![0.0f32; harmonics.len()];
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / RATE as f32;
+                let wobble = 1.0 + 0.01 * (2.0 * PI * 5.0 * t).sin();
+                let tone: f32 = harmonics
+                    .iter()
+                    .zip(phase.iter_mut())
+                    .map(|((f, gain), p)| {
+                        *p += 2.0 * PI * f * wobble / RATE as f32;
+                        gain * p.sin()
+                    })
+                    .sum();
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let breath = (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5;
+                0.05 * tone + 0.005 * breath
+            })
+            .collect()
+    }
+
+    fn silence(secs: f32) -> Vec<f32> {
+        vec![0.0; (secs * RATE as f32) as usize]
+    }
+
+    fn meeting() -> Vec<f32> {
+        let alice = |secs, seed| voice(secs, 210.0, [700.0, 1200.0], seed);
+        let bob = |secs, seed| voice(secs, 105.0, [400.0, 2200.0], seed);
+        [
+            alice(4.0, 1),
+            silence(0.4),
+            bob(4.0, 2),
+            silence(0.4),
+            alice(3.0, 3),
+            silence(0.4),
+            bob(3.0, 4),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn finds_two_speakers_and_their_turns() {
+        let turns = diarize(&meeting(), RATE, None);
+        let speakers: Vec<usize> = turns.iter().map(|t| t.speaker).collect();
+        assert_eq!(speakers, vec![1, 2, 1, 2], "{:?}", turns);
+
+        // Changes land within a window step of the true boundaries.
+        let boundaries = [4.2, 8.6, 12.0];
+        for (pair, expected) in turns.windows(2).zip(boundaries) {
+            let change = (pair[0].end_secs + pair[1].start_secs) / 2.0;
+            assert!(
+                (change - expected).abs() <= WINDOW_STEP_SECS,
+                "change at {} instead of {}",
+                change,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_one_speaker_together() {
+        let audio = [
+            voice(3.0, 150.0, [500.0, 1500.0], 1),
+            silence(0.5),
+            voice(3.0, 150.0, [500.0, 1500.0], 2),
+        ]
+        .concat();
+        let turns = diarize(&audio, RATE, None);
+        assert!(!turns.is_empty());
+        assert!(turns.iter().all(|t| t.speaker == 1), "{:?}", turns);
+
+        // A known speaker count overrides the estimate.
+        let turns = diarize(&meeting(), RATE, Some(1));
+        assert!(turns.iter().all(|t| t.speaker == 1));
+        assert!(diarize(&silence(2.0), RATE, None).is_empty());
+    }
+
+    #[test]
+    fn labels_whisper_segments_by_overlap() {
+        let turn = |speaker, start_secs, end_secs| SpeakerTurn {
+            speaker,
+            start_secs,
+            end_secs,
+        };
+        let segment = |start_secs, end_secs, text: &str| WhisperSegment {
+            start_secs,
+            end_secs,
+            text: text.to_string(),
+        };
+        let turns = [turn(1, 0.0, 4.0), turn(2, 4.5, 8.0), turn(1, 8.0, 10.0)];
+        let segments = [
+            segment(0.0, 2.0, "Shall we start?"),
+            segment(2.0, 4.6, "I think so."),
+            segment(4.6, 7.9, "Numbers are up."),
+            segment(8.2, 9.0, "Great."),
+            segment(10.5, 11.0, "Bye."),
+        ];
+        let result = label_segments(&segments, &turns);
+        assert_eq!(result.speakers, 2);
+        assert_eq!(
+            result.transcript,
+            "Speaker 1: Shall we start? I think so.\n\
+             Speaker 2: Numbers are up.\n\
+             Speaker 1: Great. Bye."
+        );
+        assert_eq!(result.segments[0].end_secs, 4.6);
+    }
+
+    /// Deterministic points in `clusters` blobs.
+    fn blobs(n: usize, clusters: usize, seed: u32) -> Vec<Vec<f32>> {
+        let mut seed = seed;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+        };
+        (0..n)
+            .map(|i| {
+                let centre = (i % clusters) as f32 * 3.0;
+                (0..4).map(|_| centre + next()).collect()
+            })
+            .collect()
+    }
+
+    /// Partition as sorted member lists, independent of label numbering.
+    fn partition(labels: &[usize]) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for label in 0..=labels.iter().copied().max().unwrap_or(0) {
+            let group: Vec<usize> = (0..labels.len()).filter(|&i| labels[i] == label).collect();
+            if !group.is_empty() {
+                groups.push(group);
+            }
+        }
+        groups.sort();
+        groups
+    }
+
+    /// Textbook average linkage: merge the closest pair, k clusters left.
+    fn naive_average_linkage(embeddings: &[Vec<f32>], k: usize) -> Vec<usize> {
+        let mut clusters: Vec<Vec<usize>> = (0..embeddings.len()).map(|i| vec![i]).collect();
+        while clusters.len() > k {
+            let mut best = (f32::INFINITY, 0, 0);
+            for a in 0..clusters.len() {
+                for b in a + 1..clusters.len() {
+                    let d = average_distance(embeddings, &clusters[a], &clusters[b]);
+                    if d < best.0 {
+                        best = (d, a, b);
+                    }
+                }
+            }
+            let moved = clusters.remove(best.2);
+            clusters[best.1].extend(moved);
+        }
+        let mut labels = vec![0; embeddings.len()];
+        for (label, cluster) in clusters.iter().enumerate() {
+            for &i in cluster {
+                labels[i] = label;
+            }
+        }
+        labels
+    }
+
+    #[test]
+    fn nearest_neighbour_chain_matches_naive_average_linkage() {
+        let embeddings = blobs(60, 4, 17);
+        for k in [1, 2, 4, 7, 20] {
+            assert_eq!(
+                partition(&cluster(&embeddings, Some(k))),
+                partition(&naive_average_linkage(&embeddings, k)),
+                "{} clusters",
+                k
+            );
+        }
+    }
+
+    #[test]
+    fn clusters_a_long_meeting() {
+        // 2000 windows is 25 minutes of speech at one per WINDOW_STEP_SECS,
+        // far past where searching the closest pair before every merge
+        // stops being usable.
+        let embeddings = blobs(2000, 3, 23);
+        let labels = cluster(&embeddings, None);
+        assert_eq!(partition(&labels).len(), 3);
+        assert!((0..labels.len()).all(|i| labels[i] == labels[i % 3]));
+    }
+}
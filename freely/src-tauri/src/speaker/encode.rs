@@ -0,0 +1,550 @@
+//! Encodings for speech sent to STT providers.
+//!
+//! Captured speech crosses IPC as base64, so plain 16-bit WAV costs its full
+//! size plus a third. FLAC is lossless and roughly halves speech; Ogg Opus is
+//! lossy and a small fraction of WAV at the bitrate used here. The frontend
+//! picks the encoding from what the selected STT provider accepts and passes
+//! it with the capture config.
+//!
+//! The FLAC encoder is deliberately simple: fixed 4096-sample blocks, one
+//! channel, and per block the best of the fixed polynomial predictors with
+//! partitioned Rice coding of the residual. That gets most of what FLAC can
+//! do on speech without an LPC search.
+
+use crate::speaker::resample::resample;
+use hound::{WavSpec, WavWriter};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::Cursor;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioEncoding {
+    #[default]
+    Wav,
+    Flac,
+    Opus,
+}
+
+/// Encode mono samples in -1..1 as a complete file in `encoding`.
+pub fn encode(
+    encoding: AudioEncoding,
+    sample_rate: u32,
+    samples: &[f32],
+) -> Result<Vec<u8>, String> {
+    match encoding {
+        AudioEncoding::Wav => encode_wav(sample_rate, samples),
+        AudioEncoding::Flac => Ok(encode_flac(sample_rate, samples)),
+        AudioEncoding::Opus => encode_opus(sample_rate, samples),
+    }
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+fn encode_wav(sample_rate: u32, samples: &[f32]) -> Result<Vec<u8>, String> {
+    let mut cursor = Cursor::new(Vec::new());
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = WavWriter::new(&mut cursor, spec)
+        .map_err(|e| format!("Failed to create WAV writer: {}", e))?;
+    for &sample in samples {
+        writer
+            .write_sample(to_i16(sample))
+            .map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+// ============================================================================
+// FLAC
+// ============================================================================
+
+const FLAC_BLOCK_SIZE: usize = 4096;
+const FLAC_MAX_FIXED_ORDER: usize = 4;
+const FLAC_MAX_PARTITION_ORDER: u32 = 8;
+/// Largest parameter of the 4-bit Rice coding method (15 is the escape code).
+const FLAC_MAX_RICE_PARAM: u32 = 14;
+
+/// MSB-first bit packer.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    /// Write the low `bits` bits of `value`; at most 32 at a time.
+    fn write(&mut self, value: u64, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+        self.acc = (self.acc << bits) | (value & ((1 << bits) - 1));
+        self.bits += bits;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+
+    /// `q` zeros and a stop bit.
+    fn write_unary(&mut self, mut q: u32) {
+        while q >= 32 {
+            self.write(0, 32);
+            q -= 32;
+        }
+        self.write(1, q + 1);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn encode_flac(sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+    let pcm: Vec<i32> = samples.iter().map(|&s| to_i16(s) as i32).collect();
+    let mut out = BitWriter::default();
+    out.bytes.extend_from_slice(b"fLaC");
+
+    // STREAMINFO, the only metadata block
+    out.write(1, 1);
+    out.write(0, 7);
+    out.write(34, 24);
+    out.write(FLAC_BLOCK_SIZE as u64, 16);
+    out.write(FLAC_BLOCK_SIZE as u64, 16);
+    out.write(0, 24); // Frame sizes unknown
+    out.write(0, 24);
+    out.write(sample_rate as u64, 20);
+    out.write(0, 3); // One channel
+    out.write(15, 5); // 16 bits per sample
+    out.write((pcm.len() as u64) >> 32, 4);
+    out.write(pcm.len() as u64, 32);
+    for _ in 0..4 {
+        out.write(0, 32); // No MD5
+    }
+
+    for (number, block) in pcm.chunks(FLAC_BLOCK_SIZE).enumerate() {
+        write_flac_frame(&mut out, number as u32, block);
+    }
+    out.bytes
+}
+
+fn write_flac_frame(out: &mut BitWriter, number: u32, block: &[i32]) {
+    let start = out.bytes.len();
+    out.write(0b11_1111_1111_1110, 14);
+    out.write(0, 1);
+    out.write(0, 1); // Fixed block size
+    out.write(0b0111, 4); // Block size follows the frame number
+    out.write(0, 4); // Sample rate from STREAMINFO
+    out.write(0, 4); // Mono
+    out.write(0b100, 3); // 16 bits per sample
+    out.write(0, 1);
+    write_utf8(out, number);
+    out.write(block.len() as u64 - 1, 16);
+    let crc = crc8(&out.bytes[start..]);
+    out.write(crc as u64, 8);
+
+    write_subframe(out, block);
+    out.align();
+    let crc = crc16(&out.bytes[start..]);
+    out.write(crc as u64, 16);
+}
+
+/// Frame numbers use the UTF-8 byte layout, extended to 31 bits.
+fn write_utf8(out: &mut BitWriter, value: u32) {
+    if value < 0x80 {
+        out.write(value as u64, 8);
+        return;
+    }
+    let mut len = 2;
+    while value >= 1 << (5 * len + 1) {
+        len += 1;
+    }
+    let lead = (0xFF00u32 >> len) & 0xFF;
+    out.write((lead | (value >> (6 * (len - 1)))) as u64, 8);
+    for i in (0..len - 1).rev() {
+        out.write((0x80 | ((value >> (6 * i)) & 0x3F)) as u64, 8);
+    }
+}
+
+fn write_subframe(out: &mut BitWriter, block: &[i32]) {
+    if block.iter().all(|&s| s == block[0]) {
+        out.write(0, 8); // CONSTANT
+        out.write(block[0] as u64, 16);
+        return;
+    }
+
+    let best = (0..=FLAC_MAX_FIXED_ORDER.min(block.len() - 1))
+        .map(|order| {
+            let residual = fixed_residual(block, order);
+            let plan = plan_rice(&residual, order, block.len());
+            (order, residual, plan)
+        })
+        .min_by_key(|(order, _, plan)| plan.bits + 16 * order)
+        .expect("at least order 0");
+    let (order, residual, plan) = best;
+
+    if plan.bits + 16 * order >= 16 * block.len() {
+        out.write(0b0000_0010, 8); // VERBATIM
+        for &sample in block {
+            out.write(sample as u64, 16);
+        }
+        return;
+    }
+
+    out.write(0, 1);
+    out.write(0b001000 | order as u64, 6); // FIXED
+    out.write(0, 1);
+    for &sample in &block[..order] {
+        out.write(sample as u64, 16);
+    }
+    out.write(0, 2); // 4-bit Rice parameters
+    out.write(plan.partition_order as u64, 4);
+    let mut rest = residual.as_slice();
+    for (i, &param) in plan.params.iter().enumerate() {
+        let len = partition_len(block.len(), plan.partition_order, order, i);
+        let (partition, tail) = rest.split_at(len);
+        rest = tail;
+        out.write(param as u64, 4);
+        for &r in partition {
+            let folded = zigzag(r);
+            out.write_unary(folded >> param);
+            out.write(folded as u64, param);
+        }
+    }
+}
+
+fn fixed_residual(block: &[i32], order: usize) -> Vec<i32> {
+    (order..block.len())
+        .map(|i| {
+            let x = |back: usize| block[i - back];
+            match order {
+                0 => x(0),
+                1 => x(0) - x(1),
+                2 => x(0) - 2 * x(1) + x(2),
+                3 => x(0) - 3 * x(1) + 3 * x(2) - x(3),
+                _ => x(0) - 4 * x(1) + 6 * x(2) - 4 * x(3) + x(4),
+            }
+        })
+        .collect()
+}
+
+fn zigzag(r: i32) -> u32 {
+    ((r << 1) ^ (r >> 31)) as u32
+}
+
+/// Residual samples in partition `i`; the first one skips the warm-up.
+fn partition_len(block_len: usize, partition_order: u32, order: usize, i: usize) -> usize {
+    let len = block_len >> partition_order;
+    if i == 0 {
+        len - order
+    } else {
+        len
+    }
+}
+
+struct RicePlan {
+    partition_order: u32,
+    params: Vec<u32>,
+    /// Size of the residual section, estimated from partition sums.
+    bits: usize,
+}
+
+fn plan_rice(residual: &[i32], order: usize, block_len: usize) -> RicePlan {
+    let folded: Vec<u64> = residual.iter().map(|&r| zigzag(r) as u64).collect();
+    (0..=FLAC_MAX_PARTITION_ORDER)
+        .take_while(|&p| p <= block_len.trailing_zeros() && (block_len >> p) > order)
+        .map(|partition_order| {
+            let mut rest = folded.as_slice();
+            let mut params = Vec::new();
+            let mut bits = 6;
+            for i in 0..1 << partition_order {
+                let len = partition_len(block_len, partition_order, order, i);
+                let (partition, tail) = rest.split_at(len);
+                rest = tail;
+                let sum: u64 = partition.iter().sum();
+                let (param, cost) = (0..=FLAC_MAX_RICE_PARAM)
+                    .map(|k| (k, len as u64 * (k as u64 + 1) + (sum >> k)))
+                    .min_by_key(|&(_, cost)| cost)
+                    .expect("at least one parameter");
+                params.push(param);
+                bits += 4 + cost as usize;
+            }
+            RicePlan {
+                partition_order,
+                params,
+                bits,
+            }
+        })
+        .min_by_key(|plan| plan.bits)
+        .expect("partition order 0 always fits")
+}
+
+// ============================================================================
+// Ogg Opus
+// ============================================================================
+
+/// Input rates libopus accepts; anything else is resampled to the fallback.
+const OPUS_RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+const OPUS_FALLBACK_RATE: u32 = 16_000;
+/// Ogg Opus granule positions always count 48 kHz samples.
+const OPUS_GRANULE_RATE: u32 = 48_000;
+const OPUS_FRAME_MS: u32 = 20;
+const OPUS_BITRATE: i32 = 24_000;
+/// Largest packet libopus will produce.
+const OPUS_MAX_PACKET: usize = 4000;
+const OPUS_SERIAL: u32 = 1;
+
+fn encode_opus(sample_rate: u32, samples: &[f32]) -> Result<Vec<u8>, String> {
+    let (rate, samples) = if OPUS_RATES.contains(&sample_rate) {
+        (sample_rate, Cow::Borrowed(samples))
+    } else {
+        let resampled = resample(samples, sample_rate, OPUS_FALLBACK_RATE)?;
+        (OPUS_FALLBACK_RATE, Cow::Owned(resampled))
+    };
+    let opus_err = |e: opus::Error| format!("Opus encoding failed: {}", e);
+
+    let mut encoder = opus::Encoder::new(rate, opus::Channels::Mono, opus::Application::Voip)
+        .map_err(opus_err)?;
+    encoder
+        .set_bitrate(opus::Bitrate::Bits(OPUS_BITRATE))
+        .map_err(opus_err)?;
+    let lookahead = encoder.get_lookahead().map_err(opus_err)? as usize;
+    let scale = (OPUS_GRANULE_RATE / rate) as u64;
+    let pre_skip = lookahead as u64 * scale;
+    let end_granule = pre_skip + samples.len() as u64 * scale;
+
+    // Pad by the encoder's lookahead so the last real samples come out
+    let frame_len = (rate * OPUS_FRAME_MS / 1000) as usize;
+    let mut padded = samples.into_owned();
+    padded.resize(padded.len() + lookahead, 0.0);
+    let frames = padded.len().div_ceil(frame_len);
+    padded.resize(frames * frame_len, 0.0);
+
+    let mut bytes = Vec::new();
+    {
+        let mut writer = PacketWriter::new(&mut bytes);
+        let write_err = |e: std::io::Error| format!("Failed to write Ogg page: {}", e);
+        writer
+            .write_packet(
+                opus_head(rate, pre_skip as u16),
+                OPUS_SERIAL,
+                PacketWriteEndInfo::EndPage,
+                0,
+            )
+            .map_err(write_err)?;
+        writer
+            .write_packet(opus_tags(), OPUS_SERIAL, PacketWriteEndInfo::EndPage, 0)
+            .map_err(write_err)?;
+
+        let mut packet = vec![0u8; OPUS_MAX_PACKET];
+        for (i, frame) in padded.chunks(frame_len).enumerate() {
+            let len = encoder.encode_float(frame, &mut packet).map_err(opus_err)?;
+            let last = i + 1 == frames;
+            // The last page's granule trims the padding off the end
+            let granule = (pre_skip + ((i + 1) * frame_len) as u64 * scale).min(end_granule);
+            let info = if last {
+                PacketWriteEndInfo::EndStream
+            } else {
+                PacketWriteEndInfo::NormalPacket
+            };
+            writer
+                .write_packet(packet[..len].to_vec(), OPUS_SERIAL, info, granule)
+                .map_err(write_err)?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Identification header (RFC 7845, section 5.1), mono, channel mapping 0.
+fn opus_head(input_rate: u32, pre_skip: u16) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // Version
+    head.push(1); // Channels
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+    head.push(0);
+    head
+}
+
+/// Comment header with just the vendor string.
+fn opus_tags() -> Vec<u8> {
+    let vendor = env!("CARGO_PKG_NAME").as_bytes();
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes()); // No user comments
+    tags
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// Voiced-speech stand-in: harmonics on a gliding pitch, with pauses.
+    fn speech(sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                if (t * 3.0) as usize % 3 == 2 {
+                    return 0.0;
+                }
+                let pitch = 150.0 + 50.0 * (2.0 * PI * 0.5 * t).sin();
+                (1..=6)
+                    .map(|h| 0.2 / h as f32 * (2.0 * PI * pitch * h as f32 * t).sin())
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Decodes with an independent FLAC decoder, which checks every CRC.
+    fn decode_flac(bytes: &[u8]) -> (u32, Vec<i32>) {
+        let mut reader = claxon::FlacReader::new(Cursor::new(bytes)).unwrap();
+        let info = reader.streaminfo();
+        assert_eq!(info.channels, 1);
+        assert_eq!(info.bits_per_sample, 16);
+        let samples: Vec<i32> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(Some(samples.len() as u64), info.samples);
+        (info.sample_rate, samples)
+    }
+
+    #[test]
+    fn flac_round_trips_losslessly_and_is_smaller_than_wav() {
+        let rate = 44_100;
+        // Several frames (past the 1-byte frame number range), a short last
+        // block, silence for constant subframes and noise-like content.
+        let mut audio = speech(rate, 130 * FLAC_BLOCK_SIZE + 1234);
+        audio[..FLAC_BLOCK_SIZE].fill(0.0);
+        let mut seed = 1u32;
+        for sample in &mut audio[FLAC_BLOCK_SIZE..2 * FLAC_BLOCK_SIZE] {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            *sample = (seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0;
+        }
+
+        let flac = encode(AudioEncoding::Flac, rate, &audio).unwrap();
+        let (decoded_rate, decoded) = decode_flac(&flac);
+        assert_eq!(decoded_rate, rate);
+        let expected: Vec<i32> = audio.iter().map(|&s| to_i16(s) as i32).collect();
+        assert_eq!(decoded, expected);
+
+        let wav = encode(AudioEncoding::Wav, rate, &audio).unwrap();
+        assert!(
+            flac.len() * 2 < wav.len(),
+            "FLAC {} bytes, WAV {} bytes",
+            flac.len(),
+            wav.len()
+        );
+    }
+
+    #[test]
+    fn flac_handles_tiny_inputs() {
+        for audio in [vec![0.5], vec![0.1, -0.2, 0.3], speech(16_000, 17)] {
+            let flac = encode(AudioEncoding::Flac, 16_000, &audio).unwrap();
+            let (_, decoded) = decode_flac(&flac);
+            let expected: Vec<i32> = audio.iter().map(|&s| to_i16(s) as i32).collect();
+            assert_eq!(decoded, expected);
+        }
+    }
+
+    #[test]
+    fn opus_round_trips_within_tolerance() {
+        let rate = 16_000;
+        let audio = speech(rate, 3 * rate as usize);
+        let ogg = encode(AudioEncoding::Opus, rate, &audio).unwrap();
+        let wav = encode(AudioEncoding::Wav, rate, &audio).unwrap();
+        assert!(
+            ogg.len() * 5 < wav.len(),
+            "Opus {} bytes, WAV {} bytes",
+            ogg.len(),
+            wav.len()
+        );
+
+        let mut reader = ogg::PacketReader::new(Cursor::new(ogg));
+        let head = reader.read_packet().unwrap().unwrap();
+        assert_eq!(&head.data[..8], b"OpusHead");
+        let pre_skip = u16::from_le_bytes([head.data[10], head.data[11]]) as u64;
+        let tags = reader.read_packet().unwrap().unwrap();
+        assert_eq!(&tags.data[..8], b"OpusTags");
+
+        let mut decoder = opus::Decoder::new(rate, opus::Channels::Mono).unwrap();
+        let mut buffer = vec![0.0f32; 5760];
+        let mut decoded = Vec::new();
+        let mut end_granule = 0;
+        while let Some(packet) = reader.read_packet().unwrap() {
+            let n = decoder
+                .decode_float(&packet.data, &mut buffer, false)
+                .unwrap();
+            decoded.extend_from_slice(&buffer[..n]);
+            end_granule = packet.absgp_page();
+        }
+        let scale = (OPUS_GRANULE_RATE / rate) as u64;
+        let skip = (pre_skip / scale) as usize;
+        let len = ((end_granule - pre_skip) / scale) as usize;
+        assert_eq!(len, audio.len());
+        let decoded = &decoded[skip..skip + len];
+
+        // Lossy, and SILK doesn't keep phase, so compare loudness over time
+        let frame = (rate / 50) as usize;
+        let envelope = |s: &[f32]| -> Vec<f32> {
+            s.chunks(frame)
+                .map(|f| (f.iter().map(|x| x * x).sum::<f32>() / f.len() as f32).sqrt())
+                .collect()
+        };
+        let (original, output) = (envelope(&audio), envelope(decoded));
+        let dot = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+        let level_db = 10.0 * (dot(&output, &output) / dot(&original, &original)).log10();
+        let correlation =
+            dot(&original, &output) / (dot(&original, &original) * dot(&output, &output)).sqrt();
+        assert!(level_db.abs() < 1.5, "level changed by {:.1} dB", level_db);
+        assert!(
+            correlation > 0.95,
+            "envelope correlation {:.3}",
+            correlation
+        );
+    }
+}
@@ -236,6 +236,16 @@ impl SpeakerInput {
         Ok(Self { source_name })
     }
 
+    // For a microphone, device_id is the PulseAudio source name itself
+    pub fn microphone(device_id: Option<String>) -> Result<Self> {
+        let source_name = super::microphone::requested_device(device_id.as_deref())
+            .unwrap_or("@DEFAULT_SOURCE@")
+            .to_string();
+        Ok(Self {
+            source_name: Some(source_name),
+        })
+    }
+
     pub fn stream(self) -> SpeakerStream {
         let sample_queue = Arc::new(Mutex::new(VecDeque::new()));
         let waker_state = Arc::new(Mutex::new(WakerState {
@@ -1,4 +1,5 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 use serde::{Serialize, Deserialize};
 
@@ -33,8 +34,20 @@ pub struct WhisperStatus {
     pub model_path: Option<String>,
 }
 
+/// A stretch of transcript with its position in the audio.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhisperSegment {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub text: String,
+}
+
+/// Cloning is cheap and shares the loaded model, so callers clone the engine
+/// out of [`crate::WhisperState`] and run inference without holding the lock.
+#[derive(Clone)]
 pub struct WhisperEngine {
-    context: Option<WhisperContext>,
+    context: Option<Arc<WhisperContext>>,
     model_name: Option<String>,
     model_path: Option<PathBuf>,
 }
@@ -62,13 +75,44 @@ impl WhisperEngine {
             .unwrap_or("unknown")
             .to_string();
 
-        self.context = Some(ctx);
+        self.context = Some(Arc::new(ctx));
         self.model_name = Some(model_name);
         self.model_path = Some(model_path);
         Ok(())
     }
 
     pub fn transcribe(&self, audio_f32: &[f32], sample_rate: u32) -> Result<String, String> {
+        let text: String = self
+            .run(audio_f32, sample_rate, true)?
+            .into_iter()
+            .map(|segment| segment.text)
+            .collect();
+        Ok(text.trim().to_string())
+    }
+
+    /// Transcribe a longer recording, keeping Whisper's own segmentation.
+    pub fn transcribe_segments(
+        &self,
+        audio_f32: &[f32],
+        sample_rate: u32,
+    ) -> Result<Vec<WhisperSegment>, String> {
+        Ok(self
+            .run(audio_f32, sample_rate, false)?
+            .into_iter()
+            .map(|segment| WhisperSegment {
+                text: segment.text.trim().to_string(),
+                ..segment
+            })
+            .filter(|segment| !segment.text.is_empty())
+            .collect())
+    }
+
+    fn run(
+        &self,
+        audio_f32: &[f32],
+        sample_rate: u32,
+        single_segment: bool,
+    ) -> Result<Vec<WhisperSegment>, String> {
         if sample_rate != 16000 {
             return Err(format!(
                 "Unsupported sample rate {}; Whisper requires 16000 Hz",
@@ -86,7 +130,7 @@ impl WhisperEngine {
         params.set_print_progress(false);
         params.set_print_realtime(false);
         params.set_print_timestamps(false);
-        params.set_single_segment(true);
+        params.set_single_segment(single_segment);
         params.set_no_context(true);
 
         state
@@ -96,14 +140,21 @@ impl WhisperEngine {
         let num_segments = state
             .full_n_segments()
             .map_err(|e| format!("Failed to get segments: {}", e))?;
-        let mut text = String::new();
+        let mut segments = Vec::new();
         for i in 0..num_segments {
-            if let Ok(segment) = state.full_get_segment_text(i) {
-                text.push_str(&segment);
+            if let Ok(text) = state.full_get_segment_text(i) {
+                // Timestamps are in 10 ms units
+                let start = state.full_get_segment_t0(i).unwrap_or(0);
+                let end = state.full_get_segment_t1(i).unwrap_or(start);
+                segments.push(WhisperSegment {
+                    start_secs: start as f32 / 100.0,
+                    end_secs: end as f32 / 100.0,
+                    text,
+                });
             }
         }
 
-        Ok(text.trim().to_string())
+        Ok(segments)
     }
 
     pub fn status(&self) -> WhisperStatus {
@@ -118,42 +169,106 @@ impl WhisperEngine {
     }
 }
 
+use crate::speaker::resample::{wav_to_whisper_input, WHISPER_SAMPLE_RATE};
 use tauri::{AppHandle, Manager};
 
+/// Resolve a model argument: a bare file name refers to a model in the
+/// managed `models/` directory, anything else is used as given.
+fn resolve_model_path(models_dir: &Path, model: &str) -> PathBuf {
+    let path = Path::new(model);
+    if path.is_relative() && path.components().count() == 1 {
+        models_dir.join(path)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Load a Whisper model, either by file name from the managed `models/`
+/// directory (see [`download_local_whisper_model`]) or from any path.
 #[tauri::command]
 pub async fn init_local_whisper(app: AppHandle, model_path: String) -> Result<(), String> {
+    let models_dir = crate::data_dir::current_layout(&app)?.models_dir();
+    let model_path = resolve_model_path(&models_dir, &model_path);
     let state = app.state::<crate::WhisperState>();
     let mut slot = state.engine.lock();
     let engine = slot.get_or_insert_with(WhisperEngine::new);
-    engine.init(PathBuf::from(model_path))
+    engine.init(model_path)
+}
+
+/// Download `model` into the managed `models/` directory, where it moves
+/// with the data directory and is listed in backups. Returns the file path;
+/// a model that is already present is not downloaded again.
+#[tauri::command]
+pub async fn download_local_whisper_model(
+    app: AppHandle,
+    model: WhisperModel,
+) -> Result<String, String> {
+    let models_dir = crate::data_dir::current_layout(&app)?.models_dir();
+    let dest = models_dir.join(model.filename());
+    if !dest.is_file() {
+        std::fs::create_dir_all(&models_dir)
+            .map_err(|e| format!("Failed to create {}: {}", models_dir.display(), e))?;
+        // Download next to the target and rename, so an interrupted download
+        // never looks like a complete model.
+        let partial = dest.with_extension("bin.part");
+        if let Err(e) = download_to(&model.download_url(), &partial).await {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&partial, &dest)
+            .await
+            .map_err(|e| format!("Failed to save {}: {}", dest.display(), e))?;
+    }
+    Ok(dest.to_string_lossy().to_string())
+}
+
+async fn download_to(url: &str, path: &Path) -> Result<(), String> {
+    use futures_util::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    let response = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| format!("Failed to download {}: {}", url, e))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// A clone of the initialized engine, taken under the [`crate::WhisperState`]
+/// lock and released before the caller runs inference.
+pub fn loaded_engine(app: &AppHandle) -> Result<WhisperEngine, String> {
+    let state = app.state::<crate::WhisperState>();
+    let engine = state.engine.lock().clone();
+    engine
+        .ok_or_else(|| "Whisper engine not initialized; call init_local_whisper first".to_string())
 }
 
 #[tauri::command]
 pub async fn transcribe_local(app: AppHandle, audio_b64: String) -> Result<String, String> {
     use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 
-    // Decode base64 and WAV before acquiring the lock so we don't hold the
-    // mutex across expensive CPU-bound work.
+    // Decode base64 and WAV, and convert to 16 kHz mono, before acquiring
+    // the lock so we don't hold the mutex across expensive CPU-bound work.
     let wav_bytes = B64
         .decode(&audio_b64)
         .map_err(|e| format!("Base64 decode error: {}", e))?;
-    let reader = hound::WavReader::new(std::io::Cursor::new(wav_bytes))
-        .map_err(|e| format!("WAV decode error: {}", e))?;
-    let sample_rate = reader.spec().sample_rate;
-    let samples: Vec<f32> = reader
-        .into_samples::<i16>()
-        .filter_map(|s| s.ok())
-        .map(|s| s as f32 / i16::MAX as f32)
-        .collect();
-
-    // Acquire lock only to call transcribe (which does the heavy Whisper work).
-    // parking_lot::Mutex doesn't poison, so no unwrap/map_err needed.
-    let state = app.state::<crate::WhisperState>();
-    let slot = state.engine.lock();
-    let engine = slot
-        .as_ref()
-        .ok_or("Whisper engine not initialized; call init_local_whisper first")?;
-    engine.transcribe(&samples, sample_rate)
+    let samples = wav_to_whisper_input(std::io::Cursor::new(wav_bytes))?;
+
+    // Hold the lock only long enough to clone the engine; inference runs
+    // without it so other transcriptions are not serialized behind this one.
+    let engine = loaded_engine(&app)?;
+    engine.transcribe(&samples, WHISPER_SAMPLE_RATE)
 }
 
 #[tauri::command]
@@ -0,0 +1,315 @@
+// Microphone input as a stream of mono f32 samples, for meeting capture.
+//
+// Device ids are the ones returned by `get_input_devices`. On Linux they are
+// PulseAudio source names and the PulseAudio capture loop is reused. On other
+// platforms the device is opened through cpal, matched by its display name.
+use anyhow::Result;
+use futures_util::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(not(target_os = "linux"))]
+use anyhow::anyhow;
+#[cfg(not(target_os = "linux"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(target_os = "linux"))]
+use std::sync::Arc;
+#[cfg(any(test, not(target_os = "linux")))]
+use tokio::sync::mpsc;
+#[cfg(not(target_os = "linux"))]
+use tracing::{error, warn};
+
+// The explicitly chosen device, or `None` for the system default.
+pub(crate) fn requested_device(device_id: Option<&str>) -> Option<&str> {
+    device_id.filter(|id| !id.is_empty() && *id != "default")
+}
+
+// Average interleaved frames down to mono.
+#[cfg(any(test, not(target_os = "linux")))]
+fn downmix(interleaved: &[f32], channels: usize) -> Vec<f32> {
+    interleaved
+        .chunks(channels)
+        .map(|f| f.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+#[cfg(any(test, not(target_os = "linux")))]
+fn i16_sample(s: i16) -> f32 {
+    s as f32 / i16::MAX as f32
+}
+
+#[cfg(any(test, not(target_os = "linux")))]
+fn u16_sample(s: u16) -> f32 {
+    (s as f32 - 32768.0) / 32768.0
+}
+
+// Display name of the input device with `id`; cpal only knows devices by name.
+#[cfg(any(test, not(target_os = "linux")))]
+fn find_device_name(devices: Vec<super::AudioDevice>, id: &str) -> Option<String> {
+    devices.into_iter().find(|d| d.id == id).map(|d| d.name)
+}
+
+// Flattens the sample blocks sent by a capture thread back into samples.
+#[cfg(any(test, not(target_os = "linux")))]
+struct BlockStream {
+    receiver: mpsc::Receiver<Vec<f32>>,
+    current: std::vec::IntoIter<f32>,
+}
+
+#[cfg(any(test, not(target_os = "linux")))]
+impl BlockStream {
+    fn new(receiver: mpsc::Receiver<Vec<f32>>) -> Self {
+        Self {
+            receiver,
+            current: Vec::new().into_iter(),
+        }
+    }
+}
+
+#[cfg(any(test, not(target_os = "linux")))]
+impl Stream for BlockStream {
+    type Item = f32;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<f32>> {
+        loop {
+            if let Some(sample) = self.current.next() {
+                return Poll::Ready(Some(sample));
+            }
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(block)) => self.current = block.into_iter(),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pub struct MicrophoneStream {
+    #[cfg(target_os = "linux")]
+    inner: super::linux::SpeakerStream,
+    #[cfg(not(target_os = "linux"))]
+    inner: CpalStream,
+}
+
+impl MicrophoneStream {
+    pub fn open(device_id: Option<String>) -> Result<Self> {
+        #[cfg(target_os = "linux")]
+        let inner = super::linux::SpeakerInput::microphone(device_id)?.stream();
+        #[cfg(not(target_os = "linux"))]
+        let inner = CpalStream::open(device_id)?;
+        Ok(Self { inner })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+}
+
+impl Stream for MicrophoneStream {
+    type Item = f32;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<f32>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+// cpal streams are not Send on every platform, so each one lives on its own
+// thread and hands sample blocks over a bounded channel.
+#[cfg(not(target_os = "linux"))]
+struct CpalStream {
+    blocks: BlockStream,
+    shutdown: Arc<AtomicBool>,
+    capture_thread: Option<std::thread::JoinHandle<()>>,
+    sample_rate: u32,
+}
+
+#[cfg(not(target_os = "linux"))]
+impl CpalStream {
+    fn open(device_id: Option<String>) -> Result<Self> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        // cpal does not expose the platform ids, so go through the name.
+        let device_name = match requested_device(device_id.as_deref()) {
+            Some(id) => find_device_name(super::list_input_devices()?, id),
+            None => None,
+        };
+
+        let (block_tx, receiver) = mpsc::channel::<Vec<f32>>(64);
+        let (init_tx, init_rx) = std::sync::mpsc::channel::<Result<u32>>();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_flag = shutdown.clone();
+
+        let capture_thread = std::thread::spawn(move || {
+            let init = (|| -> Result<(cpal::Stream, u32)> {
+                let host = cpal::default_host();
+                let device = match &device_name {
+                    Some(name) => host
+                        .input_devices()?
+                        .find(|d| d.name().map(|n| &n == name).unwrap_or(false)),
+                    None => None,
+                }
+                .or_else(|| host.default_input_device())
+                .ok_or_else(|| anyhow!("No microphone available"))?;
+
+                let supported = device.default_input_config()?;
+                let sample_rate = supported.sample_rate().0;
+                let channels = supported.channels() as usize;
+                let config: cpal::StreamConfig = supported.config();
+
+                let push = move |mono: Vec<f32>| {
+                    if block_tx.try_send(mono).is_err() {
+                        warn!("Microphone buffer full - dropping samples");
+                    }
+                };
+                let mono = move |frames: &mut dyn Iterator<Item = f32>| -> Vec<f32> {
+                    downmix(&frames.collect::<Vec<f32>>(), channels)
+                };
+                let on_error = |e: cpal::StreamError| error!("Microphone stream error: {}", e);
+
+                let stream = match supported.sample_format() {
+                    cpal::SampleFormat::F32 => device.build_input_stream(
+                        &config,
+                        move |data: &[f32], _: &_| push(mono(&mut data.iter().copied())),
+                        on_error,
+                        None,
+                    )?,
+                    cpal::SampleFormat::I16 => device.build_input_stream(
+                        &config,
+                        move |data: &[i16], _: &_| {
+                            push(mono(&mut data.iter().map(|&s| i16_sample(s))))
+                        },
+                        on_error,
+                        None,
+                    )?,
+                    cpal::SampleFormat::U16 => device.build_input_stream(
+                        &config,
+                        move |data: &[u16], _: &_| {
+                            push(mono(&mut data.iter().map(|&s| u16_sample(s))))
+                        },
+                        on_error,
+                        None,
+                    )?,
+                    other => return Err(anyhow!("Unsupported microphone format: {:?}", other)),
+                };
+                stream.play()?;
+                Ok((stream, sample_rate))
+            })();
+
+            match init {
+                Ok((stream, sample_rate)) => {
+                    let _ = init_tx.send(Ok(sample_rate));
+                    while !shutdown_flag.load(Ordering::Acquire) {
+                        std::thread::sleep(std::time::Duration::from_millis(50));
+                    }
+                    drop(stream);
+                }
+                Err(e) => {
+                    let _ = init_tx.send(Err(e));
+                }
+            }
+        });
+
+        let sample_rate = match init_rx.recv() {
+            Ok(Ok(sample_rate)) => sample_rate,
+            Ok(Err(e)) => {
+                let _ = capture_thread.join();
+                return Err(e);
+            }
+            Err(_) => {
+                let _ = capture_thread.join();
+                return Err(anyhow!("Microphone thread exited during setup"));
+            }
+        };
+
+        Ok(Self {
+            blocks: BlockStream::new(receiver),
+            shutdown,
+            capture_thread: Some(capture_thread),
+            sample_rate,
+        })
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Stream for CpalStream {
+    type Item = f32;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<f32>> {
+        Pin::new(&mut self.blocks).poll_next(cx)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Drop for CpalStream {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Release);
+        if let Some(thread) = self.capture_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speaker::AudioDevice;
+    use futures_util::StreamExt;
+
+    fn device(id: &str, name: &str) -> AudioDevice {
+        AudioDevice {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_default: false,
+        }
+    }
+
+    #[test]
+    fn default_and_empty_ids_mean_the_system_default() {
+        assert_eq!(requested_device(None), None);
+        assert_eq!(requested_device(Some("")), None);
+        assert_eq!(requested_device(Some("default")), None);
+        assert_eq!(
+            requested_device(Some("alsa_input.usb")),
+            Some("alsa_input.usb")
+        );
+    }
+
+    #[test]
+    fn resolves_device_ids_to_names() {
+        let devices = || vec![device("a", "Built-in"), device("b", "USB Headset")];
+        assert_eq!(find_device_name(devices(), "b").as_deref(), Some("USB Headset"));
+        assert_eq!(find_device_name(devices(), "missing"), None);
+    }
+
+    #[test]
+    fn downmixes_interleaved_frames() {
+        assert_eq!(downmix(&[0.2, 0.4, -1.0, 1.0], 2), vec![0.3, 0.0]);
+        assert_eq!(downmix(&[0.5, -0.5], 1), vec![0.5, -0.5]);
+    }
+
+    #[test]
+    fn converts_integer_samples_to_unit_range() {
+        assert_eq!(i16_sample(i16::MAX), 1.0);
+        assert_eq!(i16_sample(0), 0.0);
+        assert!((i16_sample(i16::MIN) + 1.0).abs() < 1e-4);
+        assert_eq!(u16_sample(32768), 0.0);
+        assert_eq!(u16_sample(0), -1.0);
+        assert!((u16_sample(u16::MAX) - 1.0).abs() < 1e-4);
+    }
+
+    #[tokio::test]
+    async fn block_stream_yields_samples_in_order_until_the_sender_closes() {
+        let (sender, receiver) = mpsc::channel(4);
+        sender.send(vec![1.0, 2.0]).await.unwrap();
+        sender.send(Vec::new()).await.unwrap();
+        sender.send(vec![3.0]).await.unwrap();
+        drop(sender);
+
+        let samples: Vec<f32> = BlockStream::new(receiver).collect().await;
+        assert_eq!(samples, vec![1.0, 2.0, 3.0]);
+    }
+}
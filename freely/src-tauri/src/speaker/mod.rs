@@ -18,13 +18,31 @@ mod linux;
 #[cfg(target_os = "linux")]
 use linux::{SpeakerInput as PlatformSpeakerInput, SpeakerStream as PlatformSpeakerStream};
 
+pub mod aec;
 mod commands;
+pub mod denoise;
+pub mod diarize;
+pub mod encode;
 pub mod local_whisper;
+mod microphone;
+pub mod recording;
+pub mod resample;
+pub mod streaming;
+pub mod vad;
 
 // Re-export commands for tauri handler
 pub use commands::*;
 pub use local_whisper::*;
 
+// Who an utterance came from in meeting capture: the microphone is "me",
+// system audio is "them".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Speaker {
+    Me,
+    Them,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioDevice {
     pub id: String,
@@ -0,0 +1,286 @@
+//! Continuous recording spilled to disk in fixed-length segments.
+//!
+//! Long recordings used to live in one in-memory buffer sized for the
+//! maximum duration. [`SegmentedRecording`] keeps at most one segment of
+//! samples in memory: every [`SEGMENT_SECS`] it writes the segment to its
+//! session directory as a 32-bit float WAV and hands the samples back so
+//! they can be sent for transcription right away. When recording stops,
+//! [`SegmentedRecording::assemble`] streams the segments into one 16-bit
+//! `recording.wav` and removes them. A recording dropped before that, because
+//! capture was aborted or ignored, deletes its session directory.
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Length of each segment file.
+pub const SEGMENT_SECS: u32 = 30;
+/// The assembled recording, in the session directory.
+const RECORDING_FILE: &str = "recording.wav";
+
+/// A segment as it is emitted for incremental transcription.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSegment {
+    pub index: usize,
+    pub start_secs: f32,
+    pub duration_secs: f32,
+    /// Base64 WAV.
+    pub audio: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedRecording {
+    pub path: String,
+    pub duration_secs: f32,
+    pub segments: usize,
+}
+
+/// A segment that was just written to disk.
+pub struct CompletedSegment {
+    pub index: usize,
+    pub start_secs: f32,
+    pub samples: Vec<f32>,
+}
+
+pub struct SegmentedRecording {
+    dir: PathBuf,
+    sample_rate: u32,
+    segment_len: usize,
+    current: Vec<f32>,
+    segments: Vec<PathBuf>,
+    /// Samples in closed segments.
+    written: usize,
+    sum_squares: f64,
+    /// Set once assembled; until then dropping removes the session directory.
+    keep: bool,
+}
+
+impl SegmentedRecording {
+    pub fn create(dir: PathBuf, sample_rate: u32, segment_secs: u32) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let segment_len = (sample_rate as usize * segment_secs as usize).max(1);
+        Ok(Self {
+            dir,
+            sample_rate,
+            segment_len,
+            current: Vec::with_capacity(segment_len),
+            segments: Vec::new(),
+            written: 0,
+            sum_squares: 0.0,
+            keep: false,
+        })
+    }
+
+    /// Samples recorded so far, including the open segment.
+    pub fn len(&self) -> usize {
+        self.written + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// RMS level of everything recorded so far.
+    pub fn rms(&self) -> f32 {
+        let open: f64 = self.current.iter().map(|&s| s as f64 * s as f64).sum();
+        match self.len() {
+            0 => 0.0,
+            len => ((self.sum_squares + open) / len as f64).sqrt() as f32,
+        }
+    }
+
+    /// Append samples. Returns the segments this completed.
+    pub fn push(&mut self, samples: &[f32]) -> Result<Vec<CompletedSegment>, String> {
+        let mut completed = Vec::new();
+        let mut rest = samples;
+        while !rest.is_empty() {
+            let take = (self.segment_len - self.current.len()).min(rest.len());
+            self.current.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+            if self.current.len() == self.segment_len {
+                completed.push(self.close_segment()?);
+            }
+        }
+        Ok(completed)
+    }
+
+    /// Write out the open segment, if it has any samples.
+    pub fn close(&mut self) -> Result<Option<CompletedSegment>, String> {
+        if self.current.is_empty() {
+            return Ok(None);
+        }
+        self.close_segment().map(Some)
+    }
+
+    fn close_segment(&mut self) -> Result<CompletedSegment, String> {
+        let index = self.segments.len();
+        let path = self.dir.join(format!("segment-{:05}.wav", index));
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let write = || -> Result<(), hound::Error> {
+            let mut writer = WavWriter::create(&path, spec)?;
+            for &sample in &self.current {
+                writer.write_sample(sample)?;
+            }
+            writer.finalize()
+        };
+        write().map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        let samples = std::mem::replace(&mut self.current, Vec::with_capacity(self.segment_len));
+        let start_secs = self.written as f32 / self.sample_rate as f32;
+        self.written += samples.len();
+        self.sum_squares += samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>();
+        self.segments.push(path);
+        Ok(CompletedSegment {
+            index,
+            start_secs,
+            samples,
+        })
+    }
+
+    /// Join the closed segments into one 16-bit WAV, passing every sample
+    /// through `map`, and delete the segment files. The session directory is
+    /// kept from then on.
+    pub fn assemble(&mut self, mut map: impl FnMut(f32) -> f32) -> Result<SavedRecording, String> {
+        let path = self.dir.join(RECORDING_FILE);
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&path, spec)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        for segment in &self.segments {
+            let reader = WavReader::open(segment)
+                .map_err(|e| format!("Failed to read {}: {}", segment.display(), e))?;
+            for sample in reader.into_samples::<f32>() {
+                let sample =
+                    sample.map_err(|e| format!("Failed to read {}: {}", segment.display(), e))?;
+                let value = (map(sample).clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                writer
+                    .write_sample(value)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            }
+        }
+        writer
+            .finalize()
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        for segment in &self.segments {
+            let _ = std::fs::remove_file(segment);
+        }
+        self.keep = true;
+        Ok(SavedRecording {
+            path: path.to_string_lossy().to_string(),
+            duration_secs: self.written as f32 / self.sample_rate as f32,
+            segments: self.segments.len(),
+        })
+    }
+}
+
+impl Drop for SegmentedRecording {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(len: usize) -> Vec<f32> {
+        (0..len).map(|i| (i % 200) as f32 / 400.0 - 0.25).collect()
+    }
+
+    #[test]
+    fn splits_into_segments_and_reassembles_in_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("session");
+        let mut recording = SegmentedRecording::create(dir.clone(), 100, 2).unwrap();
+        let audio = ramp(530);
+
+        let mut completed = Vec::new();
+        for block in audio.chunks(70) {
+            completed.extend(recording.push(block).unwrap());
+        }
+        // Only the open segment is held in memory.
+        assert!(recording.current.len() < 200);
+        completed.extend(recording.close().unwrap());
+        let starts: Vec<(usize, f32, usize)> = completed
+            .iter()
+            .map(|s| (s.index, s.start_secs, s.samples.len()))
+            .collect();
+        assert_eq!(starts, vec![(0, 0.0, 200), (1, 2.0, 200), (2, 4.0, 130)]);
+        assert_eq!(recording.len(), 530);
+
+        let saved = recording.assemble(|s| s * 2.0).unwrap();
+        assert_eq!(saved.segments, 3);
+        assert_eq!(saved.duration_secs, 5.3);
+        let samples: Vec<i16> = WavReader::open(&saved.path)
+            .unwrap()
+            .into_samples::<i16>()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(samples.len(), audio.len());
+        for (written, original) in samples.iter().zip(&audio) {
+            let expected = (original * 2.0 * i16::MAX as f32) as i16;
+            assert!((written - expected).abs() <= 1);
+        }
+        // Segment files are gone, the recording stays.
+        drop(recording);
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn dropping_before_assembly_removes_the_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("session");
+        let mut recording = SegmentedRecording::create(dir.clone(), 100, 1).unwrap();
+        assert_eq!(recording.push(&ramp(250)).unwrap().len(), 2);
+        assert!(dir.join("segment-00001.wav").is_file());
+
+        drop(recording);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn failed_assembly_removes_the_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("session");
+        let mut recording = SegmentedRecording::create(dir.clone(), 100, 1).unwrap();
+        recording.push(&ramp(150)).unwrap();
+        std::fs::remove_file(dir.join("segment-00000.wav")).unwrap();
+
+        assert!(recording.assemble(|s| s).is_err());
+        drop(recording);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn tracks_level_across_segments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut recording = SegmentedRecording::create(tmp.path().to_path_buf(), 10, 1).unwrap();
+        assert!(recording.is_empty());
+        assert_eq!(recording.rms(), 0.0);
+        recording.push(&[0.5; 25]).unwrap();
+        recording.push(&[-0.5; 3]).unwrap();
+        assert!((recording.rms() - 0.5).abs() < 1e-6);
+        assert!(recording.close().unwrap().is_some());
+        assert!(recording.close().unwrap().is_none());
+    }
+}
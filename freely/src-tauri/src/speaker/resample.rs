@@ -0,0 +1,349 @@
+//! Sample-rate conversion and WAV decoding for local transcription.
+//!
+//! Whisper only accepts 16 kHz mono, while capture runs at whatever rate the
+//! device uses (44.1 or 48 kHz in practice) and uploaded files can be any
+//! rate, channel count or sample format. [`Resampler`] is a polyphase
+//! windowed-sinc converter: a Kaiser-windowed low-pass at the lower of the
+//! two Nyquist frequencies, evaluated at each output instant. Filter phases
+//! are precomputed when the rate ratio is small enough to make that cheap.
+
+use std::io::Read;
+
+/// Sample rate Whisper expects.
+pub const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Zero crossings of the sinc on each side of the centre tap.
+const ZERO_CROSSINGS: usize = 32;
+/// Cutoff as a fraction of the lower Nyquist frequency, leaving room for the
+/// transition band.
+const ROLLOFF: f64 = 0.94;
+/// Kaiser window shape; about 90 dB of stopband attenuation.
+const KAISER_BETA: f64 = 8.6;
+/// Largest number of filter phases precomputed.
+const MAX_TABLE_PHASES: u64 = 1024;
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind.
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term, mut k) = (1.0, 1.0, 1.0);
+    while term > sum * 1e-12 {
+        term *= (x / (2.0 * k)).powi(2);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Streaming converter between two sample rates. Feed input with
+/// [`Resampler::process`] and call [`Resampler::flush`] at the end to drain
+/// the filter's look-ahead.
+pub struct Resampler {
+    /// Output samples per `down` input samples, reduced by their gcd.
+    up: u64,
+    down: u64,
+    /// Input samples on each side of an output instant that contribute to it.
+    half_taps: usize,
+    cutoff: f64,
+    /// `up` rows of `2 * half_taps` coefficients, when small enough to keep.
+    table: Option<Vec<Vec<f32>>>,
+    /// Input not yet fully consumed; `history[0]` is input sample `start`.
+    history: Vec<f32>,
+    start: u64,
+    consumed: u64,
+    /// Index of the next output sample.
+    next: u64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Result<Self, String> {
+        if from_rate == 0 || to_rate == 0 {
+            return Err(format!(
+                "Invalid sample rates: {} Hz to {} Hz",
+                from_rate, to_rate
+            ));
+        }
+        let g = gcd(from_rate as u64, to_rate as u64);
+        let (up, down) = (to_rate as u64 / g, from_rate as u64 / g);
+        let cutoff = ROLLOFF * (to_rate as f64 / from_rate as f64).min(1.0);
+        let half_taps = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+
+        let mut resampler = Self {
+            up,
+            down,
+            half_taps,
+            cutoff,
+            table: None,
+            history: Vec::new(),
+            start: 0,
+            consumed: 0,
+            next: 0,
+        };
+        if up <= MAX_TABLE_PHASES {
+            resampler.table = Some((0..up).map(|p| resampler.coefficients(p)).collect());
+        }
+        Ok(resampler)
+    }
+
+    fn is_passthrough(&self) -> bool {
+        self.up == self.down
+    }
+
+    /// Filter taps for an output instant `phase / up` input samples past an
+    /// input sample, for inputs `-half_taps + 1 ..= half_taps` around it.
+    /// Normalized so every phase passes DC at unity gain.
+    fn coefficients(&self, phase: u64) -> Vec<f32> {
+        let frac = phase as f64 / self.up as f64;
+        let half = self.half_taps as f64;
+        let norm = bessel_i0(KAISER_BETA);
+        let taps: Vec<f64> = (0..2 * self.half_taps)
+            .map(|j| {
+                // Distance from the output instant to this input sample.
+                let x = (self.half_taps as f64 - 1.0 - j as f64) + frac;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    let a = std::f64::consts::PI * self.cutoff * x;
+                    a.sin() / a
+                };
+                let r = x / half;
+                let window = if r.abs() >= 1.0 {
+                    0.0
+                } else {
+                    bessel_i0(KAISER_BETA * (1.0 - r * r).sqrt()) / norm
+                };
+                sinc * window
+            })
+            .collect();
+        let sum: f64 = taps.iter().sum();
+        taps.into_iter().map(|t| (t / sum) as f32).collect()
+    }
+
+    /// Input sample at absolute index `index`; zero before the start and
+    /// after the end of the signal.
+    fn input(&self, index: i64) -> f32 {
+        if index < self.start as i64 {
+            return 0.0;
+        }
+        self.history
+            .get((index - self.start as i64) as usize)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Produce every output whose filter window is covered by the input so
+    /// far or, when `flushing`, every output before the end of the input.
+    fn drain(&mut self, flushing: bool, out: &mut Vec<f32>) {
+        let half = self.half_taps as i64;
+        let mut computed = None;
+        loop {
+            let position = self.next * self.down;
+            let center = (position / self.up) as i64;
+            let phase = position % self.up;
+            let ready = if flushing {
+                center < self.consumed as i64
+            } else {
+                center + half < self.consumed as i64
+            };
+            if !ready {
+                break;
+            }
+            let coefficients = match &self.table {
+                Some(table) => &table[phase as usize],
+                None => computed.insert(self.coefficients(phase)),
+            };
+            let first = center - half + 1;
+            let value = coefficients
+                .iter()
+                .enumerate()
+                .map(|(j, c)| c * self.input(first + j as i64))
+                .sum();
+            out.push(value);
+            self.next += 1;
+        }
+
+        // Keep only what the next output still needs.
+        let keep_from = ((self.next * self.down / self.up) as i64 - half + 1).max(0) as u64;
+        if keep_from > self.start {
+            let drop = ((keep_from - self.start) as usize).min(self.history.len());
+            self.history.drain(..drop);
+            self.start += drop as u64;
+        }
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.is_passthrough() {
+            return input.to_vec();
+        }
+        self.history.extend_from_slice(input);
+        self.consumed += input.len() as u64;
+        let mut out = Vec::with_capacity(input.len() * self.up as usize / self.down as usize + 1);
+        self.drain(false, &mut out);
+        out
+    }
+
+    /// Outputs still held back waiting for look-ahead input, computed as if
+    /// the signal were followed by silence.
+    pub fn flush(&mut self) -> Vec<f32> {
+        if self.is_passthrough() {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        self.drain(true, &mut out);
+        out
+    }
+}
+
+/// Convert a whole signal from `from_rate` to `to_rate`.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Result<Vec<f32>, String> {
+    let mut resampler = Resampler::new(from_rate, to_rate)?;
+    let mut out = resampler.process(samples);
+    out.extend(resampler.flush());
+    Ok(out)
+}
+
+/// Decode a WAV stream of any channel count and PCM format (8/16/24/32-bit
+/// integer or 32-bit float) into mono samples in -1.0..=1.0.
+pub fn decode_wav(reader: impl Read) -> Result<(u32, Vec<f32>), String> {
+    let mut reader =
+        hound::WavReader::new(reader).map_err(|e| format!("WAV decode error: {}", e))?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect()
+        }
+    }
+    .map_err(|e| format!("WAV decode error: {}", e))?;
+
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((spec.sample_rate, mono))
+}
+
+/// Decode a WAV file and convert it to 16 kHz mono for Whisper.
+pub fn wav_to_whisper_input(wav: impl Read) -> Result<Vec<f32>, String> {
+    let (sample_rate, samples) = decode_wav(wav)?;
+    resample(&samples, sample_rate, WHISPER_SAMPLE_RATE)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn sine(rate: u32, freq: f64, amplitude: f64, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (amplitude * (2.0 * PI * freq * i as f64 / rate as f64).sin()) as f32)
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        (samples.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn converts_tones_to_16k_with_low_distortion() {
+        for from in [44_100, 48_000, 22_050, 8_000] {
+            let input = sine(from, 1000.0, 0.5, from as usize);
+            let output = resample(&input, from, WHISPER_SAMPLE_RATE).unwrap();
+            assert_eq!(output.len(), WHISPER_SAMPLE_RATE as usize, "{} Hz", from);
+
+            // Compare against the ideal tone, away from the zero-padded edges.
+            let ideal = sine(WHISPER_SAMPLE_RATE, 1000.0, 0.5, output.len());
+            let body = 1000..output.len() - 1000;
+            let error: Vec<f32> = output[body.clone()]
+                .iter()
+                .zip(&ideal[body.clone()])
+                .map(|(a, b)| a - b)
+                .collect();
+            let snr_db = 20.0 * (rms(&ideal[body]) / rms(&error)).log10();
+            assert!(snr_db > 70.0, "{} Hz: SNR {:.1} dB", from, snr_db);
+        }
+    }
+
+    #[test]
+    fn rejects_content_above_the_new_nyquist() {
+        // 10 kHz cannot be represented at 16 kHz and must not alias to 6 kHz.
+        let input = sine(44_100, 10_000.0, 0.5, 44_100);
+        let output = resample(&input, 44_100, WHISPER_SAMPLE_RATE).unwrap();
+        let body = &output[1000..output.len() - 1000];
+        let attenuation_db = 20.0 * (rms(body) / (0.5 / 2f64.sqrt())).log10();
+        assert!(attenuation_db < -70.0, "{:.1} dB", attenuation_db);
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let input = sine(44_100, 440.0, 0.8, 10_000);
+        let whole = resample(&input, 44_100, WHISPER_SAMPLE_RATE).unwrap();
+        let mut resampler = Resampler::new(44_100, WHISPER_SAMPLE_RATE).unwrap();
+        let mut streamed = Vec::new();
+        for chunk in input.chunks(1024) {
+            streamed.extend(resampler.process(chunk));
+        }
+        streamed.extend(resampler.flush());
+        assert_eq!(streamed, whole);
+        assert_eq!(resample(&input, 16_000, 16_000).unwrap(), input);
+        assert!(Resampler::new(0, 16_000).is_err());
+    }
+
+    #[test]
+    fn decodes_stereo_and_wide_formats_to_mono() {
+        let encode = |bits: u16, format: hound::SampleFormat, frames: &[(f32, f32)]| {
+            let spec = hound::WavSpec {
+                channels: 2,
+                sample_rate: 48_000,
+                bits_per_sample: bits,
+                sample_format: format,
+            };
+            let mut cursor = std::io::Cursor::new(Vec::new());
+            let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+            for &(l, r) in frames {
+                for s in [l, r] {
+                    match format {
+                        hound::SampleFormat::Float => writer.write_sample(s).unwrap(),
+                        hound::SampleFormat::Int => {
+                            let max = (1i64 << (bits - 1)) as f32;
+                            writer.write_sample((s * max) as i32).unwrap()
+                        }
+                    }
+                }
+            }
+            writer.finalize().unwrap();
+            cursor.into_inner()
+        };
+        let frames = [(0.5, -0.5), (0.25, 0.75)];
+        for (bits, format) in [
+            (16, hound::SampleFormat::Int),
+            (24, hound::SampleFormat::Int),
+            (32, hound::SampleFormat::Float),
+        ] {
+            let (rate, mono) = decode_wav(encode(bits, format, &frames).as_slice()).unwrap();
+            assert_eq!(rate, 48_000);
+            assert_eq!(mono.len(), 2);
+            assert!(
+                mono[0].abs() < 1e-4 && (mono[1] - 0.5).abs() < 1e-4,
+                "{:?}",
+                mono
+            );
+        }
+        assert!(decode_wav(&b"not a wav"[..]).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Live transcription of an utterance while it is still being spoken.
+//!
+//! VAD capture feeds the growing speech buffer to a [`StreamingTranscriber`],
+//! which resamples it to 16 kHz and, every [`STEP_SECS`] of new audio, queues
+//! the last [`WINDOW_SECS`] for local Whisper on a worker thread. Each window
+//! is transcribed from scratch, so consecutive results overlap and may
+//! disagree near the end. [`Stabilizer`] drops words already committed from
+//! an earlier window and commits a word once two consecutive windows agree
+//! on it. Every result is emitted as `transcript-partial`; when the utterance
+//! ends the remaining words are committed and sent as `transcript-final`.
+
+use crate::speaker::resample::{Resampler, WHISPER_SAMPLE_RATE};
+use crate::speaker::Speaker;
+use serde::Serialize;
+use std::sync::mpsc::{self, Receiver, Sender};
+use tauri::{AppHandle, Emitter};
+use tracing::warn;
+
+/// Longest stretch of audio transcribed at once.
+pub const WINDOW_SECS: usize = 15;
+/// New audio required before the window is transcribed again.
+pub const STEP_SECS: f32 = 1.0;
+/// Leading words of a window that may be garbled by a cut through a word and
+/// are skipped when looking for the overlap with committed text.
+const MAX_SKIPPED_WORDS: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptUpdate {
+    pub utterance_id: u64,
+    /// Set in meeting capture, where each channel numbers its own utterances.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<Speaker>,
+    /// Words that will not change any more.
+    pub text: String,
+    /// Words that may still be revised; empty in `transcript-final`.
+    pub tentative: String,
+}
+
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Commits words that two consecutive windows agree on.
+#[derive(Default)]
+pub(crate) struct Stabilizer {
+    committed: Vec<String>,
+    tentative: Vec<String>,
+}
+
+impl Stabilizer {
+    /// Words of `hypothesis` after the part that repeats committed text.
+    fn new_words(&self, hypothesis: &str) -> Vec<String> {
+        let words: Vec<&str> = hypothesis.split_whitespace().collect();
+        let committed: Vec<String> = self.committed.iter().map(|w| normalize(w)).collect();
+        let normalized: Vec<String> = words.iter().map(|w| normalize(w)).collect();
+
+        // Longest run of committed words that the window repeats, allowing a
+        // few garbled words at the cut.
+        let mut skip = 0;
+        'search: for overlap in (1..=committed.len().min(normalized.len())).rev() {
+            let tail = &committed[committed.len() - overlap..];
+            for lead in 0..=MAX_SKIPPED_WORDS.min(normalized.len() - overlap) {
+                if normalized[lead..lead + overlap] == *tail {
+                    skip = lead + overlap;
+                    break 'search;
+                }
+            }
+        }
+        words[skip..].iter().map(|w| w.to_string()).collect()
+    }
+
+    /// Take a new window transcript. Returns the update to emit.
+    pub(crate) fn push(&mut self, hypothesis: &str) -> (String, String) {
+        let words = self.new_words(hypothesis);
+        let agreed = words
+            .iter()
+            .zip(&self.tentative)
+            .take_while(|(a, b)| normalize(a) == normalize(b))
+            .count();
+        self.committed.extend(words[..agreed].iter().cloned());
+        self.tentative = words[agreed..].to_vec();
+        (self.committed.join(" "), self.tentative.join(" "))
+    }
+
+    /// Commit everything in the final window transcript.
+    pub(crate) fn finish(&mut self, hypothesis: &str) -> String {
+        let words = self.new_words(hypothesis);
+        self.committed.extend(words);
+        self.tentative.clear();
+        std::mem::take(&mut self.committed).join(" ")
+    }
+}
+
+pub(crate) enum Job {
+    Window {
+        utterance_id: u64,
+        audio: Vec<f32>,
+    },
+    /// `audio` is `None` when the utterance was discarded.
+    Finish {
+        utterance_id: u64,
+        audio: Option<Vec<f32>>,
+    },
+}
+
+impl Job {
+    fn utterance_id(&self) -> u64 {
+        match self {
+            Job::Window { utterance_id, .. } | Job::Finish { utterance_id, .. } => *utterance_id,
+        }
+    }
+}
+
+/// Transcribe queued windows until the sending side is dropped. A window is
+/// skipped when a newer job for the same utterance is already waiting, so a
+/// slow model falls behind by at most one window.
+pub(crate) fn run_worker(
+    jobs: Receiver<Job>,
+    speaker: Option<Speaker>,
+    mut transcribe: impl FnMut(&[f32]) -> Result<String, String>,
+    mut emit: impl FnMut(&str, TranscriptUpdate),
+) {
+    let mut stabilizer = Stabilizer::default();
+    while let Ok(first) = jobs.recv() {
+        let mut batch = vec![first];
+        batch.extend(jobs.try_iter());
+
+        for (i, job) in batch.iter().enumerate() {
+            let superseded = batch[i + 1..]
+                .iter()
+                .any(|later| later.utterance_id() == job.utterance_id());
+            match job {
+                Job::Window { .. } if superseded => {}
+                Job::Window {
+                    utterance_id,
+                    audio,
+                } => match transcribe(audio) {
+                    Ok(text) => {
+                        let (text, tentative) = stabilizer.push(&text);
+                        emit(
+                            "transcript-partial",
+                            TranscriptUpdate {
+                                utterance_id: *utterance_id,
+                                speaker,
+                                text,
+                                tentative,
+                            },
+                        );
+                    }
+                    Err(e) => warn!("Streaming transcription failed: {}", e),
+                },
+                Job::Finish {
+                    utterance_id,
+                    audio,
+                } => {
+                    let hypothesis = match audio.as_deref().map(&mut transcribe) {
+                        Some(Ok(text)) => text,
+                        Some(Err(e)) => {
+                            warn!("Streaming transcription failed: {}", e);
+                            String::new()
+                        }
+                        None => {
+                            stabilizer = Stabilizer::default();
+                            String::new()
+                        }
+                    };
+                    emit(
+                        "transcript-final",
+                        TranscriptUpdate {
+                            utterance_id: *utterance_id,
+                            speaker,
+                            text: stabilizer.finish(&hypothesis),
+                            tentative: String::new(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Capture-side handle; dropping it stops the worker thread.
+pub struct StreamingTranscriber {
+    jobs: Sender<Job>,
+    source_rate: u32,
+    resampler: Resampler,
+    /// The current utterance at 16 kHz.
+    audio: Vec<f32>,
+    /// Samples of the capture buffer already resampled.
+    fed: usize,
+    /// Length of `audio` when the last window was queued.
+    queued_at: usize,
+    utterance_id: u64,
+}
+
+impl StreamingTranscriber {
+    pub fn start(
+        app: AppHandle,
+        source_rate: u32,
+        speaker: Option<Speaker>,
+    ) -> Result<Self, String> {
+        let resampler = Resampler::new(source_rate, WHISPER_SAMPLE_RATE)?;
+        let (jobs, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("streaming-transcription".to_string())
+            .spawn(move || {
+                let emitter = app.clone();
+                run_worker(
+                    receiver,
+                    speaker,
+                    |audio| {
+                        let engine = crate::speaker::loaded_engine(&app)?;
+                        engine.transcribe(audio, WHISPER_SAMPLE_RATE)
+                    },
+                    |event, update| {
+                        if let Err(e) = emitter.emit(event, update) {
+                            warn!("Failed to emit {}: {}", event, e);
+                        }
+                    },
+                )
+            })
+            .map_err(|e| format!("Failed to start transcription worker: {}", e))?;
+        Ok(Self {
+            jobs,
+            source_rate,
+            resampler,
+            audio: Vec::new(),
+            fed: 0,
+            queued_at: 0,
+            utterance_id: 0,
+        })
+    }
+
+    fn feed(&mut self, speech_buffer: &[f32]) {
+        if speech_buffer.len() < self.fed {
+            // The buffer was trimmed; nothing new to add.
+            self.fed = speech_buffer.len();
+            return;
+        }
+        let resampled = self.resampler.process(&speech_buffer[self.fed..]);
+        self.audio.extend(resampled);
+        self.fed = speech_buffer.len();
+    }
+
+    fn window(&self) -> Vec<f32> {
+        let len = WINDOW_SECS * WHISPER_SAMPLE_RATE as usize;
+        self.audio[self.audio.len().saturating_sub(len)..].to_vec()
+    }
+
+    /// Call after appending to the in-progress speech buffer.
+    pub fn update(&mut self, speech_buffer: &[f32]) {
+        self.feed(speech_buffer);
+        let step = (STEP_SECS * WHISPER_SAMPLE_RATE as f32) as usize;
+        if self.audio.len() >= self.queued_at + step {
+            self.queued_at = self.audio.len();
+            let _ = self.jobs.send(Job::Window {
+                utterance_id: self.utterance_id,
+                audio: self.window(),
+            });
+        }
+    }
+
+    /// Call when the utterance ends, before the speech buffer is cleared.
+    /// A discarded utterance still gets an empty `transcript-final` if any
+    /// partials were sent for it.
+    pub fn finish(&mut self, speech_buffer: &[f32], discarded: bool) {
+        let sent_partials = self.queued_at > 0;
+        let audio = if discarded {
+            None
+        } else {
+            self.feed(speech_buffer);
+            self.audio.extend(self.resampler.flush());
+            Some(self.window())
+        };
+        if sent_partials || audio.as_ref().is_some_and(|a| !a.is_empty()) {
+            let _ = self.jobs.send(Job::Finish {
+                utterance_id: self.utterance_id,
+                audio,
+            });
+        }
+
+        self.resampler = Resampler::new(self.source_rate, WHISPER_SAMPLE_RATE)
+            .expect("rate was accepted by start");
+        self.audio.clear();
+        self.fed = 0;
+        self.queued_at = 0;
+        self.utterance_id += 1;
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commits_words_two_windows_agree_on() {
+        let mut s = Stabilizer::default();
+        assert_eq!(s.push("So the plan"), (String::new(), "So the plan".into()));
+        assert_eq!(
+            s.push("So the plan is to"),
+            ("So the plan".into(), "is to".into())
+        );
+        // The window has slid past "So"; the cut garbled "the" into "a".
+        assert_eq!(
+            s.push("a plan, is to ship it"),
+            ("So the plan is to".into(), "ship it".into())
+        );
+        assert_eq!(
+            s.finish("is to ship it Friday."),
+            "So the plan is to ship it Friday."
+        );
+        assert_eq!(s.push("Next"), (String::new(), "Next".into()));
+    }
+
+    #[test]
+    fn worker_skips_stale_windows_and_finishes_each_utterance() {
+        let (tx, rx) = mpsc::channel();
+        let window = |id, len| Job::Window {
+            utterance_id: id,
+            audio: vec![0.0; len],
+        };
+        tx.send(window(0, 1)).unwrap();
+        tx.send(window(0, 2)).unwrap();
+        tx.send(Job::Finish {
+            utterance_id: 0,
+            audio: Some(vec![0.0; 3]),
+        })
+        .unwrap();
+        tx.send(window(1, 1)).unwrap();
+        tx.send(Job::Finish {
+            utterance_id: 1,
+            audio: None,
+        })
+        .unwrap();
+        drop(tx);
+
+        let mut transcribed = Vec::new();
+        let mut events = Vec::new();
+        run_worker(
+            rx,
+            None,
+            |audio| {
+                transcribed.push(audio.len());
+                Ok(["hello", "hello there", "hello there friend"][audio.len() - 1].to_string())
+            },
+            |event, update| events.push((event.to_string(), update.utterance_id, update.text)),
+        );
+        // Everything arrives in one batch, so only the finishes run.
+        assert_eq!(transcribed, vec![3]);
+        assert_eq!(
+            events,
+            vec![
+                (
+                    "transcript-final".to_string(),
+                    0,
+                    "hello there friend".to_string()
+                ),
+                ("transcript-final".to_string(), 1, String::new()),
+            ]
+        );
+    }
+}
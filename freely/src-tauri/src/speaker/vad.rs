@@ -0,0 +1,488 @@
+//! Voice activity detectors for VAD capture, and a benchmark that scores them
+//! against labeled recordings.
+//!
+//! Two detectors implement [`VoiceDetector`]:
+//! - [`EnergyDetector`] flags a frame when its RMS or peak level crosses a
+//!   threshold. Cheap, but any loud sound counts as speech.
+//! - [`WebRtcDetector`] runs the WebRTC GMM classifier (via libfvad) on the
+//!   CPU. It looks at the spectral shape, so music, typing and notification
+//!   sounds are far less likely to start a segment.
+
+use crate::speaker::resample::{decode_wav, Resampler};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use webrtc_vad::{SampleRate, Vad, VadMode};
+
+/// Rate the WebRTC classifier runs at.
+const WEBRTC_RATE: u32 = 16_000;
+/// 20 ms at [`WEBRTC_RATE`]; libfvad accepts 10, 20 or 30 ms frames.
+const WEBRTC_FRAME: usize = 320;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VadDetector {
+    #[default]
+    Energy,
+    Webrtc,
+}
+
+pub trait VoiceDetector: Send {
+    /// Whether a frame of mono samples contains speech.
+    fn is_speech(&mut self, frame: &[f32]) -> bool;
+
+    /// Adopt thresholds derived from the measured noise floor. Detectors that
+    /// do not use level thresholds ignore this.
+    fn set_thresholds(&mut self, _thresholds: &CalibratedThresholds) {}
+}
+
+impl<F: FnMut(&[f32]) -> bool + Send> VoiceDetector for F {
+    fn is_speech(&mut self, frame: &[f32]) -> bool {
+        self(frame)
+    }
+}
+
+// Calculate RMS and peak (optimized)
+pub(crate) fn calculate_audio_metrics(chunk: &[f32]) -> (f32, f32) {
+    let mut sumsq = 0.0f32;
+    let mut peak = 0.0f32;
+
+    for &v in chunk {
+        let a = v.abs();
+        peak = peak.max(a);
+        sumsq += v * v;
+    }
+
+    let rms = (sumsq / chunk.len() as f32).sqrt();
+    (rms, peak)
+}
+
+pub struct EnergyDetector {
+    pub sensitivity_rms: f32,
+    pub peak_threshold: f32,
+}
+
+impl VoiceDetector for EnergyDetector {
+    fn is_speech(&mut self, frame: &[f32]) -> bool {
+        let (rms, peak) = calculate_audio_metrics(frame);
+        rms > self.sensitivity_rms || peak > self.peak_threshold
+    }
+
+    fn set_thresholds(&mut self, thresholds: &CalibratedThresholds) {
+        self.sensitivity_rms = thresholds.sensitivity_rms;
+        self.peak_threshold = thresholds.peak_threshold;
+    }
+}
+
+// ============================================================================
+// Noise floor calibration
+// ============================================================================
+
+/// Seconds of audio measured before the first thresholds are derived.
+pub const CALIBRATION_SECS: f32 = 2.0;
+
+/// Quietest level treated as a noise floor, so digital silence still yields
+/// usable thresholds.
+const MIN_NOISE_FLOOR: f32 = 0.0005;
+/// Percentile of warm-up hop levels taken as the floor. Low enough to ignore
+/// speech or a notification during warm-up.
+const FLOOR_PERCENTILE: f32 = 0.2;
+/// Per-hop smoothing when the level drops below the floor, and when it sits
+/// above it. The floor follows quieter rooms quickly and louder ones slowly.
+const FALL_RATE: f32 = 0.1;
+const RISE_RATE: f32 = 0.005;
+/// Relative floor change that triggers new thresholds after warm-up.
+const RECALIBRATE_CHANGE: f32 = 0.25;
+
+/// Levels derived from an ambient noise floor. The multipliers reproduce the
+/// `VadConfig` defaults for a floor of 0.003.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibratedThresholds {
+    pub noise_floor: f32,
+    pub noise_gate_threshold: f32,
+    pub sensitivity_rms: f32,
+    pub peak_threshold: f32,
+}
+
+impl CalibratedThresholds {
+    pub fn from_noise_floor(noise_floor: f32) -> Self {
+        let floor = noise_floor.max(MIN_NOISE_FLOOR);
+        Self {
+            noise_floor: floor,
+            noise_gate_threshold: floor.clamp(0.0005, 0.02),
+            sensitivity_rms: (floor * 4.0).clamp(0.002, 0.1),
+            peak_threshold: (floor * 12.0).clamp(0.005, 0.3),
+        }
+    }
+}
+
+/// Measures the ambient level during a warm-up period, then keeps tracking it
+/// on hops without speech.
+pub struct NoiseFloorTracker {
+    warmup_hops: usize,
+    warmup: Vec<f32>,
+    floor: Option<f32>,
+    /// Floor behind the thresholds last handed out.
+    reported: Option<f32>,
+}
+
+impl NoiseFloorTracker {
+    pub fn new(sample_rate: u32, hop_size: usize, warmup_secs: f32) -> Self {
+        let warmup_hops =
+            ((sample_rate as f32 * warmup_secs) / hop_size.max(1) as f32).ceil() as usize;
+        Self {
+            warmup_hops: warmup_hops.max(1),
+            warmup: Vec::new(),
+            floor: None,
+            reported: None,
+        }
+    }
+
+    /// Feed the RMS of one hop, before any noise gate. `speech` marks hops
+    /// inside a speech segment, which are ignored after warm-up. Returns new
+    /// thresholds when warm-up completes or the floor has drifted.
+    pub fn observe(&mut self, rms: f32, speech: bool) -> Option<CalibratedThresholds> {
+        match self.floor {
+            None => {
+                self.warmup.push(rms);
+                if self.warmup.len() < self.warmup_hops {
+                    return None;
+                }
+                self.finish_warmup();
+            }
+            Some(_) if speech => return None,
+            Some(floor) => {
+                let rate = if rms < floor { FALL_RATE } else { RISE_RATE };
+                self.floor = Some(floor + (rms - floor) * rate);
+            }
+        }
+
+        let floor = self.floor?.max(MIN_NOISE_FLOOR);
+        let drifted = self
+            .reported
+            .is_none_or(|reported| (floor - reported).abs() / reported > RECALIBRATE_CHANGE);
+        if !drifted {
+            return None;
+        }
+        self.reported = Some(floor);
+        Some(CalibratedThresholds::from_noise_floor(floor))
+    }
+
+    /// Thresholds from everything observed so far, ending warm-up early if
+    /// needed. `None` if nothing was observed.
+    pub fn thresholds(&mut self) -> Option<CalibratedThresholds> {
+        if self.floor.is_none() && !self.warmup.is_empty() {
+            self.finish_warmup();
+        }
+        self.floor.map(CalibratedThresholds::from_noise_floor)
+    }
+
+    fn finish_warmup(&mut self) {
+        let mut levels = std::mem::take(&mut self.warmup);
+        levels.sort_by(f32::total_cmp);
+        let index = ((levels.len() - 1) as f32 * FLOOR_PERCENTILE).round() as usize;
+        self.floor = Some(levels[index]);
+    }
+}
+
+/// Owns a libfvad instance. `Vad` holds a raw `*mut Fvad`, which makes it
+/// `!Send`, though nothing about the instance is tied to a thread.
+struct SendVad(Vad);
+
+// SAFETY: all libfvad state lives in the heap-allocated `Fvad` that `Vad`
+// creates and frees; the C code has no mutable globals or thread-local state,
+// so the instance may be used and freed on any thread. `SendVad` is not
+// `Sync` and the pointer never escapes `Vad`, so moving it hands over sole
+// ownership and calls into it are never concurrent.
+unsafe impl Send for SendVad {}
+
+pub struct WebRtcDetector {
+    vad: SendVad,
+    resampler: Resampler,
+    pending: Vec<i16>,
+    speaking: bool,
+}
+
+impl WebRtcDetector {
+    /// `aggressiveness` ranges from 0 (flags the most audio as speech) to 3
+    /// (flags the least).
+    pub fn new(source_rate: u32, aggressiveness: u8) -> Self {
+        let mode = match aggressiveness {
+            0 => VadMode::Quality,
+            1 => VadMode::LowBitrate,
+            2 => VadMode::Aggressive,
+            _ => VadMode::VeryAggressive,
+        };
+        Self {
+            vad: SendVad(Vad::new_with_rate_and_mode(SampleRate::Rate16kHz, mode)),
+            resampler: Resampler::new(source_rate.max(1), WEBRTC_RATE)
+                .expect("sample rates are non-zero"),
+            pending: Vec::with_capacity(WEBRTC_FRAME * 2),
+            speaking: false,
+        }
+    }
+}
+
+impl VoiceDetector for WebRtcDetector {
+    fn is_speech(&mut self, frame: &[f32]) -> bool {
+        let resampled = self.resampler.process(frame);
+        self.pending.extend(
+            resampled
+                .iter()
+                .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16),
+        );
+        let (mut frames, mut voiced) = (0, 0);
+        let mut chunks = self.pending.chunks_exact(WEBRTC_FRAME);
+        for chunk in &mut chunks {
+            frames += 1;
+            if self.vad.0.is_voice_segment(chunk).unwrap_or(false) {
+                voiced += 1;
+            }
+        }
+        let rest = chunks.remainder().len();
+        self.pending.drain(..self.pending.len() - rest);
+
+        // A hop shorter than one classifier frame keeps the last decision.
+        if frames > 0 {
+            self.speaking = voiced * 2 >= frames;
+        }
+        self.speaking
+    }
+}
+
+// ============================================================================
+// Benchmark
+// ============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VadBenchmarkReport {
+    pub fixtures: usize,
+    pub frames: usize,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub true_negatives: usize,
+    pub precision: f64,
+    pub recall: f64,
+}
+
+/// Speech regions, in seconds, from an Audacity label track: one
+/// `start<TAB>end[<TAB>label]` line per region.
+pub(crate) fn parse_labels(text: &str) -> Result<Vec<(f64, f64)>, String> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let mut next = || {
+                fields
+                    .next()
+                    .and_then(|f| f.parse::<f64>().ok())
+                    .ok_or_else(|| format!("Invalid label line: {}", line))
+            };
+            Ok((next()?, next()?))
+        })
+        .collect()
+}
+
+fn read_wav_mono(path: &Path) -> Result<(u32, Vec<f32>), String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    decode_wav(std::io::BufReader::new(file)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Score detectors against every `*.wav` in `dir` that has a matching
+/// `*.txt` label track. Each hop counts as speech in the reference when its
+/// midpoint falls inside a labeled region. `detector_for` is called once per
+/// fixture with the file's sample rate.
+pub(crate) fn benchmark(
+    dir: &Path,
+    hop_size: usize,
+    mut detector_for: impl FnMut(u32) -> Box<dyn VoiceDetector>,
+) -> Result<VadBenchmarkReport, String> {
+    if hop_size == 0 {
+        return Err("hop_size must be greater than 0".to_string());
+    }
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension().is_some_and(|ext| ext == "wav") && p.with_extension("txt").is_file()
+        })
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("No labeled WAV fixtures in {}", dir.display()));
+    }
+
+    let mut report = VadBenchmarkReport::default();
+    for path in &paths {
+        let labels = std::fs::read_to_string(path.with_extension("txt"))
+            .map_err(|e| format!("Failed to read labels for {}: {}", path.display(), e))
+            .and_then(|text| parse_labels(&text))?;
+        let (sample_rate, samples) = read_wav_mono(path)?;
+        let mut detector = detector_for(sample_rate);
+
+        for (i, frame) in samples.chunks_exact(hop_size).enumerate() {
+            let midpoint = (i as f64 + 0.5) * hop_size as f64 / sample_rate as f64;
+            let expected = labels.iter().any(|&(s, e)| midpoint >= s && midpoint < e);
+            match (detector.is_speech(frame), expected) {
+                (true, true) => report.true_positives += 1,
+                (true, false) => report.false_positives += 1,
+                (false, true) => report.false_negatives += 1,
+                (false, false) => report.true_negatives += 1,
+            }
+            report.frames += 1;
+        }
+        report.fixtures += 1;
+    }
+
+    let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+    report.precision = ratio(
+        report.true_positives,
+        report.true_positives + report.false_positives,
+    );
+    report.recall = ratio(
+        report.true_positives,
+        report.true_positives + report.false_negatives,
+    );
+    Ok(report)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn parses_audacity_label_tracks() {
+        let labels = parse_labels("0.5\t1.25\tspeech\n\n2\t3\n").unwrap();
+        assert_eq!(labels, vec![(0.5, 1.25), (2.0, 3.0)]);
+        assert!(parse_labels("0.5\n").is_err());
+    }
+
+    #[test]
+    fn calibrates_from_the_warmup_floor_and_tracks_drift() {
+        // Ten hops per second, one second of warm-up.
+        let mut tracker = NoiseFloorTracker::new(10, 1, 1.0);
+        let warmup = [
+            0.003, 0.2, 0.004, 0.003, 0.5, 0.003, 0.002, 0.004, 0.003, 0.003,
+        ];
+        let results: Vec<_> = warmup.iter().map(|&l| tracker.observe(l, true)).collect();
+        assert!(results[..9].iter().all(Option::is_none));
+        let first = results[9].unwrap();
+        assert_eq!(first, CalibratedThresholds::from_noise_floor(0.003));
+        assert!((first.sensitivity_rms - 0.012).abs() < 1e-6);
+        assert!((first.peak_threshold - 0.036).abs() < 1e-6);
+
+        // Speech is ignored; a quieter room lowers the floor.
+        assert_eq!(tracker.observe(0.5, true), None);
+        let updated = (0..50).find_map(|_| tracker.observe(0.001, false)).unwrap();
+        assert!(updated.noise_floor < 0.003 * (1.0 - RECALIBRATE_CHANGE));
+        assert!(tracker.thresholds().unwrap().noise_floor <= updated.noise_floor);
+
+        let mut short = NoiseFloorTracker::new(10, 1, 5.0);
+        assert_eq!(short.thresholds(), None);
+        short.observe(0.0, false);
+        assert_eq!(short.thresholds().unwrap().noise_floor, MIN_NOISE_FLOOR);
+    }
+
+    /// Writes `secs` of 16 kHz audio where speech-like sound (a gliding
+    /// 140 Hz voice with three formants and syllable-rate amplitude) fills
+    /// `speech`, and faint noise fills the rest.
+    fn write_speech_fixture(path: &Path, secs: u32, speech: (f32, f32)) {
+        let sr = 16_000;
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: sr,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        let mut seed = 1u32;
+        for i in 0..secs * sr {
+            let t = i as f32 / sr as f32;
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let mut value = 0.001 * ((seed >> 8) as f32 / (1 << 24) as f32 - 0.5);
+            if t >= speech.0 && t < speech.1 {
+                let f0 = 140.0 + 20.0 * (2.0 * PI * 3.0 * t).sin();
+                let voice: f32 = (1..=20)
+                    .map(|h| {
+                        let f = f0 * h as f32;
+                        let gain = (-((f - 700.0) / 300.0).powi(2)).exp()
+                            + 0.6 * (-((f - 1200.0) / 300.0).powi(2)).exp()
+                            + 0.3 * (-((f - 2500.0) / 400.0).powi(2)).exp();
+                        gain * (2.0 * PI * f * t).sin()
+                    })
+                    .sum();
+                value += 0.15 * voice * (0.6 + 0.4 * (2.0 * PI * 4.0 * t).sin());
+            }
+            writer
+                .write_sample((value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn benchmarks_webrtc_detector_on_speech_fixture() {
+        let dir = tempfile::tempdir().unwrap();
+        write_speech_fixture(&dir.path().join("speech.wav"), 3, (1.0, 2.0));
+        std::fs::write(dir.path().join("speech.txt"), "1.0\t2.0\tspeech\n").unwrap();
+
+        let report = benchmark(dir.path(), 800, |sr| Box::new(WebRtcDetector::new(sr, 2))).unwrap();
+        assert_eq!(report.frames, 60);
+        assert!(report.recall >= 0.9, "{:?}", report);
+        assert!(report.precision >= 0.8, "{:?}", report);
+    }
+
+    #[test]
+    fn benchmarks_labeled_fixtures() {
+        let dir = tempfile::tempdir().unwrap();
+        let sr = 16_000;
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: sr,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        // One second of silence, then one second of a 220 Hz tone.
+        let mut writer = hound::WavWriter::create(dir.path().join("tone.wav"), spec).unwrap();
+        for i in 0..2 * sr {
+            let value = if i < sr {
+                0.0
+            } else {
+                0.3 * (2.0 * PI * 220.0 * i as f32 / sr as f32).sin()
+            };
+            writer
+                .write_sample((value * i16::MAX as f32) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        std::fs::write(dir.path().join("tone.txt"), "1.0\t2.0\ttone\n").unwrap();
+        std::fs::write(dir.path().join("unlabeled.wav"), b"").unwrap();
+
+        let report = benchmark(dir.path(), 800, |_| {
+            Box::new(EnergyDetector {
+                sensitivity_rms: 0.012,
+                peak_threshold: 0.035,
+            })
+        })
+        .unwrap();
+        assert_eq!(report.fixtures, 1);
+        assert_eq!(report.frames, 40);
+        assert_eq!((report.true_positives, report.true_negatives), (20, 20));
+        assert_eq!((report.precision, report.recall), (1.0, 1.0));
+
+        let report = benchmark(dir.path(), 800, |_| Box::new(|_: &[f32]| true)).unwrap();
+        assert_eq!(report.precision, 0.5);
+        assert!(benchmark(Path::new("/nonexistent"), 800, |_| Box::new(
+            |_: &[f32]| true
+        ))
+        .is_err());
+    }
+}
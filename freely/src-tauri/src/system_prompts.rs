@@ -0,0 +1,573 @@
+//! Saved system prompts (`system_prompts` table), their version history and
+//! JSON prompt packs for sharing them.
+//!
+//! Shapes match the frontend's `SystemPrompt` type, including its
+//! snake_case timestamps, which SQLite fills in as `datetime('now')` text.
+//! Triggers (migration 10) record a new version whenever a prompt's name or
+//! text changes; restoring an old version saves it as the newest one, so
+//! history is never rewritten.
+//!
+//! # Prompt pack format (`freely.prompt-pack`, version 1)
+//!
+//! ```json
+//! {
+//!   "schema": "freely.prompt-pack",
+//!   "version": 1,
+//!   "exportedAt": 1718000000000,
+//!   "appVersion": "0.1.0",
+//!   "prompts": [{ "name": "…", "prompt": "…" }]
+//! }
+//! ```
+//!
+//! On import, prompts are matched to existing ones by name
+//! (case-insensitive). A match with different text is updated, which keeps
+//! the previous text in its history; new names are created.
+
+use crate::db;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::AppHandle;
+
+const COLUMNS: &str = "id, name, prompt, created_at, updated_at,
+    (SELECT COALESCE(MAX(version), 1) FROM system_prompt_versions v
+     WHERE v.prompt_id = system_prompts.id)";
+
+pub(crate) const PACK_SCHEMA: &str = "freely.prompt-pack";
+pub(crate) const PACK_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SystemPrompt {
+    pub id: i64,
+    pub name: String,
+    pub prompt: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Number of the latest entry in the prompt's history.
+    pub version: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SystemPromptVersion {
+    pub prompt_id: i64,
+    pub version: i64,
+    pub name: String,
+    pub prompt: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SystemPromptInput {
+    pub name: String,
+    pub prompt: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateSystemPromptInput {
+    pub name: Option<String>,
+    pub prompt: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackPrompt {
+    pub name: String,
+    pub prompt: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptPack {
+    pub schema: String,
+    pub version: u32,
+    pub exported_at: i64,
+    pub app_version: String,
+    pub prompts: Vec<PackPrompt>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+fn row_to_prompt(row: &Row) -> rusqlite::Result<SystemPrompt> {
+    Ok(SystemPrompt {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        prompt: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        version: row.get(5)?,
+    })
+}
+
+fn non_empty(value: &str, field: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("System prompt {} cannot be empty", field));
+    }
+    Ok(value.to_string())
+}
+
+pub(crate) fn list_prompts(conn: &Connection) -> Result<Vec<SystemPrompt>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM system_prompts ORDER BY created_at DESC, id DESC",
+            COLUMNS
+        ))
+        .map_err(|e| format!("Failed to list system prompts: {}", e))?;
+    let prompts = stmt
+        .query_map([], row_to_prompt)
+        .map_err(|e| format!("Failed to list system prompts: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to list system prompts: {}", e))?;
+    Ok(prompts)
+}
+
+pub(crate) fn get_prompt(conn: &Connection, id: i64) -> Result<Option<SystemPrompt>, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM system_prompts WHERE id = ?1", COLUMNS),
+        [id],
+        row_to_prompt,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to load system prompt {}: {}", id, e))
+}
+
+pub(crate) fn create_prompt(
+    conn: &Connection,
+    input: &SystemPromptInput,
+) -> Result<SystemPrompt, String> {
+    let name = non_empty(&input.name, "name")?;
+    let prompt = non_empty(&input.prompt, "text")?;
+    conn.execute(
+        "INSERT INTO system_prompts (name, prompt) VALUES (?1, ?2)",
+        params![name, prompt],
+    )
+    .map_err(|e| format!("Failed to create system prompt: {}", e))?;
+    get_prompt(conn, conn.last_insert_rowid())?
+        .ok_or_else(|| "Failed to retrieve created system prompt".to_string())
+}
+
+pub(crate) fn update_prompt(
+    conn: &Connection,
+    id: i64,
+    input: &UpdateSystemPromptInput,
+) -> Result<SystemPrompt, String> {
+    if input.name.is_none() && input.prompt.is_none() {
+        return Err("No fields to update".to_string());
+    }
+    let name = input
+        .name
+        .as_deref()
+        .map(|n| non_empty(n, "name"))
+        .transpose()?;
+    let prompt = input
+        .prompt
+        .as_deref()
+        .map(|p| non_empty(p, "text"))
+        .transpose()?;
+    let changed = conn
+        .execute(
+            "UPDATE system_prompts
+             SET name = COALESCE(?1, name), prompt = COALESCE(?2, prompt)
+             WHERE id = ?3",
+            params![name, prompt, id],
+        )
+        .map_err(|e| format!("Failed to update system prompt {}: {}", id, e))?;
+    if changed == 0 {
+        return Err("System prompt not found".to_string());
+    }
+    get_prompt(conn, id)?.ok_or_else(|| "System prompt not found after update".to_string())
+}
+
+pub(crate) fn delete_prompt(conn: &Connection, id: i64) -> Result<(), String> {
+    let deleted = conn
+        .execute("DELETE FROM system_prompts WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to delete system prompt {}: {}", id, e))?;
+    if deleted == 0 {
+        return Err("System prompt not found".to_string());
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Versions
+// ============================================================================
+
+/// History of a prompt, newest first.
+pub(crate) fn list_versions(
+    conn: &Connection,
+    id: i64,
+) -> Result<Vec<SystemPromptVersion>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT prompt_id, version, name, prompt, created_at FROM system_prompt_versions
+             WHERE prompt_id = ?1 ORDER BY version DESC",
+        )
+        .map_err(|e| format!("Failed to list prompt versions: {}", e))?;
+    let versions = stmt
+        .query_map([id], |row| {
+            Ok(SystemPromptVersion {
+                prompt_id: row.get(0)?,
+                version: row.get(1)?,
+                name: row.get(2)?,
+                prompt: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to list prompt versions: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to list prompt versions: {}", e))?;
+    Ok(versions)
+}
+
+/// Make `version` the current name and text of prompt `id`. The restored
+/// content becomes a new version unless it matches the current one.
+pub(crate) fn restore_version(
+    conn: &Connection,
+    id: i64,
+    version: i64,
+) -> Result<SystemPrompt, String> {
+    let changed = conn
+        .execute(
+            "UPDATE system_prompts
+             SET name = v.name, prompt = v.prompt
+             FROM system_prompt_versions v
+             WHERE system_prompts.id = ?1 AND v.prompt_id = ?1 AND v.version = ?2",
+            params![id, version],
+        )
+        .map_err(|e| format!("Failed to restore system prompt {}: {}", id, e))?;
+    if changed == 0 {
+        return Err(format!(
+            "Version {} of system prompt {} not found",
+            version, id
+        ));
+    }
+    get_prompt(conn, id)?.ok_or_else(|| "System prompt not found after restore".to_string())
+}
+
+// ============================================================================
+// Prompt packs
+// ============================================================================
+
+/// Pack containing the prompts in `ids`, or every prompt when `ids` is
+/// `None`, oldest first.
+pub(crate) fn export_pack(conn: &Connection, ids: Option<&[i64]>) -> Result<PromptPack, String> {
+    let mut prompts = list_prompts(conn)?;
+    prompts.reverse();
+    if let Some(ids) = ids {
+        prompts.retain(|p| ids.contains(&p.id));
+    }
+    Ok(PromptPack {
+        schema: PACK_SCHEMA.to_string(),
+        version: PACK_VERSION,
+        exported_at: db::now_ms(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        prompts: prompts
+            .into_iter()
+            .map(|p| PackPrompt {
+                name: p.name,
+                prompt: p.prompt,
+            })
+            .collect(),
+    })
+}
+
+pub(crate) fn parse_pack(json: &str) -> Result<PromptPack, String> {
+    let pack: PromptPack =
+        serde_json::from_str(json).map_err(|e| format!("Invalid prompt pack: {}", e))?;
+    if pack.schema != PACK_SCHEMA {
+        return Err(format!("Unsupported prompt pack schema: {}", pack.schema));
+    }
+    if pack.version > PACK_VERSION {
+        return Err(format!(
+            "Prompt pack version {} is newer than this app supports ({})",
+            pack.version, PACK_VERSION
+        ));
+    }
+    Ok(pack)
+}
+
+/// Add the prompts in `pack`, updating same-named prompts whose text
+/// differs. Either every prompt is imported or none is.
+pub(crate) fn import_pack(
+    conn: &mut Connection,
+    pack: &PromptPack,
+) -> Result<PackImportSummary, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut summary = PackImportSummary::default();
+    for entry in &pack.prompts {
+        let name = non_empty(&entry.name, "name")?;
+        let prompt = non_empty(&entry.prompt, "text")?;
+        let existing: Option<(i64, String)> = tx
+            .query_row(
+                "SELECT id, prompt FROM system_prompts WHERE name = ?1 COLLATE NOCASE
+                 ORDER BY id LIMIT 1",
+                [&name],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up system prompt {}: {}", name, e))?;
+        match existing {
+            Some((_, current)) if current == prompt => summary.unchanged += 1,
+            Some((id, _)) => {
+                update_prompt(
+                    &tx,
+                    id,
+                    &UpdateSystemPromptInput {
+                        name: None,
+                        prompt: Some(prompt),
+                    },
+                )?;
+                summary.updated += 1;
+            }
+            None => {
+                create_prompt(&tx, &SystemPromptInput { name, prompt })?;
+                summary.created += 1;
+            }
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit import: {}", e))?;
+    Ok(summary)
+}
+
+// ============================================================================
+// Tauri commands
+// ============================================================================
+
+#[tauri::command]
+pub fn list_system_prompts(app: AppHandle) -> Result<Vec<SystemPrompt>, String> {
+    let conn = db::open(&app)?;
+    list_prompts(&conn)
+}
+
+#[tauri::command]
+pub fn get_system_prompt(app: AppHandle, id: i64) -> Result<Option<SystemPrompt>, String> {
+    let conn = db::open(&app)?;
+    get_prompt(&conn, id)
+}
+
+#[tauri::command]
+pub fn create_system_prompt(
+    app: AppHandle,
+    input: SystemPromptInput,
+) -> Result<SystemPrompt, String> {
+    let conn = db::open(&app)?;
+    create_prompt(&conn, &input)
+}
+
+#[tauri::command]
+pub fn update_system_prompt(
+    app: AppHandle,
+    id: i64,
+    input: UpdateSystemPromptInput,
+) -> Result<SystemPrompt, String> {
+    let conn = db::open(&app)?;
+    update_prompt(&conn, id, &input)
+}
+
+#[tauri::command]
+pub fn delete_system_prompt(app: AppHandle, id: i64) -> Result<(), String> {
+    let conn = db::open(&app)?;
+    delete_prompt(&conn, id)
+}
+
+#[tauri::command]
+pub fn list_system_prompt_versions(
+    app: AppHandle,
+    id: i64,
+) -> Result<Vec<SystemPromptVersion>, String> {
+    let conn = db::open(&app)?;
+    list_versions(&conn, id)
+}
+
+#[tauri::command]
+pub fn restore_system_prompt_version(
+    app: AppHandle,
+    id: i64,
+    version: i64,
+) -> Result<SystemPrompt, String> {
+    let conn = db::open(&app)?;
+    restore_version(&conn, id, version)
+}
+
+/// Write the prompts in `ids` (all prompts if omitted) to `dest_path` as a
+/// prompt pack, returning how many were exported.
+#[tauri::command]
+pub fn export_system_prompts(
+    app: AppHandle,
+    dest_path: String,
+    ids: Option<Vec<i64>>,
+) -> Result<usize, String> {
+    let conn = db::open(&app)?;
+    let pack = export_pack(&conn, ids.as_deref())?;
+    let json =
+        serde_json::to_string_pretty(&pack).map_err(|e| format!("Failed to serialize: {}", e))?;
+    std::fs::write(Path::new(&dest_path), json)
+        .map_err(|e| format!("Failed to write {}: {}", dest_path, e))?;
+    Ok(pack.prompts.len())
+}
+
+#[tauri::command]
+pub fn import_system_prompts(
+    app: AppHandle,
+    src_path: String,
+) -> Result<PackImportSummary, String> {
+    let json = std::fs::read_to_string(&src_path)
+        .map_err(|e| format!("Failed to read {}: {}", src_path, e))?;
+    let pack = parse_pack(&json)?;
+    let mut conn = db::open(&app)?;
+    import_pack(&mut conn, &pack)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, prompt: &str) -> SystemPromptInput {
+        SystemPromptInput {
+            name: name.to_string(),
+            prompt: prompt.to_string(),
+        }
+    }
+
+    #[test]
+    fn create_update_and_delete() {
+        let conn = db::open_in_memory();
+        let created = create_prompt(&conn, &input("  Interviewer ", "Ask follow-ups")).unwrap();
+        assert_eq!(created.name, "Interviewer");
+
+        let updated = update_prompt(
+            &conn,
+            created.id,
+            &UpdateSystemPromptInput {
+                prompt: Some("Ask one follow-up".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(updated.name, "Interviewer");
+        assert_eq!(updated.prompt, "Ask one follow-up");
+        assert_eq!(list_prompts(&conn).unwrap(), vec![updated]);
+
+        delete_prompt(&conn, created.id).unwrap();
+        assert!(get_prompt(&conn, created.id).unwrap().is_none());
+        assert!(delete_prompt(&conn, created.id).is_err());
+    }
+
+    #[test]
+    fn rejects_blank_fields_and_empty_updates() {
+        let conn = db::open_in_memory();
+        assert!(create_prompt(&conn, &input(" ", "text")).is_err());
+        assert!(create_prompt(&conn, &input("name", "\n")).is_err());
+
+        let created = create_prompt(&conn, &input("name", "text")).unwrap();
+        assert!(update_prompt(&conn, created.id, &UpdateSystemPromptInput::default()).is_err());
+        assert!(update_prompt(
+            &conn,
+            999,
+            &UpdateSystemPromptInput {
+                name: Some("other".to_string()),
+                ..Default::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn edits_are_versioned_and_restorable() {
+        let conn = db::open_in_memory();
+        let created = create_prompt(&conn, &input("Coach", "Be brief")).unwrap();
+        assert_eq!(created.version, 1);
+        let rename_only = UpdateSystemPromptInput {
+            name: Some("Coach".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            update_prompt(&conn, created.id, &rename_only)
+                .unwrap()
+                .version,
+            1
+        );
+
+        let edited = update_prompt(
+            &conn,
+            created.id,
+            &UpdateSystemPromptInput {
+                prompt: Some("Be thorough".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(edited.version, 2);
+
+        let restored = restore_version(&conn, created.id, 1).unwrap();
+        assert_eq!(restored.prompt, "Be brief");
+        assert_eq!(restored.version, 3);
+        let history: Vec<_> = list_versions(&conn, created.id)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.version, v.prompt))
+            .collect();
+        assert_eq!(
+            history,
+            vec![
+                (3, "Be brief".to_string()),
+                (2, "Be thorough".to_string()),
+                (1, "Be brief".to_string())
+            ]
+        );
+        assert!(restore_version(&conn, created.id, 9).is_err());
+
+        delete_prompt(&conn, created.id).unwrap();
+        assert!(list_versions(&conn, created.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn pack_import_creates_updates_and_skips_by_name() {
+        let mut conn = db::open_in_memory();
+        create_prompt(&conn, &input("Reviewer", "Check for bugs")).unwrap();
+        create_prompt(&conn, &input("Tutor", "Explain slowly")).unwrap();
+        let json = serde_json::to_string(&export_pack(&conn, None).unwrap()).unwrap();
+        let mut pack = parse_pack(&json).unwrap();
+        assert_eq!(pack.prompts[0].name, "Reviewer");
+
+        pack.prompts[0].prompt = "Check for bugs and style".to_string();
+        pack.prompts[1].name = "tutor".to_string();
+        pack.prompts.push(PackPrompt {
+            name: "Interviewer".to_string(),
+            prompt: "Ask follow-ups".to_string(),
+        });
+        let summary = import_pack(&mut conn, &pack).unwrap();
+        assert_eq!(
+            summary,
+            PackImportSummary {
+                created: 1,
+                updated: 1,
+                unchanged: 1
+            }
+        );
+        let prompts = list_prompts(&conn).unwrap();
+        assert_eq!(prompts.len(), 3);
+        let reviewer = prompts.iter().find(|p| p.name == "Reviewer").unwrap();
+        assert_eq!(reviewer.version, 2);
+
+        pack.prompts.push(PackPrompt {
+            name: "Blank".to_string(),
+            prompt: " ".to_string(),
+        });
+        assert!(import_pack(&mut conn, &pack).is_err());
+        assert_eq!(list_prompts(&conn).unwrap().len(), 3, "import is atomic");
+        assert!(parse_pack(&json.replace("prompt-pack", "conversation")).is_err());
+    }
+}
@@ -7,6 +7,7 @@ export const SPEECH_TO_TEXT_PROVIDERS = [
       -F "file={{AUDIO}}" \\
       -F "model={{MODEL}}"`,
     responseContentPath: "text",
+    audioEncodings: ["opus", "flac"],
     streaming: false,
   },
   {
@@ -20,6 +21,7 @@ export const SPEECH_TO_TEXT_PROVIDERS = [
       -F response_format=text \\
       -F language=en`,
     responseContentPath: "text",
+    audioEncodings: ["opus", "flac"],
     streaming: false,
   },
   {
@@ -30,6 +32,7 @@ export const SPEECH_TO_TEXT_PROVIDERS = [
       -F "file={{AUDIO}}" \\
       -F "model_id={{MODEL}}"`,
     responseContentPath: "text",
+    audioEncodings: ["opus", "flac"],
     streaming: false,
   },
   {
@@ -80,6 +83,7 @@ export const SPEECH_TO_TEXT_PROVIDERS = [
       -F "data_file={{AUDIO}}" \\
       -F 'config={"type": "transcription", "transcription_config": {"language": "en"}}'`,
     responseContentPath: "job.id",
+    audioEncodings: ["opus", "flac"],
     streaming: false,
   },
   {
@@ -90,6 +94,7 @@ export const SPEECH_TO_TEXT_PROVIDERS = [
       -F "media={{AUDIO}}" \\
       -F "options={{OPTIONS}}"`,
     responseContentPath: "id",
+    audioEncodings: ["opus", "flac"],
     streaming: false,
   },
   {
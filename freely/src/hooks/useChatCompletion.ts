@@ -11,6 +11,7 @@ import {
   generateRequestId,
   getResponseSettings,
 } from "@/lib";
+import type { MessageMetadata } from "@/types";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 
@@ -28,6 +29,7 @@ interface ChatMessage {
   role: "user" | "assistant" | "system";
   content: string;
   timestamp: number;
+  metadata?: MessageMetadata;
 }
 
 interface ChatConversation {
@@ -225,6 +227,7 @@ export const useChatCompletion = (
         setTimeout(scrollToBottom, 100);
 
         let fullResponse = "";
+        let metadata: MessageMetadata | undefined;
 
         try {
           // Use the fetchAIResponse function with signal.
@@ -237,6 +240,9 @@ export const useChatCompletion = (
             userMessage: input,
             imagesBase64,
             signal,
+            onMetadata: (m) => {
+              metadata = m;
+            },
           })) {
             // Only update if this is still the current request
             if (currentRequestIdRef.current !== requestId) {
@@ -314,6 +320,7 @@ export const useChatCompletion = (
             role: "assistant",
             content: fullResponse,
             timestamp: timestamp + MESSAGE_ID_OFFSET,
+            metadata,
           };
 
           const newMessages = [
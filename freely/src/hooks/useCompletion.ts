@@ -14,6 +14,7 @@ import {
   generateRequestId,
   getResponseSettings,
 } from "@/lib";
+import type { MessageMetadata } from "@/types";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 
@@ -31,6 +32,7 @@ interface ChatMessage {
   role: "user" | "assistant" | "system";
   content: string;
   timestamp: number;
+  metadata?: MessageMetadata;
 }
 
 interface ChatConversation {
@@ -177,6 +179,7 @@ export const useCompletion = () => {
         }
 
         let fullResponse = "";
+        let metadata: MessageMetadata | undefined;
 
         // Check if AI provider is configured
         if (!selectedAIProvider.provider) {
@@ -217,6 +220,9 @@ export const useCompletion = () => {
             imagesBase64,
             signal,
             conversationId: state.currentConversationId || undefined,
+            onMetadata: (m) => {
+              metadata = m;
+            },
           })) {
             // Only update if this is still the current request
             if (currentRequestIdRef.current !== requestId) {
@@ -263,7 +269,8 @@ export const useCompletion = () => {
           await saveCurrentConversation(
             input,
             fullResponse,
-            state.attachedFiles
+            state.attachedFiles,
+            metadata
           );
           // Clear input and attached files after saving
           setState((prev) => ({
@@ -362,7 +369,8 @@ export const useCompletion = () => {
     async (
       userMessage: string,
       assistantResponse: string,
-      _attachedFiles: AttachedFile[]
+      _attachedFiles: AttachedFile[],
+      metadata?: MessageMetadata
     ) => {
       // Validate inputs
       if (!userMessage || !assistantResponse) {
@@ -386,6 +394,7 @@ export const useCompletion = () => {
         role: "assistant",
         content: assistantResponse,
         timestamp: timestamp + MESSAGE_ID_OFFSET,
+        metadata,
       };
 
       const newMessages = [...state.conversationHistory, userMsg, assistantMsg];
@@ -580,6 +589,7 @@ export const useCompletion = () => {
             }));
 
             let fullResponse = "";
+            let metadata: MessageMetadata | undefined;
 
             // Check if AI provider is configured
             if (!selectedAIProvider.provider) {
@@ -620,6 +630,9 @@ export const useCompletion = () => {
               imagesBase64: [base64],
               signal,
               conversationId: state.currentConversationId || undefined,
+              onMetadata: (m) => {
+                metadata = m;
+              },
             })) {
               // Only update if this is still the current request
               if (currentRequestIdRef.current !== requestId || signal.aborted) {
@@ -647,9 +660,12 @@ export const useCompletion = () => {
 
             // Save the conversation after successful completion
             if (fullResponse) {
-              await saveCurrentConversation(prompt, fullResponse, [
-                attachedFile,
-              ]);
+              await saveCurrentConversation(
+                prompt,
+                fullResponse,
+                [attachedFile],
+                metadata
+              );
               // Clear input after saving
               setState((prev) => ({
                 ...prev,
@@ -1,9 +1,14 @@
-import { useEffect, useState, useCallback, useRef } from "react";
+import { useEffect, useState, useCallback, useRef, useMemo } from "react";
 import { useWindowResize, useGlobalShortcuts } from ".";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { useApp } from "@/contexts";
-import { fetchSTT, fetchAIResponse } from "@/lib/functions";
+import {
+  fetchSTT,
+  fetchAIResponse,
+  sttAudioEncoding,
+  audioMimeType,
+} from "@/lib/functions";
 import {
   DEFAULT_QUICK_ACTIONS,
   DEFAULT_SYSTEM_PROMPT,
@@ -17,7 +22,10 @@ import {
   generateConversationId,
   generateMessageId,
 } from "@/lib";
-import { Message } from "@/types/completion";
+import { Message, MessageMetadata } from "@/types/completion";
+import { AudioEncoding } from "@/types";
+
+export type VadDetector = "energy" | "webrtc";
 
 // VAD Configuration interface matching Rust
 export interface VadConfig {
@@ -30,6 +38,14 @@ export interface VadConfig {
   pre_speech_chunks: number;
   noise_gate_threshold: number;
   max_recording_duration_secs: number;
+  detector: VadDetector;
+  vad_aggressiveness: number;
+  auto_calibrate: boolean;
+  streaming_transcription: boolean;
+  echo_cancellation: boolean;
+  noise_suppression: boolean;
+  noise_suppression_db: number;
+  audio_encoding: AudioEncoding;
 }
 
 // OPTIMIZED VAD defaults - matches backend exactly for perfect performance
@@ -43,8 +59,47 @@ const DEFAULT_VAD_CONFIG: VadConfig = {
   pre_speech_chunks: 12, // ~0.27s - enough to catch word start
   noise_gate_threshold: 0.003, // Stronger noise filtering
   max_recording_duration_secs: 180, // 3 minutes default
+  detector: "energy",
+  vad_aggressiveness: 2,
+  auto_calibrate: false,
+  streaming_transcription: false,
+  echo_cancellation: true,
+  noise_suppression: false,
+  noise_suppression_db: 20, // Max attenuation per frequency band
+  audio_encoding: "wav", // Set from the STT provider at capture start
 };
 
+// A continuous-mode segment, emitted as soon as it is written to disk
+export interface RecordingSegment {
+  index: number;
+  startSecs: number;
+  durationSecs: number;
+  audio: string; // Base64 WAV
+}
+
+// Live local Whisper transcript of the utterance in progress
+// (`transcript-partial`); `tentative` is empty once it is final
+export interface TranscriptUpdate {
+  utteranceId: number;
+  speaker?: "me" | "them";
+  text: string;
+  tentative: string;
+}
+
+// A meeting capture utterance (`meeting-speech-detected`): "me" is the
+// microphone, "them" is system audio
+export interface MeetingSpeech {
+  speaker: "me" | "them";
+  audio: string; // Base64 audio
+}
+
+// The assembled continuous-mode recording
+export interface SavedRecording {
+  path: string;
+  durationSecs: number;
+  segments: number;
+}
+
 // Chat message interface (reusing from useCompletion)
 interface ChatMessage {
   id: string;
@@ -72,6 +127,8 @@ export function useSystemAudio() {
   const [isProcessing, setIsProcessing] = useState(false);
   const [isAIProcessing, setIsAIProcessing] = useState(false);
   const [lastTranscription, setLastTranscription] = useState<string>("");
+  const [liveTranscript, setLiveTranscript] =
+    useState<TranscriptUpdate | null>(null);
   const [lastAIResponse, setLastAIResponse] = useState<string>("");
   const [error, setError] = useState<string>("");
   const [setupRequired, setSetupRequired] = useState<boolean>(false);
@@ -82,6 +139,8 @@ export function useSystemAudio() {
   const [vadConfig, setVadConfig] = useState<VadConfig>(DEFAULT_VAD_CONFIG);
   const [recordingProgress, setRecordingProgress] = useState<number>(0); // For continuous mode
   const [isContinuousMode, setIsContinuousMode] = useState<boolean>(false);
+  // Capture the microphone alongside system audio and label who spoke
+  const [meetingMode, setMeetingMode] = useState<boolean>(false);
   const [isRecordingInContinuousMode, setIsRecordingInContinuousMode] =
     useState<boolean>(false);
 
@@ -105,10 +164,26 @@ export function useSystemAudio() {
     systemPrompt,
     selectedAudioDevices,
   } = useApp();
+
+  // Speech is emitted in the encoding the selected STT provider prefers
+  const captureConfig = useMemo<VadConfig>(
+    () => ({
+      ...vadConfig,
+      audio_encoding: sttAudioEncoding(
+        allSttProviders.find((p) => p.id === selectedSttProvider.provider)
+      ),
+    }),
+    [vadConfig, allSttProviders, selectedSttProvider.provider]
+  );
   const abortControllerRef = useRef<AbortController | null>(null);
   const saveTimeoutRef = useRef<NodeJS.Timeout | null>(null);
   const isSavingRef = useRef<boolean>(false);
   const scrollAreaRef = useRef<HTMLDivElement>(null);
+  // Audio of the current continuous recording, by segment index. Nothing is
+  // sent for transcription until the recording is, so Ignore uploads nothing.
+  const segmentAudioRef = useRef<string[]>([]);
+  // Meeting mode: own utterances waiting to go out with the next "them" one
+  const ownSpeechRef = useRef<string[]>([]);
 
   // Load context settings and VAD config from localStorage on mount
   useEffect(() => {
@@ -135,6 +210,8 @@ export function useSystemAudio() {
         console.error("Failed to load VAD config:", error);
       }
     }
+
+    setMeetingMode(safeLocalStorage.getItem("meeting_mode") === "true");
   }, []);
 
   // Load quick actions from localStorage on mount
@@ -162,6 +239,8 @@ export function useSystemAudio() {
     let stopUnlisten: (() => void) | undefined;
     let errorUnlisten: (() => void) | undefined;
     let discardedUnlisten: (() => void) | undefined;
+    let partialUnlisten: (() => void) | undefined;
+    let finalUnlisten: (() => void) | undefined;
 
     const setupContinuousListeners = async () => {
       try {
@@ -173,6 +252,7 @@ export function useSystemAudio() {
 
         // Recording started
         startUnlisten = await listen("continuous-recording-start", () => {
+          segmentAudioRef.current = [];
           setRecordingProgress(0);
           setIsRecordingInContinuousMode(true);
         });
@@ -199,6 +279,17 @@ export function useSystemAudio() {
           console.log("Speech discarded:", reason);
           // Don't show error - this is expected behavior
         });
+
+        // Streaming transcription: words so far, then the committed text
+        partialUnlisten = await listen<TranscriptUpdate>(
+          "transcript-partial",
+          (event) => setLiveTranscript(event.payload)
+        );
+        finalUnlisten = await listen<TranscriptUpdate>(
+          "transcript-final",
+          (event) =>
+            setLiveTranscript(event.payload.text ? event.payload : null)
+        );
       } catch (err) {
         console.error("Failed to setup continuous recording listeners:", err);
       }
@@ -212,12 +303,89 @@ export function useSystemAudio() {
       if (stopUnlisten) stopUnlisten();
       if (errorUnlisten) errorUnlisten();
       if (discardedUnlisten) discardedUnlisten();
+      if (partialUnlisten) partialUnlisten();
+      if (finalUnlisten) finalUnlisten();
     };
   }, []);
 
-  // Handle single speech detection event (both VAD and continuous modes)
+  // Handle speech detection (both VAD and continuous modes)
   useEffect(() => {
     let speechUnlisten: (() => void) | undefined;
+    let segmentUnlisten: (() => void) | undefined;
+    let savedUnlisten: (() => void) | undefined;
+    let meetingUnlisten: (() => void) | undefined;
+
+    // Transcribe one base64 WAV with the selected provider
+    const transcribe = async (base64Audio: string): Promise<string> => {
+      // Convert to blob
+      const binaryString = atob(base64Audio);
+      const bytes = new Uint8Array(binaryString.length);
+      for (let i = 0; i < binaryString.length; i++) {
+        bytes[i] = binaryString.charCodeAt(i);
+      }
+      const audioBlob = new Blob([bytes], { type: audioMimeType(bytes) });
+
+      if (!selectedSttProvider.provider) {
+        throw new Error("No speech provider selected.");
+      }
+
+      const providerConfig = allSttProviders.find(
+        (p) => p.id === selectedSttProvider.provider
+      );
+
+      if (!providerConfig) {
+        throw new Error("Speech provider config not found.");
+      }
+
+      // Add timeout wrapper for STT request (30 seconds)
+      const sttPromise = fetchSTT({
+        provider: providerConfig,
+        selectedProvider: selectedSttProvider,
+        audio: audioBlob,
+        source: "system_audio",
+      });
+
+      const timeoutPromise = new Promise<never>((_, reject) => {
+        setTimeout(
+          () => reject(new Error("Speech transcription timed out (30s)")),
+          30000
+        );
+      });
+
+      const result = await Promise.race([sttPromise, timeoutPromise]);
+      return result.text;
+    };
+
+    const respond = async (transcription: string) => {
+      if (!transcription.trim()) {
+        setError("Received empty transcription");
+        return;
+      }
+
+      setLastTranscription(transcription);
+      setLiveTranscript(null);
+      setError("");
+
+      const effectiveSystemPrompt = useSystemPrompt
+        ? systemPrompt || DEFAULT_SYSTEM_PROMPT
+        : contextContent || DEFAULT_SYSTEM_PROMPT;
+
+      const previousMessages = conversation.messages.map((msg) => {
+        return { role: msg.role, content: msg.content };
+      });
+
+      await processWithAI(
+        transcription,
+        effectiveSystemPrompt,
+        previousMessages
+      );
+    };
+
+    const showSttError = (sttError: any) => {
+      console.error("STT Error:", sttError);
+      setError(sttError.message || "Failed to transcribe audio");
+      setIsPopoverOpen(true);
+    };
 
     const setupEventListener = async () => {
       try {
@@ -225,84 +393,105 @@ export function useSystemAudio() {
           try {
             if (!capturing) return;
 
-            const base64Audio = event.payload as string;
-            // Convert to blob
-            const binaryString = atob(base64Audio);
-            const bytes = new Uint8Array(binaryString.length);
-            for (let i = 0; i < binaryString.length; i++) {
-              bytes[i] = binaryString.charCodeAt(i);
-            }
-            const audioBlob = new Blob([bytes], { type: "audio/wav" });
+            setIsProcessing(true);
 
-            if (!selectedSttProvider.provider) {
-              setError("No speech provider selected.");
+            let transcription: string;
+            try {
+              transcription = await transcribe(event.payload as string);
+            } catch (sttError: any) {
+              showSttError(sttError);
               return;
             }
+            await respond(transcription);
+          } catch (err) {
+            setError("Failed to process speech");
+          } finally {
+            setIsProcessing(false);
+          }
+        });
 
-            const providerConfig = allSttProviders.find(
-              (p) => p.id === selectedSttProvider.provider
-            );
+        // Meeting mode: the AI answers the other side; what you said since
+        // goes along with it, so both halves of the exchange are labeled
+        meetingUnlisten = await listen<MeetingSpeech>(
+          "meeting-speech-detected",
+          async (event) => {
+            try {
+              if (!capturing) return;
 
-            if (!providerConfig) {
-              setError("Speech provider config not found.");
-              return;
-            }
+              setIsProcessing(true);
 
-            setIsProcessing(true);
+              const { speaker, audio } = event.payload;
+              let transcription: string;
+              try {
+                transcription = (await transcribe(audio)).trim();
+              } catch (sttError: any) {
+                showSttError(sttError);
+                return;
+              }
+              if (!transcription) return;
 
-            // Add timeout wrapper for STT request (30 seconds)
-            const sttPromise = fetchSTT({
-              provider: providerConfig,
-              selectedProvider: selectedSttProvider,
-              audio: audioBlob,
-              source: "system_audio",
-            });
-
-            const timeoutPromise = new Promise<never>((_, reject) => {
-              setTimeout(
-                () => reject(new Error("Speech transcription timed out (30s)")),
-                30000
+              if (speaker === "me") {
+                ownSpeechRef.current.push(transcription);
+                return;
+              }
+              const ownSpeech = ownSpeechRef.current.splice(0);
+              await respond(
+                [
+                  ...ownSpeech.map((text) => `Me: ${text}`),
+                  `Them: ${transcription}`,
+                ].join("\n")
               );
-            });
+            } catch (err) {
+              setError("Failed to process speech");
+            } finally {
+              setIsProcessing(false);
+            }
+          }
+        );
+
+        // Continuous mode: keep each segment as it is written to disk
+        segmentUnlisten = await listen<RecordingSegment>(
+          "recording-segment",
+          (event) => {
+            if (!capturing) return;
+
+            const segment = event.payload;
+            segmentAudioRef.current[segment.index] = segment.audio;
+          }
+        );
+
+        // Continuous mode: the full recording is saved, so every segment
+        // has been emitted and the user chose to send it; transcribe the
+        // segments in parallel
+        savedUnlisten = await listen<SavedRecording>(
+          "continuous-recording-saved",
+          async () => {
+            const pending = segmentAudioRef.current;
+            segmentAudioRef.current = [];
+            if (!capturing || pending.length === 0) return;
 
             try {
-              const result = await Promise.race([
-                sttPromise,
-                timeoutPromise,
-              ]);
-              const transcription = result.text;
-
-              if (transcription.trim()) {
-                setLastTranscription(transcription);
-                setError("");
-
-                const effectiveSystemPrompt = useSystemPrompt
-                  ? systemPrompt || DEFAULT_SYSTEM_PROMPT
-                  : contextContent || DEFAULT_SYSTEM_PROMPT;
-
-                const previousMessages = conversation.messages.map((msg) => {
-                  return { role: msg.role, content: msg.content };
-                });
-
-                await processWithAI(
-                  transcription,
-                  effectiveSystemPrompt,
-                  previousMessages
-                );
-              } else {
-                setError("Received empty transcription");
+              setIsProcessing(true);
+
+              let transcription: string;
+              try {
+                const texts = await Promise.all(pending.map(transcribe));
+                transcription = texts
+                  .map((text) => (text || "").trim())
+                  .filter(Boolean)
+                  .join(" ");
+              } catch (sttError: any) {
+                showSttError(sttError);
+                return;
               }
-            } catch (sttError: any) {
-              console.error("STT Error:", sttError);
-              setError(sttError.message || "Failed to transcribe audio");
-              setIsPopoverOpen(true);
+              await respond(transcription);
+            } catch (err) {
+              setError("Failed to process speech");
+            } finally {
+              setIsProcessing(false);
             }
-          } catch (err) {
-            setError("Failed to process speech");
-          } finally {
-            setIsProcessing(false);
           }
-        });
+        );
       } catch (err) {
         setError("Failed to setup speech listener");
       }
@@ -312,6 +501,9 @@ export function useSystemAudio() {
 
     return () => {
       if (speechUnlisten) speechUnlisten();
+      if (segmentUnlisten) segmentUnlisten();
+      if (savedUnlisten) savedUnlisten();
+      if (meetingUnlisten) meetingUnlisten();
     };
   }, [
     capturing,
@@ -440,21 +632,23 @@ export function useSystemAudio() {
 
       // Start a new continuous recording session
       await invoke<string>("start_system_audio_capture", {
-        vadConfig: vadConfig,
+        vadConfig: captureConfig,
         deviceId: deviceId,
       });
     } catch (err) {
       console.error("Failed to start continuous recording:", err);
       setError(`Failed to start recording: ${err}`);
     }
-  }, [vadConfig, selectedAudioDevices.output.id]);
+  }, [captureConfig, selectedAudioDevices.output.id]);
 
   // Ignore current recording (stop without transcription)
   const ignoreContinuousRecording = useCallback(async () => {
     try {
       if (!isContinuousMode || !isRecordingInContinuousMode) return;
 
-      // Stop the capture without processing
+      // Stop the capture without processing; the backend deletes the
+      // recorded segments
+      segmentAudioRef.current = [];
       await invoke<string>("stop_system_audio_capture");
 
       // Reset states
@@ -486,6 +680,7 @@ export function useSystemAudio() {
         setError("");
 
         let fullResponse = "";
+        let metadata: MessageMetadata | undefined;
 
         if (!selectedAIProvider.provider) {
           setError("No AI provider selected.");
@@ -508,6 +703,9 @@ export function useSystemAudio() {
             history: previousMessages,
             userMessage: transcription,
             imagesBase64: [],
+            onMetadata: (m) => {
+              metadata = m;
+            },
           })) {
             fullResponse += chunk;
             setLastAIResponse((prev) => prev + chunk);
@@ -534,6 +732,7 @@ export function useSystemAudio() {
                 role: "assistant" as const,
                 content: fullResponse,
                 timestamp: timestamp + 1,
+                metadata,
               },
               ...prev.messages,
             ],
@@ -594,9 +793,22 @@ export function useSystemAudio() {
           ? selectedAudioDevices.output.id
           : null;
 
+      if (meetingMode) {
+        ownSpeechRef.current = [];
+        await invoke<string>("start_meeting_capture", {
+          vadConfig: captureConfig,
+          microphoneId:
+            selectedAudioDevices.input.id !== "default"
+              ? selectedAudioDevices.input.id
+              : null,
+          deviceId: deviceId,
+        });
+        return;
+      }
+
       // Start capture with VAD config
       await invoke<string>("start_system_audio_capture", {
-        vadConfig: vadConfig,
+        vadConfig: captureConfig,
         deviceId: deviceId,
       });
     } catch (err) {
@@ -604,7 +816,12 @@ export function useSystemAudio() {
       setError(errorMessage);
       setIsPopoverOpen(true);
     }
-  }, [vadConfig, selectedAudioDevices.output.id]);
+  }, [
+    captureConfig,
+    meetingMode,
+    selectedAudioDevices.input.id,
+    selectedAudioDevices.output.id,
+  ]);
 
   const stopCapture = useCallback(async () => {
     try {
@@ -793,6 +1010,11 @@ export function useSystemAudio() {
     }
   }, []);
 
+  const updateMeetingMode = useCallback((enabled: boolean) => {
+    setMeetingMode(enabled);
+    safeLocalStorage.setItem("meeting_mode", String(enabled));
+  }, []);
+
   useEffect(() => {
     if (capturing) {
       setIsContinuousMode(!vadConfig.enabled);
@@ -884,6 +1106,7 @@ export function useSystemAudio() {
     isProcessing,
     isAIProcessing,
     lastTranscription,
+    liveTranscript,
     lastAIResponse,
     error,
     setupRequired,
@@ -916,6 +1139,9 @@ export function useSystemAudio() {
     // VAD configuration
     vadConfig,
     updateVadConfiguration,
+    // Meeting capture
+    meetingMode,
+    setMeetingMode: updateMeetingMode,
     // Continuous recording
     isContinuousMode,
     isRecordingInContinuousMode,
@@ -1,8 +1,34 @@
+import { useEffect, useState } from "react";
 import { Sidebar } from "@/components";
-import { Outlet } from "react-router-dom";
+import { getDatabaseEncryptionStatus } from "@/lib";
+import { Link, Outlet, useLocation } from "react-router-dom";
 import { ErrorBoundary } from "react-error-boundary";
 import { ErrorLayout } from "./ErrorLayout";
 
+/** Points to Settings when the database could not be opened at launch */
+const DatabaseAlert = () => {
+  const { pathname } = useLocation();
+  const [startupError, setStartupError] = useState<string | undefined>();
+
+  useEffect(() => {
+    getDatabaseEncryptionStatus()
+      .then((status) => setStartupError(status.startupError))
+      .catch((e) => setStartupError(String(e)));
+  }, [pathname]);
+
+  if (!startupError || pathname === "/settings") return null;
+  return (
+    <div className="mt-10 p-3 bg-red-50 border border-red-200 rounded-md">
+      <p className="text-xs text-red-700 font-medium">
+        Chat history is unavailable. {startupError}{" "}
+        <Link to="/settings" className="underline">
+          Open Settings
+        </Link>
+      </p>
+    </div>
+  );
+};
+
 export const DashboardLayout = () => {
   return (
     <ErrorBoundary
@@ -25,6 +51,7 @@ export const DashboardLayout = () => {
         <Sidebar />
         {/* Main Content */}
         <main className="flex flex-1 flex-col overflow-hidden px-8">
+          <DatabaseAlert />
           <Outlet />
         </main>
       </div>
@@ -304,6 +304,31 @@ describe('FreelyClaudeTool.executePromptWithStreaming — Tauri context', () =>
     expect(result.model).toBe('claude-opus-4');
   });
 
+  it('collects token usage and tool calls with their results', async () => {
+    mockTauriEvents = [
+      {
+        type: 'partial',
+        toolCalls: [{ id: 't1', name: 'Bash', input: { command: 'ls' }, isError: false }],
+      },
+      {
+        type: 'partial',
+        toolCalls: [{ id: 't1', name: '', input: null, output: 'a.txt', isError: false }],
+      },
+      { type: 'complete', tokenUsage: { input_tokens: 12, output_tokens: 34 } },
+    ];
+
+    const tool = makeTool();
+    const result = await tool.executePromptWithStreaming(
+      toSessionID(generateId()),
+      'Hello'
+    );
+
+    expect(result.tokenUsage).toEqual({ input: 12, output: 34 });
+    expect(result.toolCalls).toEqual([
+      { id: 't1', name: 'Bash', input: { command: 'ls' }, output: 'a.txt', isError: false },
+    ]);
+  });
+
   it('updates sessionsRepo with agentSessionId', async () => {
     mockTauriEvents = [
       { type: 'partial', textChunk: 'Hi', agentSessionId: 'agent-sess-abc' },
@@ -339,6 +364,19 @@ describe('FreelyClaudeTool.executePromptWithStreaming — Tauri context', () =>
     expect(cbs.onStreamEnd).not.toHaveBeenCalled();
   });
 
+  it('sends the active project profile id with the run', async () => {
+    const invoke = vi.fn(async (cmd: string, _args?: any) =>
+      cmd === 'get_active_project_profile' ? { id: 'profile-1' } : undefined
+    );
+    (window as any).__TAURI_INTERNALS__.invoke = invoke;
+
+    const tool = makeTool();
+    await tool.executePromptWithStreaming(toSessionID(generateId()), 'Hello');
+
+    const run = invoke.mock.calls.find(([cmd]) => cmd === 'run_claude');
+    expect(run?.[1]?.payload.profileId).toBe('profile-1');
+  });
+
   it('returns error in result when Tauri invoke rejects', async () => {
     mockInvokeError = new Error('Invoke failed');
 
@@ -3,6 +3,7 @@ import {
   FreelyAgentOrchestrator,
   AGENT_PROVIDER_IDS,
 } from '../orchestrator.js';
+import { FreelyClaudeTool } from '../claude/freely-claude-tool.js';
 import { createStorageAdapter } from '../storage-adapter.js';
 
 // Helper to drain an async generator into an array
@@ -104,6 +105,32 @@ describe('FreelyAgentOrchestrator.execute', () => {
       expect(chunks).toEqual([]);
     });
 
+    it('reports the run metadata through onMetadata', async () => {
+      const spy = vi
+        .spyOn(FreelyClaudeTool.prototype, 'executePromptWithStreaming')
+        .mockResolvedValue({
+          userMessageId: 'u' as any,
+          assistantMessageIds: [],
+          responseText: '',
+          toolType: 'claude-code',
+          model: 'claude-sonnet-4',
+          tokenUsage: { input: 5, output: 7 },
+          toolCalls: [{ id: 't1', name: 'Read', input: {}, output: 'ok' }],
+        });
+      const onMetadata = vi.fn();
+
+      await collectAll(
+        orchestrator.execute({ toolType: 'claude-code', userMessage: 'Hi', onMetadata })
+      );
+
+      expect(onMetadata).toHaveBeenCalledWith({
+        model: 'claude-sonnet-4',
+        tokenUsage: { input_tokens: 5, output_tokens: 7 },
+        toolCalls: [{ id: 't1', name: 'Read', input: {}, output: 'ok' }],
+      });
+      spy.mockRestore();
+    });
+
     // TODO: These tests need a full Tauri event system mock (__TAURI_EVENT_PLUGIN_INTERNALS__)
     // since Claude tool now uses event-based streaming via tauriListen rather than
     // returning chunks directly from invoke.
@@ -239,3 +266,38 @@ describe('FreelyAgentOrchestrator.getAvailableTools', () => {
     expect(tools['gemini-sdk']).toBe(true);
   });
 });
+
+// ============================================================================
+// resolveToolType
+// ============================================================================
+
+describe('FreelyAgentOrchestrator.resolveToolType', () => {
+  afterEach(() => {
+    delete (window as any).__TAURI_INTERNALS__;
+  });
+
+  function withActiveProfile(profile: unknown) {
+    (window as any).__TAURI_INTERNALS__ = {
+      invoke: vi.fn().mockResolvedValue(profile),
+      transformCallback: () => 0,
+    };
+  }
+
+  it('keeps the selected provider outside Tauri', async () => {
+    delete (window as any).__TAURI_INTERNALS__;
+    const orchestrator = new FreelyAgentOrchestrator(createStorageAdapter());
+    expect(await orchestrator.resolveToolType('codex')).toBe('codex');
+  });
+
+  it("uses the active profile's default backend", async () => {
+    withActiveProfile({ id: 'p1', defaultBackend: 'gemini' });
+    const orchestrator = new FreelyAgentOrchestrator(createStorageAdapter());
+    expect(await orchestrator.resolveToolType('claude-code')).toBe('gemini-sdk');
+  });
+
+  it('keeps the selected provider when no profile is active', async () => {
+    withActiveProfile(null);
+    const orchestrator = new FreelyAgentOrchestrator(createStorageAdapter());
+    expect(await orchestrator.resolveToolType('codex')).toBe('codex');
+  });
+});
@@ -11,6 +11,7 @@
  */
 
 import {
+  type AgentToolCall,
   type FreelyExecutionResult,
   type ImportOptions,
   MessageRole,
@@ -56,6 +57,8 @@ interface ClaudeInvokePayload {
   agentSessionId?: string;
   /** Optional system prompt prepended to the user prompt for per-conversation context */
   systemPrompt?: string;
+  /** Project profile to run under; the backend applies none when omitted */
+  profileId?: string;
 }
 
 /**
@@ -69,9 +72,26 @@ interface ClaudeStreamEvent {
   resolvedModel?: string;
   agentSessionId?: string;
   tokenUsage?: { input_tokens: number; output_tokens: number };
+  toolCalls?: AgentToolCall[];
   error?: string;
 }
 
+/**
+ * Fold a stream event's tool calls into `calls`. Results carry the id of the
+ * tool use they answer, so they fill in that entry instead of adding a new one.
+ */
+export function mergeToolCalls(calls: AgentToolCall[], incoming: AgentToolCall[] = []): void {
+  for (const call of incoming) {
+    const existing = call.id ? calls.find((c) => c.id === call.id) : undefined;
+    if (existing && call.output !== undefined) {
+      existing.output = call.output;
+      existing.isError = call.isError;
+    } else if (!existing) {
+      calls.push({ ...call });
+    }
+  }
+}
+
 // Tauri invoke is available at runtime in the Tauri WebView context
 declare const __TAURI_INTERNALS__: unknown;
 
@@ -87,6 +107,18 @@ async function tauriInvoke<T>(command: string, args?: Record<string, unknown>):
   return invoke(command, args);
 }
 
+/** Id of the active project profile, if any. Lookup failures never block a run. */
+async function activeProfileId(): Promise<string | undefined> {
+  try {
+    const profile = await tauriInvoke<{ id: string } | null | undefined>(
+      'get_active_project_profile'
+    );
+    return profile?.id;
+  } catch {
+    return undefined;
+  }
+}
+
 // ============================================================================
 // FreelyClaudeTool
 // ============================================================================
@@ -150,6 +182,8 @@ export class FreelyClaudeTool {
     const assistantMessageIds: MessageID[] = [];
     let responseText = '';
     let resolvedModel: string | undefined;
+    let tokenUsage: FreelyExecutionResult['tokenUsage'];
+    const toolCalls: AgentToolCall[] = [];
     let wasStopped = false;
     /** Captured from stream events; persisted after stream completes to avoid race conditions */
     let capturedAgentSessionId: string | undefined;
@@ -191,6 +225,7 @@ export class FreelyClaudeTool {
         permissionMode,
         model,
         agentSessionId,
+        profileId: await activeProfileId(),
       };
 
       // Listen for real-time streaming events from the Rust backend
@@ -209,6 +244,13 @@ export class FreelyClaudeTool {
         }
 
         if (event.resolvedModel) resolvedModel = event.resolvedModel;
+        if (event.tokenUsage) {
+          tokenUsage = {
+            input: event.tokenUsage.input_tokens,
+            output: event.tokenUsage.output_tokens,
+          };
+        }
+        mergeToolCalls(toolCalls, event.toolCalls);
 
         if (event.agentSessionId) {
           capturedAgentSessionId = event.agentSessionId;
@@ -259,6 +301,8 @@ export class FreelyClaudeTool {
       responseText,
       toolType: this.toolType,
       model: resolvedModel,
+      tokenUsage,
+      toolCalls: toolCalls.length > 0 ? toolCalls : undefined,
       wasStopped,
     };
   }
@@ -25,6 +25,7 @@ export {
   AGENT_PROVIDER_IDS,
   type AgentProviderId,
   type AgentExecuteParams,
+  type AgentRunMetadata,
 } from './orchestrator.js';
 
 // Types
@@ -54,6 +55,7 @@ export type {
   TaskResult,
   MessageRange,
   FreelyExecutionResult,
+  AgentToolCall,
   ISessionsRepository,
   TaskStatus,
 } from './types.js';
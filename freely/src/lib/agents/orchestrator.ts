@@ -21,6 +21,8 @@ import {
   type FreelyStorageAdapter,
 } from './storage-adapter.js';
 import {
+  type AgentToolCall,
+  type FreelyExecutionResult,
   type SessionID,
   type StreamingCallbacks,
   type MessageID,
@@ -63,6 +65,20 @@ async function killAgentProcess(sessionId: string): Promise<void> {
 export const AGENT_PROVIDER_IDS = ['claude-code', 'codex', 'gemini-sdk'] as const;
 export type AgentProviderId = (typeof AGENT_PROVIDER_IDS)[number];
 
+/** Agent provider for each backend a project profile can default to */
+const PROFILE_BACKEND_PROVIDERS: Record<string, AgentProviderId> = {
+  claude: 'claude-code',
+  codex: 'codex',
+  gemini: 'gemini-sdk',
+};
+
+/** Details of a finished run, in the shape saved on the assistant message */
+export interface AgentRunMetadata {
+  model?: string;
+  tokenUsage?: { input_tokens: number; output_tokens: number };
+  toolCalls?: AgentToolCall[];
+}
+
 export interface AgentExecuteParams {
   toolType: AgentProviderId;
   userMessage: string;
@@ -78,6 +94,8 @@ export interface AgentExecuteParams {
   /** Model override (e.g. "claude-sonnet-4-5-20250514", "haiku") — passed as --model to CLI */
   model?: string;
   signal?: AbortSignal;
+  /** Called once the run finishes with the model, token usage and tool calls it reported */
+  onMetadata?: (metadata: AgentRunMetadata) => void;
 }
 
 export class FreelyAgentOrchestrator {
@@ -190,6 +208,7 @@ export class FreelyAgentOrchestrator {
       this.claudeTool
         .executePromptWithStreaming(sessionId, params.userMessage, undefined, undefined, callbacks, undefined, params.model)
         .then((result) => {
+          reportMetadata(params, result);
           if (result.error) finish(new Error(result.error));
           else finish();
         })
@@ -243,6 +262,7 @@ export class FreelyAgentOrchestrator {
       this.codexTool
         .executePromptWithStreaming(sessionId, prompt, undefined, undefined, callbacks, undefined, apiKey)
         .then((result) => {
+          reportMetadata(params, result);
           if (result.error) finish(new Error(result.error));
           else finish();
         })
@@ -291,6 +311,7 @@ export class FreelyAgentOrchestrator {
       this.geminiTool
         .executePromptWithStreaming(sessionId, prompt, undefined, undefined, callbacks, undefined, apiKey)
         .then((result) => {
+          reportMetadata(params, result);
           if (result.error) finish(new Error(result.error));
           else finish();
         })
@@ -318,12 +339,40 @@ export class FreelyAgentOrchestrator {
       'gemini-sdk': gemini,
     };
   }
+
+  /**
+   * Provider a run should use: the active project profile's default backend,
+   * or `selected` when no profile is active. Lookup failures never block a run.
+   */
+  async resolveToolType(selected: AgentProviderId): Promise<AgentProviderId> {
+    try {
+      const profile = await tauriInvoke<{ defaultBackend?: string } | null>(
+        'get_active_project_profile'
+      );
+      const backend = profile?.defaultBackend;
+      return (backend && PROFILE_BACKEND_PROVIDERS[backend]) || selected;
+    } catch {
+      return selected;
+    }
+  }
 }
 
 // ============================================================================
 // Helpers: streaming callbacks → async generator bridge
 // ============================================================================
 
+/** Hand the run's model, usage and tool calls to the caller, if it asked for them */
+function reportMetadata(params: AgentExecuteParams, result: FreelyExecutionResult): void {
+  if (!params.onMetadata) return;
+  params.onMetadata({
+    model: result.model,
+    tokenUsage: result.tokenUsage
+      ? { input_tokens: result.tokenUsage.input, output_tokens: result.tokenUsage.output }
+      : undefined,
+    toolCalls: result.toolCalls,
+  });
+}
+
 /** Build StreamingCallbacks that funnel chunks into the provided enqueue/finish functions */
 function buildStreamingCallbacks(
   enqueue: (chunk: string) => void,
@@ -244,6 +244,15 @@ export interface MessageRange {
 // Execution Result (Freely-specific)
 // ============================================================================
 
+/** Tool use reported by an agent, with its result once one arrives */
+export interface AgentToolCall {
+  id?: string;
+  name: string;
+  input: unknown;
+  output?: string;
+  isError?: boolean;
+}
+
 export interface FreelyExecutionResult {
   /** ID of the user message created */
   userMessageId: MessageID;
@@ -260,6 +269,8 @@ export interface FreelyExecutionResult {
     input: number;
     output: number;
   };
+  /** Tools the agent used while producing the response */
+  toolCalls?: AgentToolCall[];
   /** True if execution was stopped early */
   wasStopped?: boolean;
   /** Any error that occurred */
@@ -1,45 +1,10 @@
-import { getDatabase } from "./config";
+import { invoke } from "@tauri-apps/api/core";
 import { ChatConversation } from "@/types";
 import { safeLocalStorage } from "@/lib";
 
 // Legacy localStorage key for migration purposes
 const LEGACY_CHAT_HISTORY_KEY = "chat_history";
 
-/**
- * Database conversation type (flattened for SQL)
- */
-interface DbConversation {
-  id: string;
-  title: string;
-  created_at: number;
-  updated_at: number;
-}
-
-/**
- * Database message type (flattened for SQL)
- */
-interface DbMessage {
-  id: string;
-  conversation_id: string;
-  role: "user" | "assistant" | "system";
-  content: string;
-  timestamp: number;
-  attached_files: string | null; // JSON string
-}
-
-/**
- * Safely parse JSON with error handling
- */
-function safeJsonParse<T>(jsonString: string | null, fallback: T): T {
-  if (!jsonString) return fallback;
-  try {
-    return JSON.parse(jsonString) as T;
-  } catch (error) {
-    console.error("Failed to parse JSON:", error);
-    return fallback;
-  }
-}
-
 /**
  * Validate conversation data
  */
@@ -86,112 +51,23 @@ function validateMessage(message: any): boolean {
 }
 
 /**
- * Create a new conversation with transaction safety
+ * Create a new conversation.
+ *
+ * Writes go through the Rust chat history repository, which runs them in a
+ * transaction and keeps the message tree (branches) consistent.
  */
 export async function createConversation(
   conversation: ChatConversation
 ): Promise<ChatConversation> {
-  if (!validateConversation(conversation)) {
-    throw new Error("Invalid conversation data");
-  }
-
-  const db = await getDatabase();
-
-  try {
-    // Insert conversation
-    await db.execute(
-      "INSERT INTO conversations (id, title, created_at, updated_at) VALUES (?, ?, ?, ?)",
-      [
-        conversation.id,
-        conversation.title,
-        conversation.createdAt || Date.now(),
-        conversation.updatedAt || Date.now(),
-      ]
-    );
-
-    // Insert all messages
-    for (const message of conversation.messages) {
-      if (!validateMessage(message)) {
-        console.warn("Skipping invalid message in conversation creation");
-        continue;
-      }
-
-      const attachedFilesJson = message.attachedFiles
-        ? JSON.stringify(message.attachedFiles)
-        : null;
-
-      await db.execute(
-        "INSERT INTO messages (id, conversation_id, role, content, timestamp, attached_files) VALUES (?, ?, ?, ?, ?, ?)",
-        [
-          message.id,
-          conversation.id,
-          message.role,
-          message.content,
-          message.timestamp,
-          attachedFilesJson,
-        ]
-      );
-    }
-
-    return conversation;
-  } catch (error) {
-    console.error("Failed to create conversation:", error);
-    // Rollback: delete conversation if message insertion failed
-    await db
-      .execute("DELETE FROM conversations WHERE id = ?", [conversation.id])
-      .catch(() => {});
-    throw error;
-  }
+  return saveConversation(conversation);
 }
 
 /**
- * Get all conversations with messages in a single optimized query
+ * Get all conversations, each with its active branch of messages
  */
 export async function getAllConversations(): Promise<ChatConversation[]> {
-  const db = await getDatabase();
-
   try {
-    // Get all conversations
-    const conversations = await db.select<DbConversation[]>(
-      "SELECT * FROM conversations ORDER BY updated_at DESC"
-    );
-
-    if (conversations.length === 0) {
-      return [];
-    }
-
-    // Get all messages for these conversations in one query
-    const conversationIds = conversations.map((c) => c.id);
-    const placeholders = conversationIds.map(() => "?").join(",");
-    const allMessages = await db.select<DbMessage[]>(
-      `SELECT * FROM messages WHERE conversation_id IN (${placeholders}) ORDER BY conversation_id, timestamp ASC`,
-      conversationIds
-    );
-
-    // Group messages by conversation_id
-    const messagesByConversation = new Map<string, DbMessage[]>();
-    for (const msg of allMessages) {
-      if (!messagesByConversation.has(msg.conversation_id)) {
-        messagesByConversation.set(msg.conversation_id, []);
-      }
-      messagesByConversation.get(msg.conversation_id)!.push(msg);
-    }
-
-    // Build result
-    return conversations.map((conv) => ({
-      id: conv.id,
-      title: conv.title,
-      createdAt: conv.created_at,
-      updatedAt: conv.updated_at,
-      messages:
-        messagesByConversation.get(conv.id)?.map((msg) => ({
-          id: msg.id,
-          role: msg.role,
-          content: msg.content,
-          timestamp: msg.timestamp,
-          attachedFiles: safeJsonParse(msg.attached_files, undefined),
-        })) || [],
-    }));
+    return await invoke<ChatConversation[]>("get_all_chat_conversations");
   } catch (error) {
     console.error("Failed to get all conversations:", error);
     throw error;
@@ -199,7 +75,7 @@ export async function getAllConversations(): Promise<ChatConversation[]> {
 }
 
 /**
- * Get a single conversation by ID
+ * Get a single conversation by ID, with its active branch of messages
  */
 export async function getConversationById(
   id: string
@@ -209,40 +85,10 @@ export async function getConversationById(
     return null;
   }
 
-  const db = await getDatabase();
-
   try {
-    // Get conversation
-    const conversations = await db.select<DbConversation[]>(
-      "SELECT * FROM conversations WHERE id = ?",
-      [id]
-    );
-
-    if (conversations.length === 0) {
-      return null;
-    }
-
-    const conv = conversations[0];
-
-    // Get messages
-    const messages = await db.select<DbMessage[]>(
-      "SELECT * FROM messages WHERE conversation_id = ? ORDER BY timestamp ASC",
-      [id]
-    );
-
-    return {
-      id: conv.id,
-      title: conv.title,
-      createdAt: conv.created_at,
-      updatedAt: conv.updated_at,
-      messages: messages.map((msg) => ({
-        id: msg.id,
-        role: msg.role,
-        content: msg.content,
-        timestamp: msg.timestamp,
-        attachedFiles: safeJsonParse(msg.attached_files, undefined),
-      })),
-    };
+    return await invoke<ChatConversation | null>("get_chat_conversation", {
+      id,
+    });
   } catch (error) {
     console.error(`Failed to get conversation ${id}:`, error);
     return null;
@@ -250,92 +96,12 @@ export async function getConversationById(
 }
 
 /**
- * Update a conversation with transaction safety
+ * Update a conversation's active branch. Other branches are kept.
  */
 export async function updateConversation(
   conversation: ChatConversation
 ): Promise<ChatConversation> {
-  if (!validateConversation(conversation)) {
-    throw new Error("Invalid conversation data");
-  }
-
-  const db = await getDatabase();
-
-  try {
-    // Update conversation
-    const updateResult = await db.execute(
-      "UPDATE conversations SET title = ?, updated_at = ? WHERE id = ?",
-      [conversation.title, conversation.updatedAt, conversation.id]
-    );
-
-    if (updateResult.rowsAffected === 0) {
-      throw new Error("Conversation not found");
-    }
-
-    // Get existing messages for backup
-    const existingMessages = await db.select<DbMessage[]>(
-      "SELECT * FROM messages WHERE conversation_id = ?",
-      [conversation.id]
-    );
-
-    // Delete existing messages
-    await db.execute("DELETE FROM messages WHERE conversation_id = ?", [
-      conversation.id,
-    ]);
-
-    // Insert updated messages
-    try {
-      for (const message of conversation.messages) {
-        if (!validateMessage(message)) {
-          console.warn("Skipping invalid message in conversation update");
-          continue;
-        }
-
-        const attachedFilesJson = message.attachedFiles
-          ? JSON.stringify(message.attachedFiles)
-          : null;
-
-        await db.execute(
-          "INSERT INTO messages (id, conversation_id, role, content, timestamp, attached_files) VALUES (?, ?, ?, ?, ?, ?)",
-          [
-            message.id,
-            conversation.id,
-            message.role,
-            message.content,
-            message.timestamp,
-            attachedFilesJson,
-          ]
-        );
-      }
-    } catch (messageError) {
-      // Rollback: restore original messages
-      console.error(
-        "Failed to insert new messages, restoring backup:",
-        messageError
-      );
-      for (const msg of existingMessages) {
-        await db
-          .execute(
-            "INSERT INTO messages (id, conversation_id, role, content, timestamp, attached_files) VALUES (?, ?, ?, ?, ?, ?)",
-            [
-              msg.id,
-              msg.conversation_id,
-              msg.role,
-              msg.content,
-              msg.timestamp,
-              msg.attached_files,
-            ]
-          )
-          .catch(() => {});
-      }
-      throw messageError;
-    }
-
-    return conversation;
-  } catch (error) {
-    console.error("Failed to update conversation:", error);
-    throw error;
-  }
+  return saveConversation(conversation);
 }
 
 /**
@@ -348,14 +114,18 @@ export async function saveConversation(
     throw new Error("Invalid conversation data");
   }
 
-  try {
-    const existing = await getConversationById(conversation.id);
-
-    if (existing) {
-      return await updateConversation(conversation);
-    } else {
-      return await createConversation(conversation);
+  const messages = conversation.messages.filter((message) => {
+    if (!validateMessage(message)) {
+      console.warn("Skipping invalid message in conversation save");
+      return false;
     }
+    return true;
+  });
+
+  try {
+    return await invoke<ChatConversation>("save_chat_conversation", {
+      conversation: { ...conversation, messages },
+    });
   } catch (error) {
     console.error("Failed to save conversation:", error);
     throw error;
@@ -371,14 +141,8 @@ export async function deleteConversation(id: string): Promise<boolean> {
     return false;
   }
 
-  const db = await getDatabase();
-
   try {
-    const result = await db.execute("DELETE FROM conversations WHERE id = ?", [
-      id,
-    ]);
-
-    return result.rowsAffected > 0;
+    return await invoke<boolean>("delete_chat_conversation", { id });
   } catch (error) {
     console.error(`Failed to delete conversation ${id}:`, error);
     throw error;
@@ -389,12 +153,8 @@ export async function deleteConversation(id: string): Promise<boolean> {
  * Delete all conversations and messages
  */
 export async function deleteAllConversations(): Promise<void> {
-  const db = await getDatabase();
-
   try {
-    // Delete in correct order (messages first due to foreign key)
-    await db.execute("DELETE FROM messages");
-    await db.execute("DELETE FROM conversations");
+    await invoke("delete_all_chat_conversations");
   } catch (error) {
     console.error("Failed to delete all conversations:", error);
     throw error;
@@ -455,9 +215,6 @@ export async function migrateLocalStorageToSQLite(): Promise<{
       return { success: true, migratedCount: 0 };
     }
 
-    // Get database instance
-    const db = await getDatabase();
-
     // Migrate each conversation
     let migratedCount = 0;
     let errorCount = 0;
@@ -480,53 +237,18 @@ export async function migrateLocalStorageToSQLite(): Promise<{
           continue;
         }
 
-        // Insert conversation
-        await db.execute(
-          "INSERT INTO conversations (id, title, created_at, updated_at) VALUES (?, ?, ?, ?)",
-          [
-            conversation.id,
-            conversation.title,
-            conversation.createdAt || Date.now(),
-            conversation.updatedAt || Date.now(),
-          ]
-        );
-
-        // Insert messages
-        if (
-          Array.isArray(conversation.messages) &&
-          conversation.messages.length > 0
-        ) {
-          for (const message of conversation.messages) {
-            // Validate message
-            if (
-              !message?.id ||
-              !message?.role ||
-              typeof message?.content !== "string"
-            ) {
-              console.warn(
-                `Skipping invalid message in conversation ${conversation.id}:`,
-                message
-              );
-              continue;
-            }
-
-            const attachedFilesJson = message.attachedFiles
-              ? JSON.stringify(message.attachedFiles)
-              : null;
-
-            await db.execute(
-              "INSERT INTO messages (id, conversation_id, role, content, timestamp, attached_files) VALUES (?, ?, ?, ?, ?, ?)",
-              [
-                message.id,
-                conversation.id,
-                message.role,
-                message.content,
-                message.timestamp || Date.now(),
-                attachedFilesJson,
-              ]
-            );
-          }
-        }
+        await saveConversation({
+          ...conversation,
+          createdAt: conversation.createdAt || Date.now(),
+          updatedAt: conversation.updatedAt || Date.now(),
+          messages: (Array.isArray(conversation.messages)
+            ? conversation.messages
+            : []
+          ).map((message) => ({
+            ...message,
+            timestamp: message?.timestamp || Date.now(),
+          })),
+        });
 
         migratedCount++;
       } catch (convError) {
@@ -534,11 +256,8 @@ export async function migrateLocalStorageToSQLite(): Promise<{
           `Failed to migrate conversation ${conversation?.id}:`,
           convError
         );
+        // saveConversation is transactional, so nothing partial is left behind
         errorCount++;
-        // Clean up partially migrated conversation
-        await db
-          .execute("DELETE FROM conversations WHERE id = ?", [conversation?.id])
-          .catch(() => {});
       }
     }
 
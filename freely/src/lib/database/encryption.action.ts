@@ -0,0 +1,31 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export interface DatabaseEncryptionStatus {
+  encrypted: boolean;
+  /** False while the database is encrypted and no key is loaded */
+  unlocked: boolean;
+  keyInKeychain: boolean;
+  /** Why the database could not be opened at launch, until it is unlocked */
+  startupError?: string;
+}
+
+/**
+ * Get the database encryption status, including any startup error
+ */
+export async function getDatabaseEncryptionStatus(): Promise<DatabaseEncryptionStatus> {
+  return invoke<DatabaseEncryptionStatus>("get_database_encryption_status");
+}
+
+/**
+ * Unlock the database with a recovery key, or with the keychain entry when
+ * no key is given. Rejects with the backend's error message on failure.
+ */
+export async function unlockDatabase(
+  recoveryKey?: string,
+  remember = false
+): Promise<DatabaseEncryptionStatus> {
+  return invoke<DatabaseEncryptionStatus>("unlock_database", {
+    recoveryKey: recoveryKey?.trim() || undefined,
+    remember,
+  });
+}
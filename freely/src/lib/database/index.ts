@@ -1,3 +1,3 @@
-export * from "./config";
 export * from "./system-prompt.action";
 export * from "./chat-history.action";
+export * from "./encryption.action";
@@ -1,7 +1,11 @@
-import { getDatabase } from "./config";
+import { invoke } from "@tauri-apps/api/core";
 import type {
+  PromptPackImportSummary,
+  PromptVariable,
+  ResolvedPrompt,
   SystemPrompt,
   SystemPromptInput,
+  SystemPromptVersion,
   UpdateSystemPromptInput,
 } from "@/types";
 
@@ -11,46 +15,14 @@ import type {
 export async function createSystemPrompt(
   input: SystemPromptInput
 ): Promise<SystemPrompt> {
-  const db = await getDatabase();
-
-  // Validate and sanitize input
-  const name = input.name.trim();
-  const prompt = input.prompt.trim();
-
-  if (!name) {
-    throw new Error("System prompt name cannot be empty");
-  }
-
-  if (!prompt) {
-    throw new Error("System prompt text cannot be empty");
-  }
-
-  const result = await db.execute(
-    "INSERT INTO system_prompts (name, prompt) VALUES (?, ?)",
-    [name, prompt]
-  );
-
-  // Get the last inserted row
-  const inserted = await db.select<SystemPrompt[]>(
-    "SELECT * FROM system_prompts WHERE id = ?",
-    [result.lastInsertId]
-  );
-
-  if (!inserted[0]) {
-    throw new Error("Failed to retrieve created system prompt");
-  }
-
-  return inserted[0];
+  return await invoke<SystemPrompt>("create_system_prompt", { input });
 }
 
 /**
  * Get all system prompts
  */
 export async function getAllSystemPrompts(): Promise<SystemPrompt[]> {
-  const db = await getDatabase();
-  return await db.select<SystemPrompt[]>(
-    "SELECT * FROM system_prompts ORDER BY created_at DESC"
-  );
+  return await invoke<SystemPrompt[]>("list_system_prompts");
 }
 
 /**
@@ -59,12 +31,7 @@ export async function getAllSystemPrompts(): Promise<SystemPrompt[]> {
 export async function getSystemPromptById(
   id: number
 ): Promise<SystemPrompt | null> {
-  const db = await getDatabase();
-  const result = await db.select<SystemPrompt[]>(
-    "SELECT * FROM system_prompts WHERE id = ?",
-    [id]
-  );
-  return result[0] || null;
+  return await invoke<SystemPrompt | null>("get_system_prompt", { id });
 }
 
 /**
@@ -74,63 +41,77 @@ export async function updateSystemPrompt(
   id: number,
   input: UpdateSystemPromptInput
 ): Promise<SystemPrompt> {
-  const db = await getDatabase();
-
-  // Build dynamic update query
-  const updates: string[] = [];
-  const values: unknown[] = [];
-
-  if (input.name !== undefined) {
-    const name = input.name.trim();
-    if (!name) {
-      throw new Error("System prompt name cannot be empty");
-    }
-    updates.push("name = ?");
-    values.push(name);
-  }
-
-  if (input.prompt !== undefined) {
-    const prompt = input.prompt.trim();
-    if (!prompt) {
-      throw new Error("System prompt text cannot be empty");
-    }
-    updates.push("prompt = ?");
-    values.push(prompt);
-  }
-
-  if (updates.length === 0) {
-    throw new Error("No fields to update");
-  }
+  return await invoke<SystemPrompt>("update_system_prompt", { id, input });
+}
 
-  values.push(id);
+/**
+ * Delete a system prompt
+ */
+export async function deleteSystemPrompt(id: number): Promise<void> {
+  await invoke("delete_system_prompt", { id });
+}
 
-  await db.execute(
-    `UPDATE system_prompts SET ${updates.join(", ")} WHERE id = ?`,
-    values
-  );
+/**
+ * Get the version history of a system prompt, newest first
+ */
+export async function getSystemPromptVersions(
+  id: number
+): Promise<SystemPromptVersion[]> {
+  return await invoke<SystemPromptVersion[]>("list_system_prompt_versions", {
+    id,
+  });
+}
 
-  const result = await db.select<SystemPrompt[]>(
-    "SELECT * FROM system_prompts WHERE id = ?",
-    [id]
-  );
+/**
+ * Restore an earlier version of a system prompt (saved as a new version)
+ */
+export async function restoreSystemPromptVersion(
+  id: number,
+  version: number
+): Promise<SystemPrompt> {
+  return await invoke<SystemPrompt>("restore_system_prompt_version", {
+    id,
+    version,
+  });
+}
 
-  if (!result[0]) {
-    throw new Error("System prompt not found after update");
-  }
+/**
+ * Export system prompts (all when ids is omitted) to a prompt pack file
+ */
+export async function exportSystemPrompts(
+  destPath: string,
+  ids?: number[]
+): Promise<number> {
+  return await invoke<number>("export_system_prompts", { destPath, ids });
+}
 
-  return result[0];
+/**
+ * Import a prompt pack file
+ */
+export async function importSystemPrompts(
+  srcPath: string
+): Promise<PromptPackImportSummary> {
+  return await invoke<PromptPackImportSummary>("import_system_prompts", {
+    srcPath,
+  });
 }
 
 /**
- * Delete a system prompt
+ * Variables that can be used as {{name}} placeholders in system prompts
  */
-export async function deleteSystemPrompt(id: number): Promise<void> {
-  const db = await getDatabase();
-  const result = await db.execute("DELETE FROM system_prompts WHERE id = ?", [
-    id,
-  ]);
+export async function getPromptVariables(): Promise<PromptVariable[]> {
+  return await invoke<PromptVariable[]>("list_prompt_variables");
+}
 
-  if (result.rowsAffected === 0) {
-    throw new Error("System prompt not found");
-  }
+/**
+ * Expand {{variable}} placeholders in a system prompt before sending
+ */
+export async function resolvePromptVariables(
+  prompt: string,
+  conversationId?: string
+): Promise<ResolvedPrompt> {
+  return await invoke<ResolvedPrompt>("resolve_prompt_variables", {
+    prompt,
+    conversationId,
+  });
 }
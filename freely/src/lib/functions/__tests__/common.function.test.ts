@@ -0,0 +1,49 @@
+import { describe, it, expect } from "vitest";
+import { collectResponseMetadata } from "../common.function";
+import type { MessageMetadata } from "@/types";
+
+describe("collectResponseMetadata", () => {
+  it("reads OpenAI-style model and usage", () => {
+    const metadata: MessageMetadata = {};
+    collectResponseMetadata(metadata, {
+      model: "gpt-4o",
+      usage: { prompt_tokens: 10, completion_tokens: 20 },
+    });
+    expect(metadata).toEqual({
+      model: "gpt-4o",
+      tokenUsage: { input_tokens: 10, output_tokens: 20 },
+    });
+  });
+
+  it("merges Anthropic stream events, keeping input tokens from message_start", () => {
+    const metadata: MessageMetadata = {};
+    collectResponseMetadata(metadata, {
+      type: "message_start",
+      message: { model: "claude-sonnet-4", usage: { input_tokens: 42, output_tokens: 1 } },
+    });
+    collectResponseMetadata(metadata, {
+      type: "content_block_delta",
+      delta: { text: "hi" },
+    });
+    collectResponseMetadata(metadata, {
+      type: "message_delta",
+      usage: { output_tokens: 15 },
+    });
+    expect(metadata).toEqual({
+      model: "claude-sonnet-4",
+      tokenUsage: { input_tokens: 42, output_tokens: 15 },
+    });
+  });
+
+  it("reads Gemini usageMetadata", () => {
+    const metadata: MessageMetadata = {};
+    collectResponseMetadata(metadata, {
+      modelVersion: "gemini-2.0-flash",
+      usageMetadata: { promptTokenCount: 3, candidatesTokenCount: 4 },
+    });
+    expect(metadata).toEqual({
+      model: "gemini-2.0-flash",
+      tokenUsage: { input_tokens: 3, output_tokens: 4 },
+    });
+  });
+});
@@ -1,5 +1,5 @@
 import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
-import { fetchSTT } from "../stt.function";
+import { fetchSTT, sttAudioEncoding, audioMimeType } from "../stt.function";
 import type { STTParams } from "../stt.function";
 import type { TYPE_PROVIDER } from "@/types";
 
@@ -243,6 +243,27 @@ describe("fetchSTT", () => {
       expect(fd.has("file")).toBe(true);
     });
 
+    it("names the uploaded file after the audio encoding", async () => {
+      mockCurl2Json.mockReturnValue(FORM_CURL_JSON);
+      global.fetch = vi.fn().mockResolvedValue(
+        makeResponse(JSON.stringify({ text: "transcribed text" }))
+      );
+
+      const provider = makeProvider({
+        curl: `curl -X POST "https://api.openai.com/v1/audio/transcriptions" -H "Authorization: Bearer {{API_KEY}}" -F model=whisper-1 -F file=@audio.wav`,
+      });
+
+      await fetchSTT({
+        provider,
+        selectedProvider: makeSelectedProvider(),
+        audio: makeBlob("fLaC-data", "audio/flac"),
+      });
+
+      const [, options] = (global.fetch as ReturnType<typeof vi.fn>).mock.calls[0];
+      const file = (options.body as FormData).get("file") as File;
+      expect(file.name).toBe("audio.flac");
+    });
+
     it("extracts transcription text via responseContentPath", async () => {
       mockCurl2Json.mockReturnValue(FORM_CURL_JSON);
       global.fetch = vi.fn().mockResolvedValue(
@@ -563,4 +584,29 @@ describe("fetchSTT", () => {
       expect(url).not.toContain("{{API_KEY}}");
     });
   });
+
+  // -------------------------------------------------------------------------
+  // Audio encoding
+  // -------------------------------------------------------------------------
+
+  describe("audio encoding", () => {
+    it("prefers the provider's first listed encoding", () => {
+      expect(
+        sttAudioEncoding(makeProvider({ audioEncodings: ["opus", "flac"] }))
+      ).toBe("opus");
+    });
+
+    it("falls back to WAV for providers without a list", () => {
+      expect(sttAudioEncoding(makeProvider())).toBe("wav");
+      expect(sttAudioEncoding(undefined)).toBe("wav");
+    });
+
+    it("detects the MIME type from magic bytes", () => {
+      const bytes = (magic: string) =>
+        new Uint8Array([...magic].map((c) => c.charCodeAt(0)));
+      expect(audioMimeType(bytes("fLaC"))).toBe("audio/flac");
+      expect(audioMimeType(bytes("OggS"))).toBe("audio/ogg");
+      expect(audioMimeType(bytes("RIFF"))).toBe("audio/wav");
+    });
+  });
 });
@@ -1,11 +1,12 @@
 import {
   buildDynamicMessages,
+  collectResponseMetadata,
   deepVariableReplacer,
   extractVariables,
   getByPath,
   getStreamingContent,
 } from "./common.function";
-import { Message, TYPE_PROVIDER } from "@/types";
+import { Message, MessageMetadata, TYPE_PROVIDER } from "@/types";
 import { fetch as tauriFetch } from "@tauri-apps/plugin-http";
 import curl2Json from "@bany/curl-to-json";
 import { getResponseSettings, RESPONSE_LENGTHS, LANGUAGES } from "@/lib";
@@ -14,6 +15,7 @@ import {
   freelyAgentOrchestrator,
   type AgentProviderId,
 } from "@/lib/agents";
+import { resolvePromptVariables } from "@/lib/database/system-prompt.action";
 
 function buildEnhancedSystemPrompt(baseSystemPrompt?: string): string {
   const responseSettings = getResponseSettings();
@@ -56,6 +58,8 @@ export async function* fetchAIResponse(params: {
   signal?: AbortSignal;
   /** Conversation ID for session continuity across mic presses */
   conversationId?: string;
+  /** Receives the model and token usage (and tool calls for agents) once the response ends */
+  onMetadata?: (metadata: MessageMetadata) => void;
 }): AsyncIterable<string> {
   try {
     const {
@@ -67,6 +71,7 @@ export async function* fetchAIResponse(params: {
       imagesBase64 = [],
       signal,
       conversationId,
+      onMetadata,
     } = params;
 
     // Check if already aborted
@@ -74,7 +79,18 @@ export async function* fetchAIResponse(params: {
       return;
     }
 
-    const enhancedSystemPrompt = buildEnhancedSystemPrompt(systemPrompt);
+    let resolvedSystemPrompt = systemPrompt;
+    if (systemPrompt?.includes("{{")) {
+      try {
+        resolvedSystemPrompt = (
+          await resolvePromptVariables(systemPrompt, conversationId)
+        ).prompt;
+      } catch (error) {
+        console.warn("Failed to resolve system prompt variables:", error);
+      }
+    }
+
+    const enhancedSystemPrompt = buildEnhancedSystemPrompt(resolvedSystemPrompt);
 
     // Route agent-backed providers (claude-code, codex, gemini-sdk) to orchestrator
     if (
@@ -86,16 +102,22 @@ export async function* fetchAIResponse(params: {
         selectedProvider.variables?.GOOGLE_API_KEY ||
         selectedProvider.variables?.api_key;
 
+      // The active project profile picks the backend; the provider's model
+      // only applies when it is the backend that runs.
+      const toolType = await freelyAgentOrchestrator.resolveToolType(
+        selectedProvider.provider as AgentProviderId
+      );
       const model =
-        selectedProvider.variables?.MODEL ||
-        selectedProvider.variables?.model;
+        toolType === selectedProvider.provider
+          ? selectedProvider.variables?.MODEL || selectedProvider.variables?.model
+          : undefined;
 
       // Claude Code uses --resume for session continuity — skip history to avoid wasted work.
       // Codex and Gemini need history injected into the prompt (no --resume equivalent).
-      const needsHistory = selectedProvider.provider !== "claude-code";
+      const needsHistory = toolType !== "claude-code";
 
       yield* freelyAgentOrchestrator.execute({
-        toolType: selectedProvider.provider as AgentProviderId,
+        toolType,
         userMessage,
         systemPrompt: enhancedSystemPrompt,
         history: needsHistory
@@ -108,6 +130,7 @@ export async function* fetchAIResponse(params: {
         apiKey,
         model,
         signal,
+        onMetadata,
       });
       return;
     }
@@ -247,6 +270,9 @@ export async function* fetchAIResponse(params: {
       }
       const content =
         getByPath(json, provider?.responseContentPath || "") || "";
+      const metadata: MessageMetadata = {};
+      collectResponseMetadata(metadata, json);
+      onMetadata?.(metadata);
       yield content;
       return;
     }
@@ -259,6 +285,7 @@ export async function* fetchAIResponse(params: {
     const reader = response.body.getReader();
     const decoder = new TextDecoder();
     let buffer = "";
+    const metadata: MessageMetadata = {};
 
     while (true) {
       // Check if aborted
@@ -302,6 +329,7 @@ export async function* fetchAIResponse(params: {
           if (!trimmed || trimmed === "[DONE]") continue;
           try {
             const parsed = JSON.parse(trimmed);
+            collectResponseMetadata(metadata, parsed);
             const delta = getStreamingContent(
               parsed,
               provider?.responseContentPath || ""
@@ -315,6 +343,7 @@ export async function* fetchAIResponse(params: {
         }
       }
     }
+    onMetadata?.(metadata);
   } catch (error) {
     throw new Error(
       `Error in fetchAIResponse: ${
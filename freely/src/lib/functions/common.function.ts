@@ -1,4 +1,4 @@
-import { Message } from "@/types";
+import { Message, MessageMetadata } from "@/types";
 
 export function getByPath(obj: any, path: string): any {
   if (!path) return obj;
@@ -234,3 +234,44 @@ export function getStreamingContent(
   // Return null if no content is found after trying all paths.
   return null;
 }
+
+/**
+ * Folds the model and token usage reported by a provider response, or by one
+ * streaming chunk, into `metadata`. Handles OpenAI-style `usage.prompt_tokens`,
+ * Anthropic `usage.input_tokens` (top level or under `message`), and Gemini
+ * `usageMetadata`. Counts a chunk omits keep their earlier value.
+ */
+export function collectResponseMetadata(
+  metadata: MessageMetadata,
+  chunk: any
+): void {
+  if (!chunk || typeof chunk !== "object") return;
+
+  const message =
+    chunk.message && typeof chunk.message === "object" ? chunk.message : {};
+  const model = message.model ?? chunk.model ?? chunk.modelVersion;
+  if (typeof model === "string" && model) {
+    metadata.model = model;
+  }
+
+  const usage = message.usage ?? chunk.usage;
+  let input: unknown;
+  let output: unknown;
+  if (usage && typeof usage === "object") {
+    input = usage.input_tokens ?? usage.prompt_tokens;
+    output = usage.output_tokens ?? usage.completion_tokens;
+  } else if (chunk.usageMetadata && typeof chunk.usageMetadata === "object") {
+    input = chunk.usageMetadata.promptTokenCount;
+    output = chunk.usageMetadata.candidatesTokenCount;
+  }
+  if (typeof input !== "number" && typeof output !== "number") return;
+
+  metadata.tokenUsage = {
+    input_tokens:
+      typeof input === "number" ? input : metadata.tokenUsage?.input_tokens ?? 0,
+    output_tokens:
+      typeof output === "number"
+        ? output
+        : metadata.tokenUsage?.output_tokens ?? 0,
+  };
+}
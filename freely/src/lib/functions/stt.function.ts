@@ -5,7 +5,7 @@ import {
 } from "./common.function";
 import { fetch as tauriFetch } from "@tauri-apps/plugin-http";
 
-import { TYPE_PROVIDER } from "@/types";
+import { AudioEncoding, TYPE_PROVIDER } from "@/types";
 import curl2Json from "@bany/curl-to-json";
 import { AudioSource } from "@/types/completion";
 
@@ -25,6 +25,32 @@ export interface STTParams {
   source?: AudioSource;
 }
 
+// Upload file names by MIME type; some endpoints go by the extension
+const AUDIO_FILE_NAMES: Record<string, string> = {
+  "audio/wav": "audio.wav",
+  "audio/flac": "audio.flac",
+  "audio/ogg": "audio.ogg",
+};
+
+/**
+ * Picks the encoding captured speech should be sent in for a provider.
+ */
+export function sttAudioEncoding(
+  provider: TYPE_PROVIDER | undefined
+): AudioEncoding {
+  return provider?.audioEncodings?.[0] ?? "wav";
+}
+
+/**
+ * Detects the MIME type of encoded speech from its magic bytes.
+ */
+export function audioMimeType(bytes: Uint8Array): string {
+  const magic = String.fromCharCode(...bytes.subarray(0, 4));
+  if (magic === "fLaC") return "audio/flac";
+  if (magic === "OggS") return "audio/ogg";
+  return "audio/wav";
+}
+
 /**
  * Transcribes audio and returns an STTResult with text, source, and timestamp.
  */
@@ -122,7 +148,11 @@ export async function fetchSTT(params: STTParams): Promise<STTResult> {
       const freshBlob = new Blob([await audio.arrayBuffer()], {
         type: audio.type,
       });
-      form.append("file", freshBlob, "audio.wav");
+      form.append(
+        "file",
+        freshBlob,
+        AUDIO_FILE_NAMES[audio.type] ?? "audio.wav"
+      );
       const headerKeys = Object.keys(headers).map((k) =>
         k.toUpperCase().replace(/[-_]/g, "")
       );
@@ -1,10 +1,12 @@
 import { ChatConversation } from "@/types";
+import type { TranscriptUpdate } from "@/hooks";
 import { Markdown, Switch, CopyButton } from "@/components";
 import { BotIcon, HeadphonesIcon, Loader2, SparklesIcon } from "lucide-react";
 import { cn } from "@/lib/utils";
 
 type Props = {
   lastTranscription: string;
+  liveTranscript?: TranscriptUpdate | null;
   lastAIResponse: string;
   isAIProcessing: boolean;
   conversation: ChatConversation;
@@ -14,6 +16,7 @@ type Props = {
 
 export const ResultsSection = ({
   lastTranscription,
+  liveTranscript,
   lastAIResponse,
   isAIProcessing,
   conversation,
@@ -23,7 +26,7 @@ export const ResultsSection = ({
   const hasResponse = lastAIResponse || isAIProcessing;
   const hasHistory = conversation.messages.length > 2;
 
-  if (!hasResponse && !lastTranscription) {
+  if (!hasResponse && !lastTranscription && !liveTranscript) {
     return null;
   }
 
@@ -53,6 +56,19 @@ export const ResultsSection = ({
         </div>
       </div>
 
+      {/* Streaming transcript of the utterance in progress */}
+      {liveTranscript && (
+        <p className="text-[11px] text-muted-foreground">
+          <span className="font-semibold">Live:</span> {liveTranscript.text}
+          {liveTranscript.tentative && (
+            <span className="italic opacity-60">
+              {liveTranscript.text ? " " : ""}
+              {liveTranscript.tentative}
+            </span>
+          )}
+        </p>
+      )}
+
       {/* RESPONSE MODE: System as text, then AI response */}
       {!conversationMode && (
         <div className="space-y-2">
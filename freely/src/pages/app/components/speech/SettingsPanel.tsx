@@ -20,7 +20,7 @@ import {
   RotateCcwIcon,
   ChevronUpIcon,
 } from "lucide-react";
-import { VadConfig } from "@/hooks/useSystemAudio";
+import { VadConfig, VadDetector } from "@/hooks/useSystemAudio";
 import {
   PROMPT_TEMPLATES,
   getPromptTemplateById,
@@ -55,6 +55,9 @@ interface SettingsPanelProps {
   // VAD Config
   vadConfig: VadConfig;
   onUpdateVadConfig: (config: VadConfig) => void;
+  // Meeting capture
+  meetingMode: boolean;
+  setMeetingMode: (enabled: boolean) => void;
   // Context settings
   useSystemPrompt: boolean;
   setUseSystemPrompt: (value: boolean) => void;
@@ -65,6 +68,8 @@ interface SettingsPanelProps {
 export const SettingsPanel = ({
   vadConfig,
   onUpdateVadConfig,
+  meetingMode,
+  setMeetingMode,
   useSystemPrompt,
   setUseSystemPrompt,
   contextContent,
@@ -118,6 +123,14 @@ export const SettingsPanel = ({
       pre_speech_chunks: 12,
       noise_gate_threshold: 0.003,
       max_recording_duration_secs: 180,
+      detector: "energy",
+      vad_aggressiveness: 2,
+      auto_calibrate: vadConfig.auto_calibrate ?? false,
+      streaming_transcription: vadConfig.streaming_transcription ?? false,
+      echo_cancellation: vadConfig.echo_cancellation ?? true,
+      noise_suppression: vadConfig.noise_suppression ?? false,
+      noise_suppression_db: 20,
+      audio_encoding: vadConfig.audio_encoding ?? "wav",
     };
     onUpdateVadConfig(defaultConfig);
   };
@@ -188,6 +201,55 @@ export const SettingsPanel = ({
               </div>
             )}
 
+            {vadConfig.enabled && (
+              <div className="flex items-center justify-between gap-4">
+                <div className="flex-1">
+                  <Label className="text-xs font-medium">Meeting Mode</Label>
+                  <p className="text-[10px] text-muted-foreground mt-0.5">
+                    Also records your microphone and labels who said what.
+                    Applies from the next capture
+                  </p>
+                </div>
+                <Switch checked={meetingMode} onCheckedChange={setMeetingMode} />
+              </div>
+            )}
+
+            {vadConfig.enabled && (
+              <div className="flex items-center justify-between gap-4">
+                <div className="flex-1">
+                  <Label className="text-xs font-medium">
+                    Auto-calibrate to Room Noise
+                  </Label>
+                  <p className="text-[10px] text-muted-foreground mt-0.5">
+                    Measures background noise when capture starts and keeps
+                    adjusting sensitivity
+                  </p>
+                </div>
+                <Switch
+                  checked={vadConfig.auto_calibrate ?? false}
+                  onCheckedChange={(checked) =>
+                    onUpdateVadConfig({ ...vadConfig, auto_calibrate: checked })
+                  }
+                />
+              </div>
+            )}
+
+            <div className="flex items-center justify-between gap-4">
+              <div className="flex-1">
+                <Label className="text-xs font-medium">Noise Suppression</Label>
+                <p className="text-[10px] text-muted-foreground mt-0.5">
+                  Filters steady background noise like fans and hum before
+                  speech is detected and sent
+                </p>
+              </div>
+              <Switch
+                checked={vadConfig.noise_suppression ?? false}
+                onCheckedChange={(checked) =>
+                  onUpdateVadConfig({ ...vadConfig, noise_suppression: checked })
+                }
+              />
+            </div>
+
             {/* Max Duration - Only for Manual mode */}
             {!vadConfig.enabled && (
               <div className="space-y-2">
@@ -295,6 +357,37 @@ export const SettingsPanel = ({
                 {/* VAD-specific advanced settings */}
                 {vadConfig.enabled && (
                   <>
+                    <div className="space-y-2">
+                      <Label className="text-xs font-medium flex items-center justify-between">
+                        <span>Speech Detector</span>
+                        <Select
+                          value={vadConfig.detector ?? "energy"}
+                          onValueChange={(value) =>
+                            onUpdateVadConfig({
+                              ...vadConfig,
+                              detector: value as VadDetector,
+                            })
+                          }
+                        >
+                          <SelectTrigger className="w-auto h-7 text-xs">
+                            <SelectValue />
+                          </SelectTrigger>
+                          <SelectContent>
+                            <SelectItem value="energy" className="text-xs">
+                              Volume level
+                            </SelectItem>
+                            <SelectItem value="webrtc" className="text-xs">
+                              Voice model
+                            </SelectItem>
+                          </SelectContent>
+                        </Select>
+                      </Label>
+                      <p className="text-[10px] text-muted-foreground">
+                        The voice model ignores most music, typing and
+                        notification sounds
+                      </p>
+                    </div>
+
                     <div className="space-y-2">
                       <Label className="text-xs font-medium flex items-center justify-between">
                         <span>Speech Sensitivity (Raw)</span>
@@ -33,6 +33,7 @@ export const SystemAudio = (props: useSystemAudioType) => {
     isProcessing,
     isAIProcessing,
     lastTranscription,
+    liveTranscript,
     lastAIResponse,
     error,
     setupRequired,
@@ -57,6 +58,8 @@ export const SystemAudio = (props: useSystemAudioType) => {
     handleQuickActionClick,
     vadConfig,
     updateVadConfiguration,
+    meetingMode,
+    setMeetingMode,
     isRecordingInContinuousMode,
     recordingProgress,
     manualStopAndSend,
@@ -348,6 +351,7 @@ export const SystemAudio = (props: useSystemAudioType) => {
                     {/* AI Response */}
                     <ResultsSection
                       lastTranscription={lastTranscription}
+                      liveTranscript={liveTranscript}
                       lastAIResponse={lastAIResponse}
                       isAIProcessing={isAIProcessing}
                       conversation={conversation}
@@ -359,6 +363,8 @@ export const SystemAudio = (props: useSystemAudioType) => {
                     <SettingsPanel
                       vadConfig={vadConfig}
                       onUpdateVadConfig={updateVadConfiguration}
+                      meetingMode={meetingMode}
+                      setMeetingMode={setMeetingMode}
                       useSystemPrompt={useSystemPrompt}
                       setUseSystemPrompt={setUseSystemPrompt}
                       contextContent={contextContent}
@@ -0,0 +1,113 @@
+import { useCallback, useEffect, useState } from "react";
+import { Loader2, LockOpenIcon } from "lucide-react";
+import { Button, Header, Input, Label, Switch } from "@/components";
+import {
+  getDatabaseEncryptionStatus,
+  unlockDatabase,
+  type DatabaseEncryptionStatus,
+} from "@/lib";
+
+interface DatabaseEncryptionProps {
+  className?: string;
+}
+
+export const DatabaseEncryption = ({ className }: DatabaseEncryptionProps) => {
+  const [status, setStatus] = useState<DatabaseEncryptionStatus | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const [recoveryKey, setRecoveryKey] = useState("");
+  const [remember, setRemember] = useState(true);
+  const [isUnlocking, setIsUnlocking] = useState(false);
+
+  const refresh = useCallback(async () => {
+    try {
+      setStatus(await getDatabaseEncryptionStatus());
+    } catch (e) {
+      setError(String(e));
+    }
+  }, []);
+
+  useEffect(() => {
+    refresh();
+  }, [refresh]);
+
+  const unlock = async () => {
+    setIsUnlocking(true);
+    setError(null);
+    try {
+      setStatus(await unlockDatabase(recoveryKey, remember));
+      setRecoveryKey("");
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setIsUnlocking(false);
+    }
+  };
+
+  const locked = status?.encrypted && !status.unlocked;
+  const problem = error ?? status?.startupError;
+
+  return (
+    <div id="database" className={`space-y-3 ${className}`}>
+      <Header
+        title="Database"
+        description={
+          status?.encrypted
+            ? "Your chat history is encrypted at rest"
+            : "Your chat history is stored unencrypted on this device"
+        }
+        isMainTitle
+      />
+
+      {problem && (
+        <div className="p-3 bg-red-50 border border-red-200 rounded-md">
+          <p className="text-xs text-red-700 font-medium">{problem}</p>
+        </div>
+      )}
+
+      {locked && (
+        <div className="space-y-2">
+          <Label className="text-sm font-medium">Recovery key</Label>
+          <p className="text-xs text-muted-foreground">
+            {status?.keyInKeychain
+              ? "Leave empty to unlock with the key saved in your keychain."
+              : "No key was found in your keychain. Enter the recovery key shown when encryption was turned on."}
+          </p>
+          <Input
+            value={recoveryKey}
+            onChange={(e) => setRecoveryKey(e.target.value)}
+            placeholder="64 hexadecimal digits"
+            disabled={isUnlocking}
+          />
+          <div className="flex items-center justify-between">
+            <Label className="text-xs text-muted-foreground">
+              Save the recovery key to the keychain
+            </Label>
+            <Switch
+              checked={remember}
+              onCheckedChange={setRemember}
+              disabled={isUnlocking || !recoveryKey.trim()}
+              aria-label="Save recovery key to keychain"
+            />
+          </div>
+          <Button
+            onClick={unlock}
+            disabled={isUnlocking || (!recoveryKey.trim() && !status?.keyInKeychain)}
+            className="w-full h-11"
+          >
+            {isUnlocking ? (
+              <>
+                <Loader2 className="h-4 w-4 mr-2 animate-spin" />
+                Unlocking...
+              </>
+            ) : (
+              <>
+                <LockOpenIcon className="h-4 w-4 mr-2" />
+                Unlock Database
+              </>
+            )}
+          </Button>
+        </div>
+      )}
+    </div>
+  );
+};
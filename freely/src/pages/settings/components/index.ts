@@ -2,3 +2,4 @@ export * from "./AlwaysOnTopToggle";
 export * from "./AppIconToggle";
 export * from "./AutostartToggle";
 export * from "./Theme";
+export * from "./DatabaseEncryption";
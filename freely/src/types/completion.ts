@@ -10,12 +10,30 @@ export interface AttachedFile {
   size: number;
 }
 
+export interface MessageToolCall {
+  id?: string;
+  name: string;
+  input: unknown;
+  output?: string;
+  isError?: boolean;
+}
+
+/** Agent details persisted with a message and included in exports. */
+export interface MessageMetadata {
+  model?: string;
+  toolCalls?: MessageToolCall[];
+  tokenUsage?: { input_tokens: number; output_tokens: number };
+}
+
 export interface ChatMessage {
   id: string;
   role: "user" | "assistant" | "system";
   content: string;
   timestamp: number;
   attachedFiles?: AttachedFile[];
+  /** Previous message on the same branch (set by the backend). */
+  parentId?: string;
+  metadata?: MessageMetadata;
   audioSource?: AudioSource;
   speakerLabel?: SpeakerLabel;
 }
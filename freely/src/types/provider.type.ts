@@ -1,3 +1,6 @@
+/** Encodings the backend can emit captured speech in */
+export type AudioEncoding = "wav" | "flac" | "opus";
+
 export interface TYPE_PROVIDER {
   id?: string;
   streaming?: boolean;
@@ -6,4 +9,6 @@ export interface TYPE_PROVIDER {
   /** Agent-backed providers (claude-code, codex, gemini-sdk) don't use curl */
   isAgent?: boolean;
   curl?: string;
+  /** Compressed encodings the STT endpoint accepts, most preferred first; WAV otherwise */
+  audioEncodings?: AudioEncoding[];
 }
@@ -4,6 +4,33 @@ export interface SystemPrompt {
   prompt: string;
   created_at: string;
   updated_at: string;
+  /** Latest entry in the prompt's version history */
+  version: number;
+}
+
+export interface SystemPromptVersion {
+  prompt_id: number;
+  version: number;
+  name: string;
+  prompt: string;
+  created_at: string;
+}
+
+export interface PromptPackImportSummary {
+  created: number;
+  updated: number;
+  unchanged: number;
+}
+
+export interface PromptVariable {
+  name: string;
+  description: string;
+}
+
+export interface ResolvedPrompt {
+  prompt: string;
+  /** Placeholders left as written (unknown or currently unavailable) */
+  unresolved: string[];
 }
 
 export interface SystemPromptInput {